@@ -26,6 +26,8 @@ pub enum CharMajorNum {
     UsbChar = 180,
     /// Direct Rendering Manager (modern graphics) (/dev/dri/*)
     Drm = 226,
+    /// Hypervisor virtual consoles (/dev/hvc*)
+    Hvc = 229,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
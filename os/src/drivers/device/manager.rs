@@ -7,14 +7,14 @@ use embedded_hal::serial;
 use flat_device_tree::Fdt;
 // use riscv::register;
 use spin::{rwlock::RwLock};
-use virtio_drivers::{device::blk::VirtIOBlk, transport::{mmio::MmioTransport, pci::PciTransport}};
+use virtio_drivers::{device::blk::VirtIOBlk, transport::{mmio::MmioTransport, pci::PciTransport, DeviceType as VirtIODeviceType}};
 
 use crate::{
     drivers::{
         device::{
             dev_core::{PhysDriver, PhysDriverProbe}, 
             dev_number::{BlockMajorNum, CharMajorNum, MajorNumber}, 
-            irq::HardIrqHandler, BlockDevice, Device
+            irq::{HandleHardIrq, HardIrqHandler}, BlockDevice, Device, NetDevice
         }, 
         irqchip::*, 
         tty::{
@@ -22,9 +22,10 @@ use crate::{
             tty_core::{CharDevice, TtyStruct}
         }, 
         vf2::Vf2SDIO, 
-        VirtIoBlkDev, VirtIoHalImpl
+        probe_virtio_mmio, probe_virtio_pci, VirtIoBlkDev, VirtIoConsoleDev, VirtIoHalImpl, VirtIoNetDev, VirtIoRngDev
     }, 
-    hal::config::{DEVICE_ADDR_OFFSET, KERNEL_ADDR_OFFSET}
+    hal::config::{DEVICE_ADDR_OFFSET, KERNEL_ADDR_OFFSET},
+    utils::register_hwrng
 };
 
 
@@ -47,7 +48,11 @@ pub struct DeviceManager {
 
     pub virtblks_mmio: Vec< Arc< VirtIoBlkDev<VirtIoHalImpl, MmioTransport<'static>> > >,
     pub virtblks_pci: Vec< Arc< VirtIoBlkDev<VirtIoHalImpl, PciTransport> > >,
-    pub vf2_sdcards: Vec< Arc< Vf2SDIO >>
+    pub vf2_sdcards: Vec< Arc< Vf2SDIO >>,
+    pub virtio_consoles: Vec< Arc< VirtIoConsoleDev<VirtIoHalImpl, MmioTransport<'static>> > >,
+    pub virtio_rngs: Vec< Arc< VirtIoRngDev<VirtIoHalImpl, MmioTransport<'static>> > >,
+    pub virtnets_mmio: Vec< Arc< VirtIoNetDev<VirtIoHalImpl, MmioTransport<'static>> > >,
+    pub virtnets_pci: Vec< Arc< VirtIoNetDev<VirtIoHalImpl, PciTransport> > >,
 
     // /// interrupt controller unit
     // pub ICU: Option<Arc<super::PLIC>>,
@@ -77,6 +82,10 @@ impl DeviceManager {
             virtblks_mmio: Vec::new(),
             virtblks_pci: Vec::new(),
             vf2_sdcards: Vec::new(),
+            virtio_consoles: Vec::new(),
+            virtio_rngs: Vec::new(),
+            virtnets_mmio: Vec::new(),
+            virtnets_pci: Vec::new(),
         }
     }
     pub fn validate_raw_fdt(&mut self, root_addr: usize) {
@@ -116,21 +125,32 @@ impl DeviceManager {
         }
     }
 
-    fn register_serials(&mut self) {
-        for uart in self.uarts.iter() {
-            let irq_number = uart.irq_number();
-            let serial = Arc::new(SerialDriver::new(uart.clone()));
-            self.serials.push(serial.clone());
-            if let Some(irq_number) = irq_number {
-                if let Some(icu) = &self.ICU {
-                    const MAX_CORES: usize = 1;
-                    for core_id in 0..MAX_CORES {
-                        icu.enable_irq(core_id, irq_number);
-                    }
-                }
-                self.irq_table[irq_number].register(serial.clone());
+    /// 在中断控制器上打开中断并登记处理函数
+    fn register_irq(&mut self, irq_number: usize, handler: Arc<dyn HandleHardIrq>) {
+        if let Some(icu) = &self.ICU {
+            const MAX_CORES: usize = 1;
+            for core_id in 0..MAX_CORES {
+                icu.enable_irq(core_id, irq_number);
             }
         }
+        self.irq_table[irq_number].register(handler);
+    }
+
+    /// 为 uart 套上 SerialDriver，并注册它的中断
+    fn new_serial(&mut self, uart: Arc<dyn UartDriver>) -> Arc<SerialDriver> {
+        let irq_number = uart.irq_number();
+        let serial = Arc::new(SerialDriver::new(uart));
+        self.serials.push(serial.clone());
+        if let Some(irq_number) = irq_number {
+            self.register_irq(irq_number, serial.clone());
+        }
+        serial
+    }
+
+    fn register_serials(&mut self) {
+        for uart in self.uarts.clone() {
+            self.new_serial(uart);
+        }
     }
 
     pub fn register_ttys(&mut self) {
@@ -146,19 +166,103 @@ impl DeviceManager {
         }
     }
 
-    pub fn probe_virtio_blks(&mut self) {
+    pub fn probe_virtio_blks(
+        &mut self,
+        mmio_transports: &mut Vec<(VirtIODeviceType, MmioTransport<'static>, Option<usize>)>,
+        pci_transports: &mut Vec<(VirtIODeviceType, PciTransport)>,
+    ) {
         // from mmio
-        let virtio_blk = VirtIoBlkDev::<VirtIoHalImpl, MmioTransport>::probe(&self.FDT.unwrap());
-        if let Some(virtio_blk) = virtio_blk {
-            self.virtblks_mmio.push(virtio_blk);
+        if let Some((transport, _)) = take_mmio_transport(mmio_transports, VirtIODeviceType::Block) {
+            let virtio_blk = VirtIoBlkDev::<VirtIoHalImpl, MmioTransport>::new(
+                transport,
+                MajorNumber::Block(BlockMajorNum::VirtBlock),
+                0, // 不重复就行，只有一个设备
+            );
+            println!("got one mmio");
+            self.virtblks_mmio.push(Arc::new(virtio_blk));
         }
 
         // from pci
-        let virtio_blk = VirtIoBlkDev::<VirtIoHalImpl, PciTransport>::probe(&self.FDT.unwrap());
-        if let Some(virtio_blk) = virtio_blk {
-            self.virtblks_pci.push(virtio_blk);
+        if let Some(transport) = take_pci_transport(pci_transports, VirtIODeviceType::Block) {
+            let virtio_blk = VirtIoBlkDev::<VirtIoHalImpl, PciTransport>::new(
+                transport,
+                MajorNumber::Block(BlockMajorNum::VirtBlock),
+                0,
+            );
+            println!("got one pci");
+            self.virtblks_pci.push(Arc::new(virtio_blk));
+        }
+
+    }
+
+    pub fn probe_virtio_nets(
+        &mut self,
+        mmio_transports: &mut Vec<(VirtIODeviceType, MmioTransport<'static>, Option<usize>)>,
+        pci_transports: &mut Vec<(VirtIODeviceType, PciTransport)>,
+    ) {
+        while let Some((transport, irq_number)) = take_mmio_transport(mmio_transports, VirtIODeviceType::Network) {
+            println!("got one mmio virtio-net");
+            self.virtnets_mmio.push(Arc::new(VirtIoNetDev::new(transport, irq_number)));
+        }
+        // PCI 网卡的 INTx 中断没有接到中断控制器上，走轮询
+        while let Some(transport) = take_pci_transport(pci_transports, VirtIODeviceType::Network) {
+            println!("got one pci virtio-net");
+            self.virtnets_pci.push(Arc::new(VirtIoNetDev::new(transport, None)));
+        }
+    }
+
+    pub fn register_virtio_net_devs(&mut self) {
+        for net in self.virtnets_mmio.clone() {
+            if let Some(irq_number) = net.irq_number() {
+                self.register_irq(irq_number, net);
+            }
         }
+    }
 
+    /// 所有网卡，按探测顺序对应 eth0、eth1……
+    pub fn net_devs(&self) -> Vec<Arc<dyn NetDevice>> {
+        self.virtnets_mmio.iter().map(|n| n.clone() as Arc<dyn NetDevice>)
+            .chain(self.virtnets_pci.iter().map(|n| n.clone() as Arc<dyn NetDevice>))
+            .collect()
+    }
+
+    /// 每个 virtio-console 设备对应一个端口
+    pub fn probe_virtio_consoles(
+        &mut self,
+        mmio_transports: &mut Vec<(VirtIODeviceType, MmioTransport<'static>, Option<usize>)>,
+    ) {
+        while let Some((transport, irq_number)) = take_mmio_transport(mmio_transports, VirtIODeviceType::Console) {
+            println!("got one virtio-console");
+            self.virtio_consoles.push(Arc::new(VirtIoConsoleDev::new(transport, irq_number)));
+        }
+    }
+
+    /// virtio-console 的端口注册为 hvc0、hvc1……
+    pub fn register_virtio_consoles(&mut self) {
+        let major = CharMajorNum::Hvc;
+        for (minor, console) in self.virtio_consoles.clone().into_iter().enumerate() {
+            let serial = self.new_serial(console);
+            let tty = TtyStruct::new(serial, MajorNumber::Char(major), minor);
+            self.char_devs.insert((major, minor), Arc::new(tty));
+        }
+    }
+
+    pub fn probe_virtio_rngs(
+        &mut self,
+        mmio_transports: &mut Vec<(VirtIODeviceType, MmioTransport<'static>, Option<usize>)>,
+    ) {
+        // 熵设备走轮询，不需要中断号
+        while let Some((transport, _)) = take_mmio_transport(mmio_transports, VirtIODeviceType::EntropySource) {
+            println!("got one virtio-rng");
+            self.virtio_rngs.push(Arc::new(VirtIoRngDev::new(transport)));
+        }
+    }
+
+    /// 内核 CRNG 只使用一个硬件源
+    pub fn register_virtio_rngs(&mut self) {
+        if let Some(rng) = self.virtio_rngs.first() {
+            register_hwrng(rng.clone());
+        }
     }
 
     pub fn probe_vf2_sdcards(&mut self) {
@@ -209,8 +313,17 @@ impl DeviceManager {
         self.register_ttys();
         #[cfg(feature = "board_qemu")]
         {
-            self.probe_virtio_blks();
+            // virtio-mmio 节点和 PCI 总线都只枚举一次，枚举出的设备由各驱动认领
+            let mut mmio_transports = probe_virtio_mmio(&self.FDT.unwrap());
+            let mut pci_transports = probe_virtio_pci(&self.FDT.unwrap());
+            self.probe_virtio_blks(&mut mmio_transports, &mut pci_transports);
             self.register_virtio_blk_devs();            
+            self.probe_virtio_consoles(&mut mmio_transports);
+            self.register_virtio_consoles();
+            self.probe_virtio_rngs(&mut mmio_transports);
+            self.register_virtio_rngs();
+            self.probe_virtio_nets(&mut mmio_transports, &mut pci_transports);
+            self.register_virtio_net_devs();
         }
         #[cfg(feature = "vf2")]
         {
@@ -250,6 +363,7 @@ impl DeviceManager {
             .expect("[DeviceManager::handle_irq] Bad icu")
             .claim_irq(hart_id)
             .expect("[DeviceManager::handle_irq] irq number not found");
+        crate::utils::add_interrupt_randomness(irq_number);
         self.irq_table[irq_number].handle_irq();
         self.ICU.as_ref().unwrap().finish_irq(hart_id, irq_number);
     }
@@ -257,6 +371,23 @@ impl DeviceManager {
     // pub fn 
 }
 
+fn take_pci_transport(
+    pci_transports: &mut Vec<(VirtIODeviceType, PciTransport)>,
+    device_type: VirtIODeviceType,
+) -> Option<PciTransport> {
+    let index = pci_transports.iter().position(|(t, _)| *t == device_type)?;
+    Some(pci_transports.remove(index).1)
+}
+
+fn take_mmio_transport(
+    mmio_transports: &mut Vec<(VirtIODeviceType, MmioTransport<'static>, Option<usize>)>,
+    device_type: VirtIODeviceType,
+) -> Option<(MmioTransport<'static>, Option<usize>)> {
+    let index = mmio_transports.iter().position(|(t, _, _)| *t == device_type)?;
+    let (_, transport, irq_number) = mmio_transports.remove(index);
+    Some((transport, irq_number))
+}
+
 // TODO: remove the lock
 lazy_static!{
     pub static ref DEVICE_MANAGER: RwLock<DeviceManager> = RwLock::new(DeviceManager::new());
@@ -1,5 +1,6 @@
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
+use core::task::Waker;
 
 use crate::drivers::{device::dev_number::MajorNumber, tty::tty_core::CharDevice};
 
@@ -83,3 +84,25 @@ pub trait BlockDevice: Device {
     /// Flushes the device to write all pending data to the storage.
     fn flush(&self) -> DevResult;
 }
+
+/// Operations that require a network card driver to implement.
+///
+/// Frames are whole Ethernet frames without any device-specific header.
+pub trait NetDevice: Send + Sync {
+    /// The hardware (MAC) address of the card.
+    fn mac_address(&self) -> [u8; 6];
+    /// The largest frame the card can send, including the Ethernet header.
+    fn max_frame_len(&self) -> usize;
+    /// Whether a frame can be sent right now.
+    fn can_send(&self) -> bool;
+    /// Takes one received frame, if any.
+    fn recv(&self) -> Option<Vec<u8>>;
+    /// Sends one frame.
+    fn send(&self, frame: &[u8]) -> DevResult;
+    /// Registers a waker to be woken once by the next receive interrupt.
+    ///
+    /// Returns `false` if the card has no interrupt and has to be polled.
+    fn register_rx_waker(&self, _waker: &Waker) -> bool {
+        false
+    }
+}
@@ -1,3 +1,4 @@
 pub mod tty_core;
 pub mod termios;
+pub mod pty;
 pub mod serial;
\ No newline at end of file
@@ -0,0 +1,366 @@
+//! UNIX98 伪终端
+//!
+//! 每一对 pty 由 master 和 slave 组成。slave 端就是一个普通的 `TtyStruct`，复用行规程和 ioctl；
+//! 它下层的 `TtyDriver` 不是串口，而是连到 master 的两条缓冲区。
+
+use core::{
+    cmp::min,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::Waker,
+};
+
+use alloc::{boxed::Box, collections::{btree_map::BTreeMap, vec_deque::VecDeque}, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+use log::info;
+
+use crate::{
+    drivers::{
+        device::dev_number::{CharMajorNum, MajorNumber},
+        tty::{
+            termios::Termios,
+            tty_core::{CharDevice, TtyDriver, TtyStruct},
+        },
+    },
+    sync::{get_waker, suspend_now, SpinNoIrqLock},
+    utils::{Errno, SysResult},
+};
+
+/// 每个方向的缓冲区大小
+const PTY_BUF_SIZE: usize = 4096;
+/// 同时存在的 pty 数量上限
+const PTY_MAX: usize = 256;
+
+lazy_static! {
+    /// 所有存在的 pty，key 是 pty 编号，也就是 /dev/pts/N 中的 N
+    static ref PTY_TABLE: SpinNoIrqLock<BTreeMap<usize, Arc<Pty>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+struct PtyChannelInner {
+    buf: VecDeque<u8>,
+    reader_waker: VecDeque<Waker>,
+    writer_waker: VecDeque<Waker>,
+}
+
+/// pty 一个方向上的数据通道
+struct PtyChannel(SpinNoIrqLock<PtyChannelInner>);
+
+impl PtyChannel {
+    fn new() -> Self {
+        Self(SpinNoIrqLock::new(PtyChannelInner {
+            buf: VecDeque::new(),
+            reader_waker: VecDeque::new(),
+            writer_waker: VecDeque::new(),
+        }))
+    }
+
+    /// 有数据可读或者对端已经挂断时返回 true，否则登记 waker
+    async fn poll_read(&self, hangup: bool) -> bool {
+        let waker = get_waker().await;
+        let mut inner = self.0.lock();
+        if !inner.buf.is_empty() || hangup {
+            return true;
+        }
+        inner.reader_waker.push_back(waker);
+        false
+    }
+
+    /// 还有空间可写或者对端已经挂断时返回 true，否则登记 waker
+    async fn poll_write(&self, hangup: bool) -> bool {
+        let waker = get_waker().await;
+        let mut inner = self.0.lock();
+        if inner.buf.len() < PTY_BUF_SIZE || hangup {
+            return true;
+        }
+        inner.writer_waker.push_back(waker);
+        false
+    }
+
+    fn read(&self, buf: &mut [u8]) -> usize {
+        let mut inner = self.0.lock();
+        let len = min(buf.len(), inner.buf.len());
+        for (i, c) in inner.buf.drain(..len).enumerate() {
+            buf[i] = c;
+        }
+        if len > 0 {
+            while let Some(waker) = inner.writer_waker.pop_front() {
+                waker.wake();
+            }
+        }
+        len
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        let mut inner = self.0.lock();
+        let len = min(buf.len(), PTY_BUF_SIZE - inner.buf.len());
+        inner.buf.extend(&buf[..len]);
+        if len > 0 {
+            while let Some(waker) = inner.reader_waker.pop_front() {
+                waker.wake();
+            }
+        }
+        len
+    }
+
+    /// 缓冲区的空闲空间
+    fn space(&self) -> usize {
+        PTY_BUF_SIZE - self.0.lock().buf.len()
+    }
+
+    fn wake_all(&self) {
+        let mut inner = self.0.lock();
+        while let Some(waker) = inner.reader_waker.pop_front() {
+            waker.wake();
+        }
+        while let Some(waker) = inner.writer_waker.pop_front() {
+            waker.wake();
+        }
+    }
+}
+
+/// master 和 slave 共享的缓冲区
+struct PtyPort {
+    /// master 写入，slave 读取
+    input: PtyChannel,
+    /// slave 写入，master 读取
+    output: PtyChannel,
+    master_closed: AtomicBool,
+}
+
+impl PtyPort {
+    fn master_closed(&self) -> bool {
+        self.master_closed.load(Ordering::Acquire)
+    }
+}
+
+/// slave 端 TtyStruct 下层的驱动，数据来自 master
+struct PtySlaveDriver(Arc<PtyPort>);
+
+#[async_trait]
+impl TtyDriver for PtySlaveDriver {
+    /// master 关闭后返回 0
+    async fn read(&self, buf: &mut [u8]) -> usize {
+        while !self.poll_in().await {
+            suspend_now().await
+        }
+        self.0.input.read(buf)
+    }
+    async fn readc(&self) -> u8 {
+        let mut c = [0u8];
+        while self.read(&mut c).await == 0 && !self.0.master_closed() {}
+        c[0]
+    }
+    async fn write(&self, buf: &[u8]) -> usize {
+        let mut written = 0;
+        while written < buf.len() && !self.0.master_closed() {
+            written += self.0.output.write(&buf[written..]);
+            if written < buf.len() && !self.poll_out().await {
+                suspend_now().await
+            }
+        }
+        written
+    }
+    async fn poll_in(&self) -> bool {
+        self.0.input.poll_read(self.0.master_closed()).await
+    }
+    async fn poll_out(&self) -> bool {
+        self.0.output.poll_write(self.0.master_closed()).await
+    }
+    async fn stop(&self) {}
+    async fn start(&self) {}
+    async fn validate_termios(&self, _termios: &Termios) -> bool {
+        true
+    }
+}
+
+/// 一对伪终端
+pub struct Pty {
+    pub index: usize,
+    port: Arc<PtyPort>,
+    /// slave 端，行规程、termios、前台进程组都在这里
+    pub slave: Arc<TtyStruct>,
+    /// TIOCSPTLCK 设置的锁，上锁时不能打开 slave
+    locked: AtomicBool,
+    /// 当前打开着的 slave 文件数
+    slave_count: AtomicUsize,
+    /// slave 是否被打开过，用来区分“还没打开”和“已经全部关闭”
+    slave_opened: AtomicBool,
+    /// 串行化 master 的写入，保证按空闲空间转换出的输入能够整体写进缓冲区
+    input_lock: SpinNoIrqLock<()>,
+    /// output 满时还没回显出去的输入，master 读走数据后继续回显
+    echo_pending: SpinNoIrqLock<VecDeque<u8>>,
+}
+
+impl Pty {
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Acquire)
+    }
+
+    pub fn set_locked(&self, locked: bool) {
+        self.locked.store(locked, Ordering::Release);
+    }
+
+    /// 打开 slave，pty 上锁或者 master 已经关闭时返回 EIO
+    pub fn slave_open(&self) -> SysResult {
+        if self.is_locked() || self.port.master_closed() {
+            return Err(Errno::EIO);
+        }
+        self.slave_count.fetch_add(1, Ordering::AcqRel);
+        self.slave_opened.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn slave_close(&self) {
+        if self.slave_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // 最后一个 slave 关闭，唤醒 master 上的读者让它们看到 EIO
+            self.port.output.wake_all();
+        }
+    }
+
+    /// slave 打开过并且已经全部关闭
+    fn slave_hangup(&self) -> bool {
+        self.slave_opened.load(Ordering::Acquire) && self.slave_count.load(Ordering::Acquire) == 0
+    }
+
+    /// 把积压的回显尽量写进 output
+    fn flush_echo(&self) {
+        let mut pending = self.echo_pending.lock();
+        if pending.is_empty() {
+            return;
+        }
+        let len = self.port.output.write(pending.make_contiguous());
+        pending.drain(..len);
+    }
+
+    pub async fn master_read(&self, buf: &mut [u8], nonblock: bool) -> SysResult<usize> {
+        loop {
+            self.flush_echo();
+            let len = self.port.output.read(buf);
+            if len > 0 {
+                self.flush_echo();
+                return Ok(len);
+            }
+            if self.slave_hangup() {
+                return Err(Errno::EIO);
+            }
+            if nonblock {
+                return Err(Errno::EAGAIN);
+            }
+            if !self.port.output.poll_read(self.slave_hangup()).await {
+                suspend_now().await
+            }
+        }
+    }
+
+    /// master 写入的数据是 slave 的输入，在这里做 ICRNL、ISIG 和 ECHO。
+    /// 返回 slave 接收的字节数，只回显已经接收的输入；output 满时回显先积压起来
+    pub async fn master_write(&self, buf: &[u8], nonblock: bool) -> SysResult<usize> {
+        let termios = *self.slave.termios.read();
+        let mut written = 0;
+        while written < buf.len() {
+            let (len, input) = {
+                let _guard = self.input_lock.lock();
+                let len = min(buf.len() - written, self.port.input.space());
+                let mut input: Vec<u8> = buf[written..written + len]
+                    .iter()
+                    .map(|&c| match c {
+                        b'\r' if termios.is_icrnl() => b'\n',
+                        c => c,
+                    })
+                    .collect();
+                // 发信号时不能拿着缓冲区的锁
+                let isig_len = self.slave.handle_isig(&mut input);
+                input.truncate(isig_len);
+                // 转换后的数据不会比输入长，这段时间里也没有别的写者，一定能整体写入
+                self.port.input.write(&input);
+                (len, input)
+            };
+            if termios.is_echo() && !input.is_empty() {
+                self.echo_pending.lock().extend(input);
+                self.flush_echo();
+            }
+            written += len;
+            if written == buf.len() {
+                break;
+            }
+            if nonblock {
+                if written == 0 {
+                    return Err(Errno::EAGAIN);
+                }
+                break;
+            }
+            if !self.port.input.poll_write(false).await {
+                suspend_now().await
+            }
+        }
+        Ok(written)
+    }
+
+    pub async fn master_poll_in(&self) -> bool {
+        self.flush_echo();
+        self.port.output.poll_read(self.slave_hangup()).await
+    }
+
+    pub async fn master_poll_out(&self) -> bool {
+        self.port.input.poll_write(false).await
+    }
+
+    /// master 关闭：挂断 slave 所在的会话并回收 pty 编号
+    pub fn master_close(&self) {
+        info!("[pty] master of pts {} closed", self.index);
+        self.port.master_closed.store(true, Ordering::Release);
+        self.port.input.wake_all();
+        self.port.output.wake_all();
+        let session = *self.slave.session.read();
+        if let Some(sid) = session {
+            self.slave.hangup_session(sid);
+        }
+        PTY_TABLE.lock().remove(&self.index);
+    }
+}
+
+/// 分配一对新的 pty，使用最小的空闲编号
+pub fn pty_alloc() -> SysResult<Arc<Pty>> {
+    let mut table = PTY_TABLE.lock();
+    let index = (0..PTY_MAX)
+        .find(|i| !table.contains_key(i))
+        .ok_or(Errno::ENOSPC)?;
+    let port = Arc::new(PtyPort {
+        input: PtyChannel::new(),
+        output: PtyChannel::new(),
+        master_closed: AtomicBool::new(false),
+    });
+    let slave = Arc::new(TtyStruct::new(
+        Arc::new(PtySlaveDriver(port.clone())),
+        MajorNumber::Char(CharMajorNum::PtySlave),
+        index,
+    ));
+    let pty = Arc::new(Pty {
+        index,
+        port,
+        slave,
+        // 和 Linux 一样，新分配的 pty 默认上锁，需要 unlockpt 之后才能打开 slave
+        locked: AtomicBool::new(true),
+        slave_count: AtomicUsize::new(0),
+        slave_opened: AtomicBool::new(false),
+        input_lock: SpinNoIrqLock::new(()),
+        echo_pending: SpinNoIrqLock::new(VecDeque::new()),
+    });
+    table.insert(index, pty.clone());
+    info!("[pty] alloc pts {}", index);
+    Ok(pty)
+}
+
+pub fn get_pty(index: usize) -> Option<Arc<Pty>> {
+    PTY_TABLE.lock().get(&index).cloned()
+}
+
+/// 当前存在的所有 pty 编号
+pub fn pty_indexes() -> Vec<usize> {
+    PTY_TABLE.lock().keys().cloned().collect()
+}
+
+/// 所有 pty 的 slave 端
+pub fn pty_slaves() -> Vec<Arc<TtyStruct>> {
+    PTY_TABLE.lock().values().map(|pty| pty.slave.clone()).collect()
+}
@@ -1,6 +1,11 @@
 use alloc::vec::Vec;
 use core::fmt;
 
+/// c_cc 数组中特殊控制字符的下标
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VSUSP: usize = 10;
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct Termios {
@@ -51,6 +56,9 @@ impl Termios {
     pub fn is_isig(&self) -> bool {
         self.lflag.contains(LFlag::ISIG)
     }
+    pub fn is_tostop(&self) -> bool {
+        self.lflag.contains(LFlag::TOSTOP)
+    }
 }
 
 
@@ -4,17 +4,54 @@ use async_trait::async_trait;
 use alloc::{boxed::Box, sync::Arc, vec::{self, Vec}};
 use log::{error, info};
 use spin::RwLock;
-use crate::{drivers::{device::{dev_number::MajorNumber, Device, DeviceType}, tty::{self, termios::{self, Termios, WinSize}}}, mm::user_ptr::{user_ref, user_ref_mut}, sync::{new_shared, Shared, SleepShared}, utils::{container::ring_buffer::LineBuffer, Errno, SysResult}};
+use crate::{drivers::{device::{dev_number::MajorNumber, manager::DEVICE_MANAGER, Device, DeviceType}, tty::{self, pty::pty_slaves, termios::{self, Termios, WinSize, VINTR, VQUIT, VSUSP}}}, mm::user_ptr::{user_ref, user_ref_mut}, signal::{SigCode, SigDetails, SigErr, SigHandlerType, SigInfo, SigNom}, sync::{new_shared, Shared, SleepShared}, task::{capable, current_task, get_target_proc_group, get_task_by_pid, is_orphaned_proc_group, CapSet}, utils::{container::ring_buffer::LineBuffer, Errno, SysResult}};
 
 #[async_trait]
 pub trait CharDevice : Device + Send + Sync + 'static {
-    async fn read(&self, buf: &mut [u8]) -> usize;
-    async fn write(&self, buf: &[u8]) -> usize;
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize>;
+    async fn write(&self, buf: &[u8]) -> SysResult<usize>;
     async fn ioctl(&self, op: TtyIoctlCmd, arg: usize) -> SysResult<usize>;
     // // poll if input is available
     async fn poll_in(&self) -> bool;
     // // poll if output is available
     async fn poll_out(&self) -> bool;
+    /// 会话首进程退出时调用，如果设备是该会话的控制终端则挂断
+    fn hangup_session(&self, sid: usize);
+    /// 以该设备为控制终端的会话
+    fn ctty_session(&self) -> Option<usize>;
+}
+
+/// 系统中所有的终端，包括 pty 的 slave 端
+fn all_ttys() -> Vec<Arc<dyn CharDevice>> {
+    let mut devs: Vec<Arc<dyn CharDevice>> = DEVICE_MANAGER.read().char_devs.values().cloned().collect();
+    devs.extend(pty_slaves().into_iter().map(|slave| slave as Arc<dyn CharDevice>));
+    devs
+}
+
+/// 会话首进程退出：挂断该会话的控制终端
+pub fn tty_session_leader_exit(sid: usize) {
+    for dev in all_ttys() {
+        dev.hangup_session(sid);
+    }
+}
+
+/// 会话是否已经有控制终端
+fn session_has_ctty(sid: usize) -> bool {
+    all_ttys().iter().any(|dev| dev.ctty_session() == Some(sid))
+}
+
+/// 向进程组中的所有进程发送由终端产生的信号
+fn kill_pgrp(pgid: usize, signo: SigNom) {
+    let Some(group) = get_target_proc_group(pgid) else {
+        return;
+    };
+    info!("[tty] send {:?} to pgrp {}", signo, pgid);
+    let siginfo = SigInfo::new(signo, SigCode::Kernel, SigErr::empty(), SigDetails::None);
+    for pid in group {
+        if let Some(task) = get_task_by_pid(pid) {
+            task.proc_recv_siginfo(siginfo);
+        }
+    }
 }
 
 // lazy_static! {
@@ -44,8 +81,9 @@ pub trait TtyDriver : Send + Sync {
 pub enum TtyIoctlCmd {
     TCGETS = 0x5401, TCSETS = 0x5402, TCSETSW = 0x5403, TCSETSF = 0x5404,
     TCGETA = 0x5405, TCSETA = 0x5406, TCSETAW = 0x5407, TCSETAF = 0x5408,
-    TCSBRK = 0x5409, TIOCGPGRP = 0x540F, TIOCSPGRP = 0x5410,
-    TIOCGWINSZ = 0x5413, TIOCSWINSZ = 0x5414,
+    TCSBRK = 0x5409, TIOCSCTTY = 0x540E, TIOCGPGRP = 0x540F, TIOCSPGRP = 0x5410,
+    TIOCGWINSZ = 0x5413, TIOCSWINSZ = 0x5414, TIOCNOTTY = 0x5422, TIOCGSID = 0x5429,
+    TIOCGPTN = 0x80045430, TIOCSPTLCK = 0x40045431,
 }
 
 impl TryFrom<usize> for TtyIoctlCmd {
@@ -55,8 +93,10 @@ impl TryFrom<usize> for TtyIoctlCmd {
             0x5401 => Ok(Self::TCGETS), 0x5402 => Ok(Self::TCSETS), 0x5403 => Ok(Self::TCSETSW),
             0x5404 => Ok(Self::TCSETSF), 0x5405 => Ok(Self::TCGETA), 0x5406 => Ok(Self::TCSETA),
             0x5407 => Ok(Self::TCSETAW), 0x5408 => Ok(Self::TCSETAF), 0x5409 => Ok(Self::TCSBRK),
-            0x540F => Ok(Self::TIOCGPGRP), 0x5410 => Ok(Self::TIOCSPGRP), 0x5413 => Ok(Self::TIOCGWINSZ),
-            0x5414 => Ok(Self::TIOCSWINSZ), _ => Err(()),
+            0x540E => Ok(Self::TIOCSCTTY), 0x540F => Ok(Self::TIOCGPGRP), 0x5410 => Ok(Self::TIOCSPGRP),
+            0x5413 => Ok(Self::TIOCGWINSZ), 0x5414 => Ok(Self::TIOCSWINSZ), 0x5422 => Ok(Self::TIOCNOTTY),
+            0x5429 => Ok(Self::TIOCGSID), 0x80045430 => Ok(Self::TIOCGPTN), 0x40045431 => Ok(Self::TIOCSPTLCK),
+            _ => Err(()),
         }
     }
 }
@@ -71,8 +111,8 @@ pub enum TtyLineDiscMode {
 
 #[async_trait]
 pub trait LineDiscPolicy : Sync + Send + 'static {
-    async fn read(&self, tty: &TtyStruct, buf: &mut [u8]) -> usize;
-    async fn write(&self, tty: &TtyStruct, buf: &[u8]) -> usize;
+    async fn read(&self, tty: &TtyStruct, buf: &mut [u8]) -> SysResult<usize>;
+    async fn write(&self, tty: &TtyStruct, buf: &[u8]) -> SysResult<usize>;
     async fn poll_in(&self, tty: &TtyStruct) -> bool;
     async fn poll_out(&self, tty: &TtyStruct) -> bool;
     /// TODO: validate_termios is a more adaptable choice
@@ -83,25 +123,33 @@ pub trait LineDiscPolicy : Sync + Send + 'static {
 pub struct TtyLineDisc;
 
 impl TtyLineDisc {
-    pub async fn read_raw(tty: &TtyStruct, buf: &mut [u8]) -> usize {
-        tty.driver.read(buf).await
+    pub async fn read_raw(tty: &TtyStruct, buf: &mut [u8]) -> SysResult<usize> {
+        let len = tty.driver.read(buf).await;
+        match tty.handle_isig(&mut buf[..len]) {
+            // 读到的字符全部被当作信号字符处理了，让读者先去处理信号
+            0 if len > 0 => Err(Errno::EINTR),
+            len => Ok(len),
+        }
     }
 }
 
 /// TODO：暂未完善
 impl TtyLineDisc {
-    pub async fn read_canonical(tty: &TtyStruct, buf: &mut [u8]) -> usize {
+    pub async fn read_canonical(tty: &TtyStruct, buf: &mut [u8]) -> SysResult<usize> {
 
         loop {
-            let c = tty.driver.readc().await;
-            match c {
+            let mut c = [tty.driver.readc().await];
+            if tty.handle_isig(&mut c) == 0 {
+                return Err(Errno::EINTR);
+            }
+            match c[0] {
                 b'\n' => {
                     tty.lbuffer.lock().push(b'\n');
-                    return 1;
+                    return Ok(1);
                 }
-                _ => {
+                c => {
                     tty.lbuffer.lock().push(c);
-                    return 1;
+                    return Ok(1);
                 }
             }
         }
@@ -110,14 +158,14 @@ impl TtyLineDisc {
 
 #[async_trait]
 impl LineDiscPolicy for TtyLineDisc {
-    async fn read(&self, tty: &TtyStruct, buf: &mut [u8]) -> usize {
+    async fn read(&self, tty: &TtyStruct, buf: &mut [u8]) -> SysResult<usize> {
         let mode = tty.n_tty_mode.read().clone();
         match mode {
             TtyLineDiscMode::Raw => TtyLineDisc::read_raw(tty, buf).await,
             TtyLineDiscMode::Canonical => TtyLineDisc::read_canonical(tty, buf).await,
         }
     }
-    async fn write(&self, tty: &TtyStruct, _buf: &[u8]) -> usize {
+    async fn write(&self, tty: &TtyStruct, _buf: &[u8]) -> SysResult<usize> {
         let mut buf = Vec::<u8>::new();
         let opost = tty.termios.read().is_opost();
         let onlcr = tty.termios.read().is_onlcr();
@@ -139,7 +187,7 @@ impl LineDiscPolicy for TtyLineDisc {
 
         }
         tty.driver.write(&buf).await;
-        len
+        Ok(len)
     }
     async fn poll_in(&self, tty: &TtyStruct) -> bool {
         tty.driver.poll_in().await
@@ -164,6 +212,8 @@ pub struct TtyStruct {
     pub ldisc: SyncUnsafeCell<Arc<dyn LineDiscPolicy>>,
     // 前台进程组
     pub fg_pgid: RwLock<u32>,
+    // 以该终端为控制终端的会话
+    pub session: RwLock<Option<usize>>,
     // 终端窗口尺寸
     pub win_size: RwLock<WinSize>,
     // 行缓冲区
@@ -185,6 +235,7 @@ impl TtyStruct {
             n_tty_mode: RwLock::new(TtyLineDiscMode::Raw),
             ldisc: SyncUnsafeCell::new(Arc::new(TtyLineDisc)),
             fg_pgid: RwLock::new(1),
+            session: RwLock::new(None),
             win_size: RwLock::new(WinSize::new()),
             lbuffer: new_shared(LineBuffer::new(4096)),
             major,
@@ -201,6 +252,77 @@ impl TtyStruct {
             f(&mut (*self.ldisc.get()))
         }
     }
+
+    /// ISIG 打开时，把输入中的 INTR/QUIT/SUSP 字符转换成发给前台进程组的信号，
+    /// 并从 buf 中去掉这些字符，返回剩余的字符数
+    pub fn handle_isig(&self, buf: &mut [u8]) -> usize {
+        let termios = *self.termios.read();
+        if !termios.is_isig() {
+            return buf.len();
+        }
+        let mut len = 0;
+        for i in 0..buf.len() {
+            let c = buf[i];
+            // 控制字符为 0 表示该功能被禁用
+            let signo = match c {
+                0 => None,
+                c if c == termios.cc[VINTR] => Some(SigNom::SIGINT),
+                c if c == termios.cc[VQUIT] => Some(SigNom::SIGQUIT),
+                c if c == termios.cc[VSUSP] => Some(SigNom::SIGTSTP),
+                _ => None,
+            };
+            match signo {
+                Some(signo) => kill_pgrp(*self.fg_pgid.read() as usize, signo),
+                None => {
+                    buf[len] = c;
+                    len += 1;
+                }
+            }
+        }
+        len
+    }
+
+    /// 后台进程组访问控制终端时的作业控制检查。
+    /// 读终端会向调用者所在进程组发 SIGTTIN；TOSTOP 打开时写终端会发 SIGTTOU。
+    fn job_control_check(&self, signo: SigNom) -> SysResult {
+        let Some(sid) = *self.session.read() else {
+            return Ok(());
+        };
+        let Some(task) = current_task() else {
+            return Ok(());
+        };
+        let pgid = task.get_pgid();
+        if task.get_sid() != sid || pgid == *self.fg_pgid.read() as usize {
+            return Ok(());
+        }
+        if signo == SigNom::SIGTTOU && !self.termios.read().is_tostop() {
+            return Ok(());
+        }
+        let ignored = task.get_blocked().have(signo as usize)
+            || task.handler.lock().fetch_signal_handler(signo as usize).sa_type == SigHandlerType::IGNORE;
+        if ignored {
+            // 信号被忽略时：读返回 EIO，写直接放行
+            return match signo {
+                SigNom::SIGTTIN => Err(Errno::EIO),
+                _ => Ok(()),
+            };
+        }
+        // 孤儿进程组停止后不会再有人发 SIGCONT，不发信号而是直接返回 EIO
+        if is_orphaned_proc_group(pgid) {
+            return Err(Errno::EIO);
+        }
+        kill_pgrp(pgid, signo);
+        // 进程被 SIGCONT 唤醒后重新执行读写，再次检查是否已经回到前台
+        Err(Errno::ERESTARTSYS)
+    }
+
+    /// 解除终端和会话的关联，向前台进程组发送 SIGHUP 和 SIGCONT
+    fn hangup(&self) {
+        let fg_pgid = *self.fg_pgid.read() as usize;
+        *self.session.write() = None;
+        kill_pgrp(fg_pgid, SigNom::SIGHUP);
+        kill_pgrp(fg_pgid, SigNom::SIGCONT);
+    }
 }
 
 
@@ -226,12 +348,14 @@ impl Device for TtyStruct {
 
 #[async_trait]
 impl CharDevice for TtyStruct {
-    async fn read(&self, buf: &mut [u8]) -> usize {
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+        self.job_control_check(SigNom::SIGTTIN)?;
         self.with_ldisc( |ldisc| {
             ldisc.read(self, buf)
         }).await
     }
-    async fn write(&self, buf: &[u8]) -> usize {
+    async fn write(&self, buf: &[u8]) -> SysResult<usize> {
+        self.job_control_check(SigNom::SIGTTOU)?;
         self.with_ldisc( |ldisc| {
             ldisc.write(self, buf)
         }).await
@@ -262,13 +386,68 @@ impl CharDevice for TtyStruct {
                     Ok(0)
                 }
                 TtyIoctlCmd::TIOCSPGRP => {
-                    let user_pgid_ref: &u32 = user_ref(arg.into())?.ok_or(Errno::EFAULT)?;
-                    *self.fg_pgid.write() = *user_pgid_ref;
+                    let pgid = *user_ref::<u32>(arg.into())?.ok_or(Errno::EFAULT)?;
+                    if (pgid as i32) < 0 {
+                        return Err(Errno::EINVAL);
+                    }
+                    let group = get_target_proc_group(pgid as usize).ok_or(Errno::ESRCH)?;
+                    // 成为控制终端后，前台进程组只能设置为同一会话中的进程组
+                    if let Some(sid) = *self.session.read() {
+                        let task = current_task().unwrap();
+                        if task.get_sid() != sid {
+                            return Err(Errno::ENOTTY);
+                        }
+                        let same_session = group
+                            .iter()
+                            .filter_map(|pid| get_task_by_pid(*pid))
+                            .all(|t| t.get_sid() == sid);
+                        if !same_session {
+                            return Err(Errno::EPERM);
+                        }
+                    }
+                    *self.fg_pgid.write() = pgid;
+                    Ok(0)
+                }
+                TtyIoctlCmd::TIOCSCTTY => {
+                    let task = current_task().unwrap();
+                    let sid = task.get_sid();
+                    if task.is_session_leader() && *self.session.read() == Some(sid) {
+                        return Ok(0);
+                    }
+                    // 只有还没有控制终端的会话首进程才能获得控制终端
+                    if !task.is_session_leader() || session_has_ctty(sid) {
+                        return Err(Errno::EPERM);
+                    }
+                    let mut session = self.session.write();
+                    // 终端已经属于别的会话，只有 arg == 1 并且有 CAP_SYS_ADMIN 时才能抢占
+                    if session.is_some() && (arg != 1 || !capable(CapSet::CAP_SYS_ADMIN)) {
+                        return Err(Errno::EPERM);
+                    }
+                    *session = Some(sid);
+                    *self.fg_pgid.write() = task.get_pgid() as u32;
+                    Ok(0)
+                }
+                TtyIoctlCmd::TIOCNOTTY => {
+                    let task = current_task().unwrap();
+                    if *self.session.read() != Some(task.get_sid()) {
+                        return Err(Errno::ENOTTY);
+                    }
+                    if task.is_session_leader() {
+                        self.hangup();
+                    }
+                    Ok(0)
+                }
+                TtyIoctlCmd::TIOCGSID => {
+                    let sid = self.session.read().ok_or(Errno::ENOTTY)?;
+                    let user_sid_ptr = user_ref_mut::<u32>(arg.into())?.ok_or(Errno::EFAULT)?;
+                    *user_sid_ptr = sid as u32;
                     Ok(0)
                 }
                 TtyIoctlCmd::TIOCSWINSZ => {
                     let user_winsize_ref: &WinSize = user_ref(arg.into())?.ok_or(Errno::EFAULT)?;
                     *self.win_size.write() = *user_winsize_ref;
+                    // 窗口大小改变，通知前台进程组
+                    kill_pgrp(*self.fg_pgid.read() as usize, SigNom::SIGWINCH);
                     Ok(0)
                 }
                 TtyIoctlCmd::TCSBRK => Ok(0),
@@ -289,4 +468,13 @@ impl CharDevice for TtyStruct {
             ldisc.poll_out(self)
         }).await
     }
+    fn hangup_session(&self, sid: usize) {
+        if *self.session.read() == Some(sid) {
+            self.hangup();
+        }
+    }
+
+    fn ctty_session(&self) -> Option<usize> {
+        *self.session.read()
+    }
 }
\ No newline at end of file
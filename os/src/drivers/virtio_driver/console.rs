@@ -0,0 +1,62 @@
+//! virtio-console 设备驱动
+//!
+//! 每个端口实现 `UartDriver`，由 `DeviceManager` 套上 `SerialDriver` 和 `TtyStruct`
+//! 注册为 hvc 设备，与 ns16550a 上的内核日志互不干扰。
+
+use virtio_drivers::{device::console::VirtIOConsole, transport::Transport, Hal};
+
+use crate::{
+    drivers::{device::dev_core::PhysDriver, tty::serial::UartDriver},
+    sync::SpinNoIrqLock,
+};
+
+pub struct VirtIoConsoleDev<H: Hal, T: Transport> {
+    inner: SpinNoIrqLock<VirtIOConsole<H, T>>,
+    irq_number: Option<usize>,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIoConsoleDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIoConsoleDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIoConsoleDev<H, T> {
+    pub fn new(transport: T, irq_number: Option<usize>) -> Self {
+        Self {
+            inner: SpinNoIrqLock::new(
+                VirtIOConsole::<H, T>::new(transport).expect("VirtIOConsole create failed"),
+            ),
+            irq_number,
+        }
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> UartDriver for VirtIoConsoleDev<H, T> {
+    fn getc(&self) -> u8 {
+        loop {
+            if let Ok(Some(c)) = self.inner.lock().recv(true) {
+                return c;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn putc(&self, c: u8) {
+        let _ = self.inner.lock().send(c);
+    }
+
+    fn poll_in(&self) -> bool {
+        let mut inner = self.inner.lock();
+        // 中断处理路径也走这里，顺带清掉设备的中断状态
+        let _ = inner.ack_interrupt();
+        matches!(inner.recv(false), Ok(Some(_)))
+    }
+
+    fn poll_out(&self) -> bool {
+        true
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> PhysDriver for VirtIoConsoleDev<H, T> {
+    fn irq_number(&self) -> Option<usize> {
+        self.irq_number
+    }
+}
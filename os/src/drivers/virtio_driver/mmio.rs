@@ -0,0 +1,33 @@
+//! virtio-mmio 设备枚举
+
+use core::ptr::NonNull;
+
+use alloc::vec::Vec;
+use flat_device_tree::Fdt;
+use log::info;
+use virtio_drivers::transport::{
+    mmio::{MmioTransport, VirtIOHeader},
+    DeviceType, Transport,
+};
+
+use crate::hal::config::KERNEL_ADDR_OFFSET;
+
+/// 枚举设备树中所有 virtio-mmio 节点，建立 transport 并记下中断号。
+///
+/// 每个节点只能建立一次 transport：transport 被丢弃时会复位设备，
+/// 多个驱动各自枚举会把属于别的驱动的设备复位掉。
+pub fn probe_virtio_mmio(fdt: &Fdt) -> Vec<(DeviceType, MmioTransport<'static>, Option<usize>)> {
+    fdt.all_nodes()
+        .filter(| node | node.compatible().map(|c| c.all().any( |s| s == "virtio,mmio" )).unwrap_or(false) )
+        .filter_map(| node | {
+            let mmio_range = node.reg().next()?;
+            let base_va = mmio_range.starting_address as usize + KERNEL_ADDR_OFFSET;
+            let header = NonNull::new(base_va as *mut VirtIOHeader)?;
+            // 没有挂设备的槽位 device id 为 0，建立 transport 会失败
+            let transport = unsafe { MmioTransport::new(header, mmio_range.size?) }.ok()?;
+            let device_type = transport.device_type();
+            info!("VirtIO {:?} at {:#x}", device_type, mmio_range.starting_address as usize);
+            Some((device_type, transport, node.interrupts().next()))
+        })
+        .collect()
+}
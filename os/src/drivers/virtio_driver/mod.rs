@@ -1,8 +1,17 @@
 mod blk;
+mod console;
+mod mmio;
+mod net;
 mod pci;
 pub mod probe;
+mod rng;
 
 pub use blk::*;
+pub use console::*;
+pub use mmio::probe_virtio_mmio;
+pub use net::*;
+pub use pci::probe_virtio_pci;
+pub use rng::*;
 use core::ptr::NonNull;
 use log::info;
 use lwext4_rust::bindings::printf;
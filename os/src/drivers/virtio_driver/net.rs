@@ -0,0 +1,127 @@
+//! virtio-net 设备驱动
+//!
+//! 收包由中断驱动：中断处理函数把设备收到的帧搬进软件接收队列并唤醒网络栈的轮询任务，
+//! 网络栈轮询时从队列里取；没有中断的设备（如 PCI 上的网卡）在取帧时直接轮询设备。
+
+use core::task::Waker;
+
+use alloc::{collections::vec_deque::VecDeque, vec::Vec};
+use virtio_drivers::{device::net::VirtIONet, transport::Transport, Hal};
+
+use crate::{
+    drivers::{
+        device::{dev_core::PhysDriver, irq::HandleHardIrq, NetDevice},
+        DevResult,
+    },
+    sync::SpinNoIrqLock,
+};
+
+use super::as_dev_err;
+
+/// 收发队列的长度
+const NET_QUEUE_SIZE: usize = 16;
+/// 每个接收缓冲区的长度，足够放下一个以太网帧和 virtio-net 头
+const NET_BUF_LEN: usize = 2048;
+/// 以太网帧的最大长度（不含 FCS）
+const ETHERNET_MAX_FRAME_LEN: usize = 1514;
+/// 软件接收队列最多缓存的帧数，超出后丢弃最旧的帧
+const RX_RING_CAPACITY: usize = 256;
+
+pub struct VirtIoNetDev<H: Hal, T: Transport> {
+    inner: SpinNoIrqLock<VirtIONet<H, T, NET_QUEUE_SIZE>>,
+    rx_ring: SpinNoIrqLock<VecDeque<Vec<u8>>>,
+    /// 收包中断到来时唤醒，由网络栈的轮询任务注册
+    rx_waker: SpinNoIrqLock<Option<Waker>>,
+    irq_number: Option<usize>,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIoNetDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIoNetDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIoNetDev<H, T> {
+    pub fn new(transport: T, irq_number: Option<usize>) -> Self {
+        let mut net = VirtIONet::<H, T, NET_QUEUE_SIZE>::new(transport, NET_BUF_LEN)
+            .expect("VirtIONet create failed");
+        if irq_number.is_some() {
+            net.enable_interrupts();
+        } else {
+            net.disable_interrupts();
+        }
+        Self {
+            inner: SpinNoIrqLock::new(net),
+            rx_ring: SpinNoIrqLock::new(VecDeque::new()),
+            rx_waker: SpinNoIrqLock::new(None),
+            irq_number,
+        }
+    }
+
+    /// 把设备中已收到的帧全部搬进软件接收队列
+    fn drain_rx(&self, net: &mut VirtIONet<H, T, NET_QUEUE_SIZE>) {
+        let mut ring = self.rx_ring.lock();
+        while net.can_recv() {
+            let Ok(rx_buf) = net.receive() else { break };
+            if ring.len() >= RX_RING_CAPACITY {
+                ring.pop_front();
+            }
+            ring.push_back(rx_buf.packet().to_vec());
+            if net.recycle_rx_buffer(rx_buf).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> NetDevice for VirtIoNetDev<H, T> {
+    fn mac_address(&self) -> [u8; 6] {
+        self.inner.lock().mac_address()
+    }
+
+    fn max_frame_len(&self) -> usize {
+        ETHERNET_MAX_FRAME_LEN
+    }
+
+    fn can_send(&self) -> bool {
+        self.inner.lock().can_send()
+    }
+
+    fn recv(&self) -> Option<Vec<u8>> {
+        if let Some(frame) = self.rx_ring.lock().pop_front() {
+            return Some(frame);
+        }
+        self.drain_rx(&mut self.inner.lock());
+        self.rx_ring.lock().pop_front()
+    }
+
+    fn send(&self, frame: &[u8]) -> DevResult {
+        let mut net = self.inner.lock();
+        let mut tx_buf = net.new_tx_buffer(frame.len());
+        tx_buf.packet_mut().copy_from_slice(frame);
+        net.send(tx_buf).map_err(as_dev_err)
+    }
+
+    fn register_rx_waker(&self, waker: &Waker) -> bool {
+        if self.irq_number.is_none() {
+            return false;
+        }
+        *self.rx_waker.lock() = Some(waker.clone());
+        true
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> HandleHardIrq for VirtIoNetDev<H, T> {
+    fn handle_irq(&self) {
+        let mut net = self.inner.lock();
+        let _ = net.ack_interrupt();
+        self.drain_rx(&mut net);
+        drop(net);
+        if let Some(waker) = self.rx_waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> PhysDriver for VirtIoNetDev<H, T> {
+    fn irq_number(&self) -> Option<usize> {
+        self.irq_number
+    }
+}
@@ -2,7 +2,7 @@ use crate::{drivers::device::dev_core::{PhysDriver, PhysDriverProbe}, hal::confi
 
 use super::probe::virtio_device;
 use super::VirtIoHalImpl;
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use flat_device_tree::{node::FdtNode, standard_nodes::Compatible, Fdt};
 use log::info;
 use zerocopy::IntoBytes;
@@ -268,3 +268,40 @@ pub fn allocate_bars(
         status, command
     );
 }
+
+/// 枚举 PCI 总线上所有 virtio 设备，为它们分配 BAR 并建立 transport。
+///
+/// 整条总线只能枚举一次：每次枚举都会从头分配 BAR 地址，
+/// 多个驱动各自枚举会给不同设备分到重叠的地址。
+pub fn probe_virtio_pci(fdt: &Fdt) -> Vec<(DeviceType, PciTransport)> {
+    let mut transports = Vec::new();
+    let (pci_node, cam) = if let Some(node) = fdt.find_compatible(&["pci-host-ecam-generic"]) {
+        (node, Cam::Ecam)
+    } else if let Some(node) = fdt.find_compatible(&["pci-host-cam-generic"]) {
+        (node, Cam::MmioCam)
+    } else {
+        return transports;
+    };
+    info!("Found PCI node: {}", pci_node.name);
+    let mut allocator = PciMemory32Allocator::for_pci_ranges(&pci_node);
+    for region in pci_node.reg() {
+        let mut pci_root = PciRoot::new(unsafe {
+            MmioCam::new(
+                (region.starting_address as usize + DEVICE_ADDR_OFFSET) as *mut u8,
+                cam,
+            )
+        });
+        for (device_function, info) in pci_root.enumerate_bus(0) {
+            let Some(virtio_type) = virtio_device_type(&info) else { continue };
+            allocate_bars(&mut pci_root, device_function, &mut allocator);
+            match PciTransport::new::<VirtIoHalImpl, _>(&mut pci_root, device_function) {
+                Ok(transport) => {
+                    info!("  VirtIO {:?} at {}", virtio_type, device_function);
+                    transports.push((virtio_type, transport));
+                }
+                Err(e) => info!("  VirtIO {:?} at {}: transport error {:?}", virtio_type, device_function, e),
+            }
+        }
+    }
+    transports
+}
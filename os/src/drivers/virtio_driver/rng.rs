@@ -0,0 +1,176 @@
+//! virtio-entropy 设备驱动
+//!
+//! virtio-drivers 没有提供熵设备，这里直接在 `Transport` 上实现：
+//! 只有一个 requestq，每次提交一个设备可写的缓冲区，轮询 used ring 等设备填满后取回。
+//! 设备读到的数据通过 `register_hwrng` 交给内核 CRNG。
+
+use core::{marker::PhantomData, sync::atomic::{fence, Ordering}};
+
+use virtio_drivers::{transport::{DeviceStatus, Transport}, BufferDirection, Hal, PAGE_SIZE};
+
+use crate::{
+    drivers::device::dev_core::PhysDriver,
+    sync::SpinNoIrqLock,
+    utils::HwRng,
+};
+
+/// 设备只有一个 requestq
+const REQUEST_QUEUE: u16 = 0;
+/// 每次只提交一个缓冲区，队列长度取 2 即可
+const QUEUE_SIZE: usize = 2;
+/// 队列与数据缓冲区共占两页：第一页放描述符表、available ring 和数据缓冲区，
+/// 第二页放 used ring（legacy 布局要求 used ring 按页对齐）
+const QUEUE_PAGES: usize = 2;
+const DESC_OFFSET: usize = 0;
+const AVAIL_OFFSET: usize = DESC_OFFSET + core::mem::size_of::<Descriptor>() * QUEUE_SIZE;
+const BUF_OFFSET: usize = PAGE_SIZE / 2;
+const USED_OFFSET: usize = PAGE_SIZE;
+/// 单次请求的最大字节数
+const RNG_BUF_LEN: usize = 64;
+/// 轮询 used ring 的上限，设备不响应时放弃本次请求
+const POLL_LIMIT: usize = 1 << 24;
+
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+const VIRTQ_AVAIL_F_NO_INTERRUPT: u16 = 1;
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+struct VirtIoRngInner<T: Transport> {
+    transport: T,
+    /// 队列区域的内核虚拟地址与物理地址
+    queue_va: usize,
+    queue_pa: usize,
+    avail_idx: u16,
+    last_used_idx: u16,
+}
+
+pub struct VirtIoRngDev<H: Hal, T: Transport> {
+    inner: SpinNoIrqLock<VirtIoRngInner<T>>,
+    _hal: PhantomData<H>,
+}
+
+unsafe impl<H: Hal, T: Transport> Send for VirtIoRngDev<H, T> {}
+unsafe impl<H: Hal, T: Transport> Sync for VirtIoRngDev<H, T> {}
+
+impl<H: Hal, T: Transport> VirtIoRngDev<H, T> {
+    pub fn new(mut transport: T) -> Self {
+        transport.set_status(DeviceStatus::empty());
+        transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+        let features = transport.read_device_features();
+        transport.write_driver_features(features & VIRTIO_F_VERSION_1);
+        transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK);
+        transport.set_guest_page_size(PAGE_SIZE as u32);
+
+        let (queue_pa, queue_ptr) = H::dma_alloc(QUEUE_PAGES, BufferDirection::DeviceToDriver);
+        let queue_va = queue_ptr.as_ptr() as usize;
+        unsafe { core::ptr::write_bytes(queue_va as *mut u8, 0, QUEUE_PAGES * PAGE_SIZE) };
+        // 轮询模式，不需要设备发中断
+        unsafe { ((queue_va + AVAIL_OFFSET) as *mut u16).write_volatile(VIRTQ_AVAIL_F_NO_INTERRUPT) };
+
+        let size = (QUEUE_SIZE as u32).min(transport.max_queue_size(REQUEST_QUEUE));
+        transport.queue_set(
+            REQUEST_QUEUE,
+            size,
+            queue_pa + DESC_OFFSET,
+            queue_pa + AVAIL_OFFSET,
+            queue_pa + USED_OFFSET,
+        );
+        transport.set_status(
+            DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK | DeviceStatus::DRIVER_OK,
+        );
+
+        Self {
+            inner: SpinNoIrqLock::new(VirtIoRngInner {
+                transport,
+                queue_va,
+                queue_pa,
+                avail_idx: 0,
+                last_used_idx: 0,
+            }),
+            _hal: PhantomData,
+        }
+    }
+}
+
+impl<T: Transport> VirtIoRngInner<T> {
+    /// 提交一次请求并等待设备填充，返回读到的字节数
+    fn request(&mut self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(RNG_BUF_LEN);
+        let slot = self.avail_idx as usize % QUEUE_SIZE;
+        unsafe {
+            let desc = (self.queue_va + DESC_OFFSET) as *mut Descriptor;
+            desc.add(slot).write_volatile(Descriptor {
+                addr: (self.queue_pa + BUF_OFFSET) as u64,
+                len: len as u32,
+                flags: VIRTQ_DESC_F_WRITE,
+                next: 0,
+            });
+            // avail ring: flags, idx, ring[QUEUE_SIZE]
+            let avail = (self.queue_va + AVAIL_OFFSET) as *mut u16;
+            avail.add(2 + slot).write_volatile(slot as u16);
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            avail.add(1).write_volatile(self.avail_idx);
+        }
+        fence(Ordering::SeqCst);
+        self.transport.notify(REQUEST_QUEUE);
+
+        // used ring: flags, idx, ring[QUEUE_SIZE]
+        let used_idx = (self.queue_va + USED_OFFSET + 2) as *const u16;
+        let mut polls = 0;
+        while unsafe { used_idx.read_volatile() } == self.last_used_idx {
+            polls += 1;
+            if polls >= POLL_LIMIT {
+                return 0;
+            }
+            core::hint::spin_loop();
+        }
+        fence(Ordering::SeqCst);
+        let elem = unsafe {
+            ((self.queue_va + USED_OFFSET + 4) as *const UsedElem)
+                .add(self.last_used_idx as usize % QUEUE_SIZE)
+                .read_volatile()
+        };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+
+        let got = (elem.len as usize).min(len);
+        let src = unsafe { core::slice::from_raw_parts((self.queue_va + BUF_OFFSET) as *const u8, got) };
+        buf[..got].copy_from_slice(src);
+        got
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> HwRng for VirtIoRngDev<H, T> {
+    fn read(&self, buf: &mut [u8]) -> usize {
+        let mut inner = self.inner.lock();
+        let mut total = 0;
+        while total < buf.len() {
+            let len = inner.request(&mut buf[total..]);
+            if len == 0 {
+                break;
+            }
+            total += len;
+        }
+        total
+    }
+}
+
+impl<H: Hal + 'static, T: Transport + 'static> PhysDriver for VirtIoRngDev<H, T> {
+    fn irq_number(&self) -> Option<usize> {
+        // 轮询模式
+        None
+    }
+}
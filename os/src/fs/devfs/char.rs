@@ -37,11 +37,25 @@ impl FileTrait for CharDev {
         if user_buf.is_empty() {
             return Ok(0);
         }
-        Ok(self.metadata.inode.read_dirctly(0, user_buf).await)
+        self.metadata
+            .inode
+            .clone()
+            .downcast_arc::<CharDevInode>()
+            .ok_or(Errno::ENODEV)?
+            .dev
+            .read(user_buf)
+            .await
     }
     async fn write(&self, user_buf: &[u8]) -> SysResult<usize> {
         assert!(self.metadata.flags.read().writable());
-        Ok(self.metadata.inode.write_directly(0, user_buf).await)
+        self.metadata
+            .inode
+            .clone()
+            .downcast_arc::<CharDevInode>()
+            .ok_or(Errno::ENODEV)?
+            .dev
+            .write(user_buf)
+            .await
     }
     fn fstat(&self, stat: &mut Kstat) -> SysResult {
         stat.st_mode = S_IFCHR;
@@ -80,17 +94,24 @@ pub struct CharDevInode {
 
 impl CharDevInode {
     pub fn new() -> Arc<dyn InodeTrait> {
+        Self::with_dev(
+            "/dev/tty",
+            DEVICE_MANAGER.read()
+                .get_char_dev(CharMajorNum::Tty, 64)
+                .unwrap()
+                // .as_char()
+                // .unwrap()
+        )
+    }
+    /// 以 DeviceManager 中已注册的字符设备建立节点，如 /dev/hvc0
+    pub fn with_dev(path: &str, dev: Arc<dyn CharDevice>) -> Arc<dyn InodeTrait> {
         Arc::new(Self {
             metadata: InodeMeta::new(
                 InodeType::CharDevice,
                 0,
-                "/dev/tty",
+                path,
             ),
-            dev: DEVICE_MANAGER.read()
-                .get_char_dev(CharMajorNum::Tty, 64)
-                .unwrap()
-                // .as_char()
-                // .unwrap()
+            dev,
         })
     }
     pub fn poll_in(&self) -> impl Future<Output = bool> + use<'_> {
@@ -111,11 +132,17 @@ impl InodeTrait for CharDevInode {
         &self.metadata
     }
 
+    async fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        self.dev.read(buf).await.unwrap_or(0)
+    }
+    async fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        self.dev.write(buf).await.unwrap_or(0)
+    }
     async fn read_dirctly(&self, _offset: usize, buf: &mut [u8]) -> usize {
-        self.dev.read(buf).await
+        self.dev.read(buf).await.unwrap_or(0)
     }
     async fn write_directly(&self, _offset: usize, buf: &[u8]) -> usize {
-        self.dev.write(buf).await
+        self.dev.write(buf).await.unwrap_or(0)
     }
     fn get_page_cache(&self) -> Option<Arc<PageCache>> {
         None
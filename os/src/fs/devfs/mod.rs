@@ -1,6 +1,9 @@
 pub mod char;
 mod dev_loop;
+#[cfg(feature = "net_pcap")]
+mod netcap;
 mod null;
+pub mod pts;
 mod root;
 mod rtc;
 pub mod tty;
@@ -9,6 +12,8 @@ mod zero;
 pub mod superblock;
 
 // use dev_loop::{DevLoop, DEVLOOP};
+#[cfg(feature = "net_pcap")]
+pub use netcap::*;
 pub use null::*;
 pub use rtc::*;
 pub use tty::*;
@@ -0,0 +1,86 @@
+use crate::{
+    fs::{page_cache::PageCache, Dirent, InodeMeta, InodeTrait, InodeType, Kstat, S_IFCHR},
+    net::pcap::NET_CAPTURE,
+    sync::{SpinNoIrqLock, TimeStamp},
+    utils::SysResult,
+};
+use alloc::boxed::Box;
+use alloc::{sync::Arc, vec::Vec};
+use async_trait::async_trait;
+
+/// /dev/netcap：以 pcap 格式读出抓到的帧，见 `net::pcap`
+///
+/// 每次从偏移 0 开始读都会先得到一个 pcap 文件头，之后是还没有被读走的记录；
+/// 记录读完时返回 0，所以 `cat` 会在取走当前缓冲的全部内容后结束
+pub struct DevNetcapInode {
+    metadata: InodeMeta,
+}
+
+impl DevNetcapInode {
+    pub fn new() -> Arc<dyn InodeTrait> {
+        Arc::new(Self {
+            metadata: InodeMeta::new(InodeType::CharDevice, 0, "/dev/netcap"),
+        })
+    }
+}
+
+#[async_trait]
+impl InodeTrait for DevNetcapInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn set_size(&self, _new_size: usize) -> SysResult {
+        Ok(())
+    }
+
+    fn fstat(&self) -> Kstat {
+        let mut stat = Kstat::new();
+        stat.st_mode = S_IFCHR;
+        stat.st_ino = self.metadata.ino as u64;
+        stat
+    }
+
+    fn look_up(&self, _path: &str) -> Option<Arc<dyn InodeTrait>> {
+        None
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        NET_CAPTURE.lock().read(offset, buf)
+    }
+
+    async fn read_dirctly(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.read_at(offset, buf).await
+    }
+
+    async fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    async fn write_directly(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn truncate(&self, _size: usize) -> usize {
+        0
+    }
+
+    async fn read_all(&self) -> SysResult<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn get_timestamp(&self) -> &SpinNoIrqLock<TimeStamp> {
+        &self.metadata.timestamp
+    }
+
+    fn get_page_cache(&self) -> Option<Arc<PageCache>> {
+        None
+    }
+
+    fn read_dents(&self) -> Option<Vec<Dirent>> {
+        None
+    }
+}
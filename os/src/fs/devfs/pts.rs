@@ -0,0 +1,379 @@
+//! devpts 文件系统和 /dev/ptmx
+//!
+//! 打开 /dev/ptmx 会分配一对新的 pty 并返回 master 端，slave 端出现在 /dev/pts/N。
+//! master 和 slave 的数据通路、锁状态都在 `drivers::tty::pty` 里。
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use log::info;
+
+use crate::{
+    drivers::{
+        device::dev_number::CharMajorNum,
+        tty::{
+            pty::{get_pty, pty_alloc, pty_indexes, Pty},
+            tty_core::{CharDevice, TtyIoctlCmd},
+        },
+    },
+    fs::{
+        dirent::build_dirents, AbsPath, Dentry, Dirent, FileMeta, FileTrait, InodeMeta, InodeTrait,
+        InodeType, Kstat, OpenFlags, SuperBlockTrait, S_IFCHR,
+    },
+    mm::user_ptr::{user_ref, user_ref_mut},
+    sync::{block_on, SpinNoIrqLock, TimeStamp},
+    utils::{downcast::Downcast, Errno, SysResult},
+};
+
+/// /dev/ptmx 的设备号为 (5, 2)
+const PTMX_MINOR: usize = 2;
+
+fn dev_id(major: CharMajorNum, minor: usize) -> u32 {
+    ((major as u32) << 8) | minor as u32
+}
+
+lazy_static! {
+    /// devpts 的超级块
+    pub static ref DEVPTS_SUPER_BLOCK: Arc<DevPtsSuperBlock> = Arc::new(DevPtsSuperBlock::new());
+}
+
+pub struct DevPtsSuperBlock {
+    root: Arc<DevPtsRootInode>,
+}
+
+impl DevPtsSuperBlock {
+    pub fn new() -> Self {
+        info!("init devpts superblock");
+        Self {
+            root: Arc::new(DevPtsRootInode {
+                metadata: InodeMeta::new(InodeType::Dir, 0, "/dev/pts"),
+            }),
+        }
+    }
+}
+
+impl SuperBlockTrait for DevPtsSuperBlock {
+    fn root_inode(&self) -> Arc<dyn InodeTrait> {
+        self.root.clone()
+    }
+    fn fs_stat(&self) -> crate::syscall::StatFs {
+        crate::syscall::StatFs::new()
+    }
+    fn ls(&self) {
+        self.root.read_dents().unwrap().iter().for_each(|x| {
+            println!("{}", x);
+        });
+    }
+    fn sync(&self) {
+        info!("devpts does not need to sync");
+    }
+}
+
+/// /dev/pts 目录，内容就是当前存在的 pty
+pub struct DevPtsRootInode {
+    metadata: InodeMeta,
+}
+
+#[async_trait]
+impl InodeTrait for DevPtsRootInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+    fn look_up(&self, path: &str) -> Option<Arc<dyn InodeTrait>> {
+        let index = AbsPath::new(String::from(path))
+            .get_filename()
+            .parse::<usize>()
+            .ok()?;
+        get_pty(index).map(|_| PtsInode::new(index))
+    }
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_ino = self.metadata.ino as u64;
+        res.st_mode = 16877;
+        res.st_nlink = 1;
+        res
+    }
+    fn get_timestamp(&self) -> &SpinNoIrqLock<TimeStamp> {
+        &self.metadata.timestamp
+    }
+    fn read_dents(&self) -> Option<Vec<Dirent>> {
+        let names: Vec<String> = pty_indexes().iter().map(|i| i.to_string()).collect();
+        let mut entries: Vec<(&str, u64, u8)> = alloc::vec![(".", 1, 4), ("..", 0, 4)];
+        for (i, name) in names.iter().enumerate() {
+            entries.push((name.as_str(), i as u64 + 2, 2));
+        }
+        Some(build_dirents(entries))
+    }
+}
+
+/// /dev/pts/N，只记录编号，每次使用时再去 pty 表里找
+pub struct PtsInode {
+    metadata: InodeMeta,
+    index: usize,
+}
+
+impl PtsInode {
+    pub fn new(index: usize) -> Arc<dyn InodeTrait> {
+        Arc::new(Self {
+            metadata: InodeMeta::new(InodeType::CharDevice, 0, &format!("/dev/pts/{}", index)),
+            index,
+        })
+    }
+}
+
+#[async_trait]
+impl InodeTrait for PtsInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_ino = self.metadata.ino as u64;
+        res.st_mode = S_IFCHR | 0o620;
+        res.st_nlink = 1;
+        res.st_rdev = dev_id(CharMajorNum::PtySlave, self.index);
+        res
+    }
+    fn get_timestamp(&self) -> &SpinNoIrqLock<TimeStamp> {
+        &self.metadata.timestamp
+    }
+    fn ioctl(&self, op: usize, arg: usize) -> SysResult<usize> {
+        let pty = get_pty(self.index).ok_or(Errno::EIO)?;
+        let cmd = TtyIoctlCmd::try_from(op).map_err(|_| Errno::ENOTTY)?;
+        block_on(async { pty.slave.ioctl(cmd, arg).await })
+    }
+}
+
+/// /dev/ptmx，本身不能读写，打开时换成新的 master
+pub struct DevPtmxInode {
+    metadata: InodeMeta,
+}
+
+impl DevPtmxInode {
+    pub fn new() -> Arc<dyn InodeTrait> {
+        Arc::new(Self {
+            metadata: InodeMeta::new(InodeType::CharDevice, 0, "/dev/ptmx"),
+        })
+    }
+}
+
+#[async_trait]
+impl InodeTrait for DevPtmxInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_ino = self.metadata.ino as u64;
+        res.st_mode = S_IFCHR | 0o666;
+        res.st_nlink = 1;
+        res.st_rdev = dev_id(CharMajorNum::TtyAux, PTMX_MINOR);
+        res
+    }
+    fn get_timestamp(&self) -> &SpinNoIrqLock<TimeStamp> {
+        &self.metadata.timestamp
+    }
+    fn ioctl(&self, _op: usize, _arg: usize) -> SysResult<usize> {
+        Err(Errno::ENOTTY)
+    }
+}
+
+/// 每个 master 独有的 inode，ioctl 通过它找到对应的 pty
+pub struct PtyMasterInode {
+    metadata: InodeMeta,
+    pty: Arc<Pty>,
+}
+
+#[async_trait]
+impl InodeTrait for PtyMasterInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_ino = self.metadata.ino as u64;
+        res.st_mode = S_IFCHR | 0o666;
+        res.st_nlink = 1;
+        res.st_rdev = dev_id(CharMajorNum::TtyAux, PTMX_MINOR);
+        res
+    }
+    fn get_timestamp(&self) -> &SpinNoIrqLock<TimeStamp> {
+        &self.metadata.timestamp
+    }
+    fn ioctl(&self, op: usize, arg: usize) -> SysResult<usize> {
+        let cmd = TtyIoctlCmd::try_from(op).map_err(|_| Errno::ENOTTY)?;
+        info!("[PtyMasterInode::ioctl] pts {} cmd: {:?}", self.pty.index, cmd);
+        match cmd {
+            TtyIoctlCmd::TIOCGPTN => {
+                let user_ptn = user_ref_mut::<u32>(arg.into())?.ok_or(Errno::EFAULT)?;
+                *user_ptn = self.pty.index as u32;
+                Ok(0)
+            }
+            TtyIoctlCmd::TIOCSPTLCK => {
+                let lock = *user_ref::<i32>(arg.into())?.ok_or(Errno::EFAULT)?;
+                self.pty.set_locked(lock != 0);
+                Ok(0)
+            }
+            // termios、窗口大小等都保存在 slave 上，master 和 slave 共用
+            _ => block_on(async { self.pty.slave.ioctl(cmd, arg).await }),
+        }
+    }
+}
+
+/// pty 的 master 端
+pub struct PtyMasterFile {
+    metadata: FileMeta,
+    pty: Arc<Pty>,
+    /// /dev/pts/N 的目录项，master 关闭时删除
+    pts_dentry: Option<Arc<Dentry>>,
+}
+
+impl PtyMasterFile {
+    pub fn new(flags: OpenFlags) -> SysResult<Arc<Self>> {
+        let pty = pty_alloc()?;
+        let inode = Arc::new(PtyMasterInode {
+            metadata: InodeMeta::new(InodeType::CharDevice, 0, "/dev/ptmx"),
+            pty: pty.clone(),
+        });
+        // devpts 的目录项只在初始化时建立，新分配的 pty 需要手动补上
+        let name = pty.index.to_string();
+        let pts_dentry = Dentry::get_dentry_from_path("/dev/pts")
+            .ok()
+            .and_then(|dir| dir.clone().bare_child(&name).or_else(|| dir.get_child(&name)));
+        Ok(Arc::new(Self {
+            metadata: FileMeta::new(flags, inode),
+            pty,
+            pts_dentry,
+        }))
+    }
+
+    fn nonblock(&self) -> bool {
+        self.metadata.flags.read().contains(OpenFlags::O_NONBLOCK)
+    }
+}
+
+impl Drop for PtyMasterFile {
+    fn drop(&mut self) {
+        if let Some(dentry) = self.pts_dentry.take() {
+            let _ = dentry.release_self();
+        }
+        self.pty.master_close();
+    }
+}
+
+#[async_trait]
+impl FileTrait for PtyMasterFile {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.pty.master_read(buf, self.nonblock()).await
+    }
+    async fn write(&self, buf: &[u8]) -> SysResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.pty.master_write(buf, self.nonblock()).await
+    }
+    fn abspath(&self) -> String {
+        "/dev/ptmx".to_string()
+    }
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = self.metadata.inode.fstat();
+        Ok(())
+    }
+    async fn pollin(&self) -> SysResult<bool> {
+        Ok(self.pty.master_poll_in().await)
+    }
+    async fn pollout(&self) -> SysResult<bool> {
+        Ok(self.pty.master_poll_out().await)
+    }
+}
+
+/// pty 的 slave 端，读写都经过 TtyStruct 的行规程
+pub struct PtySlaveFile {
+    metadata: FileMeta,
+    pty: Arc<Pty>,
+}
+
+impl PtySlaveFile {
+    pub fn new(inode: Arc<PtsInode>, flags: OpenFlags) -> SysResult<Arc<Self>> {
+        let pty = get_pty(inode.index).ok_or(Errno::EIO)?;
+        pty.slave_open()?;
+        Ok(Arc::new(Self {
+            metadata: FileMeta::new(flags, inode),
+            pty,
+        }))
+    }
+}
+
+impl Drop for PtySlaveFile {
+    fn drop(&mut self) {
+        self.pty.slave_close();
+    }
+}
+
+#[async_trait]
+impl FileTrait for PtySlaveFile {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.pty.slave.read(buf).await
+    }
+    async fn write(&self, buf: &[u8]) -> SysResult<usize> {
+        self.pty.slave.write(buf).await
+    }
+    fn abspath(&self) -> String {
+        format!("/dev/pts/{}", self.pty.index)
+    }
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = self.metadata.inode.fstat();
+        Ok(())
+    }
+    async fn pollin(&self) -> SysResult<bool> {
+        Ok(self.pty.slave.poll_in().await)
+    }
+    async fn pollout(&self) -> SysResult<bool> {
+        Ok(self.pty.slave.poll_out().await)
+    }
+}
+
+/// 打开 pty 相关的设备文件：/dev/ptmx 分配新的 master，/dev/pts/N 打开对应的 slave。
+/// 其他 inode 返回 None，按普通文件打开。
+pub fn pty_open(inode: &Arc<dyn InodeTrait>, flags: OpenFlags) -> Option<SysResult<Arc<dyn FileTrait>>> {
+    if inode.clone().downcast_arc::<DevPtmxInode>().is_some() {
+        return Some(PtyMasterFile::new(flags).map(|master| master as Arc<dyn FileTrait>));
+    }
+    let pts = inode.clone().downcast_arc::<PtsInode>()?;
+    Some(PtySlaveFile::new(pts, flags).map(|file| file as Arc<dyn FileTrait>))
+}
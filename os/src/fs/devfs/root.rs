@@ -1,11 +1,9 @@
 use async_trait::async_trait;
-use alloc::{boxed::Box, collections::btree_map::BTreeMap, string::String, sync::Arc, vec::Vec};
-#[cfg(any(feature = "board_qemu", feature = "2k1000la"))]
-use crate::fs::devfs::char::CharDevInode;
-#[cfg(feature = "vf2")]
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, format, string::String, sync::Arc, vec::Vec};
 use crate::fs::devfs::char::CharDevInode;
 use crate::{
-    fs::{devfs::{dev_loop::DevLoopInode, DevNullInode, DevRandomInode, DevRtcInode, DevTtyInode, DevZeroInode}, dirent::build_dirents, AbsPath, Dirent, InodeMeta, InodeTrait, InodeType, Kstat},
+    drivers::{device::dev_number::CharMajorNum, DEVICE_MANAGER},
+    fs::{devfs::{dev_loop::DevLoopInode, pts::{DevPtmxInode, DEVPTS_SUPER_BLOCK}, DevNullInode, DevRandomInode, DevRtcInode, DevTtyInode, DevZeroInode}, dirent::build_dirents, AbsPath, Dirent, InodeMeta, InodeTrait, InodeType, Kstat, SuperBlockTrait},
     sync::{Shared, SpinNoIrqLock, TimeStamp},
     utils::{Errno, SysResult},
 };
@@ -26,9 +24,20 @@ impl DevFsRootInode {
         children.insert("tty".into(), DevTtyInode::new());
         #[cfg(feature = "vf2")]
         children.insert("tty".into(), CharDevInode::new());
-        children.insert("urandom".into(), DevRandomInode::new());
+        children.insert("random".into(), DevRandomInode::new("/dev/random", true));
+        children.insert("urandom".into(), DevRandomInode::new("/dev/urandom", false));
         children.insert("zero".into(), DevZeroInode::new());
         children.insert("loop0".into(), DevLoopInode::new());
+        children.insert("ptmx".into(), DevPtmxInode::new());
+        children.insert("pts".into(), DEVPTS_SUPER_BLOCK.root_inode());
+        #[cfg(feature = "net_pcap")]
+        children.insert("netcap".into(), super::DevNetcapInode::new());
+        for ((major, minor), dev) in DEVICE_MANAGER.read().char_devs.iter() {
+            if *major == CharMajorNum::Hvc {
+                let path = format!("/dev/hvc{}", minor);
+                children.insert(format!("hvc{}", minor), CharDevInode::with_dev(&path, dev.clone()));
+            }
+        }
         Self {
             metadata: InodeMeta::new(
                 InodeType::Dir, 
@@ -108,8 +117,17 @@ impl InodeTrait for DevFsRootInode {
             ("tty", 4, 2),
             ("urandom", 5, 8),
             ("zero", 6, 8),
-            ("loop0", 7, 8)
+            ("loop0", 7, 8),
+            ("ptmx", 8, 2),
+            ("pts", 9, 4),
+            ("random", 10, 2)
         ];
+        let hvcs: Vec<String> = self.children.keys().filter(|name| name.starts_with("hvc")).cloned().collect();
+        for (i, name) in hvcs.iter().enumerate() {
+            entries.push((name.as_str(), 11 + i as u64, 2));
+        }
+        #[cfg(feature = "net_pcap")]
+        entries.push(("netcap", 11 + hvcs.len() as u64, 2));
         Some(build_dirents(entries))
     }
 }
@@ -2,7 +2,7 @@ use crate::{
     fs::{ffi::RenameFlags, Dirent, FileMeta, FileTrait, InodeMeta, InodeTrait, InodeType, Kstat, OpenFlags, S_IFCHR},
     mm::page::Page,
     sync::{SpinNoIrqLock, TimeStamp},
-    utils::{SysResult, RNG},
+    utils::{add_device_randomness, get_random_bytes, wait_for_random_bytes, SysResult},
 };
 use alloc::boxed::Box;
 use alloc::{
@@ -12,18 +12,25 @@ use alloc::{
 };
 use async_trait::async_trait;
 
+/// /dev/random 与 /dev/urandom
+///
+/// 两者输出同一个 CRNG；区别只在于 /dev/random 在 CRNG 就绪前会等待，
+/// /dev/urandom 则直接输出。写入的数据混入熵池但不计熵。
 pub struct DevRandomInode {
     metadata: InodeMeta,
+    /// 读之前是否等待 CRNG 就绪
+    wait_ready: bool,
 }
 
 impl DevRandomInode {
-    pub fn new() -> Arc<dyn InodeTrait> {
+    pub fn new(path: &str, wait_ready: bool) -> Arc<dyn InodeTrait> {
         Arc::new(Self {
             metadata: InodeMeta::new(
                 InodeType::CharDevice,
                 0,
-                "/dev/random",
+                path,
             ),
+            wait_ready,
         })
     }
 }
@@ -45,16 +52,20 @@ impl InodeTrait for DevRandomInode {
         Ok(())
     }
 
-    async fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
-        0
+    async fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        if self.wait_ready {
+            wait_for_random_bytes();
+        }
+        get_random_bytes(buf)
     }
 
-    async fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
-        0
+    async fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        add_device_randomness(buf);
+        buf.len()
     }
 
-    async fn write_directly(&self, _offset: usize, _buf: &[u8]) -> usize {
-        0
+    async fn write_directly(&self, offset: usize, buf: &[u8]) -> usize {
+        self.write_at(offset, buf).await
     }
 
     fn truncate(&self, _size: usize) -> usize {
@@ -86,7 +97,7 @@ impl InodeTrait for DevRandomInode {
         None
     }
 
-    async fn read_dirctly(&self, _offset: usize, _buf: &mut [u8]) -> usize {
-        0
+    async fn read_dirctly(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.read_at(offset, buf).await
     }
 }
@@ -1,8 +1,10 @@
 use core::cmp::min;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use crate::{
     fs::{
         ffi::{RenameFlags, MEMINFO},
+        fsnotify::{fsnotify, FsEventMask},
         AbsPath, Dirent, FileMeta, FileTrait, InodeTrait, Kstat, OpenFlags, SEEK_CUR, SEEK_END,
         SEEK_SET, S_IFCHR,
     },
@@ -26,6 +28,8 @@ pub struct NormalFile {
     pub abspath: String,                      // 文件的路径
     pub parent: Option<Weak<dyn InodeTrait>>, // 对父目录的弱引用
     pub metadata: FileMeta,
+    /// 由用户打开的文件会向 fsnotify 报告读写和关闭，内核内部打开的不报告
+    notify: AtomicBool,
 }
 
 impl NormalFile {
@@ -39,6 +43,29 @@ impl NormalFile {
             abspath: path,
             parent,
             metadata: FileMeta::new(flags, inode),
+            notify: AtomicBool::new(false),
+        }
+    }
+
+    /// 报告这次打开，此后文件上的读写和最终的关闭也会被报告
+    pub fn notify_open(&self) {
+        self.notify.store(true, Ordering::Relaxed);
+        self.fsnotify(FsEventMask::OPEN);
+    }
+
+    fn fsnotify(&self, mask: FsEventMask) {
+        if self.notify.load(Ordering::Relaxed) {
+            let isdir = self.metadata.inode.metadata()._type.is_dir();
+            fsnotify(&self.abspath, mask | FsEventMask::dir(isdir));
+        }
+    }
+}
+
+impl Drop for NormalFile {
+    fn drop(&mut self) {
+        match self.metadata.flags.read().writable() {
+            true => self.fsnotify(FsEventMask::CLOSE_WRITE),
+            false => self.fsnotify(FsEventMask::CLOSE_NOWRITE),
         }
     }
 }
@@ -74,6 +101,9 @@ impl FileTrait for NormalFile {
             "read file: {}, old_offset: {}, new_offset: {}",
             self.abspath, old_offset, new_offset
         );
+        if read_size > 0 {
+            self.fsnotify(FsEventMask::ACCESS);
+        }
 
         Ok(total_read_size)
     }
@@ -90,6 +120,9 @@ impl FileTrait for NormalFile {
 
         let read_size = self.metadata.inode.read_at(offset, buf).await;
         total_read_size += read_size;
+        if read_size > 0 {
+            self.fsnotify(FsEventMask::ACCESS);
+        }
 
         Ok(total_read_size)
     }
@@ -105,6 +138,9 @@ impl FileTrait for NormalFile {
         self.metadata.set_offset(old_offset + write_size);
         total_write_size += write_size;
         // info!("size = {} ============", self.metadata.inode.get_size());
+        if write_size > 0 {
+            self.fsnotify(FsEventMask::MODIFY);
+        }
 
         Ok(total_write_size)
     }
@@ -123,6 +159,9 @@ impl FileTrait for NormalFile {
 
         let write_size = self.metadata.inode.write_at(offset, buf).await;
         total_write_size += write_size;
+        if write_size > 0 {
+            self.fsnotify(FsEventMask::MODIFY);
+        }
 
         Ok(total_write_size)
     }
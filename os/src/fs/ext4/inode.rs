@@ -5,24 +5,28 @@ use crate::{
         page_cache::PageCache,
         root_inode,
         stat::as_inode_stat,
-        Dentry, Dirent, FileTrait, InodeMeta, InodeTrait, Kstat, StMode,
+        xattr::XattrFlags,
+        Dentry, Dirent, FileTrait, InodeMeta, InodeTrait, Kstat, ModeFlag, StMode,
     },
+    hal::config::PATH_MAX,
     sync::{block_on, new_shared, MutexGuard, NoIrqLock, Shared, SpinNoIrqLock, TimeStamp},
     syscall::fs::{GLOBAL_UMASK, SYS_OPENAT_MODE},
+    task::current_task,
     utils::{downcast::Downcast, Errno, SysResult},
 };
 use async_trait::async_trait;
-use core::{error, sync::atomic::Ordering};
+use core::{error, ffi::CStr, sync::atomic::Ordering};
 use log::{debug, error, info, warn};
 use lwext4_rust::{
     bindings::{
-        ext4_inode_stat, EXT4_DE_DIR, EXT4_DE_REG_FILE, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC,
-        SEEK_SET,
+        ext4_fsymlink, ext4_getxattr, ext4_inode_stat, ext4_listxattr, ext4_mode_set,
+        ext4_owner_set, ext4_readlink, ext4_removexattr, ext4_setxattr, EXT4_DE_DIR, EXT4_DE_REG_FILE, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, SEEK_SET,
     },
     file, Ext4File, Ext4InodeType,
 };
 
 use alloc::boxed::Box;
+use alloc::ffi::CString;
 use alloc::vec;
 use alloc::{
     string::{String, ToString},
@@ -60,6 +64,10 @@ impl Ext4Inode {
         if types == Ext4InodeType::EXT4_DE_DIR || types == Ext4InodeType::EXT4_INODE_MODE_DIRECTORY
         {
             file_size = 0;
+        } else if types == Ext4InodeType::EXT4_DE_SYMLINK {
+            // 符号链接的大小是目标路径的长度
+            let c_path = ext4file.lock().get_path();
+            file_size = ext4_readlink_path(&c_path).map_or(0, |target| target.len() as u64);
         } else {
             ext4file.lock().file_open(path, O_RDONLY);
             file_size = ext4file.lock().file_size();
@@ -71,6 +79,12 @@ impl Ext4Inode {
             file: ext4file,
             page_cache: page_cache.clone(),
         });
+        // 权限和所有者以磁盘上记录的为初始值，chmod/chown 之后由 sync_attr 写回
+        let disk_stat = inode.file.lock().fstat();
+        if let Ok(stat) = disk_stat {
+            *inode.metadata.i_mode.lock() = StMode::from(stat.st_mode);
+            inode.metadata.set_owner(stat.st_uid, stat.st_gid);
+        }
         // 修改 inode.page_cache
         if let Some(pg) = &inode.page_cache {
             pg.set_inode(inode.clone());
@@ -162,13 +176,16 @@ impl InodeTrait for Ext4Inode {
         let mut i_mode_origin = SYS_OPENAT_MODE.load(Ordering::Relaxed) as u32;
         let mask = GLOBAL_UMASK.load(Ordering::Relaxed);
         i_mode_origin = i_mode_origin & !mask;
-        let i_mode = StMode::from(i_mode_origin);
+        // 内核初始化时创建的文件和目录（/tmp、/etc 等）没有调用者，不做限制
+        if current_task().is_none() {
+            i_mode_origin = 0o777;
+        }
         // error!(
         //     "set path: {}, st_mode: {:?}, i_mode_origin: {:o}",
         //     &path, i_mode, i_mode_origin
         // );
         let ext4_inode = nf.clone().downcast_arc::<Ext4Inode>().unwrap();
-        *ext4_inode.metadata.i_mode.lock() = i_mode;
+        ext4_inode.metadata.set_mode(i_mode_origin);
         debug_point!("[ext4_inode] set st_mode end");
         bare_dentry.bind(nf.clone());
         if nf.is_valid() {
@@ -180,6 +197,29 @@ impl InodeTrait for Ext4Inode {
 
         Some(nf)
     }
+    /// 在 self 目录下创建指向 target 的符号链接
+    fn do_symlink(&self, bare_dentry: Arc<Dentry>, target: &str) -> SysResult<Arc<dyn InodeTrait>> {
+        if bare_dentry.is_valid() {
+            return Err(Errno::EEXIST);
+        }
+        let path = bare_dentry.get_abs_path();
+        info!("[do_symlink] {} -> {}", path, target);
+        let c_path = CString::new(path.as_str()).map_err(|_| Errno::EINVAL)?;
+        let c_target = CString::new(target).map_err(|_| Errno::EINVAL)?;
+        ext4_result(unsafe { ext4_fsymlink(c_target.as_ptr(), c_path.as_ptr()) })?;
+        let inode = Ext4Inode::new(&path, Ext4InodeType::EXT4_DE_SYMLINK, None);
+        bare_dentry.bind(inode.clone());
+        Ok(inode)
+    }
+
+    fn readlink(&self) -> SysResult<String> {
+        if !self.metadata._type.is_symlink() {
+            return Err(Errno::EINVAL);
+        }
+        let path = self.file.lock().get_path();
+        ext4_readlink_path(&path)
+    }
+
     /// 获取文件类型
     // fn node_type(&self) -> InodeType {
     //     self.metadata.file_type
@@ -334,6 +374,8 @@ impl InodeTrait for Ext4Inode {
                 Ext4InodeType::EXT4_DE_REG_FILE,
                 page_cache.clone(),
             ))
+        } else if file.check_inode_exist(path, Ext4InodeType::EXT4_DE_SYMLINK) {
+            Some(Ext4Inode::new(path, Ext4InodeType::EXT4_DE_SYMLINK, None))
         } else {
             None
         }
@@ -347,7 +389,7 @@ impl InodeTrait for Ext4Inode {
         debug!("[Ext4Inode] fstat size = {}", size);
         let mut file = self.file.lock();
         // let size = self.size();
-        match file.fstat() {
+        let mut kstat = match file.fstat() {
             Ok(mut stat) => {
                 let (atime, mtime, ctime) = self.metadata.timestamp.lock().get();
                 stat.st_mode += 0o1000;
@@ -359,7 +401,12 @@ impl InodeTrait for Ext4Inode {
                 stat.st_mode += 0o1000;
                 as_inode_stat(stat, atime, mtime, ctime, size)
             }
+        };
+        // 符号链接的权限位没有意义，总是 0777
+        if self.metadata._type.is_symlink() {
+            kstat.st_mode = ModeFlag::S_IFLNK.bits() | 0o777;
         }
+        kstat
     }
     /// 删除文件
     fn unlink(&self, valid_dentry: Arc<Dentry>) -> SysResult<usize> {
@@ -447,11 +494,10 @@ impl InodeTrait for Ext4Inode {
                     old_inode.metadata()._type.into(),
                     old_inode.get_page_cache(),
                 );
-                new_inode
-                    .clone()
-                    .get_page_cache()
-                    .unwrap()
-                    .set_inode(new_inode.clone());
+                // 符号链接没有页缓存
+                if let Some(cache) = new_inode.get_page_cache() {
+                    cache.set_inode(new_inode.clone());
+                }
                 new_inode.set_size(old_size);
                 new_dentry.bind(new_inode);
                 old_dentry.release_self();
@@ -478,4 +524,114 @@ impl InodeTrait for Ext4Inode {
         }
         Some(dir_entrys)
     }
+
+    fn getxattr(&self, name: &str) -> SysResult<Vec<u8>> {
+        let path = self.file.lock().get_path();
+        let mut size = 0usize;
+        // 先取得属性值的长度，再分配缓冲区读出
+        ext4_result(unsafe {
+            ext4_getxattr(
+                path.as_ptr(),
+                name.as_ptr() as *const _,
+                name.len(),
+                core::ptr::null_mut(),
+                0,
+                &mut size,
+            )
+        })?;
+        let mut value = vec![0u8; size];
+        ext4_result(unsafe {
+            ext4_getxattr(
+                path.as_ptr(),
+                name.as_ptr() as *const _,
+                name.len(),
+                value.as_mut_ptr() as *mut _,
+                value.len(),
+                &mut size,
+            )
+        })?;
+        value.truncate(size);
+        Ok(value)
+    }
+
+    /// 权限位和所有者写到磁盘上的 inode，文件类型位由 lwext4 保留
+    fn sync_attr(&self) -> SysResult {
+        let attr = self.metadata.attr();
+        let path = self.file.lock().get_path();
+        ext4_result(unsafe { ext4_mode_set(path.as_ptr(), attr.mode & 0o7777) })?;
+        ext4_result(unsafe { ext4_owner_set(path.as_ptr(), attr.uid, attr.gid) })
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> SysResult {
+        // lwext4 总是创建或覆盖，XATTR_CREATE/XATTR_REPLACE 在这里检查
+        if !flags.is_empty() {
+            match self.getxattr(name) {
+                Ok(_) if flags.contains(XattrFlags::XATTR_CREATE) => return Err(Errno::EEXIST),
+                Err(Errno::ENODATA) if flags.contains(XattrFlags::XATTR_REPLACE) => {
+                    return Err(Errno::ENODATA)
+                }
+                Ok(_) | Err(Errno::ENODATA) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let path = self.file.lock().get_path();
+        ext4_result(unsafe {
+            ext4_setxattr(
+                path.as_ptr(),
+                name.as_ptr() as *const _,
+                name.len(),
+                value.as_ptr() as *const _,
+                value.len(),
+            )
+        })
+    }
+
+    fn listxattr(&self) -> SysResult<Vec<String>> {
+        let path = self.file.lock().get_path();
+        let mut size = 0usize;
+        ext4_result(unsafe {
+            ext4_listxattr(path.as_ptr(), core::ptr::null_mut(), 0, &mut size)
+        })?;
+        let mut list = vec![0u8; size];
+        ext4_result(unsafe {
+            ext4_listxattr(
+                path.as_ptr(),
+                list.as_mut_ptr() as *mut _,
+                list.len(),
+                &mut size,
+            )
+        })?;
+        // 名字之间以 0 分隔
+        Ok(list[..size.min(list.len())]
+            .split(|&c| c == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect())
+    }
+
+    fn removexattr(&self, name: &str) -> SysResult {
+        let path = self.file.lock().get_path();
+        ext4_result(unsafe {
+            ext4_removexattr(path.as_ptr(), name.as_ptr() as *const _, name.len())
+        })
+    }
+}
+
+/// 读出 path 处符号链接的目标
+fn ext4_readlink_path(path: &CStr) -> SysResult<String> {
+    let mut buf = vec![0u8; PATH_MAX];
+    let mut len = 0usize;
+    ext4_result(unsafe {
+        ext4_readlink(path.as_ptr(), buf.as_mut_ptr() as *mut _, buf.len(), &mut len)
+    })?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| Errno::EINVAL)
+}
+
+/// lwext4 的返回值是正的错误码
+fn ext4_result(ret: i32) -> SysResult {
+    match ret {
+        0 => Ok(()),
+        e => Err(Errno::try_from(e as isize).unwrap_or(Errno::EIO)),
+    }
 }
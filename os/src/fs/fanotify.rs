@@ -1,34 +1,71 @@
-use alloc::{collections::BTreeMap, string::String, sync::Arc};
-use bitflags::bitflags;
-use lazy_static::lazy_static;
-use spin::Mutex;
+//! fanotify: 以打开的文件描述符报告事件的文件系统监视接口
+//!
+//! 标记挂在 fsnotify 上，对象标记只看路径本身（带 FAN_EVENT_ON_CHILD 时也看子项），
+//! 挂载点和文件系统标记看整棵子树。读取事件时在读者的进程里打开事件对象，
+//! 把新的 fd 放进 `fanotify_event_metadata`。
+//!
+//! 不支持权限事件和 FID 形式的报告，因此只能监视访问、修改、打开和关闭。
+
+use super::{FileTrait, InodeType, Kstat, OpenFlags};
+use crate::{
+    fs::{
+        fsnotify::{self, FsEvent, FsEventMask, FsnotifyGroup, FsnotifyMark},
+        open,
+        pipe::DummyInode,
+        FileMeta,
+    },
+    sync::{get_waker, SpinNoIrqLock},
+    task::{current_task, FdInfo, TaskControlBlock},
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct FanFlags: u32 {
-        const FAN_CLASS_NOTIF = 0x00000001;
-        const FAN_CLASS_CONTENT = 0x00000002;
-        const FAN_CLASS_PRE_CONTENT = 0x00000004;
-        const FAN_CLOEXEC = 0x00000008;
-        const FAN_NONBLOCK = 0x00000010;
-        const FAN_UNLIMITED_QUEUE = 0x00000020;
-        const FAN_UNLIMITED_MARKS = 0x00000040;
+        const FAN_CLASS_NOTIF = 0x00000000;
+        const FAN_CLASS_CONTENT = 0x00000004;
+        const FAN_CLASS_PRE_CONTENT = 0x00000008;
+        const FAN_CLOEXEC = 0x00000001;
+        const FAN_NONBLOCK = 0x00000002;
+        const FAN_UNLIMITED_QUEUE = 0x00000010;
+        const FAN_UNLIMITED_MARKS = 0x00000020;
+        const FAN_ENABLE_AUDIT = 0x00000040;
+        const FAN_REPORT_PIDFD = 0x00000080;
         const FAN_REPORT_TID = 0x00000100;
         const FAN_REPORT_FID = 0x00000200;
         const FAN_REPORT_DIR_FID = 0x00000400;
         const FAN_REPORT_NAME = 0x00000800;
-        const FAN_REPORT_PIDFD = 0x00001000;
-        // const FAN_REPORT_TARGET_FID =
+        const FAN_REPORT_TARGET_FID = 0x00001000;
     }
 }
 
 bitflags! {
+    /// 打开事件对象时使用的标志
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct FanEventFlags: u32 {
-        const O_RDONLY = 0x0000;
-        const O_WRONLY = 0x0001;
-        const O_RDWR = 0x0002;
-        const O_LARGEFILE = 0x0100;
+        const O_RDONLY = 0o0;
+        const O_WRONLY = 0o1;
+        const O_RDWR = 0o2;
+        const O_APPEND = 0o2000;
+        const O_NONBLOCK = 0o4000;
+        const O_DSYNC = 0o10000;
+        const O_SYNC = 0o4010000;
+        const O_LARGEFILE = 0o100000;
+        const O_NOATIME = 0o1000000;
+        const O_CLOEXEC = 0o2000000;
     }
 }
 
@@ -37,45 +74,314 @@ bitflags! {
     pub struct FanMarkFlags: u32 {
         const FAN_MARK_ADD = 0x00000001;
         const FAN_MARK_REMOVE = 0x00000002;
-        const FAN_MARK_FLUSH = 0x00000008;
-        const FAN_MARK_DONT_FOLLOW = 0x00000010;
-        const FAN_MARK_ONLYDIR = 0x00000020;
-        const FAN_MARK_MOUNT = 0x00000100;
+        const FAN_MARK_DONT_FOLLOW = 0x00000004;
+        const FAN_MARK_ONLYDIR = 0x00000008;
+        const FAN_MARK_MOUNT = 0x00000010;
         const FAN_MARK_IGNORED_MASK = 0x00000020;
         const FAN_MARK_IGNORED_SURV_MODIFY = 0x00000040;
-        const FAN_MARK_TARGET_FID = 0x00000200;
+        const FAN_MARK_FLUSH = 0x00000080;
+        const FAN_MARK_FILESYSTEM = 0x00000100;
     }
 }
-/// Represents a mark on a filesystem object.
-#[derive(Clone)]
-pub struct FanotifyMark {
+
+pub const FAN_ACCESS: u64 = 0x1;
+pub const FAN_MODIFY: u64 = 0x2;
+pub const FAN_CLOSE_WRITE: u64 = 0x8;
+pub const FAN_CLOSE_NOWRITE: u64 = 0x10;
+pub const FAN_OPEN: u64 = 0x20;
+pub const FAN_Q_OVERFLOW: u64 = 0x4000;
+/// 权限事件，需要监听者回写允许或拒绝
+pub const FAN_ALL_PERM_EVENTS: u64 = 0x70000;
+pub const FAN_EVENT_ON_CHILD: u64 = 0x08000000;
+pub const FAN_ONDIR: u64 = 0x40000000;
+/// 不需要 FID 报告就能监视的事件
+pub const FAN_FD_EVENTS: u64 =
+    FAN_ACCESS | FAN_MODIFY | FAN_CLOSE_WRITE | FAN_CLOSE_NOWRITE | FAN_OPEN;
+
+pub const FANOTIFY_METADATA_VERSION: u8 = 3;
+pub const FAN_NOFD: i32 = -1;
+/// 没有 FAN_UNLIMITED_QUEUE 时最多排队的事件数
+const FANOTIFY_MAX_EVENTS: usize = 16384;
+
+#[repr(C)]
+pub struct FanotifyEventMetadata {
+    pub event_len: u32,
+    pub vers: u8,
+    pub reserved: u8,
+    pub metadata_len: u16,
     pub mask: u64,
-    pub flags: FanMarkFlags,
+    pub fd: i32,
+    pub pid: i32,
+}
+
+struct FanotifyEvent {
+    mask: u64,
+    /// 溢出事件没有对象
+    path: Option<String>,
+    pid: usize,
+}
+
+struct FanotifyInner {
+    events: VecDeque<FanotifyEvent>,
+    waiters: Vec<Waker>,
 }
 
-/// Represents an fanotify group, which holds marks and an event queue.
-pub struct FanotifyGroup {
+pub struct Fanotify {
+    pub metadata: FileMeta,
     pub flags: FanFlags,
     pub event_f_flags: FanEventFlags,
-    /// Maps absolute path to a mark. A real implementation would use inode numbers.
-    pub marks: Mutex<BTreeMap<String, FanotifyMark>>,
-    // In a complete implementation, a pipe or other mechanism would be stored here
-    // to write events to the user.
+    group_id: usize,
+    inner: SpinNoIrqLock<FanotifyInner>,
 }
 
-impl FanotifyGroup {
-    pub fn new(flags: FanFlags, event_f_flags: FanEventFlags) -> Self {
-        Self {
+impl Fanotify {
+    pub fn new(flags: FanFlags, event_f_flags: FanEventFlags, open_flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            metadata: FileMeta::new(
+                open_flags,
+                DummyInode::new(InodeType::Unknown, "anon_inode:[fanotify]"),
+            ),
             flags,
             event_f_flags,
-            marks: Mutex::new(BTreeMap::new()),
+            group_id: fsnotify::alloc_group_id(),
+            inner: SpinNoIrqLock::new(FanotifyInner {
+                events: VecDeque::new(),
+                waiters: Vec::new(),
+            }),
+        })
+    }
+
+    /// 在 path 上增加关心或忽略的事件
+    pub fn add_mark(self: &Arc<Self>, path: &str, mask: u64, ignore: bool, subtree: bool) {
+        let group: Arc<dyn FsnotifyGroup> = self.clone();
+        let mut mark = fsnotify::find_mark(path, self.group_id, subtree).unwrap_or(FsnotifyMark {
+            group_id: self.group_id,
+            group: Arc::downgrade(&group),
+            wd: 0,
+            mask: 0,
+            ignored: 0,
+            subtree,
+        });
+        match ignore {
+            true => mark.ignored |= mask,
+            false => mark.mask |= mask,
+        }
+        fsnotify::set_mark(path, mark);
+    }
+
+    /// 从 path 上的标记中去掉事件，两个掩码都为空时移除标记
+    pub fn remove_mark(&self, path: &str, mask: u64, ignore: bool, subtree: bool) -> SysResult {
+        let mut mark = fsnotify::find_mark(path, self.group_id, subtree).ok_or(Errno::ENOENT)?;
+        match ignore {
+            true => mark.ignored &= !mask,
+            false => mark.mask &= !mask,
+        }
+        if mark.mask == 0 && mark.ignored == 0 {
+            fsnotify::remove_marks(self.group_id, |p, m| p == path && m.subtree == subtree);
+        } else {
+            fsnotify::set_mark(path, mark);
+        }
+        Ok(())
+    }
+
+    /// 移除所有对象标记，或者所有挂载点和文件系统标记
+    pub fn flush_marks(&self, subtree: bool) {
+        fsnotify::remove_marks(self.group_id, |_, m| m.subtree == subtree);
+    }
+
+    fn queue(&self, mask: u64, path: Option<&str>, pid: usize) {
+        let mut inner = self.inner.lock();
+        // 同一进程对同一对象的连续事件合并为一个
+        if let Some(last) = inner.events.back_mut() {
+            if last.path.as_deref() == path && last.pid == pid {
+                last.mask |= mask;
+                return;
+            }
+        }
+        let full = inner.events.len() >= FANOTIFY_MAX_EVENTS
+            && !self.flags.contains(FanFlags::FAN_UNLIMITED_QUEUE);
+        if !full {
+            inner.events.push_back(FanotifyEvent {
+                mask,
+                path: path.map(|path| path.to_string()),
+                pid,
+            });
+        } else if inner.events.back().map(|e| e.mask) != Some(FAN_Q_OVERFLOW) {
+            inner.events.push_back(FanotifyEvent {
+                mask: FAN_Q_OVERFLOW,
+                path: None,
+                pid: 0,
+            });
+        }
+        for waker in inner.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// 为事件对象在当前进程中打开一个 fd，对象已经不存在时返回 FAN_NOFD
+    fn open_event_fd(&self, path: &Option<String>) -> SysResult<i32> {
+        let Some(path) = path else {
+            return Ok(FAN_NOFD);
+        };
+        let flags = OpenFlags::from_bits_truncate(self.event_f_flags.bits() as i32);
+        match open(path.as_str().into(), flags) {
+            Ok(file) => {
+                let task = current_task().unwrap();
+                Ok(task.alloc_fd(FdInfo::new(file, flags))? as i32)
+            }
+            Err(_) => Ok(FAN_NOFD),
+        }
+    }
+
+    fn is_nonblock(&self) -> bool {
+        self.metadata.flags.read().contains(OpenFlags::O_NONBLOCK)
+    }
+}
+
+impl Drop for Fanotify {
+    fn drop(&mut self) {
+        fsnotify::remove_marks(self.group_id, |_, _| true);
+    }
+}
+
+impl FsnotifyGroup for Fanotify {
+    fn handle_event(&self, mark: &FsnotifyMark, event: &FsEvent) -> bool {
+        let isdir = event.mask.contains(FsEventMask::ISDIR);
+        if event.name.is_some() && mark.mask & FAN_EVENT_ON_CHILD == 0 {
+            return true;
+        }
+        if isdir && mark.mask & FAN_ONDIR == 0 {
+            return true;
+        }
+        let mask = event.mask.bits() as u64 & mark.mask & !mark.ignored & FAN_FD_EVENTS;
+        if mask == 0 {
+            return true;
+        }
+        let mask = match isdir {
+            true => mask | FAN_ONDIR,
+            false => mask,
+        };
+        let pid = match self.flags.contains(FanFlags::FAN_REPORT_TID) {
+            true => event.tid,
+            false => event.pid,
+        };
+        self.queue(mask, Some(event.path), pid);
+        true
+    }
+}
+
+/// 等待事件到来，收到未屏蔽的信号时返回 EINTR
+struct FanotifyWaitFuture<'a> {
+    fanotify: &'a Fanotify,
+    task: Arc<TaskControlBlock>,
+}
+
+impl Future for FanotifyWaitFuture<'_> {
+    type Output = SysResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.fanotify.inner.lock();
+        if !inner.events.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        if self.fanotify.is_nonblock() {
+            return Poll::Ready(Err(Errno::EAGAIN));
+        }
+        if self.task.sig_pending.lock().has_expected(!*self.task.get_blocked()).0 {
+            return Poll::Ready(Err(Errno::EINTR));
         }
+        inner.waiters.push(cx.waker().clone());
+        Poll::Pending
     }
 }
 
-lazy_static! {
-    // A global manager to hold fanotify groups, associated with their file descriptors.
-    // This is a workaround for not being able to add a new `FileClass` variant for fanotify.
-    pub static ref FANOTIFY_GROUPS: Mutex<BTreeMap<usize, Arc<FanotifyGroup>>> =
-        Mutex::new(BTreeMap::new());
+#[async_trait]
+impl FileTrait for Fanotify {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+
+    /// 读出尽可能多的事件，缓冲区放不下一个事件时返回 EINVAL
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+        const LEN: usize = core::mem::size_of::<FanotifyEventMetadata>();
+        if buf.len() < LEN {
+            return Err(Errno::EINVAL);
+        }
+        let task = current_task().unwrap();
+        task.set_wake_up_signal(!*task.get_blocked());
+        FanotifyWaitFuture {
+            fanotify: self,
+            task,
+        }
+        .await?;
+        let mut copied = 0;
+        while copied + LEN <= buf.len() {
+            let Some(event) = self.inner.lock().events.pop_front() else {
+                break;
+            };
+            let fd = match self.open_event_fd(&event.path) {
+                Ok(fd) => fd,
+                Err(e) => {
+                    // 已经读出的事件照常返回，这个事件放回队首
+                    self.inner.lock().events.push_front(event);
+                    return match copied {
+                        0 => Err(e),
+                        _ => Ok(copied),
+                    };
+                }
+            };
+            let metadata = FanotifyEventMetadata {
+                event_len: LEN as u32,
+                vers: FANOTIFY_METADATA_VERSION,
+                reserved: 0,
+                metadata_len: LEN as u16,
+                mask: event.mask,
+                fd,
+                pid: event.pid as i32,
+            };
+            let bytes = unsafe {
+                core::slice::from_raw_parts(&metadata as *const _ as *const u8, LEN)
+            };
+            buf[copied..copied + LEN].copy_from_slice(bytes);
+            copied += LEN;
+        }
+        Ok(copied)
+    }
+
+    /// 只有权限事件需要回写，这里不支持
+    async fn write(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(Errno::EINVAL)
+    }
+
+    fn abspath(&self) -> String {
+        String::from("anon_inode:[fanotify]")
+    }
+
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = Kstat::new();
+        stat.st_ino = self.metadata.inode.metadata().ino as u64;
+        stat.st_nlink = 1;
+        Ok(())
+    }
+
+    async fn pollin(&self) -> SysResult<bool> {
+        if !self.inner.lock().events.is_empty() {
+            return Ok(true);
+        }
+        let waker = get_waker().await;
+        let mut inner = self.inner.lock();
+        if !inner.events.is_empty() {
+            return Ok(true);
+        }
+        inner.waiters.push(waker);
+        Ok(false)
+    }
+
+    async fn pollout(&self) -> SysResult<bool> {
+        Ok(false)
+    }
 }
@@ -0,0 +1,300 @@
+//! fsnotify: inotify 和 fanotify 共用的文件系统事件层
+//!
+//! VFS 在创建、删除、改名、打开、读写、关闭和修改属性时调用这里的钩子。
+//! 钩子找出监视该对象、其父目录或其所在子树的标记，把事件交给标记所属的分组，
+//! 由分组决定是否入队以及如何呈现给用户。
+//!
+//! 标记按绝对路径索引，对象被改名时标记随之迁移，被删除时标记失效。
+
+use crate::{sync::SpinNoIrqLock, task::current_task};
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Weak,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+bitflags! {
+    /// 文件系统事件，inotify 和 fanotify 的事件位与之相同
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FsEventMask: u32 {
+        const ACCESS = 0x1;
+        const MODIFY = 0x2;
+        const ATTRIB = 0x4;
+        const CLOSE_WRITE = 0x8;
+        const CLOSE_NOWRITE = 0x10;
+        const OPEN = 0x20;
+        const MOVED_FROM = 0x40;
+        const MOVED_TO = 0x80;
+        const CREATE = 0x100;
+        const DELETE = 0x200;
+        const DELETE_SELF = 0x400;
+        const MOVE_SELF = 0x800;
+        const UNMOUNT = 0x2000;
+        const Q_OVERFLOW = 0x4000;
+        const IGNORED = 0x8000;
+        /// 事件的对象是目录
+        const ISDIR = 0x40000000;
+    }
+}
+
+impl FsEventMask {
+    /// 目录项事件，只报告给父目录
+    pub const DIRENT: Self = Self::CREATE
+        .union(Self::DELETE)
+        .union(Self::MOVED_FROM)
+        .union(Self::MOVED_TO);
+    /// 只报告给对象自身的事件
+    pub const SELF_ONLY: Self = Self::DELETE_SELF
+        .union(Self::MOVE_SELF)
+        .union(Self::UNMOUNT)
+        .union(Self::IGNORED);
+
+    pub fn dir(isdir: bool) -> Self {
+        match isdir {
+            true => Self::ISDIR,
+            false => Self::empty(),
+        }
+    }
+}
+
+/// 一次事件
+pub struct FsEvent<'a> {
+    pub mask: FsEventMask,
+    /// 发生事件的对象的绝对路径
+    pub path: &'a str,
+    /// 经由父目录的标记投递时，为对象在父目录中的名字
+    pub name: Option<&'a str>,
+    /// 关联同一次改名的 MOVED_FROM 和 MOVED_TO
+    pub cookie: u32,
+    /// 触发事件的线程和进程，内核自身触发时为 0
+    pub tid: usize,
+    pub pid: usize,
+}
+
+/// 标记所属的分组，即一个 inotify 或 fanotify 实例
+pub trait FsnotifyGroup: Send + Sync {
+    /// 投递命中 mark 的事件，由分组按自己的规则过滤。
+    /// 返回 false 表示这个标记应当被移除（例如 IN_ONESHOT）
+    fn handle_event(&self, mark: &FsnotifyMark, event: &FsEvent) -> bool;
+
+    /// 标记因为对象被删除、被覆盖或被显式移除而失效
+    fn mark_removed(&self, _mark: &FsnotifyMark) {}
+}
+
+/// 分组挂在某个路径上的标记
+#[derive(Clone)]
+pub struct FsnotifyMark {
+    pub group_id: usize,
+    pub group: Weak<dyn FsnotifyGroup>,
+    /// inotify 的 watch descriptor，fanotify 不使用
+    pub wd: i32,
+    /// 关心的事件，高位可以带分组自己的控制位
+    pub mask: u64,
+    /// 不关心的事件（fanotify 的 ignored mask）
+    pub ignored: u64,
+    /// 监视路径下的整棵子树（fanotify 的挂载点和文件系统标记）
+    pub subtree: bool,
+}
+
+struct MarkTable {
+    marks: BTreeMap<String, Vec<FsnotifyMark>>,
+    /// 子树标记的数量，为 0 时投递事件不需要检查祖先目录
+    nr_subtree: usize,
+}
+
+impl MarkTable {
+    fn collect(&self, path: &str, mask: FsEventMask, hits: &mut Vec<(FsnotifyMark, Option<String>)>) {
+        if !mask.intersects(FsEventMask::DIRENT) {
+            if let Some(marks) = self.marks.get(path) {
+                hits.extend(marks.iter().filter(|m| !m.subtree).map(|m| (m.clone(), None)));
+            }
+        }
+        if path != "/" && !mask.intersects(FsEventMask::SELF_ONLY) {
+            let (parent, name) = split_parent(path);
+            if let Some(marks) = self.marks.get(parent) {
+                hits.extend(
+                    marks
+                        .iter()
+                        .filter(|m| !m.subtree)
+                        .map(|m| (m.clone(), Some(name.to_string()))),
+                );
+            }
+        }
+        if self.nr_subtree > 0 {
+            for (root, marks) in self.marks.iter() {
+                if !in_subtree(root, path) {
+                    continue;
+                }
+                hits.extend(marks.iter().filter(|m| m.subtree).map(|m| (m.clone(), None)));
+            }
+        }
+    }
+
+    fn remove_if(&mut self, mut pred: impl FnMut(&str, &FsnotifyMark) -> bool) -> Vec<FsnotifyMark> {
+        let mut removed = Vec::new();
+        self.marks.retain(|path, marks| {
+            marks.retain(|mark| {
+                if pred(path, mark) {
+                    removed.push(mark.clone());
+                    return false;
+                }
+                true
+            });
+            !marks.is_empty()
+        });
+        self.nr_subtree -= removed.iter().filter(|m| m.subtree).count();
+        removed
+    }
+}
+
+lazy_static! {
+    static ref FSNOTIFY_MARKS: SpinNoIrqLock<MarkTable> = SpinNoIrqLock::new(MarkTable {
+        marks: BTreeMap::new(),
+        nr_subtree: 0,
+    });
+}
+
+static NEXT_GROUP_ID: AtomicUsize = AtomicUsize::new(1);
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// 为新的分组分配编号
+pub fn alloc_group_id() -> usize {
+    NEXT_GROUP_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+fn split_parent(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => ("/", path),
+    }
+}
+
+/// path 是否等于 root 或位于 root 之下
+fn in_subtree(root: &str, path: &str) -> bool {
+    root == "/"
+        || path
+            .strip_prefix(root)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// 查找分组挂在 path 上的标记
+pub fn find_mark(path: &str, group_id: usize, subtree: bool) -> Option<FsnotifyMark> {
+    FSNOTIFY_MARKS
+        .lock()
+        .marks
+        .get(path)?
+        .iter()
+        .find(|m| m.group_id == group_id && m.subtree == subtree)
+        .cloned()
+}
+
+/// 在 path 上挂一个标记，替换同一分组已有的同类标记
+pub fn set_mark(path: &str, mark: FsnotifyMark) {
+    let mut table = FSNOTIFY_MARKS.lock();
+    let subtree = mark.subtree;
+    let marks = table.marks.entry(path.to_string()).or_insert_with(Vec::new);
+    match marks
+        .iter_mut()
+        .find(|m| m.group_id == mark.group_id && m.subtree == subtree)
+    {
+        Some(old) => *old = mark,
+        None => {
+            marks.push(mark);
+            if subtree {
+                table.nr_subtree += 1;
+            }
+        }
+    }
+}
+
+/// 移除分组中满足条件的标记，返回被移除的标记。不会通知分组
+pub fn remove_marks(
+    group_id: usize,
+    mut pred: impl FnMut(&str, &FsnotifyMark) -> bool,
+) -> Vec<FsnotifyMark> {
+    FSNOTIFY_MARKS
+        .lock()
+        .remove_if(|path, mark| mark.group_id == group_id && pred(path, mark))
+}
+
+/// 移除 path 上的所有对象标记并通知它们的分组
+fn evict_marks(path: &str) {
+    let removed = FSNOTIFY_MARKS
+        .lock()
+        .remove_if(|p, mark| p == path && !mark.subtree);
+    for mark in removed {
+        if let Some(group) = mark.group.upgrade() {
+            group.mark_removed(&mark);
+        }
+    }
+}
+
+fn send(path: &str, mask: FsEventMask, cookie: u32) {
+    let mut hits = Vec::new();
+    {
+        let table = FSNOTIFY_MARKS.lock();
+        if table.marks.is_empty() {
+            return;
+        }
+        table.collect(path, mask, &mut hits);
+    }
+    if hits.is_empty() {
+        return;
+    }
+    let (tid, pid) = current_task().map_or((0, 0), |task| (task.get_pid(), task.get_tgid()));
+    for (mark, name) in hits {
+        let Some(group) = mark.group.upgrade() else {
+            continue;
+        };
+        let event = FsEvent {
+            mask,
+            path,
+            name: name.as_deref(),
+            cookie,
+            tid,
+            pid,
+        };
+        if !group.handle_event(&mark, &event) {
+            remove_marks(mark.group_id, |_, m| m.wd == mark.wd && m.subtree == mark.subtree);
+        }
+    }
+}
+
+/// 报告 path 上发生的事件。目录项事件报告给父目录，其余事件报告给对象自身和父目录
+pub fn fsnotify(path: &str, mask: FsEventMask) {
+    send(path, mask, 0);
+}
+
+/// 报告 path 被删除，对象上的标记随之失效
+pub fn fsnotify_delete(path: &str, isdir: bool) {
+    let dir = FsEventMask::dir(isdir);
+    send(path, FsEventMask::DELETE | dir, 0);
+    send(path, FsEventMask::DELETE_SELF | dir, 0);
+    evict_marks(path);
+}
+
+/// 报告 old 被改名为 new。new 上原有对象的标记失效，old 及其子项的标记迁移到 new 下
+pub fn fsnotify_move(old: &str, new: &str, isdir: bool) {
+    let dir = FsEventMask::dir(isdir);
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    evict_marks(new);
+    send(old, FsEventMask::MOVED_FROM | dir, cookie);
+    send(new, FsEventMask::MOVED_TO | dir, cookie);
+    send(old, FsEventMask::MOVE_SELF | dir, 0);
+
+    let mut table = FSNOTIFY_MARKS.lock();
+    let moved: Vec<String> = table
+        .marks
+        .keys()
+        .filter(|path| old != "/" && in_subtree(old, path))
+        .cloned()
+        .collect();
+    for path in moved {
+        let marks = table.marks.remove(&path).unwrap();
+        let renamed = alloc::format!("{}{}", new, &path[old.len()..]);
+        table.marks.entry(renamed).or_insert_with(Vec::new).extend(marks);
+    }
+}
@@ -0,0 +1,317 @@
+//! inotify: 通过文件描述符读取被监视路径上发生的事件
+//!
+//! 每个 watch 是挂在 fsnotify 上的一个标记，wd 记录在标记里。
+//! 事件以 `struct inotify_event` 加上补齐的文件名的形式从 read 读出。
+
+use super::{FileTrait, InodeType, Kstat, OpenFlags};
+use crate::{
+    fs::{
+        fsnotify::{self, FsEvent, FsEventMask, FsnotifyGroup, FsnotifyMark},
+        pipe::DummyInode,
+        FileMeta,
+    },
+    sync::{get_waker, SpinNoIrqLock},
+    task::{current_task, TaskControlBlock},
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+pub const IN_ALL_EVENTS: u32 = 0xfff;
+pub const IN_ONLYDIR: u32 = 0x01000000;
+pub const IN_DONT_FOLLOW: u32 = 0x02000000;
+pub const IN_EXCL_UNLINK: u32 = 0x04000000;
+pub const IN_MASK_CREATE: u32 = 0x10000000;
+pub const IN_MASK_ADD: u32 = 0x20000000;
+pub const IN_ONESHOT: u32 = 0x80000000;
+
+/// 每个实例最多排队的事件数，与 Linux 的 max_queued_events 默认值相同
+const INOTIFY_MAX_EVENTS: usize = 16384;
+/// struct inotify_event 不含文件名部分的大小
+const EVENT_HEADER_SIZE: usize = 16;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InotifyFlags: u32 {
+        const IN_NONBLOCK = 0o4000;
+        const IN_CLOEXEC = 0o2000000;
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct InotifyEvent {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: Option<String>,
+}
+
+impl InotifyEvent {
+    /// 文件名连同结尾的 0 补齐到 inotify_event 大小的整数倍
+    fn name_len(&self) -> usize {
+        self.name.as_ref().map_or(0, |name| {
+            (name.len() + 1).next_multiple_of(EVENT_HEADER_SIZE)
+        })
+    }
+
+    fn len(&self) -> usize {
+        EVENT_HEADER_SIZE + self.name_len()
+    }
+
+    fn write_to(&self, buf: &mut [u8]) {
+        let name_len = self.name_len();
+        buf[0..4].copy_from_slice(&self.wd.to_ne_bytes());
+        buf[4..8].copy_from_slice(&self.mask.to_ne_bytes());
+        buf[8..12].copy_from_slice(&self.cookie.to_ne_bytes());
+        buf[12..16].copy_from_slice(&(name_len as u32).to_ne_bytes());
+        let name_buf = &mut buf[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + name_len];
+        name_buf.fill(0);
+        if let Some(name) = &self.name {
+            name_buf[..name.len()].copy_from_slice(name.as_bytes());
+        }
+    }
+}
+
+struct InotifyInner {
+    events: VecDeque<InotifyEvent>,
+    next_wd: i32,
+    waiters: Vec<Waker>,
+}
+
+pub struct Inotify {
+    pub metadata: FileMeta,
+    group_id: usize,
+    inner: SpinNoIrqLock<InotifyInner>,
+}
+
+impl Inotify {
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            metadata: FileMeta::new(flags, DummyInode::new(InodeType::Unknown, "anon_inode:inotify")),
+            group_id: fsnotify::alloc_group_id(),
+            inner: SpinNoIrqLock::new(InotifyInner {
+                events: VecDeque::new(),
+                next_wd: 1,
+                waiters: Vec::new(),
+            }),
+        })
+    }
+
+    /// 监视 path，已经监视时按 IN_MASK_ADD/IN_MASK_CREATE 更新原有的 watch
+    pub fn add_watch(self: &Arc<Self>, path: &str, mask: u32) -> SysResult<i32> {
+        if mask & IN_ALL_EVENTS == 0 {
+            return Err(Errno::EINVAL);
+        }
+        if mask & IN_MASK_ADD != 0 && mask & IN_MASK_CREATE != 0 {
+            return Err(Errno::EINVAL);
+        }
+        let keep = mask & (IN_ALL_EVENTS | IN_ONESHOT | IN_EXCL_UNLINK);
+        if let Some(mut mark) = fsnotify::find_mark(path, self.group_id, false) {
+            if mask & IN_MASK_CREATE != 0 {
+                return Err(Errno::EEXIST);
+            }
+            mark.mask = match mask & IN_MASK_ADD {
+                0 => keep as u64,
+                _ => mark.mask | keep as u64,
+            };
+            let wd = mark.wd;
+            fsnotify::set_mark(path, mark);
+            return Ok(wd);
+        }
+        let wd = {
+            let mut inner = self.inner.lock();
+            inner.next_wd += 1;
+            inner.next_wd - 1
+        };
+        let group: Arc<dyn FsnotifyGroup> = self.clone();
+        fsnotify::set_mark(
+            path,
+            FsnotifyMark {
+                group_id: self.group_id,
+                group: Arc::downgrade(&group),
+                wd,
+                mask: keep as u64,
+                ignored: 0,
+                subtree: false,
+            },
+        );
+        Ok(wd)
+    }
+
+    /// 移除 watch，并产生 IN_IGNORED 事件
+    pub fn rm_watch(&self, wd: i32) -> SysResult {
+        let removed = fsnotify::remove_marks(self.group_id, |_, mark| mark.wd == wd);
+        if removed.is_empty() {
+            return Err(Errno::EINVAL);
+        }
+        self.queue(wd, FsEventMask::IGNORED.bits(), 0, None);
+        Ok(())
+    }
+
+    fn queue(&self, wd: i32, mask: u32, cookie: u32, name: Option<&str>) {
+        let event = InotifyEvent {
+            wd,
+            mask,
+            cookie,
+            name: name.map(|name| name.to_string()),
+        };
+        let mut inner = self.inner.lock();
+        // 与队尾相同的事件合并为一个
+        if inner.events.back() == Some(&event) {
+            return;
+        }
+        if inner.events.len() >= INOTIFY_MAX_EVENTS {
+            let overflow = FsEventMask::Q_OVERFLOW.bits();
+            if inner.events.back().map(|e| e.mask) != Some(overflow) {
+                inner.events.push_back(InotifyEvent {
+                    wd: -1,
+                    mask: overflow,
+                    cookie: 0,
+                    name: None,
+                });
+            }
+        } else {
+            inner.events.push_back(event);
+        }
+        for waker in inner.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn is_nonblock(&self) -> bool {
+        self.metadata.flags.read().contains(OpenFlags::O_NONBLOCK)
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        fsnotify::remove_marks(self.group_id, |_, _| true);
+    }
+}
+
+impl FsnotifyGroup for Inotify {
+    fn handle_event(&self, mark: &FsnotifyMark, event: &FsEvent) -> bool {
+        let mask = event.mask.bits();
+        if mask & mark.mask as u32 & IN_ALL_EVENTS == 0 {
+            return true;
+        }
+        self.queue(mark.wd, mask, event.cookie, event.name);
+        if mark.mask as u32 & IN_ONESHOT != 0 {
+            self.queue(mark.wd, FsEventMask::IGNORED.bits(), 0, None);
+            return false;
+        }
+        true
+    }
+
+    fn mark_removed(&self, mark: &FsnotifyMark) {
+        self.queue(mark.wd, FsEventMask::IGNORED.bits(), 0, None);
+    }
+}
+
+/// 等待事件到来，收到未屏蔽的信号时返回 EINTR
+struct InotifyWaitFuture<'a> {
+    inotify: &'a Inotify,
+    task: Arc<TaskControlBlock>,
+}
+
+impl Future for InotifyWaitFuture<'_> {
+    type Output = SysResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.inotify.inner.lock();
+        if !inner.events.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        if self.inotify.is_nonblock() {
+            return Poll::Ready(Err(Errno::EAGAIN));
+        }
+        if self.task.sig_pending.lock().has_expected(!*self.task.get_blocked()).0 {
+            return Poll::Ready(Err(Errno::EINTR));
+        }
+        inner.waiters.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[async_trait]
+impl FileTrait for Inotify {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+
+    /// 读出尽可能多的完整事件，缓冲区放不下第一个事件时返回 EINVAL
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+        let task = current_task().unwrap();
+        task.set_wake_up_signal(!*task.get_blocked());
+        loop {
+            InotifyWaitFuture {
+                inotify: self,
+                task: task.clone(),
+            }
+            .await?;
+            let mut inner = self.inner.lock();
+            let mut copied = 0;
+            while let Some(event) = inner.events.front() {
+                let len = event.len();
+                if copied + len > buf.len() {
+                    break;
+                }
+                event.write_to(&mut buf[copied..copied + len]);
+                copied += len;
+                inner.events.pop_front();
+            }
+            match (copied, inner.events.is_empty()) {
+                (0, true) => continue,
+                (0, false) => return Err(Errno::EINVAL),
+                _ => return Ok(copied),
+            }
+        }
+    }
+
+    async fn write(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(Errno::EINVAL)
+    }
+
+    fn abspath(&self) -> String {
+        String::from("anon_inode:inotify")
+    }
+
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = Kstat::new();
+        stat.st_ino = self.metadata.inode.metadata().ino as u64;
+        stat.st_nlink = 1;
+        Ok(())
+    }
+
+    async fn pollin(&self) -> SysResult<bool> {
+        if !self.inner.lock().events.is_empty() {
+            return Ok(true);
+        }
+        let waker = get_waker().await;
+        let mut inner = self.inner.lock();
+        if !inner.events.is_empty() {
+            return Ok(true);
+        }
+        inner.waiters.push(waker);
+        Ok(false)
+    }
+
+    async fn pollout(&self) -> SysResult<bool> {
+        Ok(false)
+    }
+}
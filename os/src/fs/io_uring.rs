@@ -0,0 +1,605 @@
+//! io_uring：与用户态共享提交队列（SQ）和完成队列（CQ）的异步 I/O 接口
+//!
+//! 队列所在的页由内核分配，用户 mmap 这个 fd 把它们映射进自己的地址空间，
+//! 之后双方直接读写队列的 head/tail 交换请求和结果。各字段的位置通过 `IoUringParams`
+//! 中的偏移告诉用户，SQ 和 CQ 放在同一块内存里（IORING_FEAT_SINGLE_MMAP）。
+//! 这里只管理队列本身，每个 SQE 如何执行由 syscall 层决定
+
+use super::{FileTrait, InodeType, Kstat, OpenFlags};
+use crate::{
+    fs::{pipe::DummyInode, FileMeta},
+    hal::config::PAGE_SIZE,
+    mm::page::Page,
+    sync::{get_waker, SpinNoIrqLock},
+    task::current_task,
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::{
+    collections::{btree_map::BTreeMap, VecDeque},
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU32, Ordering},
+    task::{Context, Poll, Waker},
+};
+use log::info;
+use num_enum::FromPrimitive;
+
+/// mmap 时用偏移区分映射哪一块内存
+pub const IORING_OFF_SQ_RING: usize = 0;
+pub const IORING_OFF_CQ_RING: usize = 0x8000000;
+pub const IORING_OFF_SQES: usize = 0x10000000;
+
+/// SQ 的最大项数，CQ 最多是它的两倍
+pub const IORING_MAX_ENTRIES: u32 = 4096;
+pub const IORING_MAX_CQ_ENTRIES: u32 = 2 * IORING_MAX_ENTRIES;
+
+/// sq_flags 中的位：有完成事件因 CQ 已满而暂存在内核里
+const IORING_SQ_CQ_OVERFLOW: u32 = 1 << 1;
+
+// 队列内存的布局。SQ 和 CQ 的头部各占一个 cache line，之后是 CQE 数组和 SQ 的索引数组
+const SQ_HEAD: usize = 0;
+const SQ_TAIL: usize = 4;
+const SQ_RING_MASK: usize = 8;
+const SQ_RING_ENTRIES: usize = 12;
+const SQ_FLAGS: usize = 16;
+const SQ_DROPPED: usize = 20;
+const CQ_HEAD: usize = 64;
+const CQ_TAIL: usize = 68;
+const CQ_RING_MASK: usize = 72;
+const CQ_RING_ENTRIES: usize = 76;
+const CQ_OVERFLOW: usize = 80;
+const CQ_FLAGS: usize = 84;
+const CQES: usize = 128;
+
+bitflags! {
+    /// io_uring_setup 的 flags
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct IoUringSetupFlags: u32 {
+        const IORING_SETUP_IOPOLL = 1 << 0;
+        const IORING_SETUP_SQPOLL = 1 << 1;
+        const IORING_SETUP_SQ_AFF = 1 << 2;
+        const IORING_SETUP_CQSIZE = 1 << 3;
+        const IORING_SETUP_CLAMP = 1 << 4;
+        const IORING_SETUP_ATTACH_WQ = 1 << 5;
+        const IORING_SETUP_R_DISABLED = 1 << 6;
+    }
+}
+
+bitflags! {
+    /// io_uring_enter 的 flags
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct IoUringEnterFlags: u32 {
+        const IORING_ENTER_GETEVENTS = 1 << 0;
+        const IORING_ENTER_SQ_WAKEUP = 1 << 1;
+        const IORING_ENTER_SQ_WAIT = 1 << 2;
+        const IORING_ENTER_EXT_ARG = 1 << 3;
+    }
+}
+
+bitflags! {
+    /// 通过 `IoUringParams::features` 告诉用户的特性
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct IoUringFeatures: u32 {
+        /// SQ 和 CQ 在同一块内存中，一次 mmap 即可
+        const IORING_FEAT_SINGLE_MMAP = 1 << 0;
+        /// CQ 满时不丢弃完成事件
+        const IORING_FEAT_NODROP = 1 << 1;
+        /// 提交返回后内核不再读取 SQE 中的数据
+        const IORING_FEAT_SUBMIT_STABLE = 1 << 2;
+        /// 读写的偏移为 -1 时使用文件自己的偏移
+        const IORING_FEAT_RW_CUR_POS = 1 << 3;
+    }
+}
+
+bitflags! {
+    /// SQE 的 flags
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SqeFlags: u8 {
+        const IOSQE_FIXED_FILE = 1 << 0;
+        const IOSQE_IO_DRAIN = 1 << 1;
+        const IOSQE_IO_LINK = 1 << 2;
+        const IOSQE_IO_HARDLINK = 1 << 3;
+        const IOSQE_ASYNC = 1 << 4;
+        const IOSQE_BUFFER_SELECT = 1 << 5;
+    }
+}
+
+/// SQE 的操作码，只列出支持的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum IoUringOp {
+    Nop = 0,
+    Readv = 1,
+    Writev = 2,
+    Fsync = 3,
+    PollAdd = 6,
+    PollRemove = 7,
+    Timeout = 11,
+    TimeoutRemove = 12,
+    Accept = 13,
+    AsyncCancel = 14,
+    Connect = 16,
+    Read = 22,
+    Write = 23,
+    Send = 26,
+    Recv = 27,
+    #[num_enum(default)]
+    Unsupported = 0xff,
+}
+
+/// IORING_OP_TIMEOUT 的 timeout_flags：timespec 是绝对时间
+pub const IORING_TIMEOUT_ABS: u32 = 1 << 0;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IoSqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub flags: u32,
+    pub dropped: u32,
+    pub array: u32,
+    pub resv1: u32,
+    pub user_addr: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IoCqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub overflow: u32,
+    pub cqes: u32,
+    pub flags: u32,
+    pub resv1: u32,
+    pub user_addr: u64,
+}
+
+/// io_uring_setup 的参数，内核填好各项偏移后写回用户
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IoUringParams {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    pub flags: u32,
+    pub sq_thread_cpu: u32,
+    pub sq_thread_idle: u32,
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3],
+    pub sq_off: IoSqringOffsets,
+    pub cq_off: IoCqringOffsets,
+}
+
+/// 提交队列项，共 64 字节。各操作对 off、addr、len、op_flags 的解释不同
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IoUringSqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub ioprio: u16,
+    pub fd: i32,
+    /// 文件偏移，accept 和 connect 中是地址长度（的指针）
+    pub off: u64,
+    /// 缓冲区地址，取消类操作中是目标的 user_data
+    pub addr: u64,
+    pub len: u32,
+    /// rw_flags、poll_events、timeout_flags、msg_flags 等
+    pub op_flags: u32,
+    pub user_data: u64,
+    pub buf_index: u16,
+    pub personality: u16,
+    pub splice_fd_in: i32,
+    pub addr3: u64,
+    pub __pad2: [u64; 1],
+}
+
+/// 完成队列项
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IoUringCqe {
+    pub user_data: u64,
+    pub res: i32,
+    pub flags: u32,
+}
+
+/// 等待完成队列的条件
+#[derive(Clone, Copy, Debug)]
+pub enum CqWait {
+    /// CQ 中至少有这么多还没被用户取走的事件
+    Ready(u32),
+    /// 自创建以来放入 CQ 的事件总数达到这个值
+    Completed(u64),
+}
+
+/// 已经提交、还没有完成的请求
+struct PendingOp {
+    user_data: u64,
+    cancelled: bool,
+    waker: Option<Waker>,
+}
+
+struct IoRingInner {
+    /// CQ 满时暂存的完成事件，等用户腾出空间后再放进 CQ
+    overflow: VecDeque<IoUringCqe>,
+    /// 自创建以来放入 CQ 的事件总数
+    completed: u64,
+    cq_waiters: Vec<Waker>,
+    pending: BTreeMap<u64, PendingOp>,
+    next_id: u64,
+    /// fd 已经关闭且不再被映射，所有请求都应尽快结束
+    closed: bool,
+}
+
+/// 一个 io_uring 实例的队列。SQ 的 head 和 CQ 的 tail 只由内核修改，另外两个只由用户修改
+pub struct IoRingCtx {
+    /// SQ、CQ 头部，CQE 数组和 SQ 索引数组所在的页
+    rings: Vec<Arc<Page>>,
+    /// SQE 数组所在的页
+    sqes: Vec<Arc<Page>>,
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    sq_array: usize,
+    inner: SpinNoIrqLock<IoRingInner>,
+}
+
+fn alloc_pages(size: usize) -> Vec<Arc<Page>> {
+    (0..size.div_ceil(PAGE_SIZE))
+        .map(|_| {
+            let page = Page::new();
+            page.fill_zero();
+            page
+        })
+        .collect()
+}
+
+/// 取得由若干不连续的页拼成的一块内存中 `offset` 处的指针，`T` 不能跨页
+fn page_ptr<T>(pages: &[Arc<Page>], offset: usize) -> *mut T {
+    let bytes = pages[offset / PAGE_SIZE].get_bytes_array();
+    bytes[offset % PAGE_SIZE..].as_mut_ptr() as *mut T
+}
+
+impl IoRingCtx {
+    /// `sq_entries` 和 `cq_entries` 都必须是 2 的幂
+    pub fn new(sq_entries: u32, cq_entries: u32) -> Arc<Self> {
+        let sq_array = CQES + cq_entries as usize * size_of::<IoUringCqe>();
+        let ring_size = sq_array + sq_entries as usize * size_of::<u32>();
+        let ctx = Self {
+            rings: alloc_pages(ring_size),
+            sqes: alloc_pages(sq_entries as usize * size_of::<IoUringSqe>()),
+            sq_entries,
+            cq_entries,
+            sq_array,
+            inner: SpinNoIrqLock::new(IoRingInner {
+                overflow: VecDeque::new(),
+                completed: 0,
+                cq_waiters: Vec::new(),
+                pending: BTreeMap::new(),
+                next_id: 0,
+                closed: false,
+            }),
+        };
+        ctx.ring_u32(SQ_RING_MASK).store(sq_entries - 1, Ordering::Relaxed);
+        ctx.ring_u32(SQ_RING_ENTRIES).store(sq_entries, Ordering::Relaxed);
+        ctx.ring_u32(CQ_RING_MASK).store(cq_entries - 1, Ordering::Relaxed);
+        ctx.ring_u32(CQ_RING_ENTRIES).store(cq_entries, Ordering::Relaxed);
+        Arc::new(ctx)
+    }
+
+    fn ring_u32(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*page_ptr::<AtomicU32>(&self.rings, offset) }
+    }
+
+    /// 填写 io_uring_setup 返回给用户的各项偏移
+    pub fn fill_params(&self, params: &mut IoUringParams) {
+        params.sq_entries = self.sq_entries;
+        params.cq_entries = self.cq_entries;
+        params.features = IoUringFeatures::all().bits();
+        params.sq_off = IoSqringOffsets {
+            head: SQ_HEAD as u32,
+            tail: SQ_TAIL as u32,
+            ring_mask: SQ_RING_MASK as u32,
+            ring_entries: SQ_RING_ENTRIES as u32,
+            flags: SQ_FLAGS as u32,
+            dropped: SQ_DROPPED as u32,
+            array: self.sq_array as u32,
+            ..Default::default()
+        };
+        params.cq_off = IoCqringOffsets {
+            head: CQ_HEAD as u32,
+            tail: CQ_TAIL as u32,
+            ring_mask: CQ_RING_MASK as u32,
+            ring_entries: CQ_RING_ENTRIES as u32,
+            overflow: CQ_OVERFLOW as u32,
+            cqes: CQES as u32,
+            flags: CQ_FLAGS as u32,
+            ..Default::default()
+        };
+    }
+
+    /// mmap 时取得偏移 `offset` 处的页。SQ 和 CQ 共用同一块内存，两个偏移映射到的是相同的页
+    pub fn mapped_page(&self, offset: usize) -> Option<Arc<Page>> {
+        let (pages, base) = self.mapped_region(offset);
+        pages.get((offset - base) / PAGE_SIZE).cloned()
+    }
+
+    /// 从 `offset` 开始映射 `length` 字节是否落在对应的环内
+    pub fn mapping_fits(&self, offset: usize, length: usize) -> bool {
+        let (pages, base) = self.mapped_region(offset);
+        (offset - base)
+            .checked_add(length)
+            .is_some_and(|end| end <= pages.len() * PAGE_SIZE)
+    }
+
+    /// mmap 偏移所在的区域及其起始偏移
+    fn mapped_region(&self, offset: usize) -> (&[Arc<Page>], usize) {
+        if offset >= IORING_OFF_SQES {
+            (&self.sqes, IORING_OFF_SQES)
+        } else if offset >= IORING_OFF_CQ_RING {
+            (&self.rings, IORING_OFF_CQ_RING)
+        } else {
+            (&self.rings, IORING_OFF_SQ_RING)
+        }
+    }
+
+    /// 取出最多 `max` 个用户已经放入 SQ 的 SQE。索引越界的项计入 sq_dropped 并跳过
+    pub fn take_sqes(&self, max: u32) -> Vec<IoUringSqe> {
+        let head = self.ring_u32(SQ_HEAD).load(Ordering::Relaxed);
+        let tail = self.ring_u32(SQ_TAIL).load(Ordering::Acquire);
+        let count = tail.wrapping_sub(head).min(self.sq_entries).min(max);
+        let mut sqes = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let slot = (head.wrapping_add(i) & (self.sq_entries - 1)) as usize;
+            let index = unsafe { *page_ptr::<u32>(&self.rings, self.sq_array + slot * size_of::<u32>()) };
+            if index >= self.sq_entries {
+                self.ring_u32(SQ_DROPPED).fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let offset = index as usize * size_of::<IoUringSqe>();
+            sqes.push(unsafe { *page_ptr::<IoUringSqe>(&self.sqes, offset) });
+        }
+        self.ring_u32(SQ_HEAD).store(head.wrapping_add(count), Ordering::Release);
+        sqes
+    }
+
+    /// SQ 中还能放入的项数
+    pub fn sq_space(&self) -> u32 {
+        let head = self.ring_u32(SQ_HEAD).load(Ordering::Relaxed);
+        let tail = self.ring_u32(SQ_TAIL).load(Ordering::Acquire);
+        self.sq_entries.saturating_sub(tail.wrapping_sub(head))
+    }
+
+    fn cq_len(&self) -> u32 {
+        let head = self.ring_u32(CQ_HEAD).load(Ordering::Acquire);
+        let tail = self.ring_u32(CQ_TAIL).load(Ordering::Relaxed);
+        tail.wrapping_sub(head)
+    }
+
+    fn push_cqe(&self, cqe: IoUringCqe) {
+        let tail = self.ring_u32(CQ_TAIL).load(Ordering::Relaxed);
+        let slot = (tail & (self.cq_entries - 1)) as usize;
+        unsafe { *page_ptr::<IoUringCqe>(&self.rings, CQES + slot * size_of::<IoUringCqe>()) = cqe };
+        self.ring_u32(CQ_TAIL).store(tail.wrapping_add(1), Ordering::Release);
+    }
+
+    /// 把暂存的完成事件放进用户已经腾出的空间
+    fn flush_overflow(&self, inner: &mut IoRingInner) {
+        while !inner.overflow.is_empty() && self.cq_len() < self.cq_entries {
+            let cqe = inner.overflow.pop_front().unwrap();
+            self.push_cqe(cqe);
+        }
+        if inner.overflow.is_empty() {
+            self.ring_u32(SQ_FLAGS).fetch_and(!IORING_SQ_CQ_OVERFLOW, Ordering::Relaxed);
+        }
+    }
+
+    /// 放入一个完成事件并唤醒等待者，CQ 已满时先暂存
+    pub fn post_cqe(&self, user_data: u64, res: i32) {
+        let cqe = IoUringCqe { user_data, res, flags: 0 };
+        let mut inner = self.inner.lock();
+        self.flush_overflow(&mut inner);
+        if inner.overflow.is_empty() && self.cq_len() < self.cq_entries {
+            self.push_cqe(cqe);
+        } else {
+            inner.overflow.push_back(cqe);
+            self.ring_u32(SQ_FLAGS).fetch_or(IORING_SQ_CQ_OVERFLOW, Ordering::Relaxed);
+        }
+        inner.completed += 1;
+        for waker in inner.cq_waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// CQ 中还没有被用户取走的事件数
+    pub fn cq_ready(&self) -> u32 {
+        let mut inner = self.inner.lock();
+        self.flush_overflow(&mut inner);
+        self.cq_len()
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.inner.lock().completed
+    }
+
+    /// 检查等待条件，不满足时挂上 waker，下一个完成事件到来时被唤醒
+    pub fn poll_wait(&self, until: CqWait, waker: &Waker) -> bool {
+        let mut inner = self.inner.lock();
+        self.flush_overflow(&mut inner);
+        let done = match until {
+            CqWait::Ready(min) => self.cq_len() >= min,
+            CqWait::Completed(target) => inner.completed >= target,
+        };
+        if !done && !inner.closed {
+            inner.cq_waiters.push(waker.clone());
+        }
+        done || inner.closed
+    }
+
+    /// 记录一个已提交的请求，返回它的编号
+    pub fn register_op(&self, user_data: u64) -> u64 {
+        let mut inner = self.inner.lock();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let cancelled = inner.closed;
+        inner.pending.insert(id, PendingOp { user_data, cancelled, waker: None });
+        id
+    }
+
+    pub fn finish_op(&self, id: u64) {
+        self.inner.lock().pending.remove(&id);
+    }
+
+    /// 请求是否应该继续执行，继续时记下 waker 以便取消时唤醒它
+    fn keep_running(&self, id: u64, waker: &Waker) -> bool {
+        let mut inner = self.inner.lock();
+        let closed = inner.closed;
+        match inner.pending.get_mut(&id) {
+            Some(op) if !op.cancelled && !closed => {
+                op.waker = Some(waker.clone());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 取消 user_data 为 `user_data` 的请求（不包括发起取消的请求 `by` 自己），没有找到时返回 ENOENT
+    pub fn cancel(&self, user_data: u64, by: u64) -> SysResult<()> {
+        let mut inner = self.inner.lock();
+        let mut found = false;
+        for (_, op) in inner
+            .pending
+            .iter_mut()
+            .filter(|(id, op)| **id != by && op.user_data == user_data && !op.cancelled)
+        {
+            op.cancelled = true;
+            found = true;
+            if let Some(waker) = op.waker.take() {
+                waker.wake();
+            }
+        }
+        match found {
+            true => Ok(()),
+            false => Err(Errno::ENOENT),
+        }
+    }
+
+    /// 取消所有请求，唤醒所有等待者
+    fn close(&self) {
+        let mut inner = self.inner.lock();
+        inner.closed = true;
+        for op in inner.pending.values_mut() {
+            op.cancelled = true;
+            if let Some(waker) = op.waker.take() {
+                waker.wake();
+            }
+        }
+        for waker in inner.cq_waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// 执行一个请求的 future。请求被取消、io_uring 被关闭或提交者已经退出时立即以 ECANCELED 结束，
+/// 不再轮询内部的 future，从而不会再访问提交者的内存
+pub struct IoRingOpFuture<F: Future<Output = SysResult<usize>>> {
+    ctx: Arc<IoRingCtx>,
+    id: u64,
+    inner: F,
+}
+
+impl<F: Future<Output = SysResult<usize>>> IoRingOpFuture<F> {
+    pub fn new(ctx: Arc<IoRingCtx>, id: u64, inner: F) -> Self {
+        Self { ctx, id, inner }
+    }
+}
+
+impl<F: Future<Output = SysResult<usize>>> Future for IoRingOpFuture<F> {
+    type Output = SysResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let exited = current_task().map_or(true, |task| task.is_zombie());
+        if exited || !this.ctx.keep_running(this.id, cx.waker()) {
+            info!("[IoRingOpFuture] op {} cancelled", this.id);
+            return Poll::Ready(Err(Errno::ECANCELED));
+        }
+        unsafe { Pin::new_unchecked(&mut this.inner) }.poll(cx)
+    }
+}
+
+/// io_uring_setup 返回的 fd。最后一个引用（fd 或者映射）消失时取消所有还没完成的请求
+pub struct IoUring {
+    pub metadata: FileMeta,
+    pub ctx: Arc<IoRingCtx>,
+}
+
+impl IoUring {
+    pub fn new(ctx: Arc<IoRingCtx>, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            metadata: FileMeta::new(flags, DummyInode::new(InodeType::Unknown, "anon_inode:[io_uring]")),
+            ctx,
+        })
+    }
+}
+
+impl Drop for IoUring {
+    fn drop(&mut self) {
+        info!("[IoUring::drop] close ring");
+        self.ctx.close();
+    }
+}
+
+#[async_trait]
+impl FileTrait for IoUring {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+
+    async fn read(&self, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(Errno::EINVAL)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(Errno::EINVAL)
+    }
+
+    fn abspath(&self) -> String {
+        String::from("anon_inode:[io_uring]")
+    }
+
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = Kstat::new();
+        stat.st_ino = self.metadata.inode.metadata().ino as u64;
+        stat.st_nlink = 1;
+        Ok(())
+    }
+
+    async fn get_page_at(&self, offset: usize) -> Option<Arc<Page>> {
+        self.ctx.mapped_page(offset)
+    }
+
+    /// CQ 中有事件时可读
+    async fn pollin(&self) -> SysResult<bool> {
+        let waker = get_waker().await;
+        Ok(self.ctx.poll_wait(CqWait::Ready(1), &waker))
+    }
+
+    /// SQ 未满时可写
+    async fn pollout(&self) -> SysResult<bool> {
+        Ok(self.ctx.sq_space() > 0)
+    }
+}
@@ -0,0 +1,336 @@
+//! 建议性文件锁：POSIX 记录锁、OFD 锁和 flock 锁
+//!
+//! 锁按 inode 编号组织。POSIX 锁属于进程，进程关闭该文件的任意一个 fd 或退出时释放；
+//! OFD 锁和 flock 锁属于打开的文件（以 FileMeta 的地址标识），文件的最后一个引用消失时释放。
+//! POSIX 锁和 OFD 锁放在同一张表里，互相冲突；flock 锁与它们互不影响。
+//!
+//! 等待锁的任务挂在 inode 的 waker 列表上，锁表发生变化时全部唤醒重新尝试。
+
+use crate::{
+    sync::SpinNoIrqLock,
+    task::{current_task, TaskControlBlock},
+    utils::{Errno, SysResult},
+};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// 沿等待关系查找死锁时最多走的步数，与 Linux 相同
+const MAX_DEADLK_ITERATIONS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockOwner {
+    /// POSIX 锁，属于进程（tgid）
+    Process(usize),
+    /// OFD 锁和 flock 锁，属于打开的文件
+    File(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i16)]
+pub enum LockType {
+    Read = 0,
+    Write = 1,
+    Unlock = 2,
+}
+
+impl TryFrom<i16> for LockType {
+    type Error = Errno;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Read),
+            1 => Ok(Self::Write),
+            2 => Ok(Self::Unlock),
+            _ => Err(Errno::EINVAL),
+        }
+    }
+}
+
+/// 字节范围锁，范围是闭区间，end 为 u64::MAX 表示一直到文件末尾之后
+#[derive(Clone, Copy, Debug)]
+pub struct FileLock {
+    pub owner: LockOwner,
+    pub ty: LockType,
+    pub start: u64,
+    pub end: u64,
+    /// 加锁的进程，F_GETLK 报告给用户
+    pub pid: usize,
+}
+
+impl FileLock {
+    fn overlaps(&self, other: &FileLock) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    fn conflicts(&self, other: &FileLock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other)
+            && (self.ty == LockType::Write || other.ty == LockType::Write)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LockRequest {
+    /// fcntl 的 POSIX 锁和 OFD 锁
+    Range(FileLock),
+    /// flock，file 为打开的文件
+    Flock { file: usize, ty: LockType },
+}
+
+#[derive(Default)]
+struct InodeLocks {
+    ranges: Vec<FileLock>,
+    flocks: Vec<(usize, LockType)>,
+    waiters: Vec<Waker>,
+}
+
+impl InodeLocks {
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.flocks.is_empty() && self.waiters.is_empty()
+    }
+
+    /// 与 req 冲突的锁的持有者
+    fn blocker(&self, req: &LockRequest) -> Option<LockOwner> {
+        match req {
+            LockRequest::Range(lock) if lock.ty != LockType::Unlock => self
+                .ranges
+                .iter()
+                .find(|l| l.conflicts(lock))
+                .map(|l| l.owner),
+            LockRequest::Flock { file, ty } if *ty != LockType::Unlock => self
+                .flocks
+                .iter()
+                .find(|(f, t)| f != file && (*t == LockType::Write || *ty == LockType::Write))
+                .map(|(f, _)| LockOwner::File(*f)),
+            _ => None,
+        }
+    }
+
+    fn apply(&mut self, req: &LockRequest) {
+        match req {
+            LockRequest::Range(lock) => self.apply_range(lock),
+            LockRequest::Flock { file, ty } => {
+                self.flocks.retain(|(f, _)| f != file);
+                if *ty != LockType::Unlock {
+                    self.flocks.push((*file, *ty));
+                }
+            }
+        }
+        self.wake_all();
+    }
+
+    /// 用 lock 替换同一持有者在范围内的锁，并与相邻的同类锁合并
+    fn apply_range(&mut self, lock: &FileLock) {
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 2);
+        for l in self.ranges.drain(..) {
+            if l.owner != lock.owner || !l.overlaps(lock) {
+                ranges.push(l);
+                continue;
+            }
+            if l.start < lock.start {
+                ranges.push(FileLock {
+                    end: lock.start - 1,
+                    ..l
+                });
+            }
+            if l.end > lock.end {
+                ranges.push(FileLock {
+                    start: lock.end + 1,
+                    ..l
+                });
+            }
+        }
+        if lock.ty != LockType::Unlock {
+            let mut new = *lock;
+            ranges.retain(|l| {
+                let adjacent = l.end.checked_add(1) == Some(new.start)
+                    || new.end.checked_add(1) == Some(l.start);
+                if l.owner == new.owner && l.ty == new.ty && adjacent {
+                    new.start = new.start.min(l.start);
+                    new.end = new.end.max(l.end);
+                    return false;
+                }
+                true
+            });
+            ranges.push(new);
+        }
+        self.ranges = ranges;
+    }
+
+    fn wake_all(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+lazy_static! {
+    /// 每个 inode 上的锁，key 是 inode 编号
+    static ref FILE_LOCKS: SpinNoIrqLock<BTreeMap<usize, InodeLocks>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+    /// 正在等待 POSIX 锁的进程以及挡住它的锁的持有者，用于检测死锁
+    static ref LOCK_WAITS: SpinNoIrqLock<BTreeMap<usize, LockOwner>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// 返回会阻止加上 lock 的一把锁，不存在时为 None
+pub fn lock_test(ino: usize, lock: &FileLock) -> Option<FileLock> {
+    FILE_LOCKS
+        .lock()
+        .get(&ino)?
+        .ranges
+        .iter()
+        .find(|l| l.conflicts(lock))
+        .copied()
+}
+
+/// 加锁或解锁。遇到冲突时 wait 为 false 返回 EAGAIN，否则等待直到成功。
+/// 等待 POSIX 锁会形成死锁时返回 EDEADLK，等待中收到信号返回 EINTR
+pub async fn lock_acquire(ino: usize, req: LockRequest, wait: bool) -> SysResult {
+    {
+        let mut table = FILE_LOCKS.lock();
+        let locks = table.entry(ino).or_default();
+        if locks.blocker(&req).is_none() {
+            locks.apply(&req);
+            if locks.is_empty() {
+                table.remove(&ino);
+            }
+            return Ok(());
+        }
+        // flock 转换锁类型不是原子的：与 Linux 相同，先放掉原来的锁再去等待
+        if let LockRequest::Flock { file, .. } = req {
+            let len = locks.flocks.len();
+            locks.flocks.retain(|(f, _)| *f != file);
+            if locks.flocks.len() != len {
+                locks.wake_all();
+            }
+        }
+    }
+    if !wait {
+        return Err(Errno::EAGAIN);
+    }
+    let task = current_task().unwrap();
+    task.set_wake_up_signal(!*task.get_blocked());
+    LockWaitFuture { ino, req, task }.await
+}
+
+/// 进程关闭了 inode 上的某个 fd，释放它在这个 inode 上的全部 POSIX 锁
+pub fn release_posix_locks(ino: usize, tgid: usize) {
+    let mut table = FILE_LOCKS.lock();
+    let Some(locks) = table.get_mut(&ino) else {
+        return;
+    };
+    let len = locks.ranges.len();
+    locks.ranges.retain(|l| l.owner != LockOwner::Process(tgid));
+    if locks.ranges.len() != len {
+        locks.wake_all();
+    }
+    if locks.is_empty() {
+        table.remove(&ino);
+    }
+}
+
+/// 进程退出，释放它持有的全部 POSIX 锁
+pub fn release_process_locks(tgid: usize) {
+    let mut table = FILE_LOCKS.lock();
+    table.retain(|_, locks| {
+        let len = locks.ranges.len();
+        locks.ranges.retain(|l| l.owner != LockOwner::Process(tgid));
+        if locks.ranges.len() != len {
+            locks.wake_all();
+        }
+        !locks.is_empty()
+    });
+}
+
+/// 打开的文件被释放，释放它持有的 OFD 锁和 flock 锁
+pub fn release_file_locks(ino: usize, file: usize) {
+    let mut table = FILE_LOCKS.lock();
+    let Some(locks) = table.get_mut(&ino) else {
+        return;
+    };
+    let len = locks.ranges.len() + locks.flocks.len();
+    locks.ranges.retain(|l| l.owner != LockOwner::File(file));
+    locks.flocks.retain(|(f, _)| *f != file);
+    if locks.ranges.len() + locks.flocks.len() != len {
+        locks.wake_all();
+    }
+    if locks.is_empty() {
+        table.remove(&ino);
+    }
+}
+
+/// 从 blocker 出发沿等待关系走，回到 tgid 说明等待会造成死锁
+fn would_deadlock(waits: &BTreeMap<usize, LockOwner>, tgid: usize, blocker: LockOwner) -> bool {
+    let mut owner = blocker;
+    for _ in 0..MAX_DEADLK_ITERATIONS {
+        match owner {
+            LockOwner::Process(pid) if pid == tgid => return true,
+            LockOwner::Process(pid) => match waits.get(&pid) {
+                Some(next) => owner = *next,
+                None => return false,
+            },
+            LockOwner::File(_) => return false,
+        }
+    }
+    false
+}
+
+struct LockWaitFuture {
+    ino: usize,
+    req: LockRequest,
+    task: Arc<TaskControlBlock>,
+}
+
+impl LockWaitFuture {
+    fn waiter(&self) -> Option<usize> {
+        match self.req {
+            LockRequest::Range(FileLock {
+                owner: LockOwner::Process(tgid),
+                ..
+            }) => Some(tgid),
+            _ => None,
+        }
+    }
+}
+
+impl Future for LockWaitFuture {
+    type Output = SysResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut table = FILE_LOCKS.lock();
+        let locks = table.entry(self.ino).or_default();
+        let Some(blocker) = locks.blocker(&self.req) else {
+            locks.apply(&self.req);
+            if let Some(tgid) = self.waiter() {
+                LOCK_WAITS.lock().remove(&tgid);
+            }
+            return Poll::Ready(Ok(()));
+        };
+        if let Some(tgid) = self.waiter() {
+            let mut waits = LOCK_WAITS.lock();
+            if would_deadlock(&waits, tgid, blocker) {
+                waits.remove(&tgid);
+                return Poll::Ready(Err(Errno::EDEADLK));
+            }
+            waits.insert(tgid, blocker);
+        }
+        if self.task.sig_pending.lock().has_expected(!*self.task.get_blocked()).0 {
+            return Poll::Ready(Err(Errno::EINTR));
+        }
+        locks.waiters.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for LockWaitFuture {
+    fn drop(&mut self) {
+        if let Some(tgid) = self.waiter() {
+            LOCK_WAITS.lock().remove(&tgid);
+        }
+    }
+}
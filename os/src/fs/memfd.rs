@@ -0,0 +1,329 @@
+//! memfd: 只存在于内存中的匿名文件
+//!
+//! 数据全部保存在 page cache 中，没有后备存储；支持 MAP_SHARED 映射和 file sealing。
+
+use crate::{
+    fs::{
+        page_cache::PageCache,
+        xattr::{XattrFlags, XattrMap},
+        FileMeta, FileTrait, InodeMeta, InodeTrait, InodeType, Kstat,
+        OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET,
+    },
+    hal::config::PAGE_SIZE,
+    mm::page::Page,
+    sync::{SpinNoIrqLock, TimeStamp},
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+use async_trait::async_trait;
+use bitflags::bitflags;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::info;
+
+use super::ffi::ModeFlag;
+
+bitflags! {
+    /// fcntl(F_ADD_SEALS) 使用的封印标志
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SealFlags: u32 {
+        /// 禁止再添加新的 seal
+        const F_SEAL_SEAL = 0x0001;
+        /// 禁止缩小文件
+        const F_SEAL_SHRINK = 0x0002;
+        /// 禁止扩大文件
+        const F_SEAL_GROW = 0x0004;
+        /// 禁止写入（包括可写的共享映射）
+        const F_SEAL_WRITE = 0x0008;
+        /// 禁止之后的写入，已有的可写映射不受影响
+        const F_SEAL_FUTURE_WRITE = 0x0010;
+    }
+}
+
+impl SealFlags {
+    /// 是否禁止写入
+    pub fn deny_write(&self) -> bool {
+        self.intersects(Self::F_SEAL_WRITE | Self::F_SEAL_FUTURE_WRITE)
+    }
+}
+
+/// memfd 的 inode，数据只保存在 page cache 里
+pub struct MemfdInode {
+    pub metadata: InodeMeta,
+    pub page_cache: Arc<PageCache>,
+    /// 当前生效的 seal
+    pub seals: SpinNoIrqLock<SealFlags>,
+    /// 与 tmpfs 相同，扩展属性只保存在内存中
+    pub xattrs: XattrMap,
+    /// 可写的 MAP_SHARED 映射个数，不为 0 时不能添加 F_SEAL_WRITE
+    writable_maps: AtomicUsize,
+}
+
+impl MemfdInode {
+    pub fn new(name: &str, allow_sealing: bool) -> Arc<Self> {
+        let seals = match allow_sealing {
+            true => SealFlags::empty(),
+            // 没有 MFD_ALLOW_SEALING 时不允许再添加 seal
+            false => SealFlags::F_SEAL_SEAL,
+        };
+        let page_cache = PageCache::new_bare();
+        let inode = Arc::new(Self {
+            metadata: InodeMeta::new(InodeType::File, 0, &format!("/memfd:{}", name)),
+            page_cache: page_cache.clone(),
+            seals: SpinNoIrqLock::new(seals),
+            xattrs: XattrMap::new(),
+            writable_maps: AtomicUsize::new(0),
+        });
+        *inode.metadata.i_mode.lock() = (ModeFlag::S_IFREG.bits() | 0o777).into();
+        page_cache.set_inode(inode.clone());
+        inode
+    }
+
+    pub fn get_seals(&self) -> SealFlags {
+        *self.seals.lock()
+    }
+
+    /// 添加 seal，已经有 F_SEAL_SEAL 时返回 EPERM，
+    /// 还有可写的共享映射时不能添加 F_SEAL_WRITE，返回 EBUSY
+    pub fn add_seals(&self, new: SealFlags) -> SysResult {
+        let mut seals = self.seals.lock();
+        if seals.contains(SealFlags::F_SEAL_SEAL) {
+            return Err(Errno::EPERM);
+        }
+        if new.contains(SealFlags::F_SEAL_WRITE)
+            && !seals.contains(SealFlags::F_SEAL_WRITE)
+            && self.writable_maps.load(Ordering::Acquire) != 0
+        {
+            return Err(Errno::EBUSY);
+        }
+        seals.insert(new);
+        Ok(())
+    }
+
+    /// 建立一个可写的共享映射，由 VmArea 在创建和获得写权限时调用
+    pub fn map_writable(&self) {
+        self.writable_maps.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// 撤销一个可写的共享映射，由 VmArea 在销毁和失去写权限时调用
+    pub fn unmap_writable(&self) {
+        self.writable_maps.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// 检查将文件大小改为 new_size 是否被 seal 禁止
+    pub fn check_resize(&self, new_size: usize) -> SysResult {
+        let seals = self.get_seals();
+        let old_size = self.get_size();
+        if new_size < old_size && seals.contains(SealFlags::F_SEAL_SHRINK) {
+            return Err(Errno::EPERM);
+        }
+        if new_size > old_size && seals.contains(SealFlags::F_SEAL_GROW) {
+            return Err(Errno::EPERM);
+        }
+        Ok(())
+    }
+
+    /// 检查在 offset 处写入 len 字节是否被 seal 禁止
+    pub fn check_write(&self, offset: usize, len: usize) -> SysResult {
+        let seals = self.get_seals();
+        if seals.deny_write() {
+            return Err(Errno::EPERM);
+        }
+        if offset + len > self.get_size() && seals.contains(SealFlags::F_SEAL_GROW) {
+            return Err(Errno::EPERM);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InodeTrait for MemfdInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+
+    fn get_page_cache(&self) -> Option<Arc<PageCache>> {
+        Some(self.page_cache.clone())
+    }
+
+    fn get_size(&self) -> usize {
+        self.metadata.size.load(Ordering::Relaxed)
+    }
+
+    fn set_size(&self, new_size: usize) -> SysResult {
+        self.metadata.size.store(new_size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn read_at(&self, offset: usize, mut buf: &mut [u8]) -> usize {
+        let file_size = self.get_size();
+        if offset >= file_size {
+            return 0;
+        }
+        if buf.len() > file_size - offset {
+            buf = &mut buf[..file_size - offset];
+        }
+        self.page_cache.read(buf, offset).await
+    }
+
+    /// 没有后备存储，缓存未命中的页就是全零页
+    async fn read_dirctly(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        buf.fill(0);
+        buf.len()
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let write_size = self.page_cache.write(buf, offset).await;
+        if self.get_size() < offset + write_size {
+            self.set_size(offset + write_size);
+        }
+        write_size
+    }
+
+    /// 数据只存在于 page cache，回写什么都不用做
+    async fn write_directly(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+
+    fn truncate(&self, size: usize) -> usize {
+        info!("[memfd truncate] {} size = {}", self.metadata.abspath, size);
+        self.page_cache.truncate(size);
+        self.set_size(size);
+        0
+    }
+
+    async fn sync(&self) {}
+
+    async fn read_all(&self) -> SysResult<Vec<u8>> {
+        let mut buf = vec![0u8; self.get_size()];
+        self.read_at(0, &mut buf).await;
+        Ok(buf)
+    }
+
+    fn look_up(&self, _path: &str) -> Option<Arc<dyn InodeTrait>> {
+        None
+    }
+
+    fn get_timestamp(&self) -> &SpinNoIrqLock<TimeStamp> {
+        &self.metadata.timestamp
+    }
+
+    fn fstat(&self) -> Kstat {
+        let mut stat = Kstat::new();
+        let size = self.get_size();
+        stat.init(size as i64, PAGE_SIZE as i32, size.div_ceil(512) as i64);
+        stat.st_ino = self.metadata.ino as u64;
+        stat.st_mode = self.metadata.i_mode.lock().mode.bits();
+        let (atime, mtime, ctime) = self.metadata.timestamp.lock().get();
+        stat.st_atime_sec = atime.tv_sec as isize;
+        stat.st_atime_nsec = atime.tv_nsec as isize;
+        stat.st_mtime_sec = mtime.tv_sec as isize;
+        stat.st_mtime_nsec = mtime.tv_nsec as isize;
+        stat.st_ctime_sec = ctime.tv_sec as isize;
+        stat.st_ctime_nsec = ctime.tv_nsec as isize;
+        stat
+    }
+
+    fn getxattr(&self, name: &str) -> SysResult<Vec<u8>> {
+        self.xattrs.get(name)
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> SysResult {
+        self.xattrs.set(name, value, flags)
+    }
+
+    fn listxattr(&self) -> SysResult<Vec<String>> {
+        Ok(self.xattrs.list())
+    }
+
+    fn removexattr(&self, name: &str) -> SysResult {
+        self.xattrs.remove(name)
+    }
+}
+
+/// memfd_create 返回的文件
+pub struct MemfdFile {
+    pub metadata: FileMeta,
+    pub inode: Arc<MemfdInode>,
+}
+
+impl MemfdFile {
+    pub fn new(name: &str, flags: OpenFlags, allow_sealing: bool) -> Arc<Self> {
+        let inode = MemfdInode::new(name, allow_sealing);
+        Arc::new(Self {
+            metadata: FileMeta::new(flags, inode.clone()),
+            inode,
+        })
+    }
+}
+
+#[async_trait]
+impl FileTrait for MemfdFile {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+        let offset = self.metadata.offset();
+        let read_size = self.inode.read_at(offset, buf).await;
+        self.metadata.set_offset(offset + read_size);
+        Ok(read_size)
+    }
+
+    async fn pread(&self, buf: &mut [u8], offset: usize, _len: usize) -> SysResult<usize> {
+        Ok(self.inode.read_at(offset, buf).await)
+    }
+
+    async fn write(&self, buf: &[u8]) -> SysResult<usize> {
+        let offset = match self.metadata.flags.read().contains(OpenFlags::O_APPEND) {
+            true => self.inode.get_size(),
+            false => self.metadata.offset(),
+        };
+        self.inode.check_write(offset, buf.len())?;
+        let write_size = self.inode.write_at(offset, buf).await;
+        self.metadata.set_offset(offset + write_size);
+        Ok(write_size)
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        self.inode.check_write(offset, buf.len())?;
+        Ok(self.inode.write_at(offset, buf).await)
+    }
+
+    async fn pwrite(&self, buf: &[u8], offset: usize, _len: usize) -> SysResult<usize> {
+        self.inode.check_write(offset, buf.len())?;
+        Ok(self.inode.write_at(offset, buf).await)
+    }
+
+    fn lseek(&self, offset: isize, whence: usize) -> SysResult<usize> {
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => self.metadata.offset() as isize,
+            SEEK_END => self.inode.get_size() as isize,
+            _ => return Err(Errno::EINVAL),
+        };
+        let res = base + offset;
+        if res < 0 {
+            return Err(Errno::EINVAL);
+        }
+        self.metadata.set_offset(res as usize);
+        Ok(res as usize)
+    }
+
+    fn abspath(&self) -> String {
+        self.inode.metadata.abspath.clone()
+    }
+
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = self.inode.fstat();
+        Ok(())
+    }
+
+    async fn get_page_at(&self, offset: usize) -> Option<Arc<Page>> {
+        self.inode.page_cache.get_page(offset).await
+    }
+}
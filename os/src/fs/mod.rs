@@ -1,17 +1,25 @@
 mod devfs;
 mod dirent;
 pub mod fanotify;
+pub mod fsnotify;
+pub mod inotify;
+pub mod io_uring;
+pub mod locks;
 // mod inode_cache;
 pub mod ext4;
+pub mod memfd;
 mod mount;
 mod page_cache;
 mod path;
+pub mod pidfd;
 mod pipe;
+mod splice;
 pub mod pre_data;
 pub mod procfs;
 mod stat;
 // mod stdio;
 pub mod vfs;
+pub mod xattr;
 // pub mod tmp;
 pub mod ffi;
 pub mod ltp;
@@ -21,6 +29,7 @@ pub use devfs::{DevTty, CharDev};
 use core::error;
 pub use dirent::Dirent;
 use ext4::{file, Ext4Inode};
+use fsnotify::{fsnotify, FsEventMask};
 pub use ext4::{ls, root_inode};
 pub use ffi::*;
 use lwext4_rust::bindings::{self, true_, O_CREAT, O_RDWR, O_TRUNC};
@@ -29,7 +38,8 @@ use lwext4_rust::{Ext4File, Ext4InodeType};
 pub use path::{path_test, resolve_path, AbsPath};
 // pub use inode_cache::*;
 pub use mount::MNT_TABLE;
-pub use pipe::Pipe;
+pub use pipe::{Pipe, PipeBuf};
+pub use splice::*;
 use procfs::super_block::PROCFS_SUPER_BLOCK;
 use crate::fs::devfs::superblock::DEVFS_SUPER_BLOCK;
 // use sbi_rt::NonRetentive;
@@ -226,6 +236,9 @@ fn create_open_file(
                 match res {
                     Some(inode) => {
                         debug_point!("");
+                        init_owner(&parent_dir, &inode);
+                        let isdir = flags.contains(OpenFlags::O_DIRECTORY);
+                        fsnotify(target_abs_path, FsEventMask::CREATE | FsEventMask::dir(isdir));
                         inode
                     }
                     None => {
@@ -244,8 +257,17 @@ fn create_open_file(
         );
         return Err(Errno::ENOTDIR);
     }
+    // 最后一个分量是符号链接说明调用者要求不跟随：O_PATH 打开链接本身，否则返回 ELOOP
+    if target_inode.metadata()._type.is_symlink() && !flags.contains(OpenFlags::O_PATH) {
+        return Err(Errno::ELOOP);
+    }
     info!("[create_file] got target inode, flags = {:?}", flags);
 
+    // ptmx 每次打开都要分配新的 pty，pts 需要检查锁状态，不能按普通文件打开
+    if let Some(res) = devfs::pts::pty_open(&target_inode, flags) {
+        return res;
+    }
+
     let res = {
         let osinode = NormalFile::new(
             flags,
@@ -259,7 +281,7 @@ fn create_open_file(
     Ok(res)
 }
 
-/// path为绝对路径
+/// path为绝对路径，路径上的符号链接都会被展开，带 O_NOFOLLOW 时最后一个分量除外
 pub fn open(path: AbsPath, flags: OpenFlags) -> SysResult<Arc<dyn FileTrait>> {
     info!(
         "    [fs_open] abspath = {}, flags = {:?}",
@@ -281,9 +303,38 @@ pub fn open(path: AbsPath, flags: OpenFlags) -> SysResult<Arc<dyn FileTrait>> {
     //     return Err(Errno::EIO);
     // }
 
+    let path = path.follow(!flags.contains(OpenFlags::O_NOFOLLOW))?;
+    open_resolved(path, flags)
+}
+
+/// 打开调用者已经用 `AbsPath::follow` 展开过符号链接的路径，不再重复跟随
+pub fn open_resolved(path: AbsPath, flags: OpenFlags) -> SysResult<Arc<dyn FileTrait>> {
     create_open_file(&path.get(), &path.get_parent_abs(), flags)
 }
 
+/// 在 linkpath 处创建指向 target 的符号链接
+///
+/// target 原样保存，不要求存在；linkpath 的最后一个分量不跟随，已经存在时返回 EEXIST
+pub fn symlink(target: &str, linkpath: AbsPath) -> SysResult<()> {
+    info!("[symlink] {} -> {}", linkpath.get(), target);
+    if Dentry::get_inode_from_path(&linkpath.get()).is_ok() {
+        return Err(Errno::EEXIST);
+    }
+    let parent_dentry = Dentry::get_dentry_from_path(&linkpath.get_parent_abs())?;
+    let parent_dir = parent_dentry.get_inode().ok_or(Errno::ENOENT)?;
+    if !parent_dir.metadata()._type.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    may_create(&parent_dir)?;
+    let bare_dentry = parent_dentry
+        .bare_child(&linkpath.get_filename())
+        .ok_or(Errno::EEXIST)?;
+    let inode = parent_dir.do_symlink(bare_dentry, target)?;
+    init_owner(&parent_dir, &inode);
+    fsnotify(&linkpath.get(), FsEventMask::CREATE);
+    Ok(())
+}
+
 /// 创建一个新的文件夹
 ///
 /// - path: 文件夹目录（绝对路径）
@@ -319,7 +370,7 @@ pub fn chdir(target: AbsPath) -> SysResult<()> {
 
     let inode = Dentry::get_inode_from_path(&target.get())?;
     if inode.metadata()._type.is_dir() {
-        return Ok(());
+        return inode.permission(MayAccess::MAY_EXEC);
     }
     return Err(Errno::ENOTDIR);
 }
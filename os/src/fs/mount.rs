@@ -46,6 +46,20 @@ impl MountTable {
     pub fn is_mounted(&self, dir: String) -> bool {
         self.mnt_list.iter().find(|&(_, d, _)| *d == dir).is_some()
     }
+
+    /// 包含 path 的最深的挂载点，都不包含时为根目录
+    pub fn mount_point_of(&self, path: &str) -> String {
+        self.mnt_list
+            .iter()
+            .map(|(_, dir, _)| dir.trim_end_matches('/'))
+            .filter(|dir| {
+                path.strip_prefix(dir)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|dir| dir.len())
+            .filter(|dir| !dir.is_empty())
+            .map_or(String::from("/"), String::from)
+    }
 }
 
 lazy_static! {
@@ -4,6 +4,7 @@
 //! including path joining, splitting, and path type checking operations.
 
 use alloc::{
+    collections::VecDeque,
     format,
     string::{String, ToString},
     vec::Vec,
@@ -11,7 +12,14 @@ use alloc::{
 use core::fmt::Debug;
 use log::info;
 
-use crate::task::current_task;
+use crate::{
+    fs::Dentry,
+    task::current_task,
+    utils::{Errno, SysResult},
+};
+
+/// 解析一个路径时最多展开的符号链接数，超过时返回 ELOOP，与 Linux 的 MAXSYMLINKS 相同
+pub const MAX_SYMLINKS: usize = 40;
 
 /// Represents a file system path.
 ///
@@ -134,6 +142,74 @@ impl AbsPath {
     }
 }
 
+impl AbsPath {
+    /// 展开路径上的符号链接，得到一条不经过符号链接的绝对路径
+    ///
+    /// 中间的分量总是跟随；最后一个分量只在 follow 为 true 时跟随，否则保留链接本身。
+    /// 相对的链接目标以链接所在的目录为起点，绝对的链接目标从根目录重新开始，
+    /// 挂载的文件系统都挂在同一棵 dentry 树上，所以链接目标可以跨越挂载点。
+    ///
+    /// 最后一个分量不存在时原样保留，由调用者决定是创建还是返回 ENOENT
+    pub fn follow(&self, follow: bool) -> SysResult<AbsPath> {
+        // 路径上有符号链接时 dentry 查找会在链接处返回 ENOTDIR，能直接找到说明只有最后一个分量可能是链接
+        match Dentry::get_inode_from_path(&self.content) {
+            Ok(inode) if !follow || !inode.metadata()._type.is_symlink() => {
+                return Ok(self.clone());
+            }
+            _ => {}
+        }
+        let mut resolved = String::from("/");
+        let mut pending: VecDeque<String> = self.content.split('/').map(String::from).collect();
+        let mut links = 0;
+        while let Some(name) = pending.pop_front() {
+            match name.as_str() {
+                "" | "." => continue,
+                ".." => {
+                    resolved = AbsPath::from(resolved.as_str()).get_parent_abs();
+                    continue;
+                }
+                _ => {}
+            }
+            let next = match resolved.as_str() {
+                "/" => format!("/{}", name),
+                _ => format!("{}/{}", resolved, name),
+            };
+            let last = pending.iter().all(|name| name.is_empty() || name == ".");
+            if last && !follow {
+                resolved = next;
+                break;
+            }
+            let inode = match Dentry::get_inode_from_path(&next) {
+                Ok(inode) => inode,
+                Err(Errno::ENOENT) if last => {
+                    resolved = next;
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            if !inode.metadata()._type.is_symlink() {
+                resolved = next;
+                continue;
+            }
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(Errno::ELOOP);
+            }
+            let target = inode.readlink()?;
+            if target.is_empty() {
+                return Err(Errno::ENOENT);
+            }
+            if target.starts_with('/') {
+                resolved = String::from("/");
+            }
+            for name in target.split('/').rev() {
+                pending.push_front(String::from(name));
+            }
+        }
+        Ok(AbsPath::new(resolved))
+    }
+}
+
 /// 处理路径中..和.以及多于的/,可以参考下方的test实例
 fn parse_path(path: String) -> String {
     let components: Vec<&str> = path.split("/").collect();
@@ -185,19 +261,43 @@ fn parse_path(path: String) -> String {
 ///
 /// path: 是目标路径，可以是绝对路径或相对路径
 pub fn resolve_path(base: String, path: String) -> AbsPath {
-    // 首先处理一下传入的path, 去掉多于的//、..和.等
-    let path = parse_path(path);
-    // 已经是绝对路径直接返回,忽略base
+    // 已经是绝对路径直接处理,忽略base
     if path.starts_with("/") {
-        return AbsPath::new(path);
+        return AbsPath::from(normalize_abs(&path).as_str());
     }
 
     // 根据当前路径进行拼接
     let trim_base = base.trim_end_matches("/").to_string();
     let target_abs = format!("{}/{}", trim_base, path);
-    // let target_abs = parse_path(target_abs);
 
-    AbsPath::new(target_abs)
+    AbsPath::from(normalize_abs(&target_abs).as_str())
+}
+
+/// 逐个分量规范化绝对路径，去掉多余的/和.
+///
+/// 遇到..时先展开已经走过的部分上的符号链接再回到上一级，
+/// 这样 `dirlink/..` 回到的是链接目标的父目录，而不是链接所在的目录。
+/// 走过的部分不存在时（例如即将创建的路径）只能按文本回退
+fn normalize_abs(path: &str) -> String {
+    let mut resolved = String::from("/");
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                if let Ok(real) = AbsPath::from(resolved.as_str()).follow(true) {
+                    resolved = real.get();
+                }
+                resolved = AbsPath::from(resolved.as_str()).get_parent_abs();
+            }
+            _ => {
+                if resolved != "/" {
+                    resolved.push('/');
+                }
+                resolved.push_str(name);
+            }
+        }
+    }
+    resolved
 }
 
 /// Unit tests for the Path implementation.
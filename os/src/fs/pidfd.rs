@@ -0,0 +1,128 @@
+//! pidfd: 指向某个进程的文件描述符
+//!
+//! 持有目标进程的弱引用，不会因为 pid 复用而指向别的进程。
+//! 进程退出（成为 zombie）后 pidfd 变为可读，可以配合 ppoll 等待进程结束。
+
+use super::{FileTrait, InodeType, Kstat, OpenFlags};
+use crate::{
+    fs::{pipe::DummyInode, FileMeta},
+    sync::{get_waker, SpinNoIrqLock},
+    task::TaskControlBlock,
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::{
+    collections::btree_map::BTreeMap,
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::task::Waker;
+use log::info;
+
+lazy_static! {
+    /// 等待进程退出的 waker，key 是进程的 pid
+    static ref PIDFD_WAITERS: SpinNoIrqLock<BTreeMap<usize, Vec<Waker>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// 进程退出时调用，唤醒所有在该进程 pidfd 上等待的任务
+pub fn wake_pidfd_waiters(pid: usize) {
+    if let Some(wakers) = PIDFD_WAITERS.lock().remove(&pid) {
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+pub struct PidFd {
+    pub metadata: FileMeta,
+    /// 目标进程的 pid，只用于调试和挂起 waker
+    pid: usize,
+    task: Weak<TaskControlBlock>,
+}
+
+impl PidFd {
+    pub fn new(task: &Arc<TaskControlBlock>, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            metadata: FileMeta::new(flags, DummyInode::new(InodeType::Unknown, "anon_inode:[pidfd]")),
+            pid: task.get_pid(),
+            task: Arc::downgrade(task),
+        })
+    }
+
+    /// 获取目标进程，进程已经退出时返回 ESRCH
+    pub fn get_task(&self) -> SysResult<Arc<TaskControlBlock>> {
+        match self.task.upgrade() {
+            Some(task) if !task.is_zombie() => Ok(task),
+            _ => Err(Errno::ESRCH),
+        }
+    }
+
+    /// 目标进程的 pid，进程退出后依然有效
+    pub fn pid(&self) -> usize {
+        self.pid
+    }
+
+    /// 目标进程是否已经退出
+    pub fn is_exited(&self) -> bool {
+        self.task.upgrade().map_or(true, |task| task.is_zombie())
+    }
+}
+
+#[async_trait]
+impl FileTrait for PidFd {
+    fn metadata(&self) -> &FileMeta {
+        &self.metadata
+    }
+
+    fn set_flags(&self, flags: OpenFlags) {
+        *self.metadata.flags.write() = flags;
+    }
+
+    async fn read(&self, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(Errno::EINVAL)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(Errno::EINVAL)
+    }
+
+    fn abspath(&self) -> String {
+        format!("anon_inode:[pidfd {}]", self.pid)
+    }
+
+    fn fstat(&self, stat: &mut Kstat) -> SysResult {
+        *stat = Kstat::new();
+        stat.st_ino = self.metadata.inode.metadata().ino as u64;
+        stat.st_nlink = 1;
+        Ok(())
+    }
+
+    /// 进程退出后可读
+    async fn pollin(&self) -> SysResult<bool> {
+        if self.is_exited() {
+            return Ok(true);
+        }
+        // 先挂上 waker 再检查一次，避免错过在两者之间发生的退出。
+        // 同一个任务反复 poll 时只保留一个 waker
+        let waker = get_waker().await;
+        let mut waiters = PIDFD_WAITERS.lock();
+        let wakers = waiters.entry(self.pid).or_insert_with(Vec::new);
+        if !wakers.iter().any(|w| w.will_wake(&waker)) {
+            wakers.push(waker);
+        }
+        drop(waiters);
+        if self.is_exited() {
+            info!("[PidFd::pollin] pid {} exited", self.pid);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn pollout(&self) -> SysResult<bool> {
+        Ok(false)
+    }
+}
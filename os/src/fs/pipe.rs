@@ -1,6 +1,6 @@
 use super::{ffi::RenameFlags, FileTrait, InodeTrait, Kstat, OpenFlags};
 use crate::{
-    fs::{FileMeta, InodeMeta, InodeType}, hal::config::PIPE_BUFFER_SIZE, mm::page::Page, sync::{get_waker, once::LateInit, SpinNoIrqLock}, utils::{Errno, SysResult}
+    fs::{FileMeta, InodeMeta, InodeType}, hal::config::{PAGE_SIZE, PIPE_BUFFER_SIZE}, mm::page::{Page, PageType}, sync::{get_waker, once::LateInit, SpinNoIrqLock}, utils::{Errno, SysResult}
 };
 use alloc::boxed::Box;
 use alloc::{
@@ -65,7 +65,7 @@ impl Pipe {
     }
     /// 判断当前pipe的缓冲区是否满
     pub fn is_full(&self) -> bool {
-        self.buffer.lock().buf.len() < PIPE_BUFFER_SIZE
+        self.buffer.lock().len >= PIPE_BUFFER_SIZE
     }
     /// 判断对方是否存活,没有的话代表已经关闭通道
     pub fn other_alive(&self) -> bool {
@@ -86,6 +86,18 @@ impl Pipe {
     pub fn with_mut_buffer<T>(&self, f: impl FnOnce(&mut PipeInner) -> T) -> T {
         f(&mut self.buffer.lock())
     }
+    /// 是否以非阻塞方式打开
+    pub fn is_nonblock(&self) -> bool {
+        self.metadata.flags.read().contains(OpenFlags::O_NONBLOCK)
+    }
+    /// 等到管道中有数据可读，或者写端已经全部关闭（此时返回 `Ok(false)`，表示读到了末尾）
+    pub async fn wait_readable(&self, nonblock: bool) -> SysResult<bool> {
+        PipeWaitFuture { pipe: self, write: false, nonblock }.await
+    }
+    /// 等到管道中有空闲空间可写，读端已经全部关闭时返回 EPIPE
+    pub async fn wait_writable(&self, nonblock: bool) -> SysResult<bool> {
+        PipeWaitFuture { pipe: self, write: true, nonblock }.await
+    }
 }
 
 impl Drop for Pipe {
@@ -105,14 +117,39 @@ impl Drop for Pipe {
 
 /// 管道缓冲区状态
 #[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
+pub enum RingBufferStatus {
     Full,
     Empty,
     Normal,
 }
 
+/// 管道缓冲区中的一段数据：`page` 中 `[offset, offset + len)` 的内容
+///
+/// write 写入的数据复制到管道自己分配的匿名页中；splice 从文件读入时直接引用页缓存中的页，
+/// tee 则让两个管道引用同一页，这两种情况都不复制数据
+#[derive(Clone)]
+pub struct PipeBuf {
+    pub page: Arc<Page>,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl PipeBuf {
+    pub fn as_slice(&self) -> &[u8] {
+        &self.page.get_bytes_array()[self.offset..self.offset + self.len]
+    }
+
+    /// 只有管道独占的匿名页才能在末尾继续追加 write 的数据，
+    /// 页缓存中的页和 tee 出去的页都不能改写
+    fn can_merge(&self) -> bool {
+        matches!(self.page.page_type, PageType::Anon) && Arc::strong_count(&self.page) == 1
+    }
+}
+
 pub struct PipeInner {
-    pub buf: VecDeque<u8>,
+    pub bufs: VecDeque<PipeBuf>,
+    /// 缓冲区中的总字节数
+    pub len: usize,
     pub reader_waker: VecDeque<Waker>,
     pub writer_waker: VecDeque<Waker>,
     pub status: RingBufferStatus,
@@ -121,7 +158,8 @@ pub struct PipeInner {
 impl PipeInner {
     pub fn new() -> Self {
         Self {
-            buf: VecDeque::new(),
+            bufs: VecDeque::new(),
+            len: 0,
             reader_waker: VecDeque::new(),
             writer_waker: VecDeque::new(),
             status: RingBufferStatus::Empty,
@@ -131,12 +169,92 @@ impl PipeInner {
     /// 获取管道中剩余可读长度
     /// 需要比较用户buf还可以读多少数据，以及现在还剩多少数据
     pub fn available_read(&self, userbuf_left: usize) -> usize {
-        return min(userbuf_left, self.buf.len());
+        return min(userbuf_left, self.len);
     }
     /// 获取管道中剩余可写长度
     /// 判断用户还有多少数据要写，以及现在pipe还剩余多少空间
     pub fn available_write(&self, userbuf_left: usize) -> usize {
-        return min(userbuf_left, PIPE_BUFFER_SIZE - self.buf.len());
+        return min(userbuf_left, PIPE_BUFFER_SIZE.saturating_sub(self.len));
+    }
+
+    /// 把缓冲区开头的数据复制到 `buf` 并从管道中取走
+    pub fn read_to(&mut self, buf: &mut [u8]) -> usize {
+        let mut cur = 0;
+        while cur < buf.len() {
+            let Some(front) = self.bufs.front_mut() else { break };
+            let len = min(buf.len() - cur, front.len);
+            buf[cur..cur + len].copy_from_slice(&front.as_slice()[..len]);
+            cur += len;
+            self.consume_front(len);
+        }
+        cur
+    }
+
+    /// 把 `buf` 复制进管道，先填满末尾可追加的页，再分配新页，返回写入的字节数
+    pub fn write_from(&mut self, buf: &[u8]) -> usize {
+        let total = self.available_write(buf.len());
+        let mut cur = 0;
+        if let Some(back) = self.bufs.back_mut().filter(|back| back.can_merge()) {
+            let end = back.offset + back.len;
+            let len = min(total, PAGE_SIZE - end);
+            back.page.get_bytes_array()[end..end + len].copy_from_slice(&buf[..len]);
+            back.len += len;
+            cur = len;
+        }
+        while cur < total {
+            let len = min(total - cur, PAGE_SIZE);
+            let page = Page::new();
+            page.get_bytes_array()[..len].copy_from_slice(&buf[cur..cur + len]);
+            self.bufs.push_back(PipeBuf { page, offset: 0, len });
+            cur += len;
+        }
+        self.len += total;
+        total
+    }
+
+    /// 把一段页数据整体放进管道，不检查剩余空间
+    pub fn push_buf(&mut self, buf: PipeBuf) {
+        if buf.len == 0 {
+            return;
+        }
+        self.len += buf.len;
+        self.bufs.push_back(buf);
+    }
+
+    /// 复制出开头不超过 `max` 字节的页引用，数据仍留在管道中
+    pub fn peek_bufs(&self, max: usize) -> Vec<PipeBuf> {
+        let mut res = Vec::new();
+        let mut left = max;
+        for buf in self.bufs.iter() {
+            if left == 0 {
+                break;
+            }
+            let mut buf = buf.clone();
+            buf.len = min(buf.len, left);
+            left -= buf.len;
+            res.push(buf);
+        }
+        res
+    }
+
+    /// 从管道开头丢弃 `len` 字节
+    pub fn consume(&mut self, mut len: usize) {
+        while len > 0 {
+            let Some(front) = self.bufs.front() else { break };
+            let n = min(len, front.len);
+            self.consume_front(n);
+            len -= n;
+        }
+    }
+
+    fn consume_front(&mut self, len: usize) {
+        let Some(front) = self.bufs.front_mut() else { return };
+        front.offset += len;
+        front.len -= len;
+        self.len -= len;
+        if front.len == 0 {
+            self.bufs.pop_front();
+        }
     }
 }
 
@@ -187,7 +305,7 @@ impl FileTrait for Pipe {
     /// 异步管道（Pipe）读取操作的核心逻辑，用于检查管道是否可读（有数据可读或对端已关闭），
     /// 并根据情况注册 Waker 以便在数据到达时唤醒异步任务。
     async fn pollin(&self) -> SysResult<bool> {
-        if !self.buffer.lock().buf.is_empty() || self.other.strong_count() == 0 {
+        if self.buffer.lock().len != 0 || self.other.strong_count() == 0 {
             return Ok(true);
         }
 
@@ -230,10 +348,7 @@ impl Future for PipeReadFuture<'_> {
 
         if read_size > 0 {
             let mut inner = this.pipe.buffer.lock();
-            let target = &mut this.userbuf[this.cur..this.cur + read_size];
-            for (i, byte) in inner.buf.drain(..read_size).enumerate() {
-                target[i] = byte;
-            }
+            inner.read_to(&mut this.userbuf[this.cur..this.cur + read_size]);
             this.cur += read_size;
             this.pipe.wake_writers(&mut inner);
             Poll::Ready(Ok(read_size))
@@ -273,9 +388,7 @@ impl Future for PipeWriteFuture<'_> {
 
         if write_size > 0 {
             let mut inner = this.pipe.buffer.lock();
-            inner
-                .buf
-                .extend(&this.userbuf[this.cur..this.cur + write_size]);
+            inner.write_from(&this.userbuf[this.cur..this.cur + write_size]);
             this.cur += write_size;
             this.pipe.wake_readers(&mut inner);
             Poll::Ready(Ok(write_size))
@@ -287,6 +400,44 @@ impl Future for PipeWriteFuture<'_> {
     }
 }
 
+/// splice、tee 等待管道可读或可写
+struct PipeWaitFuture<'a> {
+    pipe: &'a Pipe,
+    write: bool,
+    nonblock: bool,
+}
+
+impl Future for PipeWaitFuture<'_> {
+    type Output = SysResult<bool>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.pipe.buffer.lock();
+        if self.write {
+            if !self.pipe.other_alive() {
+                return Poll::Ready(Err(Errno::EPIPE));
+            }
+            if inner.len < PIPE_BUFFER_SIZE {
+                return Poll::Ready(Ok(true));
+            }
+        } else {
+            if inner.len != 0 {
+                return Poll::Ready(Ok(true));
+            }
+            if !self.pipe.other_alive() {
+                return Poll::Ready(Ok(false));
+            }
+        }
+        if self.nonblock {
+            return Poll::Ready(Err(Errno::EAGAIN));
+        }
+        match self.write {
+            true => inner.writer_waker.push_back(cx.waker().clone()),
+            false => inner.reader_waker.push_back(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
 pub struct DummyInode(pub InodeMeta);
 
 impl DummyInode {
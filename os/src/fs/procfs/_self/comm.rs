@@ -0,0 +1,56 @@
+use alloc::{boxed::Box, format, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+use crate::{fs::{InodeMeta, InodeTrait, InodeType, Kstat, ModeFlag, StMode}, task::current_task, utils::SysResult};
+
+/// /proc/self/comm：读出当前线程名，写入时修改线程名
+pub struct CommInode(pub InodeMeta);
+
+impl CommInode {
+    pub fn new() -> Arc<dyn InodeTrait> {
+        Arc::new(Self(InodeMeta::new(
+            InodeType::File,
+            0,
+            "/proc/self/comm".into(),
+        )))
+    }
+}
+
+#[async_trait]
+impl InodeTrait for CommInode {
+    fn metadata(&self) ->  &InodeMeta {
+        &self.0
+    }
+
+    async fn read_all(&self) -> SysResult<Vec<u8>> {
+        let comm = current_task().map(|task| task.get_comm()).unwrap_or_default();
+        Ok(Vec::from(format!("{}\n", comm)))
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let comm = self.read_all().await.unwrap();
+        let len = comm.len();
+        if offset < len {
+            let read_len = core::cmp::min(len - offset, buf.len());
+            buf[..read_len].copy_from_slice(&comm[offset..offset + read_len]);
+            read_len
+        } else {
+            0
+        }
+    }
+
+    async fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        if let Some(task) = current_task() {
+            let name = buf.strip_suffix(b"\n").unwrap_or(buf);
+            task.set_comm(&alloc::string::String::from_utf8_lossy(name));
+        }
+        buf.len()
+    }
+
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_mode = StMode::new(
+            ModeFlag::S_IRUSR | ModeFlag::S_IWUSR | ModeFlag::S_IRGRP | ModeFlag::S_IROTH | ModeFlag::S_IFREG).into();
+        res.st_nlink = 1;
+        res
+    }
+}
@@ -1,6 +1,6 @@
 use alloc::{collections::btree_map::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec, boxed::Box};
 use async_trait::async_trait;
-use crate::fs::{dirent::build_dirents, procfs::_self::{exe::ExeInode, maps::MapsInode}, AbsPath, Dirent, InodeMeta, InodeTrait, Kstat};
+use crate::fs::{dirent::build_dirents, procfs::_self::{comm::CommInode, exe::ExeInode, maps::MapsInode}, AbsPath, Dirent, InodeMeta, InodeTrait, Kstat};
 
 
 pub struct _SelfInode{
@@ -13,6 +13,7 @@ impl _SelfInode {
         let mut children = BTreeMap::new();
         children.insert("exe".to_string(), ExeInode::new());
         children.insert("maps".to_string(), MapsInode::new());
+        children.insert("comm".to_string(), CommInode::new());
         Arc::new(Self{
             inodeMeta: InodeMeta::new(
                 crate::fs::InodeType::Dir,
@@ -45,7 +46,8 @@ impl InodeTrait for _SelfInode {
             (".", 2, 4), 
             ("..", 1, 4), 
             ("exe", 4, 8), 
-            ("maps", 6, 8)
+            ("maps", 6, 8),
+            ("comm", 7, 8)
         ];
         Some(build_dirents(entries))
     }
@@ -1,14 +1,15 @@
-use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc};
 use async_trait::async_trait;
-use crate::{fs::{open, InodeMeta, InodeTrait, InodeType, Kstat, OpenFlags}, utils::SysResult};
+use crate::{fs::{InodeMeta, InodeTrait, InodeType, Kstat, ModeFlag, StMode}, task::current_task, utils::{Errno, SysResult}};
 
 
+/// /proc/self/exe：指向当前进程可执行文件的符号链接
 pub struct ExeInode(pub InodeMeta);
 
 impl ExeInode {
     pub fn new() -> Arc<dyn InodeTrait> {
         Arc::new(Self(InodeMeta::new(
-            InodeType::File,
+            InodeType::SymLink,
             0,
             "/proc/self/exe".into(),
         )))
@@ -21,33 +22,14 @@ impl InodeTrait for ExeInode {
         &self.0
     }
 
-    async fn read_all(&self) -> SysResult<Vec<u8>> {
-        // Ok(alloc::vec![])
-        if let Ok(exe) = open("/bin/sh".into(), OpenFlags::O_RDONLY) {
-            exe.metadata().inode.read_all().await
-        } else {
-            Err(crate::utils::Errno::EACCES)
-        }
-    }
-
-    async fn read_at(&self, offset: usize, mut buf: &mut [u8]) -> usize {
-        // 非常重要
-        if let Ok(exe) = open("/bin/sh".into(), OpenFlags::O_RDONLY) {
-            exe.metadata().inode.read_at(offset, &mut buf).await
-        } else {
-            0
-        }
+    fn readlink(&self) -> SysResult<String> {
+        current_task().map(|task| task.get_exe_path()).ok_or(Errno::ENOENT)
     }
 
     fn fstat(&self) -> Kstat {
         let mut res = Kstat::new();
-
-        if let Ok(exe) = open("/bin/sh".into(), OpenFlags::O_RDONLY) {
-            exe.metadata().inode.fstat()
-        } else {
-            // error!("open /bin/sh failed");
-            res.st_mode = InodeType::File as u32;
-            res
-        }
+        res.st_mode = StMode::new(ModeFlag::S_IFLNK | ModeFlag::from_bits_truncate(0o777)).into();
+        res.st_nlink = 1;
+        res
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+mod comm;
 mod dir;
 mod exe;
 mod maps;
@@ -5,4 +5,5 @@ mod meminfo;
 mod mounts;
 mod _self;
 mod interrupts;
+mod net;
 mod sys;
\ No newline at end of file
@@ -0,0 +1,80 @@
+use alloc::{collections::btree_map::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec};
+use crate::fs::{dirent::build_dirents, procfs::net::stat::{NetStatInode, NetStatKind}, AbsPath, Dirent, InodeMeta, InodeTrait, InodeType, PageCache};
+use async_trait::async_trait;
+use alloc::boxed::Box;
+
+
+pub struct NetDirInode {
+    metadata: InodeMeta,
+    pub children: BTreeMap<String, Arc<dyn InodeTrait>>,
+}
+
+impl NetDirInode {
+    pub fn new() -> Arc<dyn InodeTrait> {
+        let mut children = BTreeMap::new();
+        children.insert("dev".to_string(), NetStatInode::new("/proc/net/dev", NetStatKind::Dev));
+        children.insert("route".to_string(), NetStatInode::new("/proc/net/route", NetStatKind::Route));
+        children.insert("tcp".to_string(), NetStatInode::new("/proc/net/tcp", NetStatKind::Tcp));
+        children.insert("udp".to_string(), NetStatInode::new("/proc/net/udp", NetStatKind::Udp));
+        children.insert("if_inet6".to_string(), NetStatInode::new("/proc/net/if_inet6", NetStatKind::IfInet6));
+        children.insert("ipv6_route".to_string(), NetStatInode::new("/proc/net/ipv6_route", NetStatKind::Ipv6Route));
+        children.insert("tcp6".to_string(), NetStatInode::new("/proc/net/tcp6", NetStatKind::Tcp6));
+        children.insert("udp6".to_string(), NetStatInode::new("/proc/net/udp6", NetStatKind::Udp6));
+        Arc::new(Self {
+            metadata: InodeMeta::new(
+                InodeType::Dir,
+                0,
+                "/proc/net".into(),
+            ),
+            children
+        })
+    }
+}
+
+#[async_trait]
+impl InodeTrait for NetDirInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+
+    fn get_page_cache(&self) -> Option<Arc<PageCache>> {
+        None
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    async fn write_directly(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+
+    fn look_up(&self,path: &str) -> Option<Arc<dyn InodeTrait> > {
+        let binding = AbsPath::new(String::from(path)).get_filename();
+        let pattern = binding.as_str();
+        return self.children.get(pattern).cloned();
+    }
+
+    fn get_size(&self) -> usize {
+        512
+    }
+
+    fn read_dents(&self) -> Option<Vec<Dirent>> {
+        let mut entries = alloc::vec![
+            (".", 6, 4),
+            ("..", 1, 4),
+            ("dev", 8, 8),
+            ("route", 9, 8),
+            ("tcp", 10, 8),
+            ("udp", 11, 8),
+            ("if_inet6", 12, 8),
+            ("ipv6_route", 13, 8),
+            ("tcp6", 14, 8),
+            ("udp6", 15, 8)
+        ];
+        Some(build_dirents(entries))
+    }
+}
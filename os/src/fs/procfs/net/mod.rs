@@ -0,0 +1,4 @@
+mod dir;
+mod stat;
+
+pub use dir::NetDirInode;
@@ -0,0 +1,99 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use crate::{fs::{InodeMeta, InodeTrait, InodeType, Kstat, ModeFlag, PageCache, StMode}, net::{proc_net_dev, proc_net_if_inet6, proc_net_ipv6_route, proc_net_route, proc_net_tcp, proc_net_tcp6, proc_net_udp, proc_net_udp6}, utils::SysResult};
+use async_trait::async_trait;
+use alloc::boxed::Box;
+
+#[derive(Clone, Copy)]
+pub enum NetStatKind {
+    Dev,
+    Route,
+    Tcp,
+    Udp,
+    IfInet6,
+    Ipv6Route,
+    Tcp6,
+    Udp6,
+}
+
+/// /proc/net 下的只读文件，每次读取时现场生成内容
+pub struct NetStatInode {
+    metadata: InodeMeta,
+    kind: NetStatKind,
+}
+
+impl NetStatInode {
+    pub fn new(path: &str, kind: NetStatKind) -> Arc<dyn InodeTrait> {
+        Arc::new(Self {
+            metadata: InodeMeta::new(
+                InodeType::File,
+                0,
+                path.into(),
+            ),
+            kind,
+        })
+    }
+
+    fn content(&self) -> String {
+        match self.kind {
+            NetStatKind::Dev => proc_net_dev(),
+            NetStatKind::Route => proc_net_route(),
+            NetStatKind::Tcp => proc_net_tcp(),
+            NetStatKind::Udp => proc_net_udp(),
+            NetStatKind::IfInet6 => proc_net_if_inet6(),
+            NetStatKind::Ipv6Route => proc_net_ipv6_route(),
+            NetStatKind::Tcp6 => proc_net_tcp6(),
+            NetStatKind::Udp6 => proc_net_udp6(),
+        }
+    }
+}
+
+#[async_trait]
+impl InodeTrait for NetStatInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+
+    fn get_page_cache(&self) -> Option<Arc<PageCache>> {
+        None
+    }
+
+    async fn read_all(&self) -> SysResult<Vec<u8>> {
+        Ok(Vec::from(self.content()))
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+
+    async fn read_dirctly(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.read_at(offset, buf).await
+    }
+
+    async fn write_directly(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = self.content();
+        let len = content.len();
+        if offset < len {
+            let read_len = core::cmp::min(len - offset, buf.len());
+            buf[..read_len].copy_from_slice(&content.as_bytes()[offset..offset + read_len]);
+            read_len
+        } else {
+            0
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        self.content().len()
+    }
+
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_mode = StMode::new(
+            ModeFlag::S_IRUSR | ModeFlag::S_IRGRP | ModeFlag::S_IROTH | ModeFlag::S_IFREG).into();
+        res.st_nlink = 1;
+        res
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     fs::{
-        dirent::build_dirents, ffi::MEMINFO, open, procfs::{_self::_SelfInode, interrupts::InterruptInode, irqtable::{SupervisorExternal, SupervisorTimer, IRQTABLE}, meminfo::MeminfoInode, mounts::MountsInode, sys::SysDirInode}, AbsPath, Dirent, FileClass, InodeMeta, InodeTrait, InodeType, Kstat, OpenFlags
+        dirent::build_dirents, ffi::MEMINFO, open, procfs::{_self::_SelfInode, interrupts::InterruptInode, irqtable::{SupervisorExternal, SupervisorTimer, IRQTABLE}, meminfo::MeminfoInode, mounts::MountsInode, net::NetDirInode, sys::SysDirInode}, AbsPath, Dirent, FileClass, InodeMeta, InodeTrait, InodeType, Kstat, OpenFlags
     },
     mm::frame_allocator::{FrameAllocator, StackFrameAllocator, FRAME_ALLOCATOR},
     sync::{SpinNoIrqLock, TimeStamp},
@@ -30,6 +30,7 @@ impl ProcFsRootInode {
         children.insert("mounts".to_string(), MountsInode::new());
         children.insert("interrupts".into(), InterruptInode::new());
         children.insert("sys".into(), SysDirInode::new());
+        children.insert("net".into(), NetDirInode::new());
         Self {
             metadata: InodeMeta::new(
                 InodeType::Dir,
@@ -108,6 +109,7 @@ impl InodeTrait for ProcFsRootInode {
             ("meminfo", 3, 8),
             ("mounts", 4, 8),
             ("interrupts", 5, 8),
+            ("net", 6, 4),
         ];
 
         Some(build_dirents(entries))
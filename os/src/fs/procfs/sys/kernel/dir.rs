@@ -1,5 +1,5 @@
 use alloc::{collections::btree_map::BTreeMap, format, string::{String, ToString}, sync::Arc, vec::Vec};
-use crate::{fs::{dirent::build_dirents, procfs::sys::kernel::domainname::DomainNameInode, AbsPath, Dirent, InodeMeta, InodeTrait, InodeType, PageCache}, utils::SysResult};
+use crate::{fs::{dirent::build_dirents, procfs::sys::kernel::{domainname::DomainNameInode, random::RandomDirInode}, AbsPath, Dirent, InodeMeta, InodeTrait, InodeType, PageCache}, utils::SysResult};
 use async_trait::async_trait;
 use alloc::boxed::Box;
 
@@ -13,6 +13,7 @@ impl KernelDirInode {
     pub fn new() -> Arc<dyn InodeTrait> {
         let mut children = BTreeMap::new();
         children.insert("domainname".to_string(), DomainNameInode::new());
+        children.insert("random".to_string(), RandomDirInode::new());
         Arc::new(Self {
             metadata: InodeMeta::new(
                 InodeType::Dir,
@@ -62,7 +63,8 @@ impl InodeTrait for KernelDirInode {
         let mut entries = alloc::vec![
             (".", 2, 4), 
             ("..", 1, 4), 
-            ("domainname", 4, 8)
+            ("domainname", 4, 8),
+            ("random", 5, 4)
         ];
         Some(build_dirents(entries))
     }
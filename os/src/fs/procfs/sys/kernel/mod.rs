@@ -1,4 +1,5 @@
 mod domainname;
+mod random;
 mod dir;
 
 pub use dir::KernelDirInode;
\ No newline at end of file
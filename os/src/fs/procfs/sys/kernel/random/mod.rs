@@ -0,0 +1,4 @@
+mod uuid;
+mod dir;
+
+pub use dir::RandomDirInode;
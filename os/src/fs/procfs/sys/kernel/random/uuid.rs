@@ -0,0 +1,94 @@
+use alloc::{format, string::String, sync::Arc};
+use crate::{fs::{InodeMeta, InodeTrait, InodeType, Kstat, ModeFlag, PageCache, StMode}, sync::SpinNoIrqLock, utils::get_random_bytes};
+use async_trait::async_trait;
+use alloc::boxed::Box;
+
+/// /proc/sys/kernel/random/{boot_id,uuid}
+///
+/// boot_id 在第一次读取时生成，本次启动期间保持不变；uuid 每次读取都生成新的值
+pub struct UuidInode {
+    metadata: InodeMeta,
+    boot_id: bool,
+}
+
+impl UuidInode {
+    pub fn new(path: &str, boot_id: bool) -> Arc<dyn InodeTrait> {
+        Arc::new(Self {
+            metadata: InodeMeta::new(
+                InodeType::File,
+                0,
+                path.into(),
+            ),
+            boot_id,
+        })
+    }
+
+    fn content(&self) -> String {
+        if !self.boot_id {
+            return generate_uuid();
+        }
+        BOOT_ID.lock().get_or_insert_with(generate_uuid).clone()
+    }
+}
+
+/// 生成一个随机（第 4 版）UUID，带结尾换行
+fn generate_uuid() -> String {
+    let mut b = [0u8; 16];
+    get_random_bytes(&mut b);
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}\n",
+        b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+        b[8], b[9], b[10], b[11], b[12], b[13], b[14], b[15]
+    )
+}
+
+#[async_trait]
+impl InodeTrait for UuidInode {
+    fn metadata(&self) -> &InodeMeta {
+        &self.metadata
+    }
+
+    fn get_page_cache(&self) -> Option<Arc<PageCache>> {
+        None
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+
+    async fn read_dirctly(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.read_at(offset, buf).await
+    }
+
+    async fn write_directly(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = self.content();
+        let len = content.len();
+        if offset < len {
+            let read_len = core::cmp::min(len - offset, buf.len());
+            buf[..read_len].copy_from_slice(&content.as_bytes()[offset..offset + read_len]);
+            read_len
+        } else {
+            0
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        37
+    }
+
+    fn fstat(&self) -> Kstat {
+        let mut res = Kstat::new();
+        res.st_mode = StMode::new(
+            ModeFlag::S_IRUSR | ModeFlag::S_IRGRP | ModeFlag::S_IROTH | ModeFlag::S_IFREG).into();
+        res.st_nlink = 1;
+        res
+    }
+}
+
+static BOOT_ID: SpinNoIrqLock<Option<String>> = SpinNoIrqLock::new(None);
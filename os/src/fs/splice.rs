@@ -0,0 +1,187 @@
+//! splice、tee、vmsplice、sendfile 共用的数据搬运
+//!
+//! 数据以 `PipeBuf`（页引用）为单位在文件、管道和套接字之间传递：
+//! 有页缓存的普通文件直接交出页缓存中的页，管道之间只移动或复制页引用，
+//! 只有写入最终目标（套接字、文件）时才发生一次复制
+
+use super::{pipe::PipeBuf, FileTrait, Page, Pipe};
+use crate::{
+    hal::config::PAGE_SIZE,
+    utils::{Errno, SysResult},
+};
+use alloc::sync::Arc;
+use core::cmp::min;
+
+/// 从文件中读出从 `offset` 开始、不超过 `max` 字节且不跨页的一段数据，读到末尾时返回 `None`
+///
+/// 有页缓存的普通文件直接返回页缓存中的页；其他文件（套接字、设备等）读进一个新分配的页。
+/// `offset` 为 `None` 时按文件自己的偏移读，并推进偏移
+pub async fn read_page(
+    file: &Arc<dyn FileTrait>,
+    offset: Option<usize>,
+    max: usize,
+) -> SysResult<Option<PipeBuf>> {
+    let inode = file.metadata().inode.clone();
+    if let Some(cache) = inode.get_page_cache().filter(|_| inode.metadata()._type.is_file()) {
+        let pos = offset.unwrap_or_else(|| file.metadata().offset());
+        let size = inode.get_size();
+        if pos >= size {
+            return Ok(None);
+        }
+        let page_offset = pos % PAGE_SIZE;
+        let len = min(max, min(PAGE_SIZE - page_offset, size - pos));
+        let page = cache.get_page(pos).await.ok_or(Errno::EIO)?;
+        if offset.is_none() {
+            file.metadata().set_offset(pos + len);
+        }
+        return Ok(Some(PipeBuf { page, offset: page_offset, len }));
+    }
+
+    let page = Page::new();
+    let buf = &mut page.get_bytes_array()[..min(max, PAGE_SIZE)];
+    let len = match offset {
+        Some(offset) => file.read_at(offset, buf).await?,
+        None => file.read(buf).await?,
+    };
+    match len {
+        0 => Ok(None),
+        len => Ok(Some(PipeBuf { page, offset: 0, len })),
+    }
+}
+
+/// 把一段页数据写入文件，`offset` 为 `None` 时按文件自己的偏移写
+pub async fn write_page(file: &Arc<dyn FileTrait>, offset: Option<usize>, buf: &PipeBuf) -> SysResult<usize> {
+    match offset {
+        Some(offset) => file.write_at(offset, buf.as_slice()).await,
+        None => file.write(buf.as_slice()).await,
+    }
+}
+
+/// 从文件读入不超过 `len` 字节到管道，返回读入的字节数
+///
+/// 只有普通文件会一直读到管道满或读够为止；套接字等只读一次，避免已经读到数据后又阻塞
+pub async fn splice_to_pipe(
+    file: &Arc<dyn FileTrait>,
+    mut offset: Option<usize>,
+    pipe: &Pipe,
+    len: usize,
+    nonblock: bool,
+) -> SysResult<usize> {
+    pipe.wait_writable(nonblock).await?;
+    let is_file = file.metadata().inode.metadata()._type.is_file();
+    let mut moved = 0;
+    while moved < len {
+        let space = pipe.with_mut_buffer(|inner| inner.available_write(len - moved));
+        if space == 0 {
+            break;
+        }
+        let Some(buf) = read_page(file, offset, space).await? else { break };
+        moved += buf.len;
+        offset = offset.map(|offset| offset + buf.len);
+        pipe.with_mut_buffer(|inner| {
+            inner.push_buf(buf);
+            pipe.wake_readers(inner);
+        });
+        if !is_file {
+            break;
+        }
+    }
+    Ok(moved)
+}
+
+/// 把管道中不超过 `len` 字节写入文件，返回写出的字节数
+///
+/// 先按页引用写出，再从管道中丢弃真正写出去的部分，目标只写了一部分时剩下的数据仍留在管道中
+pub async fn splice_from_pipe(
+    pipe: &Pipe,
+    file: &Arc<dyn FileTrait>,
+    mut offset: Option<usize>,
+    len: usize,
+    nonblock: bool,
+) -> SysResult<usize> {
+    if !pipe.wait_readable(nonblock).await? {
+        return Ok(0);
+    }
+    let bufs = pipe.with_mut_buffer(|inner| inner.peek_bufs(len));
+    let mut moved = 0;
+    for buf in bufs.iter() {
+        let n = match write_page(file, offset, buf).await {
+            Ok(n) => n,
+            Err(e) if moved == 0 => return Err(e),
+            Err(_) => break,
+        };
+        moved += n;
+        offset = offset.map(|offset| offset + n);
+        if n < buf.len {
+            break;
+        }
+    }
+    pipe.with_mut_buffer(|inner| {
+        inner.consume(moved);
+        pipe.wake_writers(inner);
+    });
+    Ok(moved)
+}
+
+/// 在两个管道之间移动（`keep` 为 false，即 splice）或复制（`keep` 为 true，即 tee）不超过 `len` 字节，
+/// 两种情况都只传递页引用
+pub async fn pipe_to_pipe(
+    pipe_in: &Pipe,
+    pipe_out: &Pipe,
+    len: usize,
+    nonblock: bool,
+    keep: bool,
+) -> SysResult<usize> {
+    if Arc::ptr_eq(&pipe_in.buffer, &pipe_out.buffer) {
+        return Err(Errno::EINVAL);
+    }
+    if !pipe_in.wait_readable(nonblock).await? {
+        return Ok(0);
+    }
+    pipe_out.wait_writable(nonblock).await?;
+    let space = pipe_out.with_mut_buffer(|inner| inner.available_write(len));
+    let bufs = pipe_in.with_mut_buffer(|inner| inner.peek_bufs(space));
+    let moved = bufs.iter().map(|buf| buf.len).sum();
+    pipe_out.with_mut_buffer(|inner| {
+        for buf in bufs {
+            inner.push_buf(buf);
+        }
+        pipe_out.wake_readers(inner);
+    });
+    if !keep {
+        pipe_in.with_mut_buffer(|inner| {
+            inner.consume(moved);
+            pipe_in.wake_writers(inner);
+        });
+    }
+    Ok(moved)
+}
+
+/// 按页把 `src` 中不超过 `len` 字节写到 `dest`，用于 sendfile 和 copy_file_range
+///
+/// `src` 必须能按偏移读：目标只写了一部分时停止，两个偏移都停在实际复制到的位置，
+/// 没写出去的数据仍留在源文件中。`pos_out` 为 `None` 时使用目标文件自己的偏移
+pub async fn copy_pages(
+    src: &Arc<dyn FileTrait>,
+    pos_in: &mut usize,
+    dest: &Arc<dyn FileTrait>,
+    pos_out: &mut Option<usize>,
+    len: usize,
+) -> SysResult<usize> {
+    let mut copied = 0;
+    while copied < len {
+        let Some(buf) = read_page(src, Some(*pos_in), len - copied).await? else { break };
+        let n = match write_page(dest, *pos_out, &buf).await {
+            Ok(n) => n,
+            Err(e) if copied == 0 => return Err(e),
+            Err(_) => break,
+        };
+        copied += n;
+        *pos_in += n;
+        *pos_out = pos_out.map(|pos| pos + n);
+        if n < buf.len {
+            break;
+        }
+    }
+    Ok(copied)
+}
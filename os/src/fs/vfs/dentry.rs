@@ -12,7 +12,7 @@ use lwext4_rust::{
 };
 // use riscv::{interrupt::Mutex, register::fcsr::read};
 // use sbi_rt::{NonRetentive, SharedPtr};
-use super::{inode, InodeTrait, SuperBlockTrait};
+use super::{inode, search_unrestricted, InodeTrait, MayAccess, SuperBlockTrait};
 use crate::{
     fs::{
         ffi::InodeType, mkdir, open, path, root_inode, AbsPath,
@@ -273,9 +273,8 @@ impl Dentry {
         parent_children.remove(&child_name);
         self.set_status(DentryStatus::Negtive);
         self.set_status(DentryStatus::Negtive);
-        DENTRY_CACHE
-            .remove(&self.get_abs_path())
-            .expect("failed to remove dentry from cache");
+        // 没有经过路径查找的 dentry 不在缓存里，例如 devpts 手动建立的目录项
+        DENTRY_CACHE.remove(&self.get_abs_path());
         // error!("[release_self] release dentry {}, ref count: {}", self.get_abs_path(), Arc::strong_count(&self));
         Ok(0)
     }
@@ -399,12 +398,23 @@ impl Dentry {
 
     /// 根据绝对路径找到dentry
     /// path： 绝对路径
+    ///
+    /// 调用者需要对路径上的每一级目录都有搜索权限，否则返回 EACCES
     pub fn get_dentry_from_path(path: &str) -> SysResult<Arc<Self>> {
         // info!("[get_dentry_from_path] {}", path);
-        {
-            if let Some(dentry) = DENTRY_CACHE.get(path) {
-                return Ok(dentry);
+        let check_search = !search_unrestricted();
+        if let Some(dentry) = DENTRY_CACHE.get(path) {
+            // 缓存命中时跳过了路径遍历，沿着 parent 检查每一级目录的搜索权限
+            if check_search {
+                let mut dir = dentry.parent();
+                while let Some(dentry) = dir {
+                    if let Some(inode) = dentry.get_inode() {
+                        inode.permission(MayAccess::MAY_EXEC)?;
+                    }
+                    dir = dentry.parent();
+                }
             }
+            return Ok(dentry);
         }
         if !path.starts_with('/') {
             panic!("path should start with /");
@@ -418,6 +428,11 @@ impl Dentry {
         let path_split = path.split('/').enumerate();
         let size_of_path = path_split.clone().count();
         for (i, name) in path_split {
+            if check_search {
+                if let Some(dir) = dentry_now.get_inode() {
+                    dir.permission(MayAccess::MAY_EXEC)?;
+                }
+            }
             let child = dentry_now.get_child(name).ok_or(Errno::ENOENT)?;
             dentry_now = child;
             let mid_inode = dentry_now.get_inode().ok_or(Errno::ENOENT)?;
@@ -1,5 +1,5 @@
 use crate::{
-    fs::{ffi::RenameFlags, Dirent, Kstat, OpenFlags},
+    fs::{ffi::RenameFlags, locks::release_file_locks, Dirent, Kstat, OpenFlags},
     mm::page::Page,
     net::Socket,
     utils::{Errno, SysResult},
@@ -44,6 +44,17 @@ impl FileMeta {
     pub fn set_offset(&self, new_offset: usize) {
         self.offset.store(new_offset, Ordering::Relaxed);
     }
+
+    /// 打开文件的标识，作为 OFD 锁和 flock 锁的持有者
+    pub fn file_id(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl Drop for FileMeta {
+    fn drop(&mut self) {
+        release_file_locks(self.inode.metadata().ino, self.file_id());
+    }
 }
 
 /// 文件接口
@@ -1,9 +1,13 @@
-use core::{any::Any, sync::atomic::AtomicUsize};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicU32, AtomicUsize},
+};
 
 use crate::{
     fs::{
-        ext4::NormalFile, ffi::InodeType, page_cache::PageCache, vfs::alloc_ino, AbsPath, Dentry,
-        Dirent, FileClass, FileTrait, Kstat, ModeFlag, OpenFlags, StMode, SEEK_END,
+        ext4::NormalFile, ffi::InodeType, page_cache::PageCache, vfs::alloc_ino,
+        xattr::XattrFlags, AbsPath, Dentry, Dirent, FileClass, FileTrait, Kstat, ModeFlag,
+        OpenFlags, StMode, SEEK_END,
     },
     sync::{once::LateInit, MutexGuard, NoIrqLock, SpinNoIrqLock, TimeStamp},
     utils::{downcast::Downcast, Errno, SysResult},
@@ -34,6 +38,9 @@ pub struct InodeMeta {
     pub timestamp: SpinNoIrqLock<TimeStamp>,
     pub abspath: String,
     pub i_mode: SpinNoIrqLock<StMode>,
+    /// 所有者的 uid 和 gid，只在 i_mode 带有文件类型时有效
+    pub i_uid: AtomicU32,
+    pub i_gid: AtomicU32,
 }
 
 impl InodeMeta {
@@ -45,6 +52,8 @@ impl InodeMeta {
             timestamp: SpinNoIrqLock::new(TimeStamp::new()),
             abspath: String::from(path),
             i_mode: SpinNoIrqLock::new(StMode::new(ModeFlag::empty())),
+            i_uid: AtomicU32::new(0),
+            i_gid: AtomicU32::new(0),
         }
     }
 }
@@ -76,6 +85,23 @@ pub trait InodeTrait: Any + Send + Sync {
     fn do_create(&self, bare_dentry: Arc<Dentry>, _ty: InodeType) -> Option<Arc<dyn InodeTrait>> {
         None
     }
+    /// chmod、chown 修改 InodeMeta 之后调用，把权限位和所有者写回文件系统。
+    /// 默认只保存在内存中
+    fn sync_attr(&self) -> SysResult {
+        Ok(())
+    }
+
+    /// 在 self 目录下创建指向 target 的符号链接，bare_dentry 是链接所在的无效 dentry。
+    /// 默认文件系统不支持符号链接
+    fn do_symlink(&self, _bare_dentry: Arc<Dentry>, _target: &str) -> SysResult<Arc<dyn InodeTrait>> {
+        Err(Errno::EPERM)
+    }
+
+    /// 读取符号链接的目标，不是符号链接时返回 EINVAL
+    fn readlink(&self) -> SysResult<String> {
+        Err(Errno::EINVAL)
+    }
+
     /// 确实应当剥夺walk去创造Inode的权利
     fn look_up(&self, _path: &str) -> Option<Arc<dyn InodeTrait>> {
         todo!()
@@ -154,6 +180,26 @@ pub trait InodeTrait: Any + Send + Sync {
     fn ioctl(&self, op: usize, arg: usize) -> SysResult<usize> {
         Ok(0)
     }
+
+    /// 读取扩展属性，name 带命名空间前缀。默认不支持扩展属性
+    fn getxattr(&self, _name: &str) -> SysResult<Vec<u8>> {
+        Err(Errno::EOPNOTSUPP)
+    }
+
+    /// 设置扩展属性，flags 决定属性必须存在或必须不存在
+    fn setxattr(&self, _name: &str, _value: &[u8], _flags: XattrFlags) -> SysResult {
+        Err(Errno::EOPNOTSUPP)
+    }
+
+    /// 列出全部扩展属性的名字
+    fn listxattr(&self) -> SysResult<Vec<String>> {
+        Err(Errno::EOPNOTSUPP)
+    }
+
+    /// 删除扩展属性
+    fn removexattr(&self, _name: &str) -> SysResult {
+        Err(Errno::EOPNOTSUPP)
+    }
 }
 
 impl dyn InodeTrait {
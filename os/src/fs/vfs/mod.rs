@@ -1,11 +1,13 @@
 mod dentry;
 mod file;
 mod inode;
+mod perm;
 mod super_block;
 
 pub use dentry::*;
 pub use file::*;
 pub use inode::*;
+pub use perm::*;
 pub use super_block::*;
 
 use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
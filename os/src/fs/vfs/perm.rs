@@ -0,0 +1,288 @@
+//! VFS 的权限检查
+//!
+//! inode 的权限位和所有者记录在 `InodeMeta` 中，检查时使用调用者的 fsuid/fsgid 和补充组。
+//! `i_mode` 中没有文件类型位的 inode 表示文件系统没有记录权限（devfs、procfs 的大部分节点），
+//! 这类 inode 按照属于 root、权限 0777 处理，chmod/chown 之后才有真正的权限。
+
+use core::sync::atomic::Ordering;
+
+use alloc::sync::Arc;
+use bitflags::bitflags;
+use log::warn;
+
+use crate::{
+    fs::{AbsPath, Dentry, InodeMeta, InodeTrait, Kstat, ModeFlag, OpenFlags, StMode},
+    task::{current_task, CapSet, Cred},
+    utils::{Errno, SysResult},
+};
+
+bitflags! {
+    /// 要检查的访问类型，取值和 access(2) 的 X_OK/W_OK/R_OK 相同
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct MayAccess: u32 {
+        const MAY_EXEC = 1;
+        const MAY_WRITE = 2;
+        const MAY_READ = 4;
+    }
+}
+
+const S_IFMT: u32 = ModeFlag::S_IFMT.bits();
+const S_IFDIR: u32 = ModeFlag::S_IFDIR.bits();
+const S_ISUID: u32 = ModeFlag::S_ISUID.bits();
+const S_ISGID: u32 = ModeFlag::S_ISGID.bits();
+const S_ISVTX: u32 = ModeFlag::S_ISVTX.bits();
+const S_IXGRP: u32 = ModeFlag::S_IXGRP.bits();
+const S_IXUGO: u32 = 0o111;
+const S_IALLUGO: u32 = 0o7777;
+
+/// 权限检查用到的 inode 属性
+#[derive(Clone, Copy, Debug)]
+pub struct InodeAttr {
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl InodeAttr {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+}
+
+impl InodeMeta {
+    /// i_mode 是否由文件系统或者 chmod 设置过
+    fn has_attr(&self) -> bool {
+        self.i_mode.lock().mode.bits() & S_IFMT != 0
+    }
+
+    pub fn attr(&self) -> InodeAttr {
+        let mode = self.i_mode.lock().mode.bits();
+        match mode & S_IFMT {
+            0 => InodeAttr {
+                mode: ((self._type as u32) << 12) | 0o777,
+                uid: 0,
+                gid: 0,
+            },
+            _ => InodeAttr {
+                mode,
+                uid: self.i_uid.load(Ordering::Relaxed),
+                gid: self.i_gid.load(Ordering::Relaxed),
+            },
+        }
+    }
+
+    /// 修改权限位，文件类型保持不变
+    pub fn set_mode(&self, mode: u32) {
+        let file_type = self.attr().mode & S_IFMT;
+        *self.i_mode.lock() = StMode::from(file_type | (mode & S_IALLUGO));
+    }
+
+    pub fn set_owner(&self, uid: u32, gid: u32) {
+        // 先把默认权限固定下来，否则设置的所有者不会生效
+        if !self.has_attr() {
+            self.set_mode(0o777);
+        }
+        self.i_uid.store(uid, Ordering::Relaxed);
+        self.i_gid.store(gid, Ordering::Relaxed);
+    }
+
+    /// 用 chmod/chown 的结果覆盖文件系统报告的 stat
+    pub fn fill_attr(&self, stat: &mut Kstat) {
+        if self.has_attr() {
+            let attr = self.attr();
+            stat.st_mode = attr.mode;
+            stat.st_uid = attr.uid;
+            stat.st_gid = attr.gid;
+        }
+    }
+}
+
+impl dyn InodeTrait {
+    /// 使用当前任务的凭证检查访问权限
+    pub fn permission(&self, mask: MayAccess) -> SysResult {
+        match current_task() {
+            Some(task) => {
+                let cred = task.cred.lock();
+                self.permission_with(&cred, mask)
+            }
+            // 内核初始化时没有当前任务，不做检查
+            None => Ok(()),
+        }
+    }
+
+    /// 按照 owner/group/other 的顺序选出一组权限位进行检查。
+    /// CAP_DAC_OVERRIDE 忽略读写权限，执行权限要求至少有一个 x 位（目录除外）；
+    /// CAP_DAC_READ_SEARCH 忽略读权限和目录的搜索权限。
+    pub fn permission_with(&self, cred: &Cred, mask: MayAccess) -> SysResult {
+        let attr = self.metadata().attr();
+        let bits = if cred.fsuid == attr.uid {
+            attr.mode >> 6
+        } else if cred.in_group(attr.gid) {
+            attr.mode >> 3
+        } else {
+            attr.mode
+        };
+        if bits & mask.bits() == mask.bits() {
+            return Ok(());
+        }
+        if cred.capable(CapSet::CAP_DAC_OVERRIDE)
+            && (!mask.contains(MayAccess::MAY_EXEC) || attr.is_dir() || attr.mode & S_IXUGO != 0)
+        {
+            return Ok(());
+        }
+        let read_search = match attr.is_dir() {
+            true => MayAccess::MAY_READ | MayAccess::MAY_EXEC,
+            false => MayAccess::MAY_READ,
+        };
+        if cred.capable(CapSet::CAP_DAC_READ_SEARCH) && read_search.contains(mask) {
+            return Ok(());
+        }
+        Err(Errno::EACCES)
+    }
+
+    /// chmod、utimensat 等操作要求调用者是文件的所有者
+    pub fn check_owner(&self) -> SysResult {
+        let task = match current_task() {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        let cred = task.cred.lock();
+        if cred.fsuid == self.metadata().attr().uid || cred.capable(CapSet::CAP_FOWNER) {
+            return Ok(());
+        }
+        Err(Errno::EPERM)
+    }
+
+    /// chmod：非特权进程设置不属于自己的组时清除 S_ISGID
+    pub fn chmod(&self, mode: u32) -> SysResult {
+        self.check_owner()?;
+        let meta = self.metadata();
+        let mut mode = mode & S_IALLUGO;
+        if let Some(task) = current_task() {
+            let cred = task.cred.lock();
+            if !cred.capable(CapSet::CAP_FSETID) && !cred.in_group(meta.attr().gid) {
+                mode &= !S_ISGID;
+            }
+        }
+        meta.set_mode(mode);
+        self.sync_attr()
+    }
+
+    /// chown：只有具有 CAP_CHOWN 的进程能修改所有者；所有者可以把组改成自己所在的组。
+    /// 普通文件的所有者变化后清除 set-user-id 和 set-group-id 位。
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> SysResult {
+        if uid.is_none() && gid.is_none() {
+            return Ok(());
+        }
+        let meta = self.metadata();
+        let attr = meta.attr();
+        if let Some(task) = current_task() {
+            let cred = task.cred.lock();
+            if !cred.capable(CapSet::CAP_CHOWN) {
+                if uid.is_some_and(|uid| uid != attr.uid) || cred.fsuid != attr.uid {
+                    return Err(Errno::EPERM);
+                }
+                if gid.is_some_and(|gid| gid != attr.gid && !cred.in_group(gid)) {
+                    return Err(Errno::EPERM);
+                }
+            }
+        }
+        meta.set_owner(uid.unwrap_or(attr.uid), gid.unwrap_or(attr.gid));
+        if !attr.is_dir() {
+            let mut mode = attr.mode & !S_ISUID;
+            if mode & S_IXGRP != 0 {
+                mode &= !S_ISGID;
+            }
+            meta.set_mode(mode);
+        }
+        self.sync_attr()
+    }
+}
+
+/// 路径查找时调用者是否不需要检查搜索权限
+pub fn search_unrestricted() -> bool {
+    current_task().map_or(true, |task| {
+        let cred = task.cred.lock();
+        cred.capable(CapSet::CAP_DAC_OVERRIDE) || cred.capable(CapSet::CAP_DAC_READ_SEARCH)
+    })
+}
+
+/// 在 parent 中创建文件需要目录的写和搜索权限
+pub fn may_create(parent: &Arc<dyn InodeTrait>) -> SysResult {
+    parent.permission(MayAccess::MAY_WRITE | MayAccess::MAY_EXEC)
+}
+
+/// 从 parent 中删除 victim：需要目录的写和搜索权限；
+/// 目录设置了 sticky 位时只有文件或目录的所有者才能删除
+pub fn may_delete(parent: &Arc<dyn InodeTrait>, victim: &Arc<dyn InodeTrait>) -> SysResult {
+    may_create(parent)?;
+    let dir = parent.metadata().attr();
+    if dir.mode & S_ISVTX == 0 {
+        return Ok(());
+    }
+    if let Some(task) = current_task() {
+        let cred = task.cred.lock();
+        let owner = victim.metadata().attr().uid;
+        if cred.fsuid != owner && cred.fsuid != dir.uid && !cred.capable(CapSet::CAP_FOWNER) {
+            return Err(Errno::EPERM);
+        }
+    }
+    Ok(())
+}
+
+/// 删除 path 对应的目录项前的检查
+pub fn may_unlink(path: &AbsPath) -> SysResult {
+    let parent = Dentry::get_inode_from_path(&path.get_parent_abs())?;
+    let victim = Dentry::get_inode_from_path(&path.get())?;
+    may_delete(&parent, &victim)
+}
+
+/// open 前的检查：已存在的文件按打开方式检查读写权限，
+/// O_CREAT 创建新文件时检查父目录
+pub fn may_open(path: &AbsPath, flags: OpenFlags) -> SysResult {
+    match Dentry::get_inode_from_path(&path.get()) {
+        Ok(inode) => {
+            let mut mask = match flags.bits() & OpenFlags::O_ACCMODE.bits() {
+                0 => MayAccess::MAY_READ,
+                1 => MayAccess::MAY_WRITE,
+                _ => MayAccess::MAY_READ | MayAccess::MAY_WRITE,
+            };
+            if flags.contains(OpenFlags::O_TRUNC) {
+                mask |= MayAccess::MAY_WRITE;
+            }
+            inode.permission(mask)
+        }
+        Err(Errno::ENOENT) if flags.contains(OpenFlags::O_CREAT) => {
+            let parent = Dentry::get_inode_from_path(&path.get_parent_abs())?;
+            may_create(&parent)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// 新建的 inode 属于调用者的 fsuid；父目录有 S_ISGID 时继承父目录的组，
+/// 新建的目录同时继承 S_ISGID 位
+pub fn init_owner(parent: &Arc<dyn InodeTrait>, child: &Arc<dyn InodeTrait>) {
+    let task = match current_task() {
+        Some(task) => task,
+        None => return,
+    };
+    let (fsuid, fsgid) = {
+        let cred = task.cred.lock();
+        (cred.fsuid, cred.fsgid)
+    };
+    let dir = parent.metadata().attr();
+    let meta = child.metadata();
+    if dir.mode & S_ISGID != 0 {
+        meta.set_owner(fsuid, dir.gid);
+        let attr = meta.attr();
+        if attr.is_dir() {
+            meta.set_mode(attr.mode | S_ISGID);
+        }
+    } else {
+        meta.set_owner(fsuid, fsgid);
+    }
+    if let Err(e) = child.sync_attr() {
+        warn!("[init_owner] failed to write owner back: {:?}", e);
+    }
+}
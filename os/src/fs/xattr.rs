@@ -0,0 +1,148 @@
+//! 扩展属性
+//!
+//! 属性名带命名空间前缀，支持 user.*、trusted.* 和 security.* 三个命名空间：
+//! - user.*：只能加在普通文件和目录上，按文件的读写权限检查；
+//! - trusted.*：读写和列出都需要 CAP_SYS_ADMIN，没有权限时与 Linux 一样读不到（ENODATA）、写不进（EPERM）；
+//! - security.*：任何人都可以读，修改需要 CAP_SYS_ADMIN。
+//!
+//! ext4 的属性保存在磁盘上，没有后备存储的文件系统使用内存中的 `XattrMap`。
+
+use crate::{
+    fs::{InodeTrait, InodeType, MayAccess},
+    sync::SpinNoIrqLock,
+    task::{capable, CapSet},
+    utils::{Errno, SysResult},
+};
+use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
+
+/// 属性名的最大长度，不含结尾的 0
+pub const XATTR_NAME_MAX: usize = 255;
+/// 单个属性值的最大长度
+pub const XATTR_SIZE_MAX: usize = 65536;
+/// listxattr 返回的名字列表的最大长度
+pub const XATTR_LIST_MAX: usize = 65536;
+
+bitflags! {
+    /// setxattr 的 flags
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct XattrFlags: u32 {
+        /// 属性已经存在时返回 EEXIST
+        const XATTR_CREATE = 1;
+        /// 属性不存在时返回 ENODATA
+        const XATTR_REPLACE = 2;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XattrNamespace {
+    User,
+    Trusted,
+    Security,
+}
+
+impl XattrNamespace {
+    /// 根据属性名的前缀确定命名空间，前缀之后必须还有名字
+    pub fn of(name: &str) -> SysResult<Self> {
+        let (ns, rest) = if let Some(rest) = name.strip_prefix("user.") {
+            (Self::User, rest)
+        } else if let Some(rest) = name.strip_prefix("trusted.") {
+            (Self::Trusted, rest)
+        } else if let Some(rest) = name.strip_prefix("security.") {
+            (Self::Security, rest)
+        } else {
+            return Err(Errno::EOPNOTSUPP);
+        };
+        if rest.is_empty() {
+            return Err(Errno::EINVAL);
+        }
+        Ok(ns)
+    }
+}
+
+/// 检查属性名的长度并解析命名空间
+pub fn xattr_name(name: &str) -> SysResult<XattrNamespace> {
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return Err(Errno::ERANGE);
+    }
+    XattrNamespace::of(name)
+}
+
+impl dyn InodeTrait {
+    /// 对 name 属性做 mask 访问的权限检查，mask 为 MAY_READ 或 MAY_WRITE
+    pub fn xattr_permission(&self, name: &str, mask: MayAccess) -> SysResult {
+        match xattr_name(name)? {
+            XattrNamespace::Trusted => match (capable(CapSet::CAP_SYS_ADMIN), mask.contains(MayAccess::MAY_WRITE)) {
+                (true, _) => Ok(()),
+                (false, true) => Err(Errno::EPERM),
+                (false, false) => Err(Errno::ENODATA),
+            },
+            XattrNamespace::Security => {
+                if mask.contains(MayAccess::MAY_WRITE) && !capable(CapSet::CAP_SYS_ADMIN) {
+                    return Err(Errno::EPERM);
+                }
+                self.permission(mask)
+            }
+            XattrNamespace::User => {
+                // 设备、管道、套接字等文件上的 user.* 属性由使用者自己解释，不允许设置
+                let ty = self.metadata()._type;
+                if ty != InodeType::File && !ty.is_dir() {
+                    return match mask.contains(MayAccess::MAY_WRITE) {
+                        true => Err(Errno::EPERM),
+                        false => Err(Errno::ENODATA),
+                    };
+                }
+                self.permission(mask)
+            }
+        }
+    }
+
+    /// 调用者能否在 listxattr 中看到 name
+    pub fn xattr_listable(&self, name: &str) -> bool {
+        match XattrNamespace::of(name) {
+            Ok(XattrNamespace::Trusted) => capable(CapSet::CAP_SYS_ADMIN),
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+/// 保存在内存中的扩展属性
+pub struct XattrMap {
+    attrs: SpinNoIrqLock<BTreeMap<String, Vec<u8>>>,
+}
+
+impl XattrMap {
+    pub fn new() -> Self {
+        Self {
+            attrs: SpinNoIrqLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> SysResult<Vec<u8>> {
+        self.attrs.lock().get(name).cloned().ok_or(Errno::ENODATA)
+    }
+
+    pub fn set(&self, name: &str, value: &[u8], flags: XattrFlags) -> SysResult {
+        let mut attrs = self.attrs.lock();
+        match attrs.get_mut(name) {
+            Some(_) if flags.contains(XattrFlags::XATTR_CREATE) => Err(Errno::EEXIST),
+            Some(old) => {
+                *old = value.to_vec();
+                Ok(())
+            }
+            None if flags.contains(XattrFlags::XATTR_REPLACE) => Err(Errno::ENODATA),
+            None => {
+                attrs.insert(String::from(name), value.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.attrs.lock().keys().cloned().collect()
+    }
+
+    pub fn remove(&self, name: &str) -> SysResult {
+        self.attrs.lock().remove(name).map(|_| ()).ok_or(Errno::ENODATA)
+    }
+}
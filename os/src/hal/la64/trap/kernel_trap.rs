@@ -28,6 +28,7 @@ pub fn kernel_trap_handler() {
             // info!("timer interrupt from kernel");
             // ticlr::clear_timer_interrupt();
             TIMER_QUEUE.handle_expired();
+            crate::utils::add_interrupt_randomness(11);
             get_current_cpu().timer_irq_inc();
             set_next_trigger();
         }
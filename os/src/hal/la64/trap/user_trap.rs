@@ -5,6 +5,7 @@ use crate::hal::arch::sstatus::SPP;
 use crate::sync::{disable_supervisor_interrupt, set_next_trigger, yield_now};
 use crate::syscall::syscall;
 use crate::task::{current_task, current_trap_cx, executor, get_current_hart_id};
+use crate::utils::Errno;
 use core::arch::asm;
 use log::info;
 
@@ -36,6 +37,7 @@ pub async fn user_trap_handler() {
             // 清除时钟专断
             // info!("timer interrupt from kernel");
             TIMER_QUEUE.handle_expired();
+            crate::utils::add_interrupt_randomness(11);
             set_next_trigger();
             yield_now().await;
         }
@@ -66,15 +68,23 @@ pub async fn user_trap_handler() {
                         cx.user_gp.a5,
                     ];
                     // info!("[user_trap_handler] syscall id:{}, args:{:?}", syscall_id, args);
-                    let result = syscall(syscall_id, args).await;
+                    let Some(result) = syscall(syscall_id, args).await else {
+                        // 被 seccomp 杀死，由 trap_loop 检查状态后退出
+                        return;
+                    };
 
                     cx = current_trap_cx();
+                    task.set_restart_syscall(result == Err(Errno::ERESTARTSYS));
 
                     match result {
                         Ok(ret) => {
                             cx.user_gp.a0 = ret as usize;
                             // info!("[syscall ret] OK:{}", ret);
                         }
+                        Err(Errno::ERESTARTSYS) => {
+                            // 回到 syscall 指令重新执行，a0 没有被改写，仍然是第一个参数
+                            cx.sepc -= 4;
+                        }
                         Err(err) => {
                             if err as isize == -1 {
                                 cx.user_gp.a0 = err as usize;
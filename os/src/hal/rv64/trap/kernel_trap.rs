@@ -37,6 +37,7 @@ pub fn kernel_trap_handler() {
             TIMER_QUEUE.handle_expired();
             get_current_cpu().timer_irq_inc();
             IRQTABLE.lock().inc(SupervisorTimer);
+            crate::utils::add_interrupt_randomness(5);
             set_next_trigger();
         }
         Trap::Exception(e) => match e {
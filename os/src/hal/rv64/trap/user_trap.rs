@@ -6,6 +6,7 @@ use crate::mm::memory_space::PageFaultAccessType;
 use crate::sync::{disable_supervisor_interrupt, set_next_trigger, yield_now};
 use crate::syscall::syscall;
 use crate::task::{current_task, current_trap_cx, executor, get_current_cpu, get_current_hart_id};
+use crate::utils::Errno;
 use log::info;
 #[cfg(target_arch = "riscv64")]
 use riscv::register::scause::{self, Exception, Interrupt, Trap};
@@ -40,7 +41,7 @@ pub async fn user_trap_handler() {
             let syscall_id = cx.user_gp.a7;
             cx.set_sepc(old_sepc + 4);
 
-            let result = syscall(
+            let Some(result) = syscall(
                 syscall_id, 
                 [cx.user_gp.a0, 
                 cx.user_gp.a1, 
@@ -48,15 +49,23 @@ pub async fn user_trap_handler() {
                 cx.user_gp.a3, 
                 cx.user_gp.a4,
                 cx.user_gp.a5]
-            ).await;
+            ).await else {
+                // 被 seccomp 杀死，由 trap_loop 检查状态后退出
+                return;
+            };
 
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
+            task.set_restart_syscall(result == Err(Errno::ERESTARTSYS));
 
             match result {
                 Ok(ret) => {
                     cx.user_gp.a0 = ret as usize;
                 }
+                Err(Errno::ERESTARTSYS) => {
+                    // 回到 ecall 重新执行，a0 没有被改写，仍然是第一个参数
+                    cx.set_sepc(old_sepc);
+                }
                 Err(err) => {
                     // TODO：这里单独处理的waitpid返回值情况，后序要修改
                     if (err as isize) < 0 {
@@ -116,6 +125,7 @@ pub async fn user_trap_handler() {
             TIMER_QUEUE.handle_expired();
             set_next_trigger();
             IRQTABLE.lock().inc(SupervisorTimer);
+            crate::utils::add_interrupt_randomness(5);
             yield_now().await;
         }
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
@@ -115,6 +115,7 @@ pub fn rust_main(hart_id: usize, dt_root: usize) -> ! {
         println!("finished mm::init");
         // utils::logger_init();
         sync::time_init();
+        utils::rand_init(hart_id, dt_root);
 
         // TODO:后期可以丰富打印的初始化信息
         println!(
@@ -195,6 +196,8 @@ pub fn rust_main(hart_id: usize, dt_root: usize) -> ! {
 
         INIT_FINISHED.store(true, Ordering::SeqCst);
         spawn_kernel_task(async move { task::add_initproc().await });
+        spawn_kernel_task(net::dhcp_task());
+        spawn_kernel_task(net::net_poll_task());
         #[cfg(feature = "mul_hart")]
         hal::entry::boot::boot_all_harts(hart_id);
     } else {
@@ -36,7 +36,7 @@ use super::page::Page;
 use super::page_table::PageTable;
 use crate::hal::mem::page_table::PTEFlags;
 use crate::utils::container::range_map::RangeMap;
-use crate::utils::{Errno, SysResult};
+use crate::utils::{get_random_bytes, Errno, SysResult};
 use crate::{
     mm::memory_space::vm_area::{MapPerm, VmAreaType},
     task::{
@@ -366,7 +366,8 @@ impl MemorySpace {
         let header_va = self.map_elf(elf_file, &elf, 0.into())?;
 
         let ph_head_addr = header_va.0 + elf.header.pt2.ph_offset() as usize;
-        auxv.push(AuxHeader::new(AT_RANDOM, ph_head_addr));
+        // AT_RANDOM 的地址在 init_stack 中压入随机字节后填写
+        auxv.push(AuxHeader::new(AT_RANDOM, 0));
         // log::info!("[parse_and_map_elf] AT_PHDR  ph_head_addr is {ph_head_addr:x}",);
         auxv.push(AuxHeader::new(AT_PHDR, ph_head_addr));
 
@@ -802,6 +803,14 @@ impl MemorySpace {
             .areas_mut()
             .get_key_value_mut(range.start)
             .ok_or(Errno::ENOMEM)?;
+        // 被禁止写入的 memfd 上的共享映射不能再获得写权限
+        if perm.contains(MapPerm::W) && !area.perm().contains(MapPerm::W) {
+            if let Some(memfd) = area.shared_memfd() {
+                if memfd.get_seals().deny_write() {
+                    return Err(Errno::EACCES);
+                }
+            }
+        }
         if range == old_range {
             area.set_perm_and_flush(self.page_table_mut(), perm);
         } else {
@@ -925,20 +934,29 @@ pub fn create_elf_tables(
     let env_ptrs: Vec<usize> = envp.iter().rev().map(|s| push_str(&mut sp, s)).collect();
     let arg_ptrs: Vec<usize> = args.iter().rev().map(|s| push_str(&mut sp, s)).collect();
 
-    // 随机对齐 (我们取 0 长度的随机对齐), 平台标识符，随机数与对齐
+    // 平台标识符，随机数与对齐
     fn align16(sp: &mut usize) {
         *sp = (*sp - 1) & !0xf;
     }
 
-    let rand_size = 0;
     let platform = "RISC-V64";
-    let rand_bytes = "Dlnx w.r.t.Phnx"; // 15 + 1 char for 16bytes
 
-    sp -= rand_size;
     push_str(&mut sp, platform);
-    push_str(&mut sp, rand_bytes);
+    // AT_RANDOM 指向的 16 个随机字节，libc 用它初始化栈保护值和指针加密
+    let mut rand_bytes = [0u8; 16];
+    get_random_bytes(&mut rand_bytes);
+    sp -= rand_bytes.len();
+    unsafe {
+        core::ptr::copy_nonoverlapping(rand_bytes.as_ptr(), sp as *mut u8, rand_bytes.len());
+    }
+    let rand_addr = sp;
     align16(&mut sp);
 
+    let mut auxv = auxv;
+    auxv.iter_mut()
+        .filter(|aux| aux.aux_type == AT_RANDOM)
+        .for_each(|aux| aux.value = rand_addr);
+
 
     // 注意推栈是 "倒着" 推的，所以先放 null, 再逆着放别的
     push_aux_elm(&mut sp, &AuxHeader::new(AT_NULL, 0));
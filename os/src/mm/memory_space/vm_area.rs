@@ -13,11 +13,13 @@ use crate::hal::mem::page_table::{PTEFlags, PageTableEntry};
 use crate::mm::page_table::PageTable;
 // use PTEFlags
 use super::{MmapFlags, PageFaultAccessType};
+use crate::fs::memfd::MemfdInode;
 use crate::fs::{FileClass, FileTrait};
 use crate::mm::address::{VirtAddr, VirtPageNum};
 use crate::mm::page::Page;
 use crate::sync::block_on;
 use crate::task::current_task;
+use crate::utils::downcast::Downcast;
 use crate::utils::{backtrace, Errno, SysResult};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -103,7 +105,6 @@ impl From<xmas_elf::program::Flags> for MapPerm {
 
 /// A contiguous virtual memory area.
 /// ADDITION: only in user space
-#[derive(Clone)]
 pub struct VmArea {
     /// Aligned `VirtAddr` range for the `VmArea`.
     range_va: Range<VirtAddr>,
@@ -135,9 +136,27 @@ impl core::fmt::Debug for VmArea {
     }
 }
 
+impl Clone for VmArea {
+    fn clone(&self) -> Self {
+        let new = Self {
+            range_va: self.range_va.clone(),
+            pages: self.pages.clone(),
+            map_perm: self.map_perm,
+            vma_type: self.vma_type,
+            mmap_flags: self.mmap_flags,
+            backed_file: self.backed_file.clone(),
+            offset: self.offset,
+            shared: self.shared,
+        };
+        new.account_writable(true);
+        new
+    }
+}
+
 impl Drop for VmArea {
     fn drop(&mut self) {
         // log::debug!("[VmArea::drop] drop {self:?}",);
+        self.account_writable(false);
     }
 }
 
@@ -177,11 +196,12 @@ impl VmArea {
             offset,
             shared,
         };
+        new.account_writable(true);
         new
     }
 
     pub fn from_another(another: &Self) -> Self {
-        Self {
+        let new = Self {
             range_va: another.range_va(),
             pages: BTreeMap::new(),
             vma_type: another.vma_type,
@@ -190,6 +210,30 @@ impl VmArea {
             mmap_flags: another.mmap_flags,
             offset: another.offset,
             shared: another.shared,
+        };
+        new.account_writable(true);
+        new
+    }
+
+    /// 共享映射的 memfd，seal 需要检查它上面的映射
+    pub fn shared_memfd(&self) -> Option<Arc<MemfdInode>> {
+        if !self.shared {
+            return None;
+        }
+        let file = self.backed_file.as_ref()?;
+        file.metadata().inode.clone().downcast_arc::<MemfdInode>()
+    }
+
+    /// 可写的共享 memfd 映射在创建时计数，销毁时撤销
+    fn account_writable(&self, map: bool) {
+        if !self.map_perm.contains(MapPerm::W) {
+            return;
+        }
+        if let Some(memfd) = self.shared_memfd() {
+            match map {
+                true => memfd.map_writable(),
+                false => memfd.unmap_writable(),
+            }
         }
     }
 
@@ -226,7 +270,9 @@ impl VmArea {
     }
 
     pub fn set_perm(&mut self, perm: MapPerm) {
+        self.account_writable(false);
         self.map_perm = perm;
+        self.account_writable(true);
     }
 
     pub fn get_page(&self, vpn: VirtPageNum) -> &Arc<Page> {
@@ -431,14 +477,15 @@ impl VmArea {
                         let offset = self.offset + (vpn - self.start_vpn()) * PAGE_SIZE;
                         let offset_aligned = align_down_by_page(offset);
                         if self.mmap_flags.contains(MmapFlags::MAP_SHARED) {
-                            let page =
-                                block_on(async { file.get_page_at(offset_aligned).await }).unwrap();
+                            // 超出文件（或 io_uring 环）范围的页没有内容可以映射
+                            let page = block_on(async { file.get_page_at(offset_aligned).await })
+                                .ok_or(Errno::EFAULT)?;
                             page_table.map_leaf(vpn, page.ppn(), self.map_perm.into());
                             self.pages.insert(vpn, page);
                             unsafe { sfence_vma_vaddr(vpn.to_vaddr().into()) };
                         } else {
-                            let page =
-                                block_on(async { file.get_page_at(offset_aligned).await }).unwrap();
+                            let page = block_on(async { file.get_page_at(offset_aligned).await })
+                                .ok_or(Errno::EFAULT)?;
                             if access_type.contains(PageFaultAccessType::WRITE) {
                                 let new_page = Page::new();
                                 new_page.copy_from_slice(page.get_bytes_array());
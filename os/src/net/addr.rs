@@ -60,6 +60,10 @@ impl SockAddr {
 pub enum Sock {
     Tcp,
     Udp,
+    /// ICMP 回显套接字（SOCK_DGRAM + IPPROTO_ICMP / IPPROTO_ICMPV6）
+    Icmp,
+    /// 原始 IP 套接字（SOCK_RAW）
+    Raw,
     Unix,
     Unspec,
 }
@@ -70,6 +74,29 @@ pub enum IpType {
     Ipv6,
 }
 
+impl IpType {
+    /// 该地址族的套接字能否与 `addr` 通信：AF_INET 只能用 IPv4，
+    /// AF_INET6 在没有设置 IPV6_V6ONLY 时同时接受 IPv4（以 v4-mapped 地址的形式出现）
+    pub fn accepts(&self, v6only: bool, addr: &IpAddress) -> bool {
+        match (self, addr) {
+            (IpType::Ipv4, IpAddress::Ipv4(_)) => true,
+            (IpType::Ipv4, IpAddress::Ipv6(_)) => false,
+            (IpType::Ipv6, IpAddress::Ipv4(_)) => !v6only,
+            (IpType::Ipv6, IpAddress::Ipv6(_)) => true,
+        }
+    }
+
+    /// 把协议栈里的端点转换为返回给用户的地址，AF_INET6 套接字上的 IPv4 端点写成 ::ffff:a.b.c.d
+    pub fn sockaddr(&self, endpoint: IpEndpoint) -> SockAddr {
+        match (self, endpoint.addr) {
+            (IpType::Ipv6, IpAddress::Ipv4(addr)) => {
+                SockAddr::Inet6(SockIpv6::new(endpoint.port, addr.to_ipv6_mapped().octets()))
+            }
+            _ => endpoint.into(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct SockIpv4 {
@@ -178,6 +205,14 @@ impl SockAddr {
         unsafe { SockAddr::Inet6(addr).big2little() }
     }
 
+    /// 是否为 ::ffff:a.b.c.d 形式的 IPv4 映射地址
+    pub fn is_v4_mapped(&self) -> bool {
+        match self {
+            SockAddr::Inet6(addr) => core::net::Ipv6Addr::from(addr.addr).to_ipv4_mapped().is_some(),
+            _ => false,
+        }
+    }
+
     /// 需要注意传入的addr *const u8，如果直接强转为SocketIpv6或者SocketIpv4
     /// 那么里面字段是大端序的数据，需要转化为小端序，然后保存在SockAddr中
     fn big2little(&self) -> Self {
@@ -275,7 +310,11 @@ impl TryFrom<SockAddr> for IpEndpoint {
                     u16::from_be_bytes([addr.addr[14], addr.addr[15]]),
                 );
                 let port = addr.port;
-                Ok(IpEndpoint::new(ip.into(), port))
+                // v4-mapped 地址在协议栈内部按 IPv4 处理
+                match ip.to_ipv4_mapped() {
+                    Some(ip) => Ok(IpEndpoint::new(ip.into(), port)),
+                    None => Ok(IpEndpoint::new(ip.into(), port)),
+                }
             }
             SockAddr::Unix(addr) => Err(Errno::EAFNOSUPPORT),
             _ => return Err(Errno::EINVAL),
@@ -1,69 +1,626 @@
-use crate::sync::{once::LateInit, timer::get_time_ms, SpinNoIrqLock};
+use crate::{
+    drivers::{device::NetDevice, DEVICE_MANAGER},
+    sync::{once::LateInit, timer::get_time_ms, SpinNoIrqLock},
+    utils::{get_random_u32, Errno, SysResult},
+};
+use alloc::{collections::VecDeque, format, string::{String, ToString}, sync::Arc, vec, vec::Vec};
+use log::{info, warn};
 use smoltcp::{
-    iface::{Config, Interface},
-    phy::{Loopback, Medium, Tracer},
+    iface::{Config, Context, Interface, Route as SmolRoute, SocketSet},
+    phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken},
+    socket::{dhcpv4, Socket as SmolSocket},
     time::Instant,
-    wire::{EthernetAddress, IpAddress, IpCidr},
+    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr},
 };
 
-use super::SOCKET_SET;
+use super::{route::{network_of, Route, RouteTable}, SOCKET_SET};
+
+pub static NET_DEV: LateInit<SpinNoIrqLock<NetIfaces>> = LateInit::new();
 
-pub static NET_DEV: LateInit<SpinNoIrqLock<NetDev>> = LateInit::new();
+/// 回环接口的 ifindex
+pub const LOOPBACK_IFINDEX: usize = 1;
+/// 以太网帧头长度，smoltcp 以太网设备的 MTU 包含帧头
+const ETHERNET_HEADER_LEN: usize = 14;
+/// 回环接口的默认 MTU，与 Linux 相同
+const LOOPBACK_MTU: usize = 65536;
+/// 接口允许设置的最小 MTU（IPv4 要求的最小值）
+const MIN_MTU: usize = 68;
 
+/// 建立回环接口和每张网卡对应的接口。
+///
+/// 网卡的 IPv4 地址和默认路由由 DHCP 配置（见 `dhcp_task`），也可以之后通过 ioctl 手动设置；
+/// IPv6 链路本地地址在这里按 EUI-64 直接生成。不处理路由器通告，
+/// 网卡上的 IPv6 默认路由需要在 AF_INET6 套接字上用 SIOCADDRT 手动添加
 pub fn init_net_dev() {
-    NET_DEV.init(SpinNoIrqLock::new(NetDev::new()));
+    let mut ifaces = NetIfaces::new();
+    ifaces.add(NetDev::new_loopback());
+    let cards = DEVICE_MANAGER.read().net_devs();
+    for (i, card) in cards.into_iter().enumerate() {
+        let mut dev = NetDev::new_ethernet(format!("eth{}", i), card);
+        dev.enable_dhcp();
+        let link_local = link_local_cidr(dev.mac_address());
+        let ifindex = ifaces.add(dev);
+        if let Err(e) = ifaces.add_addr(ifindex, link_local) {
+            warn!("[init_net_dev] eth{} add link-local address failed: {:?}", i, e);
+        }
+    }
+    if ifaces.iter().all(|dev| dev.is_loopback()) {
+        // 没有网卡时保持原来的行为：所有流量都走回环接口
+        let _ = ifaces.add_route(Route {
+            dest: IpCidr::new(IpAddress::v4(0, 0, 0, 0), 0),
+            gateway: Some(IpAddress::v4(127, 0, 0, 1)),
+            ifindex: LOOPBACK_IFINDEX,
+            metric: 0,
+        });
+        let _ = ifaces.add_route(Route {
+            dest: IpCidr::new(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0), 0),
+            gateway: Some(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1)),
+            ifindex: LOOPBACK_IFINDEX,
+            metric: 0,
+        });
+    }
+    NET_DEV.init(SpinNoIrqLock::new(ifaces));
 }
 
 pub enum NetDevType {
-    Loopback(Loopback),
-    Unspec,
+    Loopback(LoopbackPhy),
+    Ethernet(EthernetPhy),
+}
+
+/// 接口的收发统计，对应 /proc/net/dev 中的字段
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NetStats {
+    pub rx_bytes: usize,
+    pub rx_packets: usize,
+    pub tx_bytes: usize,
+    pub tx_packets: usize,
+    pub tx_errors: usize,
+}
+
+/// DHCP 客户端的事件，已从 smoltcp 的事件中拷出，不再借用 DHCP socket
+pub enum DhcpEvent {
+    Configured {
+        address: Ipv4Cidr,
+        router: Option<Ipv4Address>,
+        dns_servers: Vec<Ipv4Address>,
+    },
+    Deconfigured,
+}
+
+impl From<dhcpv4::Event<'_>> for DhcpEvent {
+    fn from(event: dhcpv4::Event<'_>) -> Self {
+        match event {
+            dhcpv4::Event::Configured(config) => DhcpEvent::Configured {
+                address: config.address,
+                router: config.router,
+                dns_servers: config.dns_servers.iter().copied().collect(),
+            },
+            dhcpv4::Event::Deconfigured => DhcpEvent::Deconfigured,
+        }
+    }
 }
 
+/// 一个网络接口：底层设备加上 smoltcp 的 Interface
 pub struct NetDev {
+    pub name: String,
+    pub ifindex: usize,
     pub device: NetDevType,
     pub iface: Interface,
+    /// 接口是否处于 up 状态，down 的接口不参与轮询
+    pub up: bool,
+    /// 该接口的 DHCP 客户端
+    ///
+    /// DHCP socket 不放进共享的 `SOCKET_SET`，否则回环接口轮询时也会替它发包；
+    /// 只在轮询本接口时临时加入
+    dhcp: Option<dhcpv4::Socket<'static>>,
+    /// 当前由 DHCP 配置的地址
+    dhcp_addr: Option<Ipv4Cidr>,
 }
 
 impl NetDev {
-    pub fn new() -> Self {
-        let mut loopback = Loopback::new(Medium::Ethernet);
-        let config = Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into());
+    pub fn new_loopback() -> Self {
+        let mut loopback = LoopbackPhy {
+            queue: VecDeque::new(),
+            mtu: LOOPBACK_MTU,
+            stats: NetStats::default(),
+        };
+        let config = Self::config(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]));
         let instant = Instant::from_millis(get_time_ms() as i64);
         let mut iface = Interface::new(config, &mut loopback, instant);
         iface.update_ip_addrs(|ip_addrs| {
             ip_addrs
-                .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 24))
+                .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
                 .unwrap();
             ip_addrs
                 .push(IpCidr::new(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1), 128))
                 .unwrap();
         });
-        let gateway = IpAddress::v4(127, 0, 0, 1);
-        let device = NetDevType::Loopback(loopback);
-        let mut res = Self { device, iface };
-        res.set_gateway(gateway);
-        res
+        Self {
+            name: "lo".to_string(),
+            ifindex: LOOPBACK_IFINDEX,
+            device: NetDevType::Loopback(loopback),
+            iface,
+            up: true,
+            dhcp: None,
+            dhcp_addr: None,
+        }
+    }
+
+    pub fn new_ethernet(name: String, card: Arc<dyn NetDevice>) -> Self {
+        let mtu = card.max_frame_len() - ETHERNET_HEADER_LEN;
+        let mut phy = EthernetPhy { card, mtu, stats: NetStats::default() };
+        let config = Self::config(EthernetAddress(phy.card.mac_address()));
+        let instant = Instant::from_millis(get_time_ms() as i64);
+        let iface = Interface::new(config, &mut phy, instant);
+        Self {
+            name,
+            ifindex: 0,
+            device: NetDevType::Ethernet(phy),
+            iface,
+            up: true,
+            dhcp: None,
+            dhcp_addr: None,
+        }
+    }
+
+    /// 在该接口上启动 DHCP 客户端
+    pub fn enable_dhcp(&mut self) {
+        self.dhcp = Some(dhcpv4::Socket::new());
+    }
+
+    pub fn dhcp_enabled(&self) -> bool {
+        self.dhcp.is_some()
+    }
+
+    /// DHCP 是否已经拿到租约
+    pub fn dhcp_configured(&self) -> bool {
+        self.dhcp_addr.is_some()
+    }
+
+    fn config(mac: EthernetAddress) -> Config {
+        let mut config = Config::new(mac.into());
+        config.random_seed = (get_random_u32() as u64) << 32 | get_random_u32() as u64;
+        config
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        matches!(self.device, NetDevType::Loopback(_))
+    }
+
+    pub fn mac_address(&self) -> [u8; 6] {
+        // 与 Linux 一致，回环接口的硬件地址显示为全 0
+        if self.is_loopback() {
+            return [0; 6];
+        }
+        match self.iface.hardware_addr() {
+            smoltcp::wire::HardwareAddress::Ethernet(mac) => mac.0,
+            #[allow(unreachable_patterns)]
+            _ => [0; 6],
+        }
     }
 
-    fn set_gateway(&mut self, gateway: IpAddress) {
-        match gateway {
-            IpAddress::Ipv4(ip) => {
-                self.iface.routes_mut().add_default_ipv4_route(ip).unwrap();
+    /// 接口上的第一个 IPv4 地址
+    pub fn ipv4_cidr(&self) -> Option<Ipv4Cidr> {
+        self.iface.ip_addrs().iter().find_map(|cidr| match cidr {
+            IpCidr::Ipv4(cidr) => Some(*cidr),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        })
+    }
+
+    pub fn mtu(&self) -> usize {
+        match &self.device {
+            NetDevType::Loopback(dev) => dev.mtu,
+            NetDevType::Ethernet(dev) => dev.mtu,
+        }
+    }
+
+    pub fn set_mtu(&mut self, mtu: usize) -> SysResult<()> {
+        match &mut self.device {
+            NetDevType::Loopback(dev) if (MIN_MTU..=LOOPBACK_MTU).contains(&mtu) => dev.mtu = mtu,
+            NetDevType::Ethernet(dev)
+                if (MIN_MTU..=dev.card.max_frame_len() - ETHERNET_HEADER_LEN).contains(&mtu) =>
+            {
+                dev.mtu = mtu
             }
-            IpAddress::Ipv6(_) => unimplemented!(),
+            _ => return Err(Errno::EINVAL),
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> NetStats {
+        match &self.device {
+            NetDevType::Loopback(dev) => dev.stats,
+            NetDevType::Ethernet(dev) => dev.stats,
         }
     }
 
     /// 用于接受和发送数据包
     /// 处理网络接口的事件，包括接收和发送数据包，以及更新套接字的状态。
     /// 它是网络栈的核心循环，用于驱动网络接口的操作
-    pub fn poll(&mut self) {
+    ///
+    /// 若接口上运行着 DHCP 客户端，返回本次轮询产生的 DHCP 事件
+    pub fn poll(&mut self, sockets: &mut SocketSet<'static>) -> Option<DhcpEvent> {
         let instant = Instant::from_millis(get_time_ms() as i64);
-        let mut socket = SOCKET_SET.lock();
-        let device = match self.device {
-            NetDevType::Loopback(ref mut dev) => dev,
-            NetDevType::Unspec => panic!("Device not initialized"),
+        let dhcp = self.dhcp.take().map(|socket| sockets.add(socket));
+        match self.device {
+            NetDevType::Loopback(ref mut dev) => self.iface.poll(instant, dev, sockets),
+            NetDevType::Ethernet(ref mut dev) => self.iface.poll(instant, dev, sockets),
+        };
+        let handle = dhcp?;
+        let event = sockets.get_mut::<dhcpv4::Socket>(handle).poll().map(DhcpEvent::from);
+        if let SmolSocket::Dhcpv4(socket) = sockets.remove(handle) {
+            self.dhcp = Some(socket);
+        }
+        event
+    }
+}
+
+/// 所有网络接口和路由表
+///
+/// 所有接口共用同一个 `SOCKET_SET`。每次轮询都先轮询回环接口，
+/// 这样目的地址在本机的报文总是先由回环接口发出；
+/// 其余接口上 smoltcp 找不到路由的报文不会被发出，socket 状态也不会前进，留给正确的接口发送。
+pub struct NetIfaces {
+    devs: Vec<NetDev>,
+    pub routes: RouteTable,
+}
+
+impl NetIfaces {
+    pub fn new() -> Self {
+        Self { devs: Vec::new(), routes: RouteTable::new() }
+    }
+
+    /// 加入一个接口，分配 ifindex，并为它已有的地址添加直连路由
+    pub fn add(&mut self, mut dev: NetDev) -> usize {
+        if dev.ifindex == 0 {
+            dev.ifindex = self.devs.iter().map(|d| d.ifindex).max().unwrap_or(0) + 1;
+        }
+        let ifindex = dev.ifindex;
+        let cidrs: Vec<IpCidr> = dev.iface.ip_addrs().to_vec();
+        self.devs.push(dev);
+        for cidr in cidrs {
+            let _ = self.routes.add(Route { dest: network_of(cidr), gateway: None, ifindex, metric: 0 });
+        }
+        ifindex
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NetDev> {
+        self.devs.iter()
+    }
+
+    pub fn get(&self, ifindex: usize) -> Option<&NetDev> {
+        self.devs.iter().find(|d| d.ifindex == ifindex)
+    }
+
+    pub fn get_mut(&mut self, ifindex: usize) -> Option<&mut NetDev> {
+        self.devs.iter_mut().find(|d| d.ifindex == ifindex)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&NetDev> {
+        self.devs.iter().find(|d| d.name == name)
+    }
+
+    /// 给接口添加地址，并添加对应的直连路由。
+    ///
+    /// 发往本机地址的报文都从回环接口走，所以网卡上的地址同时以主机地址的形式加到回环接口上
+    pub fn add_addr(&mut self, ifindex: usize, cidr: IpCidr) -> SysResult<()> {
+        let dev = self.get_mut(ifindex).ok_or(Errno::ENODEV)?;
+        let is_loopback = dev.is_loopback();
+        Self::push_addr(dev, cidr)?;
+        if !is_loopback {
+            let host = host_cidr(cidr.address());
+            if let Some(lo) = self.get_mut(LOOPBACK_IFINDEX) {
+                Self::push_addr(lo, host)?;
+            }
+        }
+        let _ = self.routes.add(Route { dest: network_of(cidr), gateway: None, ifindex, metric: 0 });
+        Ok(())
+    }
+
+    fn push_addr(dev: &mut NetDev, cidr: IpCidr) -> SysResult<()> {
+        let mut res = Ok(());
+        dev.iface.update_ip_addrs(|addrs| {
+            if !addrs.contains(&cidr) {
+                res = addrs.push(cidr).map_err(|_| Errno::ENOSPC);
+            }
+        });
+        res
+    }
+
+    /// 删除接口上的地址及其直连路由
+    pub fn del_addr(&mut self, ifindex: usize, cidr: IpCidr) -> SysResult<()> {
+        let dev = self.get_mut(ifindex).ok_or(Errno::ENODEV)?;
+        let is_loopback = dev.is_loopback();
+        dev.iface.update_ip_addrs(|addrs| addrs.retain(|a| *a != cidr));
+        if !is_loopback {
+            let host = host_cidr(cidr.address());
+            if let Some(lo) = self.get_mut(LOOPBACK_IFINDEX) {
+                lo.iface.update_ip_addrs(|addrs| addrs.retain(|a| *a != host));
+            }
+        }
+        let _ = self.routes.del(network_of(cidr), Some(ifindex));
+        Ok(())
+    }
+
+    pub fn add_route(&mut self, route: Route) -> SysResult<()> {
+        if self.get(route.ifindex).is_none() {
+            return Err(Errno::ENODEV);
+        }
+        self.routes.add(route)?;
+        self.sync_routes(route.ifindex);
+        Ok(())
+    }
+
+    pub fn del_route(&mut self, dest: IpCidr, ifindex: Option<usize>) -> SysResult<()> {
+        let route = self.routes.del(dest, ifindex)?;
+        self.sync_routes(route.ifindex);
+        Ok(())
+    }
+
+    /// 把路由表中经由该接口、带网关的路由同步到接口的 smoltcp 路由表
+    fn sync_routes(&mut self, ifindex: usize) {
+        let gateways: Vec<(IpCidr, IpAddress)> = self.routes
+            .routes()
+            .iter()
+            .filter(|r| r.ifindex == ifindex)
+            .filter_map(|r| r.gateway.map(|gw| (r.dest, gw)))
+            .collect();
+        let Some(dev) = self.get_mut(ifindex) else { return };
+        dev.iface.routes_mut().update(|table| {
+            table.clear();
+            for (cidr, via_router) in gateways {
+                if table.push(SmolRoute { cidr, via_router, preferred_until: None, expires_at: None }).is_err() {
+                    warn!("[NetIfaces] smoltcp route table of {} is full", dev.name);
+                    break;
+                }
+            }
+        });
+    }
+
+    /// 按路由表选出发往 `dst` 的接口，本机地址和找不到路由时使用回环接口
+    pub fn route_ifindex(&self, dst: &IpAddress) -> usize {
+        if dst.is_loopback() || self.devs.iter().any(|d| d.iface.has_ip_addr(*dst)) {
+            return LOOPBACK_IFINDEX;
+        }
+        self.routes.lookup(dst).map_or(LOOPBACK_IFINDEX, |r| r.ifindex)
+    }
+
+    /// 原始套接字自己构造 IP 首部时使用的源地址：发往本机时就用目的地址，
+    /// 否则取出口接口上同一地址族的地址，IPv6 优先选与目的地址作用域相同的那个
+    pub fn source_addr_for(&self, dst: &IpAddress) -> Option<IpAddress> {
+        if self.devs.iter().any(|d| d.iface.has_ip_addr(*dst)) {
+            return Some(*dst);
+        }
+        let dev = self.get(self.route_ifindex(dst))?;
+        let is_link_local = |addr: &IpAddress| match addr {
+            IpAddress::Ipv6(addr) => addr.segments()[0] & 0xffc0 == 0xfe80,
+            IpAddress::Ipv4(_) => false,
         };
-        self.iface.poll(instant, device, &mut socket);
+        let candidates = || {
+            dev.iface
+                .ip_addrs()
+                .iter()
+                .map(|cidr| cidr.address())
+                .filter(|addr| addr.version() == dst.version())
+        };
+        candidates()
+            .find(|addr| is_link_local(addr) == is_link_local(dst))
+            .or_else(|| candidates().next())
+    }
+
+    /// 发往 `dst` 的接口的 smoltcp 上下文，用于 connect 时选择源地址
+    pub fn context_for(&mut self, dst: &IpAddress) -> &mut Context {
+        let ifindex = self.route_ifindex(dst);
+        let index = self.devs.iter().position(|d| d.ifindex == ifindex).unwrap_or(0);
+        self.devs[index].iface.context()
+    }
+
+    /// 用新的 IPv4 地址替换接口上原有的 IPv4 地址，`None` 表示删除。
+    ///
+    /// 手动配置地址后该接口上的 DHCP 客户端随之停止
+    pub fn set_ipv4_cidr(&mut self, ifindex: usize, cidr: Option<Ipv4Cidr>) -> SysResult<()> {
+        let dev = self.get_mut(ifindex).ok_or(Errno::ENODEV)?;
+        dev.dhcp = None;
+        dev.dhcp_addr = None;
+        if let Some(old) = dev.ipv4_cidr() {
+            self.del_addr(ifindex, IpCidr::Ipv4(old))?;
+        }
+        match cidr {
+            Some(cidr) => self.add_addr(ifindex, IpCidr::Ipv4(cidr)),
+            None => Ok(()),
+        }
+    }
+
+    /// 轮询所有处于 up 状态的接口，并应用 DHCP 客户端产生的配置
+    pub fn poll(&mut self) {
+        let mut events = Vec::new();
+        {
+            let mut sockets = SOCKET_SET.lock();
+            for dev in self.devs.iter_mut().filter(|dev| dev.up) {
+                if let Some(event) = dev.poll(&mut sockets) {
+                    events.push((dev.ifindex, event));
+                }
+            }
+        }
+        for (ifindex, event) in events {
+            self.apply_dhcp(ifindex, event);
+        }
+    }
+
+    fn apply_dhcp(&mut self, ifindex: usize, event: DhcpEvent) {
+        let Some(dev) = self.get_mut(ifindex) else { return };
+        let old = dev.dhcp_addr.take();
+        let name = dev.name.clone();
+        if let Some(old) = old {
+            let _ = self.del_addr(ifindex, IpCidr::Ipv4(old));
+        }
+        let _ = self.del_route(IpCidr::new(IpAddress::v4(0, 0, 0, 0), 0), Some(ifindex));
+        match event {
+            DhcpEvent::Configured { address, router, dns_servers } => {
+                info!("[DHCP] {}: address {}, router {:?}, dns {:?}", name, address, router, dns_servers);
+                if let Err(e) = self.add_addr(ifindex, IpCidr::Ipv4(address)) {
+                    warn!("[DHCP] {}: failed to add address {}: {:?}", name, address, e);
+                    return;
+                }
+                if let Some(dev) = self.get_mut(ifindex) {
+                    dev.dhcp_addr = Some(address);
+                }
+                if let Some(router) = router {
+                    let _ = self.add_route(Route {
+                        dest: IpCidr::new(IpAddress::v4(0, 0, 0, 0), 0),
+                        gateway: Some(IpAddress::Ipv4(router)),
+                        ifindex,
+                        metric: ifindex as u32,
+                    });
+                }
+            }
+            DhcpEvent::Deconfigured => {
+                info!("[DHCP] {}: lease lost", name);
+            }
+        }
+    }
+}
+
+/// 由 MAC 地址按 EUI-64 生成 fe80::/64 链路本地地址（RFC 4291 附录 A）
+fn link_local_cidr(mac: [u8; 6]) -> IpCidr {
+    let iid = [mac[0] ^ 0x02, mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]];
+    let segment = |i: usize| u16::from_be_bytes([iid[i], iid[i + 1]]);
+    let addr = IpAddress::v6(0xfe80, 0, 0, 0, segment(0), segment(2), segment(4), segment(6));
+    IpCidr::new(addr, 64)
+}
+
+/// 只包含 `addr` 本身的网段
+fn host_cidr(addr: IpAddress) -> IpCidr {
+    match addr {
+        IpAddress::Ipv4(_) => IpCidr::new(addr, 32),
+        IpAddress::Ipv6(_) => IpCidr::new(addr, 128),
+    }
+}
+
+/// 回环设备：发出的帧放进队列，下次接收时原样取回
+pub struct LoopbackPhy {
+    queue: VecDeque<Vec<u8>>,
+    mtu: usize,
+    stats: NetStats,
+}
+
+pub struct LoopbackTxToken<'a> {
+    queue: &'a mut VecDeque<Vec<u8>>,
+    stats: &'a mut NetStats,
+}
+
+impl Device for LoopbackPhy {
+    type RxToken<'a> = EthRxToken where Self: 'a;
+    type TxToken<'a> = LoopbackTxToken<'a> where Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.queue.pop_front()?;
+        self.stats.rx_bytes += frame.len();
+        self.stats.rx_packets += 1;
+        Some((EthRxToken(frame), LoopbackTxToken { queue: &mut self.queue, stats: &mut self.stats }))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken { queue: &mut self.queue, stats: &mut self.stats })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = self.mtu + ETHERNET_HEADER_LEN;
+        caps
+    }
+}
+
+impl<'a> TxToken for LoopbackTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0u8; len];
+        let res = f(&mut frame);
+        self.stats.tx_bytes += len;
+        self.stats.tx_packets += 1;
+        // 回环的帧接收时还会再经过一次，只在发送时记录
+        #[cfg(feature = "net_pcap")]
+        super::pcap::capture(&frame);
+        self.queue.push_back(frame);
+        res
+    }
+}
+
+/// 把 `NetDevice` 适配成 smoltcp 的 `Device`
+pub struct EthernetPhy {
+    card: Arc<dyn NetDevice>,
+    mtu: usize,
+    stats: NetStats,
+}
+
+pub struct EthRxToken(Vec<u8>);
+
+pub struct EthTxToken<'a> {
+    card: &'a Arc<dyn NetDevice>,
+    stats: &'a mut NetStats,
+}
+
+impl Device for EthernetPhy {
+    type RxToken<'a> = EthRxToken where Self: 'a;
+    type TxToken<'a> = EthTxToken<'a> where Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.card.recv()?;
+        self.stats.rx_bytes += frame.len();
+        self.stats.rx_packets += 1;
+        #[cfg(feature = "net_pcap")]
+        super::pcap::capture(&frame);
+        Some((EthRxToken(frame), EthTxToken { card: &self.card, stats: &mut self.stats }))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        if !self.card.can_send() {
+            return None;
+        }
+        Some(EthTxToken { card: &self.card, stats: &mut self.stats })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = self.mtu + ETHERNET_HEADER_LEN;
+        caps.max_burst_size = None;
+        caps
+    }
+}
+
+impl RxToken for EthRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+impl<'a> TxToken for EthTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0u8; len];
+        let res = f(&mut frame);
+        #[cfg(feature = "net_pcap")]
+        super::pcap::capture(&frame);
+        match self.card.send(&frame) {
+            Ok(_) => {
+                self.stats.tx_bytes += len;
+                self.stats.tx_packets += 1;
+            }
+            Err(e) => {
+                self.stats.tx_errors += 1;
+                warn!("[EthTxToken] send failed: {:?}", e);
+            }
+        }
+        res
     }
 }
@@ -0,0 +1,35 @@
+//! 内核 DHCP 任务
+//!
+//! DHCP 客户端本身挂在各个网卡接口上（见 `NetDev::poll`），只有网络栈被轮询时才会前进。
+//! 平时轮询由套接字操作顺带完成，没有网络活动时由这个任务定期轮询，保证开机时能拿到地址、之后能按时续租。
+
+use core::time::Duration;
+
+use log::info;
+
+use super::NET_DEV;
+use crate::sync::TimeoutFuture;
+
+/// 还有接口没拿到租约时的轮询间隔
+const DHCP_DISCOVER_INTERVAL: Duration = Duration::from_millis(100);
+/// 所有接口都已配置后的轮询间隔，只需要赶上续租
+const DHCP_RENEW_INTERVAL: Duration = Duration::from_secs(1);
+
+pub async fn dhcp_task() {
+    loop {
+        let interval = {
+            let mut ifaces = NET_DEV.lock();
+            ifaces.poll();
+            let mut dhcp_devs = ifaces.iter().filter(|dev| dev.dhcp_enabled()).peekable();
+            if dhcp_devs.peek().is_none() {
+                info!("[dhcp_task] no interface uses DHCP, exit");
+                return;
+            }
+            match dhcp_devs.all(|dev| dev.dhcp_configured()) {
+                true => DHCP_RENEW_INTERVAL,
+                false => DHCP_DISCOVER_INTERVAL,
+            }
+        };
+        let _ = TimeoutFuture::new(core::future::pending::<()>(), interval).await;
+    }
+}
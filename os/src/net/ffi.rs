@@ -1,17 +1,21 @@
-use core::sync::atomic::AtomicU32;
 use crate::{fs::OpenFlags, hal::config::KB};
+use num_enum::TryFromPrimitive;
 
 pub const AF_UNIX: u16 = 1;
 pub const AF_INET: u16 = 2;
 pub const AF_INET6: u16 = 10;
 pub const META_SIZE: usize = 1 * KB;
 pub const BUFF_SIZE: usize = 32 * KB; // 用于设置接受和发送缓冲区大小
+/// listen 的 backlog 上限。每个等待中的连接都是一个带完整收发缓冲区的 smoltcp 套接字，
+/// 所以这里远小于 Linux 的 SOMAXCONN
+pub const MAX_BACKLOG: usize = 16;
 pub const MAX_BUFFER_SIZE: u32 = 128 * KB as u32; // 只用于当下
 pub const TCP_MSS_DEFAULT: u32 = 32 * KB as u32;
-pub static TCP_MSS: AtomicU32 = AtomicU32::new(match TCP_MSS_DEFAULT > MAX_BUFFER_SIZE {
+/// 新套接字 TCP_MAXSEG 的初始值
+pub const TCP_MSS: u32 = match TCP_MSS_DEFAULT > MAX_BUFFER_SIZE {
     true => MAX_BUFFER_SIZE,
     false => TCP_MSS_DEFAULT,
-});
+};
 pub const Congestion: &str = "reno"; // TCP 拥塞控制算法名称
 pub const MAX_HOST_NAME: usize = 64; // 本机domin name长度最大值，不包含0结尾
 pub static mut HOST_NAME: [u8; 65] = [0; 65];
@@ -43,7 +47,112 @@ bitflags! {
         const IPPROTO_UDP = 17;
         /// ICMP 协议
         const IPPROTO_ICMP = 1;
+        /// ICMPv6 协议
+        const IPPROTO_ICMPV6 = 58;
         /// IP 协议族
         const IPPROTO_IP   = 0;
     }
 }
+
+/// 网络接口名的最大长度（含结尾 0）
+pub const IFNAMSIZ: usize = 16;
+/// SIOCGIFHWADDR 返回的硬件类型
+pub const ARPHRD_ETHER: u16 = 1;
+pub const ARPHRD_LOOPBACK: u16 = 772;
+
+/// 套接字上的网络配置 ioctl
+#[derive(TryFromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum SockIoctlCmd {
+    SIOCADDRT = 0x890B, SIOCDELRT = 0x890C, SIOCGIFNAME = 0x8910, SIOCGIFCONF = 0x8912,
+    SIOCGIFFLAGS = 0x8913, SIOCSIFFLAGS = 0x8914, SIOCGIFADDR = 0x8915, SIOCSIFADDR = 0x8916,
+    SIOCGIFBRDADDR = 0x8919, SIOCGIFNETMASK = 0x891B, SIOCSIFNETMASK = 0x891C,
+    SIOCGIFMTU = 0x8921, SIOCSIFMTU = 0x8922, SIOCGIFHWADDR = 0x8927, SIOCGIFINDEX = 0x8933,
+}
+
+bitflags! {
+    /// 网络接口标志，SIOCGIFFLAGS / SIOCSIFFLAGS 使用
+    #[derive(Debug, Clone, Copy)]
+    pub struct IfFlags: u16 {
+        const IFF_UP        = 1 << 0;
+        const IFF_BROADCAST = 1 << 1;
+        const IFF_LOOPBACK  = 1 << 3;
+        const IFF_RUNNING   = 1 << 6;
+        const IFF_MULTICAST = 1 << 12;
+    }
+
+    /// 路由标志，SIOCADDRT 和 /proc/net/route 使用
+    #[derive(Debug, Clone, Copy)]
+    pub struct RouteFlags: u16 {
+        const RTF_UP      = 1 << 0;
+        const RTF_GATEWAY = 1 << 1;
+        const RTF_HOST    = 1 << 2;
+    }
+}
+
+/// struct ifreq
+///
+/// 接口名之后是一个 24 字节的联合体，按请求不同解释为 sockaddr、short 或 int
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct IfReq {
+    pub name: [u8; IFNAMSIZ],
+    pub data: [u8; 24],
+}
+
+/// struct ifconf
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct IfConf {
+    pub len: i32,
+    /// 用户缓冲区地址，为 0 时只返回需要的长度
+    pub buf: usize,
+}
+
+/// struct rtentry
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct RtEntry {
+    pub pad1: usize,
+    pub dst: [u8; 16],
+    pub gateway: [u8; 16],
+    pub genmask: [u8; 16],
+    pub flags: u16,
+    pub pad2: i16,
+    pub pad3: usize,
+    pub pad4: usize,
+    /// 用户态传入的是 metric + 1
+    pub metric: i16,
+    /// 出接口名，为 0 时根据网关选择
+    pub dev: usize,
+    pub mtu: usize,
+    pub window: usize,
+    pub irtt: u16,
+}
+
+/// struct in6_rtmsg，AF_INET6 套接字上 SIOCADDRT / SIOCDELRT 的参数
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct In6RtMsg {
+    pub dst: [u8; 16],
+    pub src: [u8; 16],
+    pub gateway: [u8; 16],
+    pub rt_type: u32,
+    pub dst_len: u16,
+    pub src_len: u16,
+    pub metric: u32,
+    pub info: usize,
+    /// 与 rtentry 的 flags 含义相同，但是 32 位
+    pub flags: u32,
+    /// 出接口，为 0 时根据网关选择
+    pub ifindex: i32,
+}
+
+/// struct linger，SO_LINGER 的参数
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Linger {
+    pub l_onoff: i32,
+    /// 秒
+    pub l_linger: i32,
+}
@@ -0,0 +1,270 @@
+use super::{
+    addr::{IpType, Sock, SockAddr},
+    with_timeout, SockMeta, SockOpts, Socket, BUFF_SIZE, META_SIZE, NET_DEV, PORT_MANAGER, SOCKET_SET,
+};
+use crate::{
+    fs::OpenFlags,
+    net::{do_port_aloc, net_async::{IcmpRecvFuture, IcmpSendFuture}},
+    sync::{get_waker, SpinNoIrqLock},
+    syscall::ShutHow,
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::vec;
+use async_trait::async_trait;
+use log::info;
+use smoltcp::{
+    iface::SocketHandle,
+    socket::icmp::{self, PacketBuffer, PacketMetadata},
+    wire::{IpAddress, IpEndpoint, IpListenEndpoint},
+};
+
+/// ICMP 首部（类型、代码、校验和、标识符、序号）的长度
+const ICMP_HEADER_LEN: usize = 8;
+
+/// 该地址族上回显请求和回显应答的 ICMP 类型
+pub fn echo_types(iptype: IpType) -> (u8, u8) {
+    match iptype {
+        IpType::Ipv4 => (8, 0),
+        IpType::Ipv6 => (128, 129),
+    }
+}
+
+/// ICMP 回显套接字，即 Linux 上不需要特权的 ping 套接字（SOCK_DGRAM + IPPROTO_ICMP）。
+///
+/// 用户收发的是不含 IP 首部的 ICMP 报文，只能发送回显请求，也只会收到回显应答。
+/// 发出的报文中的标识符由内核改写为套接字绑定的标识符，它与端口一样由 `PortManager` 分配
+pub struct IcmpSocket {
+    pub handle: SocketHandle,
+    pub sockmeta: SpinNoIrqLock<SockMeta>,
+}
+
+impl Drop for IcmpSocket {
+    fn drop(&mut self) {
+        info!("[IcmpSocket::drop] start");
+        let mut binding = SOCKET_SET.lock();
+        let sock = binding.remove(self.handle);
+        drop(sock);
+        drop(binding);
+
+        let sockmeta = self.sockmeta.lock();
+        if let Some(ident) = sockmeta.port {
+            PORT_MANAGER.lock().dealloc(Sock::Icmp, ident);
+        }
+    }
+}
+
+impl IcmpSocket {
+    pub fn new(iptype: IpType, flags: OpenFlags) -> Self {
+        let recv_buf =
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; META_SIZE], vec![0; BUFF_SIZE]);
+        let send_buf =
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; META_SIZE], vec![0; BUFF_SIZE]);
+        let handle = SOCKET_SET.lock().add(icmp::Socket::new(recv_buf, send_buf));
+        let sockmeta = SockMeta::new(Sock::Icmp, iptype, BUFF_SIZE, BUFF_SIZE, OpenFlags::O_RDWR | flags);
+        Self {
+            handle,
+            sockmeta: SpinNoIrqLock::new(sockmeta),
+        }
+    }
+
+    pub fn with_socket<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut icmp::Socket<'_>) -> R,
+    {
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<icmp::Socket>(self.handle);
+        f(socket)
+    }
+
+    /// 解析对端地址。ICMP 与 ICMPv6 是两个协议，所以这里不接受另一个地址族的地址
+    fn remote_addr(&self, addr: &SockAddr) -> SysResult<IpAddress> {
+        let iptype = self.sockmeta.lock().iptype;
+        let mut endpoint = IpEndpoint::try_from(*addr)?;
+        if !iptype.accepts(true, &endpoint.addr) {
+            return Err(Errno::EAFNOSUPPORT);
+        }
+        // 与 UDP 相同，未指定的目的地址表示本机
+        if endpoint.addr.is_unspecified() {
+            endpoint.addr = match iptype {
+                IpType::Ipv4 => IpAddress::v4(127, 0, 0, 1),
+                IpType::Ipv6 => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1),
+            };
+        }
+        Ok(endpoint.addr)
+    }
+
+    /// 分配标识符并绑定，`endpoint.port` 为 0 时自动分配
+    fn bind_ident(&self, mut endpoint: IpEndpoint) -> SysResult<()> {
+        let reuse = {
+            let sockmeta = self.sockmeta.lock();
+            if sockmeta.local_end.is_some() {
+                return Err(Errno::EINVAL);
+            }
+            sockmeta.opts.reuse
+        };
+        let ident = do_port_aloc(&mut endpoint, Sock::Icmp, reuse)?;
+        if self.with_socket(|socket| socket.bind(icmp::Endpoint::Ident(ident))).is_err() {
+            PORT_MANAGER.lock().dealloc(Sock::Icmp, ident);
+            return Err(Errno::EINVAL);
+        }
+        let local_end = match endpoint.addr.is_unspecified() {
+            true => IpListenEndpoint::from(ident),
+            false => IpListenEndpoint::from(endpoint),
+        };
+        let mut sockmeta = self.sockmeta.lock();
+        sockmeta.local_end = Some(local_end);
+        sockmeta.port = Some(ident);
+        info!("[IcmpSocket::bind] bind to ident {}", ident);
+        Ok(())
+    }
+
+    /// 发送前还没有绑定时自动分配一个标识符
+    fn ensure_bound(&self) -> SysResult<u16> {
+        if let Some(ident) = self.sockmeta.lock().port {
+            return Ok(ident);
+        }
+        let unspecified = match self.sockmeta.lock().iptype {
+            IpType::Ipv4 => IpAddress::v4(0, 0, 0, 0),
+            IpType::Ipv6 => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0),
+        };
+        self.bind_ident(IpEndpoint::new(unspecified, 0))?;
+        self.sockmeta.lock().port.ok_or(Errno::EINVAL)
+    }
+}
+
+#[async_trait]
+impl Socket for IcmpSocket {
+    async fn accept(&self, _sockfd: usize, _flags: OpenFlags) -> SysResult<(IpEndpoint, usize)> {
+        Err(Errno::EOPNOTSUPP)
+    }
+    fn bind(&self, _sockfd: usize, addr: &SockAddr) -> SysResult<()> {
+        info!("[IcmpSocket::bind] start, addr = {:?}", addr);
+        let iptype = self.sockmeta.lock().iptype;
+        let endpoint = IpEndpoint::try_from(*addr)?;
+        if !iptype.accepts(true, &endpoint.addr) {
+            return Err(Errno::EAFNOSUPPORT);
+        }
+        self.bind_ident(endpoint)
+    }
+    async fn connect(&self, _sockfd: usize, addr: &SockAddr) -> SysResult<()> {
+        info!("[IcmpSocket::connect] start, addr = {:?}", addr);
+        let remote_addr = self.remote_addr(addr)?;
+        self.ensure_bound()?;
+        self.sockmeta.lock().remote_end = Some(IpEndpoint::new(remote_addr, 0));
+        Ok(())
+    }
+    fn listen(&self, _backlog: usize) -> SysResult<()> {
+        Err(Errno::EOPNOTSUPP)
+    }
+    async fn send_msg(&self, buf: &[u8], dest_addr: Option<SockAddr>) -> SysResult<usize> {
+        info!("[IcmpSocket::send_msg] start, dest_addr = {:?}", dest_addr);
+        let remote_addr = match dest_addr {
+            Some(addr) => self.remote_addr(&addr)?,
+            None => self.sockmeta.lock().remote_end.ok_or(Errno::EDESTADDRREQ)?.addr,
+        };
+        let iptype = self.sockmeta.lock().iptype;
+        let (echo_request, _) = echo_types(iptype);
+        if buf.len() < ICMP_HEADER_LEN || buf[0] != echo_request || buf[1] != 0 {
+            return Err(Errno::EINVAL);
+        }
+        if buf.len() > BUFF_SIZE {
+            return Err(Errno::EMSGSIZE);
+        }
+        let ident = self.ensure_bound()?;
+
+        // 校验和由 smoltcp 发送时重新计算
+        let mut packet = buf.to_vec();
+        packet[4..6].copy_from_slice(&ident.to_be_bytes());
+        let timeout = self.sockmeta.lock().opts.send_timeout;
+        with_timeout(IcmpSendFuture::new(&packet, self, remote_addr), timeout).await
+    }
+    async fn recv_msg(&self, buf: &mut [u8]) -> SysResult<(usize, SockAddr)> {
+        info!("[IcmpSocket::recv_msg] start");
+        let timeout = self.sockmeta.lock().opts.recv_timeout;
+        with_timeout(IcmpRecvFuture::new(buf, self), timeout).await
+    }
+    fn set_recv_buf_size(&self, size: u32) -> SysResult<()> {
+        self.sockmeta.lock().recv_buf_size = size as usize;
+        Ok(())
+    }
+    fn set_send_buf_size(&self, size: u32) -> SysResult<()> {
+        self.sockmeta.lock().send_buf_size = size as usize;
+        Ok(())
+    }
+    fn get_recv_buf_size(&self) -> SysResult<usize> {
+        Ok(self.sockmeta.lock().recv_buf_size)
+    }
+    fn get_send_buf_size(&self) -> SysResult<usize> {
+        Ok(self.sockmeta.lock().send_buf_size)
+    }
+    fn shutdown(&self, how: ShutHow) -> SysResult<()> {
+        self.sockmeta.lock().shuthow = Some(how);
+        Ok(())
+    }
+    fn get_sockname(&self) -> SysResult<SockAddr> {
+        let sockmeta = self.sockmeta.lock();
+        let unspecified = match sockmeta.iptype {
+            IpType::Ipv4 => IpAddress::v4(0, 0, 0, 0),
+            IpType::Ipv6 => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0),
+        };
+        let endpoint = match sockmeta.local_end {
+            Some(local_end) => IpEndpoint::new(local_end.addr.unwrap_or(unspecified), local_end.port),
+            None => IpEndpoint::new(unspecified, 0),
+        };
+        Ok(sockmeta.iptype.sockaddr(endpoint))
+    }
+    fn get_peername(&self) -> SysResult<SockAddr> {
+        let sockmeta = self.sockmeta.lock();
+        let remote_end = sockmeta.remote_end.ok_or(Errno::ENOTCONN)?;
+        Ok(sockmeta.iptype.sockaddr(remote_end))
+    }
+    fn get_sockopts(&self) -> SysResult<SockOpts> {
+        Ok(self.sockmeta.lock().opts)
+    }
+    fn set_sockopts(&self, opts: SockOpts) -> SysResult<()> {
+        self.sockmeta.lock().opts = opts;
+        self.with_socket(|socket| socket.set_hop_limit(Some(opts.ttl)));
+        Ok(())
+    }
+    fn take_error(&self) -> SysResult<Option<Errno>> {
+        Ok(self.sockmeta.lock().error.take())
+    }
+    fn get_socktype(&self) -> SysResult<Sock> {
+        Ok(Sock::Icmp)
+    }
+    fn get_family(&self) -> u16 {
+        self.sockmeta.lock().family()
+    }
+    fn set_v6only(&self, v6only: bool) -> SysResult<()> {
+        self.sockmeta.lock().set_v6only(v6only)
+    }
+    fn get_v6only(&self) -> SysResult<bool> {
+        self.sockmeta.lock().get_v6only()
+    }
+    async fn pollin(&self) -> SysResult<bool> {
+        NET_DEV.lock().poll();
+        let waker = get_waker().await;
+        self.with_socket(|socket| {
+            if socket.can_recv() {
+                return Ok(true);
+            }
+            socket.register_recv_waker(&waker);
+            Ok(false)
+        })
+    }
+    async fn pollout(&self) -> SysResult<bool> {
+        NET_DEV.lock().poll();
+        let waker = get_waker().await;
+        self.with_socket(|socket| {
+            if socket.can_send() {
+                return Ok(true);
+            }
+            socket.register_send_waker(&waker);
+            Ok(false)
+        })
+    }
+    fn get_flags(&self) -> SysResult<OpenFlags> {
+        Ok(self.sockmeta.lock().flags)
+    }
+}
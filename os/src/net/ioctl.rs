@@ -0,0 +1,282 @@
+//! 套接字上的网络配置 ioctl，供 ifconfig / route 等工具使用
+//!
+//! 与 Linux 一样，这些请求作用于整个网络栈而不是某个套接字，任何 AF_INET 套接字都可以发起
+
+use core::mem::size_of;
+
+use log::info;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address};
+
+use super::{
+    route::{network_of, Route}, IfConf, IfFlags, IfReq, In6RtMsg, NetDev, NetIfaces, RouteFlags, RtEntry, SockIoctlCmd,
+    AF_INET, AF_INET6, ARPHRD_ETHER, ARPHRD_LOOPBACK, IFNAMSIZ, NET_DEV,
+};
+use crate::{
+    mm::user_ptr::{user_cstr, user_ref, user_ref_mut, user_slice_mut},
+    task::{capable, CapSet},
+    utils::{Errno, SysResult},
+};
+
+/// 处理套接字上的 ioctl，不认识的请求返回 None，由调用者按原来的方式处理。
+///
+/// `family` 是发起请求的套接字的地址族，路由请求的参数格式由它决定
+pub fn sock_ioctl(family: u16, op: usize, arg: usize) -> Option<SysResult<usize>> {
+    let cmd = SockIoctlCmd::try_from(op).ok()?;
+    info!("[sock_ioctl] cmd: {:?}, arg: {:#x}", cmd, arg);
+    Some(match cmd {
+        SockIoctlCmd::SIOCGIFCONF => ifconf(arg),
+        SockIoctlCmd::SIOCADDRT | SockIoctlCmd::SIOCDELRT if family == AF_INET6 => route6_ioctl(cmd, arg),
+        SockIoctlCmd::SIOCADDRT | SockIoctlCmd::SIOCDELRT => route_ioctl(cmd, arg),
+        _ => user_ref_mut::<IfReq>(arg.into())
+            .and_then(|req| req.ok_or(Errno::EFAULT))
+            .and_then(|req| ifreq_ioctl(cmd, req)),
+    })
+}
+
+fn ifreq_ioctl(cmd: SockIoctlCmd, req: &mut IfReq) -> SysResult<usize> {
+    let setter = matches!(
+        cmd,
+        SockIoctlCmd::SIOCSIFFLAGS | SockIoctlCmd::SIOCSIFADDR | SockIoctlCmd::SIOCSIFNETMASK | SockIoctlCmd::SIOCSIFMTU
+    );
+    if setter && !capable(CapSet::CAP_NET_ADMIN) {
+        return Err(Errno::EPERM);
+    }
+
+    let mut ifaces = NET_DEV.lock();
+    if cmd == SockIoctlCmd::SIOCGIFNAME {
+        let ifindex = i32::from_ne_bytes(req.data[..4].try_into().unwrap());
+        let dev = ifaces.get(ifindex as usize).ok_or(Errno::ENODEV)?;
+        req.name = ifname(dev);
+        return Ok(0);
+    }
+
+    let name = core::str::from_utf8(&req.name)
+        .map_err(|_| Errno::EINVAL)?
+        .trim_end_matches('\0');
+    let ifindex = ifaces.get_by_name(name).ok_or(Errno::ENODEV)?.ifindex;
+    let dev = ifaces.get_mut(ifindex).unwrap();
+    match cmd {
+        SockIoctlCmd::SIOCGIFINDEX => write_int(req, ifindex as i32),
+        SockIoctlCmd::SIOCGIFFLAGS => {
+            req.data[..2].copy_from_slice(&if_flags(dev).bits().to_ne_bytes());
+        }
+        SockIoctlCmd::SIOCSIFFLAGS => {
+            let flags = IfFlags::from_bits_truncate(u16::from_ne_bytes([req.data[0], req.data[1]]));
+            dev.up = flags.contains(IfFlags::IFF_UP);
+        }
+        SockIoctlCmd::SIOCGIFMTU => write_int(req, dev.mtu() as i32),
+        SockIoctlCmd::SIOCSIFMTU => {
+            let mtu = i32::from_ne_bytes(req.data[..4].try_into().unwrap());
+            dev.set_mtu(usize::try_from(mtu).map_err(|_| Errno::EINVAL)?)?;
+        }
+        SockIoctlCmd::SIOCGIFHWADDR => {
+            let family = if dev.is_loopback() { ARPHRD_LOOPBACK } else { ARPHRD_ETHER };
+            req.data = [0; 24];
+            req.data[..2].copy_from_slice(&family.to_ne_bytes());
+            req.data[2..8].copy_from_slice(&dev.mac_address());
+        }
+        SockIoctlCmd::SIOCGIFADDR => {
+            let cidr = dev.ipv4_cidr().ok_or(Errno::EADDRNOTAVAIL)?;
+            write_sockaddr_in(req, cidr.address());
+        }
+        SockIoctlCmd::SIOCGIFNETMASK => {
+            let cidr = dev.ipv4_cidr().ok_or(Errno::EADDRNOTAVAIL)?;
+            write_sockaddr_in(req, cidr.netmask());
+        }
+        SockIoctlCmd::SIOCGIFBRDADDR => {
+            let cidr = dev.ipv4_cidr().ok_or(Errno::EADDRNOTAVAIL)?;
+            write_sockaddr_in(req, cidr.broadcast().unwrap_or(cidr.address()));
+        }
+        SockIoctlCmd::SIOCSIFADDR => {
+            let addr = read_sockaddr_in(&req.data)?;
+            let cidr = match dev.ipv4_cidr() {
+                _ if addr.is_unspecified() => None,
+                Some(old) => Some(Ipv4Cidr::new(addr, old.prefix_len())),
+                // 与 Linux 相同，没有掩码时按地址类别给出默认掩码
+                None => Some(Ipv4Cidr::new(addr, classful_prefix(addr))),
+            };
+            ifaces.set_ipv4_cidr(ifindex, cidr)?;
+        }
+        SockIoctlCmd::SIOCSIFNETMASK => {
+            let prefix = mask_prefix(read_sockaddr_in(&req.data)?)?;
+            let old = dev.ipv4_cidr().ok_or(Errno::EADDRNOTAVAIL)?;
+            ifaces.set_ipv4_cidr(ifindex, Some(Ipv4Cidr::new(old.address(), prefix)))?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(0)
+}
+
+/// SIOCGIFCONF：列出所有配置了 IPv4 地址的接口
+fn ifconf(arg: usize) -> SysResult<usize> {
+    let conf = user_ref_mut::<IfConf>(arg.into())?.ok_or(Errno::EFAULT)?;
+    let ifaces = NET_DEV.lock();
+    let reqs = ifaces.iter().filter_map(|dev| {
+        let cidr = dev.ipv4_cidr()?;
+        let mut req = IfReq { name: ifname(dev), data: [0; 24] };
+        write_sockaddr_in(&mut req, cidr.address());
+        Some(req)
+    });
+    if conf.buf == 0 {
+        conf.len = (reqs.count() * size_of::<IfReq>()) as i32;
+        return Ok(0);
+    }
+    let cap = conf.len.max(0) as usize / size_of::<IfReq>();
+    let buf = user_slice_mut::<IfReq>(conf.buf.into(), cap)?.ok_or(Errno::EFAULT)?;
+    let mut count = 0;
+    for (slot, req) in buf.iter_mut().zip(reqs) {
+        *slot = req;
+        count += 1;
+    }
+    conf.len = (count * size_of::<IfReq>()) as i32;
+    Ok(0)
+}
+
+/// SIOCADDRT / SIOCDELRT，IPv4 路由，参数为 struct rtentry
+fn route_ioctl(cmd: SockIoctlCmd, arg: usize) -> SysResult<usize> {
+    if !capable(CapSet::CAP_NET_ADMIN) {
+        return Err(Errno::EPERM);
+    }
+    let rt = *user_ref::<RtEntry>(arg.into())?.ok_or(Errno::EFAULT)?;
+    let flags = RouteFlags::from_bits_truncate(rt.flags);
+    let dst = read_sockaddr_in(&rt.dst)?;
+    let prefix = match flags.contains(RouteFlags::RTF_HOST) {
+        true => 32,
+        false => mask_prefix(read_sockaddr_in(&rt.genmask)?)?,
+    };
+    let dest = network_of(IpCidr::Ipv4(Ipv4Cidr::new(dst, prefix)));
+    let gateway = match flags.contains(RouteFlags::RTF_GATEWAY) {
+        true => Some(IpAddress::Ipv4(read_sockaddr_in(&rt.gateway)?)),
+        false => None,
+    };
+
+    let dev_name = match rt.dev {
+        0 => None,
+        dev => Some(user_cstr(dev.into())?.ok_or(Errno::EFAULT)?),
+    };
+
+    let mut ifaces = NET_DEV.lock();
+    let dev = match dev_name {
+        Some(name) => Some(ifaces.get_by_name(&name).ok_or(Errno::ENODEV)?.ifindex),
+        None => None,
+    };
+    if cmd == SockIoctlCmd::SIOCDELRT {
+        ifaces.del_route(dest, dev)?;
+        return Ok(0);
+    }
+    let ifindex = match (dev, gateway) {
+        (Some(ifindex), _) => ifindex,
+        // 没有指定接口时，使用网关所在直连网段的接口
+        (None, Some(gw)) => connected_ifindex(&ifaces, &gw).ok_or(Errno::ENETUNREACH)?,
+        (None, None) => return Err(Errno::ENODEV),
+    };
+    ifaces.add_route(Route {
+        dest,
+        gateway,
+        ifindex,
+        metric: (rt.metric.max(0) as u32).saturating_sub(1),
+    })?;
+    Ok(0)
+}
+
+/// SIOCADDRT / SIOCDELRT，IPv6 路由，参数为 struct in6_rtmsg
+fn route6_ioctl(cmd: SockIoctlCmd, arg: usize) -> SysResult<usize> {
+    if !capable(CapSet::CAP_NET_ADMIN) {
+        return Err(Errno::EPERM);
+    }
+    let rt = *user_ref::<In6RtMsg>(arg.into())?.ok_or(Errno::EFAULT)?;
+    let flags = RouteFlags::from_bits_truncate(rt.flags as u16);
+    let prefix = match flags.contains(RouteFlags::RTF_HOST) {
+        true => 128,
+        false => rt.dst_len,
+    };
+    if prefix > 128 {
+        return Err(Errno::EINVAL);
+    }
+    let dest = network_of(IpCidr::new(IpAddress::Ipv6(Ipv6Address::from(rt.dst)), prefix as u8));
+    let gateway = match flags.contains(RouteFlags::RTF_GATEWAY) {
+        true => Some(IpAddress::Ipv6(Ipv6Address::from(rt.gateway))),
+        false => None,
+    };
+
+    let mut ifaces = NET_DEV.lock();
+    let dev = match rt.ifindex {
+        0 => None,
+        ifindex => Some(ifaces.get(ifindex as usize).ok_or(Errno::ENODEV)?.ifindex),
+    };
+    if cmd == SockIoctlCmd::SIOCDELRT {
+        ifaces.del_route(dest, dev)?;
+        return Ok(0);
+    }
+    let ifindex = match (dev, gateway) {
+        (Some(ifindex), _) => ifindex,
+        (None, Some(gw)) => connected_ifindex(&ifaces, &gw).ok_or(Errno::ENETUNREACH)?,
+        (None, None) => return Err(Errno::ENODEV),
+    };
+    ifaces.add_route(Route { dest, gateway, ifindex, metric: rt.metric })?;
+    Ok(0)
+}
+
+fn connected_ifindex(ifaces: &NetIfaces, addr: &IpAddress) -> Option<usize> {
+    ifaces
+        .routes
+        .routes()
+        .iter()
+        .filter(|r| r.gateway.is_none() && r.dest.contains_addr(addr))
+        .max_by_key(|r| r.dest.prefix_len())
+        .map(|r| r.ifindex)
+}
+
+fn if_flags(dev: &NetDev) -> IfFlags {
+    let mut flags = match dev.is_loopback() {
+        true => IfFlags::IFF_LOOPBACK,
+        false => IfFlags::IFF_BROADCAST | IfFlags::IFF_MULTICAST,
+    };
+    if dev.up {
+        flags |= IfFlags::IFF_UP | IfFlags::IFF_RUNNING;
+    }
+    flags
+}
+
+fn ifname(dev: &NetDev) -> [u8; IFNAMSIZ] {
+    let mut name = [0u8; IFNAMSIZ];
+    let len = dev.name.len().min(IFNAMSIZ - 1);
+    name[..len].copy_from_slice(&dev.name.as_bytes()[..len]);
+    name
+}
+
+fn write_int(req: &mut IfReq, value: i32) {
+    req.data[..4].copy_from_slice(&value.to_ne_bytes());
+}
+
+/// 把地址以 sockaddr_in 的形式写入 ifreq 的联合体
+fn write_sockaddr_in(req: &mut IfReq, addr: Ipv4Address) {
+    req.data = [0; 24];
+    req.data[..2].copy_from_slice(&AF_INET.to_ne_bytes());
+    req.data[4..8].copy_from_slice(&addr.octets());
+}
+
+fn read_sockaddr_in(data: &[u8]) -> SysResult<Ipv4Address> {
+    let family = u16::from_ne_bytes([data[0], data[1]]);
+    if family != AF_INET {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+    Ok(Ipv4Address::new(data[4], data[5], data[6], data[7]))
+}
+
+/// 子网掩码转前缀长度，掩码必须是连续的 1
+fn mask_prefix(mask: Ipv4Address) -> SysResult<u8> {
+    let bits = u32::from_be_bytes(mask.octets());
+    if bits.leading_ones() != bits.count_ones() {
+        return Err(Errno::EINVAL);
+    }
+    Ok(bits.count_ones() as u8)
+}
+
+fn classful_prefix(addr: Ipv4Address) -> u8 {
+    match addr.octets()[0] {
+        0..=127 => 8,
+        128..=191 => 16,
+        _ => 24,
+    }
+}
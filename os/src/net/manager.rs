@@ -24,12 +24,45 @@ pub const PORT_START: u16 = 49152;
 pub const PORT_END: u16 = 65535;
 pub const PORT_RANGE: u32 = PORT_END as u32 - PORT_START as u32 + 1;
 
+/// 端口复用选项，对应 SO_REUSEADDR / SO_REUSEPORT
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PortReuse {
+    pub addr: bool,
+    pub port: bool,
+}
+
+impl PortReuse {
+    /// 双方设置了同一个复用选项时才能共用端口
+    fn compatible(&self, other: &PortReuse) -> bool {
+        (self.addr && other.addr) || (self.port && other.port)
+    }
+
+    fn intersect(&self, other: &PortReuse) -> PortReuse {
+        PortReuse {
+            addr: self.addr && other.addr,
+            port: self.port && other.port,
+        }
+    }
+}
+
+/// 一个已被占用的端口：持有它的套接字数，以及所有持有者都设置了的复用选项
+#[derive(Debug, Clone, Copy)]
+struct PortUsers {
+    count: usize,
+    reuse: PortReuse,
+}
+
 pub struct PortManager {
     pub start: u16,
     pub end: u16,
     pub recycled: VecDeque<u16>,
     pub tcp_used_ports: BitVec,
     pub udp_used_ports: BitVec,
+    /// ICMP 回显套接字的标识符与端口共用同一套分配方式
+    pub icmp_used_ports: BitVec,
+    tcp_users: HashMap<u16, PortUsers>,
+    udp_users: HashMap<u16, PortUsers>,
+    icmp_users: HashMap<u16, PortUsers>,
 }
 
 impl PortManager {
@@ -40,9 +73,46 @@ impl PortManager {
             recycled: VecDeque::new(),
             tcp_used_ports: BitVec::from_elem(65536, false),
             udp_used_ports: BitVec::from_elem(65536, false),
+            icmp_used_ports: BitVec::from_elem(65536, false),
+            tcp_users: HashMap::new(),
+            udp_users: HashMap::new(),
+            icmp_users: HashMap::new(),
+        }
+    }
+    fn users_mut(&mut self, domain: &Sock) -> Option<&mut HashMap<u16, PortUsers>> {
+        match domain {
+            Sock::Tcp => Some(&mut self.tcp_users),
+            Sock::Udp => Some(&mut self.udp_users),
+            Sock::Icmp => Some(&mut self.icmp_users),
+            _ => None,
+        }
+    }
+    /// 占用指定端口。端口已被占用时，只有已有持有者和新套接字都设置了同一个复用选项才能共用
+    fn try_share(&mut self, domain: &Sock, port: u16, reuse: PortReuse) -> bool {
+        if self.try_mark_used(domain, port) {
+            if let Some(users) = self.users_mut(domain) {
+                users.insert(port, PortUsers { count: 1, reuse });
+            }
+            return true;
         }
+        let Some(users) = self.users_mut(domain).and_then(|users| users.get_mut(&port)) else {
+            return false;
+        };
+        if !users.reuse.compatible(&reuse) {
+            return false;
+        }
+        users.count += 1;
+        users.reuse = users.reuse.intersect(&reuse);
+        true
     }
-    fn alloc(&mut self, domain: Sock) -> SysResult<u16> {
+    fn alloc(&mut self, domain: Sock, reuse: PortReuse) -> SysResult<u16> {
+        let port = self.alloc_unused(&domain)?;
+        if let Some(users) = self.users_mut(&domain) {
+            users.insert(port, PortUsers { count: 1, reuse });
+        }
+        Ok(port)
+    }
+    fn alloc_unused(&mut self, domain: &Sock) -> SysResult<u16> {
         // if let Some(port) = self.recycled.pop_front() {
         //     info!("[port alloc] recycled port: {}", port);
         //     self.mark_used(domain, port);
@@ -52,13 +122,13 @@ impl PortManager {
         let chance = self.end - self.start - self.recycled.len() as u16;
         for _ in 0..chance {
             let random_port = self.start + (RNG.lock().next() % PORT_RANGE as u32) as u16;
-            if self.try_mark_used(&domain, random_port) {
+            if self.try_mark_used(domain, random_port) {
                 return Ok(random_port);
             }
         }
 
         for port in self.start..=self.end {
-            if self.try_mark_used(&domain, port) {
+            if self.try_mark_used(domain, port) {
                 return Ok(port);
             }
         }
@@ -66,6 +136,19 @@ impl PortManager {
     }
     pub fn dealloc(&mut self, domain: Sock, port: u16) {
         info!("[port dealloc] port: {}", port);
+        // 还有其他套接字复用这个端口时只减少计数
+        if let Some(users) = self.users_mut(&domain) {
+            match users.get_mut(&port) {
+                Some(user) if user.count > 1 => {
+                    user.count -= 1;
+                    return;
+                }
+                Some(_) => {
+                    users.remove(&port);
+                }
+                None => {}
+            }
+        }
         // assert!(
         //     port >= self.start && port <= self.end,
         //     "port {} is out of range",
@@ -84,6 +167,9 @@ impl PortManager {
             Sock::Udp => {
                 self.udp_used_ports.set(port as usize, false);
             }
+            Sock::Icmp => {
+                self.icmp_used_ports.set(port as usize, false);
+            }
             _ => {}
         }
     }
@@ -98,6 +184,9 @@ impl PortManager {
             Sock::Udp => {
                 self.udp_used_ports.set(port as usize, true);
             }
+            Sock::Icmp => {
+                self.icmp_used_ports.set(port as usize, true);
+            }
             _ => {}
         }
     }
@@ -121,6 +210,12 @@ impl PortManager {
                     return true;
                 }
             }
+            Sock::Icmp => {
+                if !self.icmp_used_ports[port as usize] {
+                    self.icmp_used_ports.set(port as usize, true);
+                    return true;
+                }
+            }
             _ => {}
         }
         false
@@ -128,16 +223,18 @@ impl PortManager {
 }
 
 /// 检查传入的endpoint的port，分配port，并返回
-pub fn do_port_aloc(endpoint: &mut IpEndpoint, port_type: Sock) -> SysResult<u16> {
+///
+/// `reuse` 是套接字上的 SO_REUSEADDR / SO_REUSEPORT，决定能否与其他套接字共用指定的端口
+pub fn do_port_aloc(endpoint: &mut IpEndpoint, port_type: Sock, reuse: PortReuse) -> SysResult<u16> {
     let p: u16;
     if endpoint.port == 0 {
-        p = PORT_MANAGER.lock().alloc(port_type)?;
+        p = PORT_MANAGER.lock().alloc(port_type, reuse)?;
         endpoint.port = p;
     } else {
         let mut port_manager = PORT_MANAGER.lock();
         p = endpoint.port;
         // 标记已使用
-        if !port_manager.try_mark_used(&port_type, p) {
+        if !port_manager.try_share(&port_type, p, reuse) {
             drop(port_manager);
             info!("[do_port] port = {} is in use", p);
             return Err(Errno::EADDRINUSE);
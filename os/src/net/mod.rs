@@ -1,18 +1,32 @@
 pub mod addr;
 pub mod dev;
+mod dhcp;
+mod icmp;
 pub mod ffi;
+mod ioctl;
 mod net_async;
 mod manager;
+mod poll;
+#[cfg(feature = "net_pcap")]
+pub mod pcap;
+mod raw;
+pub mod route;
 pub mod socket;
+mod stat;
 pub mod tcp;
 mod udp;
 mod unix;
 
 pub use dev::*;
+pub use dhcp::dhcp_task;
 pub use ffi::*;
+pub use ioctl::sock_ioctl;
 use net_async::*;
 pub use manager::*;
+pub use poll::net_poll_task;
+pub use route::*;
 pub use socket::*;
+pub use stat::*;
 pub use tcp::*;
 
 use crate::fs::FileTrait;
@@ -20,6 +34,8 @@ use alloc::sync::Arc;
 pub enum SockClass {
     Tcp(Arc<tcp::TcpSocket>),
     Udp(Arc<udp::UdpSocket>),
+    Icmp(Arc<icmp::IcmpSocket>),
+    Raw(Arc<raw::RawSocket>),
     Unix(Arc<unix::UnixSocket>),
     Unspec(),
 }
@@ -29,6 +45,8 @@ impl SockClass {
         match self {
             SockClass::Tcp(tcp) => tcp.clone(),
             SockClass::Udp(udp) => udp.clone(),
+            SockClass::Icmp(icmp) => icmp.clone(),
+            SockClass::Raw(raw) => raw.clone(),
             SockClass::Unix(unix) => unix.clone(),
             SockClass::Unspec() => unreachable!(),
         }
@@ -1,14 +1,16 @@
-use super::{tcp::TcpSocket, udp::UdpSocket, TcpState, NET_DEV};
+use super::{addr::IpType, icmp::{self as icmp_sock, IcmpSocket}, raw::RawSocket, tcp::TcpSocket, udp::UdpSocket, TcpState, NET_DEV};
 use crate::{
     console::print, fs::OpenFlags, net::{addr::SockAddr, Socket, MAX_BUFFER_SIZE, SOCKET_SET}, signal::{SigMask, SigNom}, sync::yield_now, task::current_task, utils::{Errno, SysResult}
 };
 use core::{future::Future, task::Poll};
 use log::info;
 use smoltcp::{
+    iface::SocketHandle,
     socket::{
+        icmp, raw,
         tcp::{self},
         udp::{self, UdpMetadata},
-    }, wire::IpEndpoint}
+    }, wire::{IpAddress, IpEndpoint, Ipv4Packet, Ipv6Packet}}
 ;
 
 pub struct TcpAcceptFuture<'a> {
@@ -23,7 +25,8 @@ impl<'a> TcpAcceptFuture<'a> {
 }
 
 impl<'a> Future for TcpAcceptFuture<'a> {
-    type Output = SysResult<IpEndpoint>;
+    /// 已完成握手、从等待队列中取出的连接
+    type Output = SysResult<SocketHandle>;
 
     fn poll(
         self: core::pin::Pin<&mut Self>,
@@ -31,40 +34,24 @@ impl<'a> Future for TcpAcceptFuture<'a> {
     ) -> core::task::Poll<Self::Output> {
         NET_DEV.lock().poll();
 
-        let mut binding = SOCKET_SET.lock();
-        let socket = binding.get_mut::<tcp::Socket>(self.socket.handle);
-        let cur_state = socket.state();
-        match cur_state {
-            TcpState::Closed => return Poll::Ready(Err(Errno::EINVAL)),
-            TcpState::TimeWait => return Poll::Ready(Err(Errno::EINVAL)),
-            TcpState::Established | TcpState::SynReceived => {
-                // 代表已经建立好链接，此时服务器知道了远端链接的地址，可以返回远端
-                self.socket.set_state(cur_state);
-                let remote_end = socket
-                    .remote_endpoint()
-                    .expect("[tcpacceptFuture] poll fail: remote is none.");
-                return Poll::Ready(Ok(remote_end));
-            }
-            _ => {
-                // The socket is marked nonblocking and no connections are present to be accepted.
-                if self.socket.get_flags()?.contains(OpenFlags::O_NONBLOCK) {
-                    return Poll::Ready(Err(Errno::EAGAIN));
-                }
-                info!("[TcpAcceptFuture] now state is {:?}", socket.state());
-                // 注册waker，当socket状态改变时会重新唤醒任务执行，执行poll，直到返回Ready
-                socket.register_recv_waker(cx.waker());
-                drop(binding);
-                NET_DEV.lock().poll();
-                cx.waker().clone().wake();
+        if let Some(handle) = self.socket.take_established()? {
+            return Poll::Ready(Ok(handle));
+        }
+        // The socket is marked nonblocking and no connections are present to be accepted.
+        if self.socket.get_flags()?.contains(OpenFlags::O_NONBLOCK) {
+            return Poll::Ready(Err(Errno::EAGAIN));
+        }
+        // 注册waker，当等待队列中任一套接字状态改变时会重新唤醒任务执行，执行poll，直到返回Ready
+        self.socket.register_accept_waker(cx.waker());
+        NET_DEV.lock().poll();
+        cx.waker().clone().wake();
 
-                let task = current_task().unwrap();
-                let sig_pending = task.sig_pending.lock();
-                if sig_pending.has_expected(SigMask::SIGALRM).0 {
-                    return Poll::Ready(Err(Errno::EINTR));
-                }
-                return Poll::Pending;
-            }
+        let task = current_task().unwrap();
+        let sig_pending = task.sig_pending.lock();
+        if sig_pending.has_expected(SigMask::SIGALRM).0 {
+            return Poll::Ready(Err(Errno::EINTR));
         }
+        Poll::Pending
     }
 }
 
@@ -190,6 +177,7 @@ impl<'a> Future for TcpRecvFuture<'a> {
         cx: &mut core::task::Context<'_>,
     ) -> Poll<Self::Output> {
         NET_DEV.lock().poll();
+        let iptype = self.tcpsocket.sockmeta.lock().iptype;
         let mut binding = SOCKET_SET.lock();
         let socket = binding.get_mut::<tcp::Socket>(self.tcpsocket.handle);
         
@@ -214,7 +202,7 @@ impl<'a> Future for TcpRecvFuture<'a> {
                     info!("[TcpRecvFuture] success recv msg, remote end is {:?}", remote_end);
                     drop(binding);
                     NET_DEV.lock().poll();
-                    return Poll::Ready(Ok((size, remote_end.into())));
+                    return Poll::Ready(Ok((size, iptype.sockaddr(remote_end))));
                 }
                 socket.register_recv_waker(cx.waker());
                 drop(binding);
@@ -257,9 +245,21 @@ impl<'a> Future for UdpRecvFuture<'a> {
     )-> Poll<Self::Output> {
         NET_DEV.lock().poll();
 
+        let (iptype, v6only) = {
+            let sockmeta = self.udpsocket.sockmeta.lock();
+            (sockmeta.iptype, sockmeta.v6only)
+        };
         let mut binding = SOCKET_SET.lock();
         let socket = binding.get_mut::<udp::Socket>(self.udpsocket.handle);
 
+        // 绑定在未指定地址上的套接字会收到两种地址族的报文，丢弃本套接字不接受的那些
+        while let Ok((_, metadata)) = socket.peek() {
+            if iptype.accepts(v6only, &metadata.endpoint.addr) {
+                break;
+            }
+            let _ = socket.recv();
+        }
+
         if !socket.can_recv() {
             let flags = self.udpsocket.get_flags()?;
             if flags.contains(OpenFlags::O_NONBLOCK) {
@@ -279,10 +279,201 @@ impl<'a> Future for UdpRecvFuture<'a> {
             }
             drop(binding);
             NET_DEV.lock().poll();
-            return Poll::Ready(Ok((size, metadata.endpoint.into())));
+            return Poll::Ready(Ok((size, iptype.sockaddr(metadata.endpoint))));
         } else {
             return Poll::Ready(Err(Errno::ENOTCONN));
         }
 
     }
-}
\ No newline at end of file
+}
+pub struct IcmpSendFuture<'a> {
+    pub packet: &'a [u8],
+    pub icmpsocket: &'a IcmpSocket,
+    pub remote_addr: IpAddress,
+}
+
+impl<'a> IcmpSendFuture<'a> {
+    pub fn new(packet: &'a [u8], icmpsocket: &'a IcmpSocket, remote_addr: IpAddress) -> Self {
+        Self { packet, icmpsocket, remote_addr }
+    }
+}
+
+impl<'a> Future for IcmpSendFuture<'a> {
+    type Output = SysResult<usize>;
+
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        NET_DEV.lock().poll();
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<icmp::Socket>(self.icmpsocket.handle);
+
+        if !socket.can_send() {
+            if self.icmpsocket.get_flags()?.contains(OpenFlags::O_NONBLOCK) {
+                return Poll::Ready(Err(Errno::EAGAIN));
+            }
+            socket.register_send_waker(cx.waker());
+            drop(binding);
+            cx.waker().clone().wake();
+            return Poll::Pending;
+        }
+        match socket.send_slice(self.packet, self.remote_addr) {
+            Ok(_) => {
+                drop(binding);
+                NET_DEV.lock().poll();
+                Poll::Ready(Ok(self.packet.len()))
+            }
+            Err(icmp::SendError::Unaddressable) => Poll::Ready(Err(Errno::EHOSTUNREACH)),
+            Err(icmp::SendError::BufferFull) => Poll::Ready(Err(Errno::ENOBUFS)),
+        }
+    }
+}
+
+pub struct IcmpRecvFuture<'a> {
+    pub msg_buf: &'a mut [u8],
+    pub icmpsocket: &'a IcmpSocket,
+}
+
+impl<'a> IcmpRecvFuture<'a> {
+    pub fn new(msg_buf: &'a mut [u8], icmpsocket: &'a IcmpSocket) -> Self {
+        Self { msg_buf, icmpsocket }
+    }
+}
+
+impl<'a> Future for IcmpRecvFuture<'a> {
+    type Output = SysResult<(usize, SockAddr)>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        NET_DEV.lock().poll();
+        let iptype = self.icmpsocket.sockmeta.lock().iptype;
+        let (_, echo_reply) = icmp_sock::echo_types(iptype);
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<icmp::Socket>(self.icmpsocket.handle);
+
+        // smoltcp 按标识符把回显请求也交给套接字（例如 ping 回环地址时自己发出的请求），
+        // 这里只把回显应答交给用户
+        while let Ok((data, addr)) = socket.recv() {
+            if data.first() != Some(&echo_reply) {
+                continue;
+            }
+            let size = data.len().min(self.msg_buf.len());
+            self.msg_buf[..size].copy_from_slice(&data[..size]);
+            drop(binding);
+            NET_DEV.lock().poll();
+            return Poll::Ready(Ok((size, iptype.sockaddr(IpEndpoint::new(addr, 0)))));
+        }
+
+        let flags = self.icmpsocket.get_flags()?;
+        if flags.contains(OpenFlags::O_NONBLOCK) {
+            return Poll::Ready(Err(Errno::EAGAIN));
+        }
+        socket.register_recv_waker(cx.waker());
+        drop(binding);
+        NET_DEV.lock().poll();
+        cx.waker().clone().wake();
+        Poll::Pending
+    }
+}
+
+pub struct RawSendFuture<'a> {
+    pub packet: &'a [u8],
+    pub rawsocket: &'a RawSocket,
+    /// 不含 IP 首部的长度，即返回给用户的发送字节数
+    pub payload_len: usize,
+}
+
+impl<'a> RawSendFuture<'a> {
+    pub fn new(packet: &'a [u8], rawsocket: &'a RawSocket, payload_len: usize) -> Self {
+        Self { packet, rawsocket, payload_len }
+    }
+}
+
+impl<'a> Future for RawSendFuture<'a> {
+    type Output = SysResult<usize>;
+
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        NET_DEV.lock().poll();
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<raw::Socket>(self.rawsocket.handle);
+
+        if !socket.can_send() {
+            if self.rawsocket.get_flags()?.contains(OpenFlags::O_NONBLOCK) {
+                return Poll::Ready(Err(Errno::EAGAIN));
+            }
+            socket.register_send_waker(cx.waker());
+            drop(binding);
+            cx.waker().clone().wake();
+            return Poll::Pending;
+        }
+        match socket.send_slice(self.packet) {
+            Ok(_) => {
+                drop(binding);
+                NET_DEV.lock().poll();
+                Poll::Ready(Ok(self.payload_len))
+            }
+            Err(_) => Poll::Ready(Err(Errno::ENOBUFS)),
+        }
+    }
+}
+
+pub struct RawRecvFuture<'a> {
+    pub msg_buf: &'a mut [u8],
+    pub rawsocket: &'a RawSocket,
+}
+
+impl<'a> RawRecvFuture<'a> {
+    pub fn new(msg_buf: &'a mut [u8], rawsocket: &'a RawSocket) -> Self {
+        Self { msg_buf, rawsocket }
+    }
+}
+
+impl<'a> Future for RawRecvFuture<'a> {
+    type Output = SysResult<(usize, SockAddr)>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        NET_DEV.lock().poll();
+        let iptype = self.rawsocket.sockmeta.lock().iptype;
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<raw::Socket>(self.rawsocket.handle);
+
+        // smoltcp 交上来的是完整的 IP 报文。与 Linux 一样，IPv4 原始套接字连同首部一起交给用户，
+        // IPv6 原始套接字只交出首部之后的部分
+        while let Ok(packet) = socket.recv() {
+            let (src, payload) = match iptype {
+                IpType::Ipv4 => match Ipv4Packet::new_checked(packet) {
+                    Ok(ip) => (IpAddress::Ipv4(ip.src_addr()), packet),
+                    Err(_) => continue,
+                },
+                IpType::Ipv6 => match Ipv6Packet::new_checked(packet) {
+                    Ok(ip) => (IpAddress::Ipv6(ip.src_addr()), &packet[ip.header_len()..]),
+                    Err(_) => continue,
+                },
+            };
+            let size = payload.len().min(self.msg_buf.len());
+            self.msg_buf[..size].copy_from_slice(&payload[..size]);
+            drop(binding);
+            NET_DEV.lock().poll();
+            return Poll::Ready(Ok((size, iptype.sockaddr(IpEndpoint::new(src, 0)))));
+        }
+
+        let flags = self.rawsocket.get_flags()?;
+        if flags.contains(OpenFlags::O_NONBLOCK) {
+            return Poll::Ready(Err(Errno::EAGAIN));
+        }
+        socket.register_recv_waker(cx.waker());
+        drop(binding);
+        NET_DEV.lock().poll();
+        cx.waker().clone().wake();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,103 @@
+//! 网络抓包：记录所有接口收发的以太网帧，通过 /dev/netcap 以 pcap 格式读出。
+//!
+//! 只在打开 `net_pcap` feature 时编译。可以在 QEMU 里 `cat /dev/netcap > /tmp/trace.pcap`
+//! 之后把文件取出来用 Wireshark 打开。读取会取走已经读到的记录，缓冲区满时丢弃最早的记录
+
+use crate::{
+    hal::config::MB,
+    sync::{time::CLOCK_REALTIME, time_duration, SpinNoIrqLock, CLOCK_MANAGER},
+};
+use alloc::{collections::VecDeque, vec::Vec};
+
+/// pcap 文件头的长度
+pub const PCAP_HEADER_LEN: usize = 24;
+/// 缓冲的记录总长度上限
+const CAPTURE_LIMIT: usize = 4 * MB;
+/// 每条记录最多保存的帧长度
+const SNAPLEN: u32 = 65535;
+/// LINKTYPE_ETHERNET，回环接口的帧也带以太网帧头
+const LINKTYPE_ETHERNET: u32 = 1;
+
+lazy_static! {
+    pub static ref NET_CAPTURE: SpinNoIrqLock<PacketCapture> = SpinNoIrqLock::new(PacketCapture::new());
+}
+
+/// 还没有被读走的抓包记录，每条记录都已经带上了 pcap 记录头
+pub struct PacketCapture {
+    records: VecDeque<Vec<u8>>,
+    /// 队首记录中已经被读走的字节数
+    front_pos: usize,
+    bytes: usize,
+    /// 因缓冲区满而丢弃的记录数
+    pub dropped: usize,
+}
+
+impl PacketCapture {
+    fn new() -> Self {
+        Self { records: VecDeque::new(), front_pos: 0, bytes: 0, dropped: 0 }
+    }
+
+    fn push(&mut self, frame: &[u8]) {
+        let now = CLOCK_MANAGER.lock()[CLOCK_REALTIME] + time_duration();
+        let incl_len = frame.len().min(SNAPLEN as usize);
+        let mut record = Vec::with_capacity(16 + incl_len);
+        record.extend_from_slice(&(now.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&now.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(incl_len as u32).to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(&frame[..incl_len]);
+
+        self.bytes += record.len();
+        self.records.push_back(record);
+        while self.bytes > CAPTURE_LIMIT {
+            // 读了一半的队首记录要留着，否则读者拿到的流会从记录中间断开
+            let victim = match self.front_pos {
+                0 => 0,
+                _ => 1,
+            };
+            let Some(old) = self.records.remove(victim) else { break };
+            self.bytes -= old.len();
+            self.dropped += 1;
+        }
+    }
+
+    /// 从偏移 `offset` 处读取：前 24 字节是文件头，之后依次取走缓冲的记录，没有记录时返回 0
+    pub fn read(&mut self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+        if offset < PCAP_HEADER_LEN {
+            let header = pcap_header();
+            n = (PCAP_HEADER_LEN - offset).min(buf.len());
+            buf[..n].copy_from_slice(&header[offset..offset + n]);
+        }
+        while n < buf.len() {
+            let Some(record) = self.records.front() else { break };
+            let len = (record.len() - self.front_pos).min(buf.len() - n);
+            buf[n..n + len].copy_from_slice(&record[self.front_pos..self.front_pos + len]);
+            n += len;
+            self.front_pos += len;
+            if self.front_pos == record.len() {
+                self.bytes -= record.len();
+                self.records.pop_front();
+                self.front_pos = 0;
+            }
+        }
+        n
+    }
+}
+
+/// pcap 文件头：小端、微秒时间戳、版本 2.4
+fn pcap_header() -> [u8; PCAP_HEADER_LEN] {
+    let mut header = [0u8; PCAP_HEADER_LEN];
+    header[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    header[4..6].copy_from_slice(&2u16.to_le_bytes());
+    header[6..8].copy_from_slice(&4u16.to_le_bytes());
+    // thiszone 与 sigfigs 为 0
+    header[16..20].copy_from_slice(&SNAPLEN.to_le_bytes());
+    header[20..24].copy_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+    header
+}
+
+/// 记录一帧，由各个 phy 在真正收发时调用
+pub fn capture(frame: &[u8]) {
+    NET_CAPTURE.lock().push(frame);
+}
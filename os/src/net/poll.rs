@@ -0,0 +1,43 @@
+//! 内核网络轮询任务
+//!
+//! 网卡中断只把帧搬进驱动的接收队列，协议处理要等网络栈被轮询。
+//! 这个任务在收包中断后轮询所有接口，smoltcp 处理完收到的包后会唤醒等在对应套接字上的任务。
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use alloc::{sync::Arc, vec::Vec};
+use log::info;
+
+use super::NET_DEV;
+use crate::drivers::{device::NetDevice, DEVICE_MANAGER};
+
+struct NetPollFuture {
+    cards: Vec<Arc<dyn NetDevice>>,
+}
+
+impl Future for NetPollFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 先注册再轮询，轮询之后才到的帧会再次唤醒
+        let mut has_irq = false;
+        for card in self.cards.iter() {
+            has_irq |= card.register_rx_waker(cx.waker());
+        }
+        NET_DEV.lock().poll();
+        match has_irq {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    }
+}
+
+pub async fn net_poll_task() {
+    let cards = DEVICE_MANAGER.read().net_devs();
+    NetPollFuture { cards }.await;
+    info!("[net_poll_task] no interface has a receive interrupt, exit");
+}
@@ -0,0 +1,283 @@
+use super::{
+    addr::{IpType, Sock, SockAddr},
+    with_timeout, SockMeta, SockOpts, Socket, BUFF_SIZE, META_SIZE, NET_DEV, SOCKET_SET,
+};
+use crate::{
+    fs::OpenFlags,
+    net::net_async::{RawRecvFuture, RawSendFuture},
+    sync::{get_waker, SpinNoIrqLock},
+    syscall::ShutHow,
+    utils::{Errno, SysResult},
+};
+use alloc::boxed::Box;
+use alloc::{vec, vec::Vec};
+use async_trait::async_trait;
+use core::net::Ipv6Addr;
+use log::info;
+use smoltcp::{
+    iface::SocketHandle,
+    phy::ChecksumCapabilities,
+    socket::raw::{self, PacketBuffer, PacketMetadata},
+    wire::{IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpVersion, Ipv4Packet, Ipv4Repr, Ipv6Packet, Ipv6Repr},
+};
+
+/// ICMPv6 首部中校验和的偏移
+const ICMPV6_CHECKSUM_OFFSET: usize = 2;
+
+/// 原始 IP 套接字（SOCK_RAW），创建时需要 CAP_NET_RAW。
+///
+/// 发送时用户只给出 IP 首部之后的部分，首部由内核按目的地址和 IP_TTL 构造；
+/// 接收时交给用户的内容与 Linux 相同：IPv4 含 IP 首部，IPv6 不含
+pub struct RawSocket {
+    pub handle: SocketHandle,
+    pub sockmeta: SpinNoIrqLock<SockMeta>,
+    /// 创建时指定的 IP 协议号，只收发这个协议的报文
+    protocol: IpProtocol,
+}
+
+impl Drop for RawSocket {
+    fn drop(&mut self) {
+        info!("[RawSocket::drop] start");
+        let mut binding = SOCKET_SET.lock();
+        let sock = binding.remove(self.handle);
+        drop(sock);
+    }
+}
+
+impl RawSocket {
+    pub fn new(iptype: IpType, protocol: u8, flags: OpenFlags) -> Self {
+        let version = match iptype {
+            IpType::Ipv4 => IpVersion::Ipv4,
+            IpType::Ipv6 => IpVersion::Ipv6,
+        };
+        let protocol = IpProtocol::from(protocol);
+        let recv_buf =
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; META_SIZE], vec![0; BUFF_SIZE]);
+        let send_buf =
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; META_SIZE], vec![0; BUFF_SIZE]);
+        let socket = raw::Socket::new(Some(version), Some(protocol), recv_buf, send_buf);
+        let handle = SOCKET_SET.lock().add(socket);
+        let sockmeta = SockMeta::new(Sock::Raw, iptype, BUFF_SIZE, BUFF_SIZE, OpenFlags::O_RDWR | flags);
+        Self {
+            handle,
+            sockmeta: SpinNoIrqLock::new(sockmeta),
+            protocol,
+        }
+    }
+
+    pub fn with_socket<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut raw::Socket<'_>) -> R,
+    {
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<raw::Socket>(self.handle);
+        f(socket)
+    }
+
+    /// 原始套接字只收发本地址族的报文，不接受 v4-mapped 地址
+    fn parse_addr(&self, addr: &SockAddr) -> SysResult<IpAddress> {
+        let iptype = self.sockmeta.lock().iptype;
+        let endpoint = IpEndpoint::try_from(*addr)?;
+        if !iptype.accepts(true, &endpoint.addr) {
+            return Err(Errno::EAFNOSUPPORT);
+        }
+        Ok(endpoint.addr)
+    }
+
+    /// 在 `payload` 前面加上 IP 首部，得到交给 smoltcp 的完整报文
+    fn build_packet(&self, dst: IpAddress, payload: &[u8]) -> SysResult<Vec<u8>> {
+        let (bound, ttl) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.local_end.and_then(|end| end.addr), sockmeta.opts.ttl)
+        };
+        let src = match bound {
+            Some(addr) => addr,
+            None => NET_DEV.lock().source_addr_for(&dst).ok_or(Errno::ENETUNREACH)?,
+        };
+        match (src, dst) {
+            (IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr)) => {
+                let repr = Ipv4Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: self.protocol,
+                    payload_len: payload.len(),
+                    hop_limit: ttl,
+                };
+                let header_len = repr.buffer_len();
+                let mut packet = vec![0u8; header_len + payload.len()];
+                repr.emit(&mut Ipv4Packet::new_unchecked(&mut packet[..]), &ChecksumCapabilities::default());
+                packet[header_len..].copy_from_slice(payload);
+                Ok(packet)
+            }
+            (IpAddress::Ipv6(src_addr), IpAddress::Ipv6(dst_addr)) => {
+                let repr = Ipv6Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: self.protocol,
+                    payload_len: payload.len(),
+                    hop_limit: ttl,
+                };
+                let header_len = repr.buffer_len();
+                let mut packet = vec![0u8; header_len + payload.len()];
+                repr.emit(&mut Ipv6Packet::new_unchecked(&mut packet[..]));
+                packet[header_len..].copy_from_slice(payload);
+                // 与 Linux 相同，ICMPv6 原始套接字的校验和总是由内核计算
+                if self.protocol == IpProtocol::Icmpv6 && payload.len() >= ICMPV6_CHECKSUM_OFFSET + 2 {
+                    let msg = &mut packet[header_len..];
+                    msg[ICMPV6_CHECKSUM_OFFSET..ICMPV6_CHECKSUM_OFFSET + 2].fill(0);
+                    let checksum = icmpv6_checksum(&src_addr, &dst_addr, msg);
+                    msg[ICMPV6_CHECKSUM_OFFSET..ICMPV6_CHECKSUM_OFFSET + 2].copy_from_slice(&checksum.to_be_bytes());
+                }
+                Ok(packet)
+            }
+            _ => Err(Errno::EAFNOSUPPORT),
+        }
+    }
+}
+
+/// 按 RFC 4443 计算 ICMPv6 校验和，伪首部包含源地址、目的地址、报文长度和下一首部
+fn icmpv6_checksum(src: &Ipv6Addr, dst: &Ipv6Addr, msg: &[u8]) -> u16 {
+    fn sum_words(mut sum: u32, bytes: &[u8]) -> u32 {
+        for chunk in bytes.chunks(2) {
+            sum += u16::from_be_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]) as u32;
+        }
+        sum
+    }
+    let mut sum = sum_words(0, &src.octets());
+    sum = sum_words(sum, &dst.octets());
+    sum = sum_words(sum, &(msg.len() as u32).to_be_bytes());
+    sum = sum_words(sum, &[0, 0, 0, u8::from(IpProtocol::Icmpv6)]);
+    sum = sum_words(sum, msg);
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[async_trait]
+impl Socket for RawSocket {
+    async fn accept(&self, _sockfd: usize, _flags: OpenFlags) -> SysResult<(IpEndpoint, usize)> {
+        Err(Errno::EOPNOTSUPP)
+    }
+    fn bind(&self, _sockfd: usize, addr: &SockAddr) -> SysResult<()> {
+        info!("[RawSocket::bind] start, addr = {:?}", addr);
+        let addr = self.parse_addr(addr)?;
+        // 绑定的地址作为发出报文的源地址，必须是本机地址
+        if !addr.is_unspecified() && !NET_DEV.lock().iter().any(|dev| dev.iface.has_ip_addr(addr)) {
+            return Err(Errno::EADDRNOTAVAIL);
+        }
+        let local_end = match addr.is_unspecified() {
+            true => IpListenEndpoint { addr: None, port: 0 },
+            false => IpListenEndpoint { addr: Some(addr), port: 0 },
+        };
+        self.sockmeta.lock().local_end = Some(local_end);
+        Ok(())
+    }
+    async fn connect(&self, _sockfd: usize, addr: &SockAddr) -> SysResult<()> {
+        info!("[RawSocket::connect] start, addr = {:?}", addr);
+        let addr = self.parse_addr(addr)?;
+        self.sockmeta.lock().remote_end = Some(IpEndpoint::new(addr, 0));
+        Ok(())
+    }
+    fn listen(&self, _backlog: usize) -> SysResult<()> {
+        Err(Errno::EOPNOTSUPP)
+    }
+    async fn send_msg(&self, buf: &[u8], dest_addr: Option<SockAddr>) -> SysResult<usize> {
+        info!("[RawSocket::send_msg] start, dest_addr = {:?}, len = {}", dest_addr, buf.len());
+        let dst = match dest_addr {
+            Some(addr) => self.parse_addr(&addr)?,
+            None => self.sockmeta.lock().remote_end.ok_or(Errno::EDESTADDRREQ)?.addr,
+        };
+        let packet = self.build_packet(dst, buf)?;
+        if packet.len() > BUFF_SIZE {
+            return Err(Errno::EMSGSIZE);
+        }
+        let timeout = self.sockmeta.lock().opts.send_timeout;
+        with_timeout(RawSendFuture::new(&packet, self, buf.len()), timeout).await
+    }
+    async fn recv_msg(&self, buf: &mut [u8]) -> SysResult<(usize, SockAddr)> {
+        info!("[RawSocket::recv_msg] start");
+        let timeout = self.sockmeta.lock().opts.recv_timeout;
+        with_timeout(RawRecvFuture::new(buf, self), timeout).await
+    }
+    fn set_recv_buf_size(&self, size: u32) -> SysResult<()> {
+        self.sockmeta.lock().recv_buf_size = size as usize;
+        Ok(())
+    }
+    fn set_send_buf_size(&self, size: u32) -> SysResult<()> {
+        self.sockmeta.lock().send_buf_size = size as usize;
+        Ok(())
+    }
+    fn get_recv_buf_size(&self) -> SysResult<usize> {
+        Ok(self.sockmeta.lock().recv_buf_size)
+    }
+    fn get_send_buf_size(&self) -> SysResult<usize> {
+        Ok(self.sockmeta.lock().send_buf_size)
+    }
+    fn shutdown(&self, how: ShutHow) -> SysResult<()> {
+        self.sockmeta.lock().shuthow = Some(how);
+        Ok(())
+    }
+    fn get_sockname(&self) -> SysResult<SockAddr> {
+        let sockmeta = self.sockmeta.lock();
+        let unspecified = match sockmeta.iptype {
+            IpType::Ipv4 => IpAddress::v4(0, 0, 0, 0),
+            IpType::Ipv6 => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0),
+        };
+        let addr = sockmeta.local_end.and_then(|end| end.addr).unwrap_or(unspecified);
+        Ok(sockmeta.iptype.sockaddr(IpEndpoint::new(addr, 0)))
+    }
+    fn get_peername(&self) -> SysResult<SockAddr> {
+        let sockmeta = self.sockmeta.lock();
+        let remote_end = sockmeta.remote_end.ok_or(Errno::ENOTCONN)?;
+        Ok(sockmeta.iptype.sockaddr(remote_end))
+    }
+    fn get_sockopts(&self) -> SysResult<SockOpts> {
+        Ok(self.sockmeta.lock().opts)
+    }
+    fn set_sockopts(&self, opts: SockOpts) -> SysResult<()> {
+        // IP_TTL 在构造首部时读取
+        self.sockmeta.lock().opts = opts;
+        Ok(())
+    }
+    fn take_error(&self) -> SysResult<Option<Errno>> {
+        Ok(self.sockmeta.lock().error.take())
+    }
+    fn get_socktype(&self) -> SysResult<Sock> {
+        Ok(Sock::Raw)
+    }
+    fn get_family(&self) -> u16 {
+        self.sockmeta.lock().family()
+    }
+    fn set_v6only(&self, v6only: bool) -> SysResult<()> {
+        self.sockmeta.lock().set_v6only(v6only)
+    }
+    fn get_v6only(&self) -> SysResult<bool> {
+        self.sockmeta.lock().get_v6only()
+    }
+    async fn pollin(&self) -> SysResult<bool> {
+        NET_DEV.lock().poll();
+        let waker = get_waker().await;
+        self.with_socket(|socket| {
+            if socket.can_recv() {
+                return Ok(true);
+            }
+            socket.register_recv_waker(&waker);
+            Ok(false)
+        })
+    }
+    async fn pollout(&self) -> SysResult<bool> {
+        NET_DEV.lock().poll();
+        let waker = get_waker().await;
+        self.with_socket(|socket| {
+            if socket.can_send() {
+                return Ok(true);
+            }
+            socket.register_send_waker(&waker);
+            Ok(false)
+        })
+    }
+    fn get_flags(&self) -> SysResult<OpenFlags> {
+        Ok(self.sockmeta.lock().flags)
+    }
+}
@@ -0,0 +1,90 @@
+use alloc::vec::Vec;
+use smoltcp::wire::{IpAddress, IpCidr};
+
+use crate::utils::{Errno, SysResult};
+
+/// 把网段中的主机位清零，得到网络地址
+pub fn network_of(cidr: IpCidr) -> IpCidr {
+    let prefix = cidr.prefix_len() as usize;
+    let clear = |bytes: &mut [u8]| {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let keep = prefix.saturating_sub(i * 8).min(8);
+            *byte &= !(0xffu8.checked_shr(keep as u32).unwrap_or(0));
+        }
+    };
+    match cidr.address() {
+        IpAddress::Ipv4(addr) => {
+            let mut bytes = addr.octets();
+            clear(&mut bytes);
+            IpCidr::new(IpAddress::Ipv4(bytes.into()), cidr.prefix_len())
+        }
+        IpAddress::Ipv6(addr) => {
+            let mut bytes = addr.octets();
+            clear(&mut bytes);
+            IpCidr::new(IpAddress::Ipv6(bytes.into()), cidr.prefix_len())
+        }
+    }
+}
+
+/// 内核路由表中的一条路由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// 目的网段
+    pub dest: IpCidr,
+    /// 下一跳网关，直连网段为 None
+    pub gateway: Option<IpAddress>,
+    /// 出接口的 ifindex
+    pub ifindex: usize,
+    /// 前缀长度相同时，metric 小的优先
+    pub metric: u32,
+}
+
+/// 路由表
+///
+/// 按最长前缀匹配选择出接口；选定接口后由该接口上的 smoltcp 路由决定下一跳，
+/// 因此带网关的路由会同步到对应接口的 smoltcp 路由表中（见 `NetIfaces::sync_routes`）。
+pub struct RouteTable {
+    routes: Vec<Route>,
+}
+
+impl RouteTable {
+    pub const fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn add(&mut self, route: Route) -> SysResult<()> {
+        if self.routes.iter().any(|r| r.dest == route.dest && r.ifindex == route.ifindex && r.metric == route.metric) {
+            return Err(Errno::EEXIST);
+        }
+        self.routes.push(route);
+        Ok(())
+    }
+
+    /// 删除目的网段为 `dest` 的路由，`ifindex` 为 None 时不限出接口
+    pub fn del(&mut self, dest: IpCidr, ifindex: Option<usize>) -> SysResult<Route> {
+        let index = self.routes
+            .iter()
+            .position(|r| r.dest == dest && ifindex.map_or(true, |i| r.ifindex == i))
+            .ok_or(Errno::ESRCH)?;
+        Ok(self.routes.remove(index))
+    }
+
+    /// 删除某个接口上的所有路由
+    pub fn remove_iface(&mut self, ifindex: usize) {
+        self.routes.retain(|r| r.ifindex != ifindex);
+    }
+
+    /// 最长前缀匹配
+    pub fn lookup(&self, dst: &IpAddress) -> Option<&Route> {
+        self.routes
+            .iter()
+            .filter(|r| r.dest.contains_addr(dst))
+            .min_by(|a, b| {
+                b.dest.prefix_len().cmp(&a.dest.prefix_len()).then(a.metric.cmp(&b.metric))
+            })
+    }
+}
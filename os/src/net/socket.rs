@@ -1,14 +1,18 @@
-use core::task::Waker;
+use core::{future::Future, task::Waker, time::Duration};
 use super::{
     addr::{IpType, Sock, SockAddr},
+    icmp::IcmpSocket,
+    raw::RawSocket,
     tcp::TcpSocket,
     udp::UdpSocket,
     unix::UnixSocket,
-    SockClass, SocketType, AF_INET, AF_INET6,
+    PortReuse, Protocol, SockClass, SocketType, AF_INET, AF_INET6, TCP_MSS,
 };
 use crate::{
     fs::{FileMeta, FileTrait, OpenFlags},
+    sync::TimeoutFuture,
     syscall::ShutHow,
+    task::{capable, CapSet},
     utils::{Errno, SysResult},
 };
 use alloc::boxed::Box;
@@ -18,6 +22,54 @@ use log::info;
 use smoltcp::{socket::tcp, wire::{IpEndpoint, IpListenEndpoint}};
 pub type TcpState = tcp::State;
 
+/// IP_TTL 的默认值，与 Linux 的 net.ipv4.ip_default_ttl 相同
+pub const DEFAULT_TTL: u8 = 64;
+/// TCP_KEEPIDLE / TCP_KEEPINTVL 的默认值，与 Linux 相同
+const DEFAULT_KEEPIDLE: Duration = Duration::from_secs(7200);
+const DEFAULT_KEEPINTVL: Duration = Duration::from_secs(75);
+
+/// 通过 setsockopt 设置的选项，getsockopt 读回的就是这里保存的值
+#[derive(Debug, Clone, Copy)]
+pub struct SockOpts {
+    /// SO_REUSEADDR / SO_REUSEPORT，在绑定端口时由 `PortManager` 检查
+    pub reuse: PortReuse,
+    /// SO_LINGER，`Some` 表示打开，其中是 close 时等待的时长
+    pub linger: Option<Duration>,
+    /// SO_RCVTIMEO，`None` 表示一直阻塞
+    pub recv_timeout: Option<Duration>,
+    /// SO_SNDTIMEO，`None` 表示一直阻塞
+    pub send_timeout: Option<Duration>,
+    /// SO_KEEPALIVE
+    pub keep_alive: bool,
+    /// TCP_KEEPIDLE：连接空闲多久之后开始发送保活报文
+    pub keep_idle: Duration,
+    /// TCP_KEEPINTVL：保活报文的间隔
+    pub keep_intvl: Duration,
+    /// TCP_NODELAY
+    pub nodelay: bool,
+    /// TCP_MAXSEG
+    pub max_seg: u32,
+    /// IP_TTL / IPV6_UNICAST_HOPS
+    pub ttl: u8,
+}
+
+impl Default for SockOpts {
+    fn default() -> Self {
+        Self {
+            reuse: PortReuse::default(),
+            linger: None,
+            recv_timeout: None,
+            send_timeout: None,
+            keep_alive: false,
+            keep_idle: DEFAULT_KEEPIDLE,
+            keep_intvl: DEFAULT_KEEPINTVL,
+            nodelay: false,
+            max_seg: TCP_MSS,
+            ttl: DEFAULT_TTL,
+        }
+    }
+}
+
 pub struct SockMeta {
     pub domain: Sock,
     pub iptype: IpType,
@@ -28,6 +80,11 @@ pub struct SockMeta {
     pub shuthow: Option<ShutHow>,
     pub local_end: Option<IpListenEndpoint>,
     pub remote_end: Option<IpEndpoint>,
+    /// IPV6_V6ONLY：AF_INET6 套接字是否只收发 IPv6
+    pub v6only: bool,
+    pub opts: SockOpts,
+    /// SO_ERROR：还没有被取走的错误
+    pub error: Option<Errno>,
 }
 
 impl SockMeta {
@@ -42,6 +99,47 @@ impl SockMeta {
             shuthow: None,
             local_end: None,
             remote_end: None,
+            v6only: false,
+            opts: SockOpts::default(),
+            error: None,
+        }
+    }
+
+    /// 解析用户给出的对端地址，并检查它与本套接字的地址族是否相容
+    pub fn remote_endpoint(&self, addr: &SockAddr) -> SysResult<IpEndpoint> {
+        if self.v6only && addr.is_v4_mapped() {
+            return Err(Errno::ENETUNREACH);
+        }
+        let endpoint = IpEndpoint::try_from(*addr)?;
+        if !self.iptype.accepts(self.v6only, &endpoint.addr) {
+            return Err(Errno::EAFNOSUPPORT);
+        }
+        Ok(endpoint)
+    }
+
+    pub fn set_v6only(&mut self, v6only: bool) -> SysResult<()> {
+        if !matches!(self.iptype, IpType::Ipv6) {
+            return Err(Errno::ENOPROTOOPT);
+        }
+        // 与 Linux 相同，绑定之后不能再修改
+        if self.local_end.is_some() {
+            return Err(Errno::EINVAL);
+        }
+        self.v6only = v6only;
+        Ok(())
+    }
+
+    pub fn get_v6only(&self) -> SysResult<bool> {
+        match self.iptype {
+            IpType::Ipv6 => Ok(self.v6only),
+            IpType::Ipv4 => Err(Errno::ENOPROTOOPT),
+        }
+    }
+
+    pub fn family(&self) -> u16 {
+        match self.iptype {
+            IpType::Ipv4 => AF_INET,
+            IpType::Ipv6 => AF_INET6,
         }
     }
 }
@@ -75,47 +173,93 @@ pub trait Socket: Send + Sync {
 
     fn get_peername(&self) -> SysResult<SockAddr>;
 
-    fn set_keep_alive(&self, action: u32) -> SysResult<()>;
+    fn get_sockopts(&self) -> SysResult<SockOpts>;
 
-    fn enable_nagle(&self, action: u32) -> SysResult<()>;
+    /// 保存新的选项，并把其中协议栈需要知道的部分应用到 smoltcp 套接字上
+    fn set_sockopts(&self, opts: SockOpts) -> SysResult<()>;
+
+    /// 取走 SO_ERROR 中记录的错误
+    fn take_error(&self) -> SysResult<Option<Errno>>;
 
     fn get_socktype(&self) -> SysResult<Sock>;
 
+    /// 创建套接字时的地址族（AF_INET / AF_INET6 / AF_UNIX）
+    fn get_family(&self) -> u16;
+
+    fn set_v6only(&self, v6only: bool) -> SysResult<()>;
+
+    fn get_v6only(&self) -> SysResult<bool>;
+
     async fn pollin(&self) -> SysResult<bool>;
 
     async fn pollout(&self) -> SysResult<bool>;
 
     fn get_flags(&self) -> SysResult<OpenFlags>;
+
+    /// 关闭最后一个文件描述符时调用，SO_LINGER 打开且时长不为 0 时在这里等待连接关闭
+    async fn linger(&self) {}
+}
+
+/// 按 SO_RCVTIMEO / SO_SNDTIMEO 限制一次阻塞操作的时长，超时与 Linux 一样返回 EAGAIN
+pub async fn with_timeout<T, F>(fut: F, timeout: Option<Duration>) -> SysResult<T>
+where
+    F: Future<Output = SysResult<T>>,
+{
+    match timeout {
+        Some(timeout) => TimeoutFuture::new(fut, timeout).await.map_err(|_| Errno::EAGAIN)?,
+        None => fut.await,
+    }
 }
 
 impl dyn Socket {
-    pub fn new(family: u16, socket_type: SocketType) -> SysResult<SockClass> {
+    pub fn new(family: u16, socket_type: SocketType, protocol: u8) -> SysResult<SockClass> {
         match family {
-            AF_INET => Self::new_socket(IpType::Ipv4, socket_type),
-            AF_INET6 => Self::new_socket(IpType::Ipv6, socket_type),
+            AF_INET => Self::new_socket(IpType::Ipv4, socket_type, protocol),
+            AF_INET6 => Self::new_socket(IpType::Ipv6, socket_type, protocol),
             AF_UNIX => return Err(Errno::EAFNOSUPPORT),
             _ => return Err(Errno::EAFNOSUPPORT),
         }
     }
 
-    fn new_socket(ip_type: IpType, socket_type: SocketType) -> SysResult<SockClass> {
-        match socket_type {
-            ty if ty.contains(SocketType::SOCK_STREAM) => {
-                let mut flags = OpenFlags::empty();
-                if socket_type.contains(SocketType::SOCK_NONBLOCK) {
-                    flags.insert(OpenFlags::O_NONBLOCK);
-                }
-                if socket_type.contains(SocketType::SOCK_CLOEXEC) {
-                    flags.insert(OpenFlags::O_CLOEXEC);
-                }
+    /// 按套接字类型和协议号创建套接字，协议号为 0 时使用该类型的默认协议
+    fn new_socket(ip_type: IpType, socket_type: SocketType, protocol: u8) -> SysResult<SockClass> {
+        let mut flags = OpenFlags::empty();
+        if socket_type.contains(SocketType::SOCK_NONBLOCK) {
+            flags.insert(OpenFlags::O_NONBLOCK);
+        }
+        if socket_type.contains(SocketType::SOCK_CLOEXEC) {
+            flags.insert(OpenFlags::O_CLOEXEC);
+        }
+        let icmp_proto = match ip_type {
+            IpType::Ipv4 => Protocol::IPPROTO_ICMP.bits() as u8,
+            IpType::Ipv6 => Protocol::IPPROTO_ICMPV6.bits() as u8,
+        };
+        // 类型的低 4 位才是套接字类型，SOCK_RAW 的位同时包含了 SOCK_STREAM 和 SOCK_DGRAM
+        let ty = socket_type.bits() & 0xf;
+        match (ty, protocol) {
+            (ty, 0 | 6) if ty == SocketType::SOCK_STREAM.bits() => {
                 info!("[new_socket] new Tcp socket, iptype = {:?}", ip_type);
                 Ok(SockClass::Tcp(Arc::new(TcpSocket::new(ip_type, flags))))
             }
-            ty if ty.contains(SocketType::SOCK_DGRAM) => {
+            (ty, 0 | 17) if ty == SocketType::SOCK_DGRAM.bits() => {
                 info!("[new_socket] new Udp socket, iptype = {:?}", ip_type);
                 Ok(SockClass::Udp(Arc::new(UdpSocket::new(ip_type))))
             }
-            _ => Err(Errno::EINVAL),
+            (ty, proto) if ty == SocketType::SOCK_DGRAM.bits() && proto == icmp_proto => {
+                info!("[new_socket] new Icmp socket, iptype = {:?}", ip_type);
+                Ok(SockClass::Icmp(Arc::new(IcmpSocket::new(ip_type, flags))))
+            }
+            (ty, 1..=255) if ty == SocketType::SOCK_RAW.bits() => {
+                if !capable(CapSet::CAP_NET_RAW) {
+                    return Err(Errno::EPERM);
+                }
+                info!("[new_socket] new Raw socket, iptype = {:?}, protocol = {}", ip_type, protocol);
+                Ok(SockClass::Raw(Arc::new(RawSocket::new(ip_type, protocol, flags))))
+            }
+            (ty, _) if ty == SocketType::SOCK_STREAM.bits()
+                || ty == SocketType::SOCK_DGRAM.bits()
+                || ty == SocketType::SOCK_RAW.bits() => Err(Errno::EPROTONOSUPPORT),
+            _ => Err(Errno::ESOCKTNOSUPPORT),
         }
     }
 }
@@ -0,0 +1,335 @@
+//! /proc/net 下各文件的内容，格式与 Linux 保持一致，供 busybox 的 ifconfig / route / netstat 解析
+
+use alloc::{format, string::String};
+use core::fmt::Write;
+use smoltcp::{
+    socket::{tcp, Socket as SmolSocket},
+    wire::{IpAddress, IpCidr, IpEndpoint, IpListenEndpoint, Ipv6Address},
+};
+
+use super::{RouteFlags, TcpState, NET_DEV, SOCKET_SET};
+
+/// if_inet6 中的地址作用域，取自 Linux include/net/ipv6.h
+const IFA_HOST: u8 = 0x10;
+const IFA_LINK: u8 = 0x20;
+const IFA_GLOBAL: u8 = 0x00;
+/// IFA_F_PERMANENT：静态配置的地址
+const IFA_F_PERMANENT: u8 = 0x80;
+
+/// /proc/net/dev
+pub fn proc_net_dev() -> String {
+    let mut s = String::from(
+        "Inter-|   Receive                                                |  Transmit\n \
+         face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n",
+    );
+    for dev in NET_DEV.lock().iter() {
+        let stats = dev.stats();
+        let _ = writeln!(
+            s,
+            "{:>6}:{:>8} {:>7} {:>4} {:>4} {:>4} {:>5} {:>10} {:>9} {:>8} {:>7} {:>4} {:>4} {:>4} {:>5} {:>7} {:>10}",
+            dev.name, stats.rx_bytes, stats.rx_packets, 0, 0, 0, 0, 0, 0,
+            stats.tx_bytes, stats.tx_packets, stats.tx_errors, 0, 0, 0, 0, 0,
+        );
+    }
+    s
+}
+
+/// /proc/net/route，只列出 IPv4 路由
+pub fn proc_net_route() -> String {
+    let mut s = String::from(
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n",
+    );
+    let ifaces = NET_DEV.lock();
+    for route in ifaces.routes.routes() {
+        let IpAddress::Ipv4(dest) = route.dest.address() else { continue };
+        let Some(dev) = ifaces.get(route.ifindex) else { continue };
+        let mut flags = RouteFlags::RTF_UP;
+        let gateway = match route.gateway {
+            Some(IpAddress::Ipv4(gw)) => {
+                flags |= RouteFlags::RTF_GATEWAY;
+                gw.octets()
+            }
+            _ => [0; 4],
+        };
+        if route.dest.prefix_len() == 32 {
+            flags |= RouteFlags::RTF_HOST;
+        }
+        let mask = u32::MAX.checked_shl(32 - route.dest.prefix_len() as u32).unwrap_or(0);
+        let _ = writeln!(
+            s,
+            "{}\t{:08X}\t{:08X}\t{:04X}\t0\t0\t{}\t{:08X}\t0\t0\t0",
+            dev.name,
+            u32::from_le_bytes(dest.octets()),
+            u32::from_le_bytes(gateway),
+            flags.bits(),
+            route.metric,
+            u32::from_le_bytes(mask.to_be_bytes()),
+        );
+    }
+    s
+}
+
+/// /proc/net/if_inet6：每个接口上的 IPv6 地址
+pub fn proc_net_if_inet6() -> String {
+    let mut s = String::new();
+    for dev in NET_DEV.lock().iter() {
+        for cidr in dev.iface.ip_addrs() {
+            let IpCidr::Ipv6(cidr) = cidr else { continue };
+            let addr = cidr.address();
+            let scope = match () {
+                _ if addr.is_loopback() => IFA_HOST,
+                _ if addr.segments()[0] & 0xffc0 == 0xfe80 => IFA_LINK,
+                _ => IFA_GLOBAL,
+            };
+            let _ = writeln!(
+                s,
+                "{} {:02x} {:02x} {:02x} {:02x} {:>8}",
+                hex_ipv6(&addr, false),
+                dev.ifindex,
+                cidr.prefix_len(),
+                scope,
+                IFA_F_PERMANENT,
+                dev.name,
+            );
+        }
+    }
+    s
+}
+
+/// /proc/net/ipv6_route，源地址一栏总是 ::/0
+pub fn proc_net_ipv6_route() -> String {
+    let mut s = String::new();
+    let ifaces = NET_DEV.lock();
+    for route in ifaces.routes.routes() {
+        let IpAddress::Ipv6(dest) = route.dest.address() else { continue };
+        let Some(dev) = ifaces.get(route.ifindex) else { continue };
+        let mut flags = RouteFlags::RTF_UP;
+        let gateway = match route.gateway {
+            Some(IpAddress::Ipv6(gw)) => {
+                flags |= RouteFlags::RTF_GATEWAY;
+                gw
+            }
+            _ => Ipv6Address::UNSPECIFIED,
+        };
+        if route.dest.prefix_len() == 128 {
+            flags |= RouteFlags::RTF_HOST;
+        }
+        let _ = writeln!(
+            s,
+            "{} {:02x} {} {:02x} {} {:08x} {:08x} {:08x} {:08x} {:>8}",
+            hex_ipv6(&dest, false),
+            route.dest.prefix_len(),
+            hex_ipv6(&Ipv6Address::UNSPECIFIED, false),
+            0,
+            hex_ipv6(&gateway, false),
+            route.metric,
+            1,
+            0,
+            flags.bits(),
+            dev.name,
+        );
+    }
+    s
+}
+
+/// /proc/net/tcp，只列出 IPv4 套接字
+pub fn proc_net_tcp() -> String {
+    let mut s = String::from(
+        "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n",
+    );
+    let sockets = SOCKET_SET.lock();
+    let tcp_sockets = sockets.iter().filter_map(|(_, socket)| match socket {
+        SmolSocket::Tcp(socket) => Some(socket),
+        _ => None,
+    });
+    let mut sl = 0;
+    for socket in tcp_sockets {
+        let local = match socket.local_endpoint() {
+            Some(endpoint) => endpoint.into(),
+            None => socket.listen_endpoint(),
+        };
+        if matches!(local.addr, Some(IpAddress::Ipv6(_))) {
+            continue;
+        }
+        let _ = writeln!(
+            s,
+            "{:>4}: {} {} {:02X} {:08X}:{:08X} 00:00000000 00000000 {:>5} {:>8} 0",
+            sl,
+            hex_listen_endpoint(&local),
+            hex_endpoint(socket.remote_endpoint()),
+            tcp_state_code(socket.state()),
+            socket.send_queue(),
+            socket.recv_queue(),
+            0,
+            0,
+        );
+        sl += 1;
+    }
+    s
+}
+
+/// /proc/net/tcp6，列出本端地址为 IPv6 的套接字。
+///
+/// smoltcp 不区分监听在未指定地址上的套接字属于哪个地址族，这些套接字都出现在 /proc/net/tcp 中
+pub fn proc_net_tcp6() -> String {
+    let mut s = String::from(
+        "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n",
+    );
+    let sockets = SOCKET_SET.lock();
+    let tcp_sockets = sockets.iter().filter_map(|(_, socket)| match socket {
+        SmolSocket::Tcp(socket) => Some(socket),
+        _ => None,
+    });
+    let mut sl = 0;
+    for socket in tcp_sockets {
+        let local = match socket.local_endpoint() {
+            Some(endpoint) => endpoint.into(),
+            None => socket.listen_endpoint(),
+        };
+        if !matches!(local.addr, Some(IpAddress::Ipv6(_))) {
+            continue;
+        }
+        let _ = writeln!(
+            s,
+            "{:>4}: {} {} {:02X} {:08X}:{:08X} 00:00000000 00000000 {:>5} {:>8} 0",
+            sl,
+            hex6_listen_endpoint(&local),
+            hex6_endpoint(socket.remote_endpoint()),
+            tcp_state_code(socket.state()),
+            socket.send_queue(),
+            socket.recv_queue(),
+            0,
+            0,
+        );
+        sl += 1;
+    }
+    s
+}
+
+/// /proc/net/udp6，列出本端地址为 IPv6 的套接字
+pub fn proc_net_udp6() -> String {
+    let mut s = String::from(
+        "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops\n",
+    );
+    let sockets = SOCKET_SET.lock();
+    let udp_sockets = sockets.iter().filter_map(|(_, socket)| match socket {
+        SmolSocket::Udp(socket) => Some(socket),
+        _ => None,
+    });
+    let mut sl = 0;
+    for socket in udp_sockets {
+        let local = socket.endpoint();
+        if !matches!(local.addr, Some(IpAddress::Ipv6(_))) {
+            continue;
+        }
+        let _ = writeln!(
+            s,
+            "{:>5}: {} {} 07 {:08X}:{:08X} 00:00000000 00000000 {:>5} {:>8} 0 2 0000000000000000 0",
+            sl,
+            hex6_listen_endpoint(&local),
+            hex6_endpoint(None),
+            socket.send_queue(),
+            socket.recv_queue(),
+            0,
+            0,
+        );
+        sl += 1;
+    }
+    s
+}
+
+/// /proc/net/udp，只列出 IPv4 套接字
+pub fn proc_net_udp() -> String {
+    let mut s = String::from(
+        "   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops\n",
+    );
+    let sockets = SOCKET_SET.lock();
+    let udp_sockets = sockets.iter().filter_map(|(_, socket)| match socket {
+        SmolSocket::Udp(socket) => Some(socket),
+        _ => None,
+    });
+    let mut sl = 0;
+    for socket in udp_sockets {
+        let local = socket.endpoint();
+        if matches!(local.addr, Some(IpAddress::Ipv6(_))) {
+            continue;
+        }
+        // UDP 套接字在 smoltcp 中没有对端，状态总是 TCP_CLOSE
+        let _ = writeln!(
+            s,
+            "{:>5}: {} {} 07 {:08X}:{:08X} 00:00000000 00000000 {:>5} {:>8} 0 2 0000000000000000 0",
+            sl,
+            hex_listen_endpoint(&local),
+            hex_endpoint(None),
+            socket.send_queue(),
+            socket.recv_queue(),
+            0,
+            0,
+        );
+        sl += 1;
+    }
+    s
+}
+
+/// 以 Linux 的格式输出 IPv4 地址和端口：地址按主机字节序打印，端口按数值打印
+fn hex_listen_endpoint(endpoint: &IpListenEndpoint) -> String {
+    let addr = match endpoint.addr {
+        Some(IpAddress::Ipv4(addr)) => addr.octets(),
+        _ => [0; 4],
+    };
+    format!("{:08X}:{:04X}", u32::from_le_bytes(addr), endpoint.port)
+}
+
+fn hex_endpoint(endpoint: Option<IpEndpoint>) -> String {
+    match endpoint {
+        Some(endpoint) => hex_listen_endpoint(&endpoint.into()),
+        None => hex_listen_endpoint(&IpListenEndpoint::default()),
+    }
+}
+
+/// IPv6 地址的十六进制形式。
+///
+/// if_inet6 / ipv6_route 按网络字节序逐字节打印；tcp6 / udp6 则与 IPv4 一样，
+/// 把每 4 个字节当作主机字节序的 u32 打印（`words` 为 true）
+fn hex_ipv6(addr: &Ipv6Address, words: bool) -> String {
+    let octets = addr.octets();
+    match words {
+        true => octets
+            .chunks(4)
+            .map(|w| format!("{:08X}", u32::from_le_bytes(w.try_into().unwrap())))
+            .collect(),
+        false => octets.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+fn hex6_listen_endpoint(endpoint: &IpListenEndpoint) -> String {
+    let addr = match endpoint.addr {
+        Some(IpAddress::Ipv6(addr)) => addr,
+        _ => Ipv6Address::UNSPECIFIED,
+    };
+    format!("{}:{:04X}", hex_ipv6(&addr, true), endpoint.port)
+}
+
+fn hex6_endpoint(endpoint: Option<IpEndpoint>) -> String {
+    match endpoint {
+        Some(endpoint) => hex6_listen_endpoint(&endpoint.into()),
+        None => hex6_listen_endpoint(&IpListenEndpoint::default()),
+    }
+}
+
+/// 转换为 Linux include/net/tcp_states.h 中的编号
+fn tcp_state_code(state: TcpState) -> u8 {
+    match state {
+        tcp::State::Established => 0x01,
+        tcp::State::SynSent => 0x02,
+        tcp::State::SynReceived => 0x03,
+        tcp::State::FinWait1 => 0x04,
+        tcp::State::FinWait2 => 0x05,
+        tcp::State::TimeWait => 0x06,
+        tcp::State::Closed => 0x07,
+        tcp::State::CloseWait => 0x08,
+        tcp::State::LastAck => 0x09,
+        tcp::State::Listen => 0x0A,
+        tcp::State::Closing => 0x0B,
+    }
+}
@@ -7,17 +7,18 @@ use super::addr::SockAddr;
 use super::net_async::TcpSendFuture;
 use super::NetDev;
 use super::SockMeta;
+use super::SockOpts;
 use super::Socket;
 use super::TcpState;
 use super::AF_INET;
 use super::BUFF_SIZE;
+use super::MAX_BACKLOG;
 use super::NET_DEV;
+use super::with_timeout;
 use crate::fs::FileMeta;
 use crate::fs::FileTrait;
 use crate::fs::OpenFlags;
 use crate::fs::RenameFlags;
-use crate::net::addr::SockIpv4;
-use crate::net::addr::SockIpv6;
 use crate::net::addr::Sock;
 use crate::net::do_port_aloc;
 use crate::net::net_async::TcpAcceptFuture;
@@ -31,7 +32,6 @@ use crate::sync::yield_now;
 use crate::sync::SpinNoIrqLock;
 use crate::syscall::ShutHow;
 use crate::task::current_task;
-use crate::task::exchange_sock_fdinfo;
 use crate::task::sock_map_fd;
 use crate::utils::Errno;
 use crate::utils::SysResult;
@@ -39,6 +39,7 @@ use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec;
+use alloc::vec::Vec;
 use async_trait::async_trait;
 use log::info;
 use log::trace;
@@ -58,15 +59,31 @@ pub struct TcpSocket {
     pub handle: SocketHandle,
     pub sockmeta: SpinNoIrqLock<SockMeta>,
     pub state: SpinNoIrqLock<TcpState>,
+    /// 监听套接字的等待队列：每个元素都是在同一端口上监听的 smoltcp 套接字，
+    /// 完成握手后由 accept 取走并补上一个新的。不为空即表示正在监听
+    backlog: SpinNoIrqLock<Vec<SocketHandle>>,
 }
 
 impl Drop for TcpSocket {
     fn drop(&mut self) {
         info!("[TcpSocket::drop] start");
+        // SO_LINGER 打开且时长为 0 时与 Linux 一样直接复位连接。
+        // 时长不为 0 时 close 已经在 `linger` 中等待过，这里照常关闭
+        let reset = self.sockmeta.lock().opts.linger == Some(Duration::ZERO);
+        // 还没被 accept 的连接直接复位
+        let backlog = core::mem::take(&mut *self.backlog.lock());
+        {
+            let mut sockets = SOCKET_SET.lock();
+            for &handle in backlog.iter() {
+                sockets.get_mut::<tcp::Socket>(handle).abort();
+            }
+        }
         NET_DEV.lock().poll();
         self.with_socket(|socket| {
             info!("[TcoSocket::drop] brfore state is {:?}", socket.state());
-            if socket.is_open() {
+            if reset {
+                socket.abort();
+            } else if socket.is_open() {
                 socket.close();    
             }
             info!("[TcpSocket::drop] after drop, state = {:?}", socket.state());
@@ -78,6 +95,9 @@ impl Drop for TcpSocket {
         let mut binding = SOCKET_SET.lock();
         let sock = binding.remove(self.handle);
         drop(sock);
+        for handle in backlog {
+            binding.remove(handle);
+        }
         drop(binding);
 
         // 释放端口，同时释放端口复用的port
@@ -105,6 +125,30 @@ impl TcpSocket {
             handle,
             sockmeta,
             state: SpinNoIrqLock::new(TcpState::Closed),
+            backlog: SpinNoIrqLock::new(Vec::new()),
+        }
+    }
+
+    /// 包装等待队列中已经完成握手的 smoltcp 套接字，作为 accept 返回的新连接。
+    ///
+    /// 新连接与监听套接字共用端口，端口仍由监听套接字持有，所以这里不记录 port。
+    /// 与 Linux 一样，新连接继承监听套接字的选项
+    fn from_established(handle: SocketHandle, iptype: IpType, flags: OpenFlags, opts: SockOpts) -> Self {
+        let (local_end, remote_end, state) = {
+            let mut sockets = SOCKET_SET.lock();
+            let socket = sockets.get_mut::<tcp::Socket>(handle);
+            Self::apply_opts(socket, &opts);
+            (socket.local_endpoint(), socket.remote_endpoint(), socket.state())
+        };
+        let mut sockmeta = SockMeta::new(Sock::Tcp, iptype, BUFF_SIZE, BUFF_SIZE, flags);
+        sockmeta.local_end = local_end.map(IpListenEndpoint::from);
+        sockmeta.remote_end = remote_end;
+        sockmeta.opts = opts;
+        Self {
+            handle,
+            sockmeta: SpinNoIrqLock::new(sockmeta),
+            state: SpinNoIrqLock::new(state),
+            backlog: SpinNoIrqLock::new(Vec::new()),
         }
     }
 
@@ -124,7 +168,7 @@ impl TcpSocket {
             // 存在问题? 有的情况下不需要做这个动作?或者说do_port不够满足要求?
             // 因为同一时间可能会有多个socket在使用某个local end?只是remote end不一样而已
             // 特别是在accept中会产生多个同一local end的socket
-            p = do_port_aloc(local_point, sockmeta.domain)?;
+            p = do_port_aloc(local_point, sockmeta.domain, sockmeta.opts.reuse)?;
 
             let mut listen_point;
             if local_point.addr.is_unspecified() {
@@ -152,7 +196,7 @@ impl TcpSocket {
 
         self.with_socket(|socket| -> SysResult<()>{
             let mut binding = NET_DEV.lock();
-            let context = binding.iface.context();
+            let context = binding.context_for(&remote_point.addr);
             match socket.connect(context, remote_point, local_end) {
                 Err(ConnectError::InvalidState) => return Err(Errno::EISCONN),
                 Err(ConnectError::Unaddressable) => return Err(Errno::EADDRNOTAVAIL),
@@ -194,20 +238,103 @@ impl TcpSocket {
         }
 
         if sockmeta.local_end.is_none() {
-            match sockmeta.iptype {
-                IpType::Ipv4 => {
-                    let mut local_point = IpEndpoint::new(IpAddress::v4(0, 0, 0, 0), 0);
-                    drop(sockmeta);
-                    info!("[check_addr] call bind");
-                    self.do_bind(&mut local_point);
-                }
-                IpType::Ipv6 => todo!(),
+            let unspecified = match sockmeta.iptype {
+                IpType::Ipv4 => IpAddress::v4(0, 0, 0, 0),
+                IpType::Ipv6 => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0),
             };
+            let mut local_point = IpEndpoint::new(unspecified, 0);
+            drop(sockmeta);
+            info!("[check_addr] call bind");
+            self.do_bind(&mut local_point);
         }
 
         Ok(())
     }
 
+    /// 把选项中由协议栈实现的部分设置到 smoltcp 套接字上。
+    ///
+    /// smoltcp 只有一个保活间隔：连接空闲这么久就发送一次保活报文，这里取 TCP_KEEPIDLE
+    fn apply_opts(socket: &mut tcp::Socket, opts: &SockOpts) {
+        socket.set_keep_alive(opts.keep_alive.then(|| opts.keep_idle.into()));
+        socket.set_nagle_enabled(!opts.nodelay);
+        socket.set_hop_limit(Some(opts.ttl));
+    }
+
+    /// 新建一个在 `local_end` 上监听的 smoltcp 套接字，放入等待队列
+    fn listen_handle(local_end: IpListenEndpoint) -> SysResult<SocketHandle> {
+        let mut socket = Self::new_sock();
+        socket.listen(local_end).map_err(|_| Errno::EINVAL)?;
+        Ok(SOCKET_SET.lock().add(socket))
+    }
+
+    pub fn is_listening(&self) -> bool {
+        !self.backlog.lock().is_empty()
+    }
+
+    /// 从等待队列中取出一个已完成握手的连接，并在原位置补上新的监听套接字。
+    ///
+    /// 调用前需要先轮询网卡。握手失败、或者被复位的套接字在这里重新开始监听
+    pub fn take_established(&self) -> SysResult<Option<SocketHandle>> {
+        let (iptype, v6only, local_end) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.iptype, sockmeta.v6only, sockmeta.local_end)
+        };
+        let local_end = local_end.ok_or(Errno::EINVAL)?;
+        let mut backlog = self.backlog.lock();
+        if backlog.is_empty() {
+            return Err(Errno::EINVAL);
+        }
+        let mut sockets = SOCKET_SET.lock();
+        for slot in backlog.iter_mut() {
+            let socket = sockets.get_mut::<tcp::Socket>(*slot);
+            match socket.state() {
+                TcpState::Listen | TcpState::SynReceived => {}
+                TcpState::Established | TcpState::CloseWait => {
+                    let remote_end = socket.remote_endpoint();
+                    if remote_end.is_some_and(|end| iptype.accepts(v6only, &end.addr)) {
+                        let mut new_socket = Self::new_sock();
+                        new_socket.listen(local_end).map_err(|_| Errno::EINVAL)?;
+                        let handle = core::mem::replace(slot, sockets.add(new_socket));
+                        return Ok(Some(handle));
+                    }
+                    // smoltcp 监听未指定地址时两种地址族的连接都会接受，
+                    // 不属于本套接字地址族的连接复位，RST 发出后再重新监听
+                    info!("[take_established] reject {:?}, iptype = {:?}, v6only = {}", remote_end, iptype, v6only);
+                    socket.abort();
+                }
+                _ if !socket.is_open() => {
+                    let _ = socket.listen(local_end);
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// 等待队列中是否有可以 accept 的连接
+    fn has_established(&self) -> bool {
+        let (iptype, v6only) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.iptype, sockmeta.v6only)
+        };
+        let backlog = self.backlog.lock();
+        let sockets = SOCKET_SET.lock();
+        backlog.iter().any(|&handle| {
+            let socket = sockets.get::<tcp::Socket>(handle);
+            matches!(socket.state(), TcpState::Established | TcpState::CloseWait)
+                && socket.remote_endpoint().is_some_and(|end| iptype.accepts(v6only, &end.addr))
+        })
+    }
+
+    /// 在等待队列的每个套接字上注册 waker，任何一个连接状态变化都会唤醒
+    pub fn register_accept_waker(&self, waker: &Waker) {
+        let backlog = self.backlog.lock();
+        let mut sockets = SOCKET_SET.lock();
+        for &handle in backlog.iter() {
+            sockets.get_mut::<tcp::Socket>(handle).register_recv_waker(waker);
+        }
+    }
+
     pub fn set_state(&self, state: TcpState) {
         *self.state.lock() = state;
     }
@@ -220,6 +347,9 @@ impl TcpSocket {
 
     // 判断是否可以从tcp socket中读取数据
     pub fn shoule_return_ready(&self) -> bool {
+        if self.is_listening() {
+            return self.has_established();
+        }
         let res = self.with_socket(|socket| -> bool {
             if socket.can_recv() {
                 info!("[TcpSocket::pollin] can recv");
@@ -231,8 +361,6 @@ impl TcpSocket {
                 || socket.state() == TcpState::FinWait2
                 || socket.state() == TcpState::FinWait1
                 || socket.state() == TcpState::TimeWait
-                || (*self.state.lock() == TcpState::Listen
-                    && socket.state() == TcpState::Established)
                 || socket.state() == TcpState::SynReceived
             {
                 info!(
@@ -291,67 +419,46 @@ impl Socket for TcpSocket {
     }
     fn listen(&self, backlog: usize) -> SysResult<()> {
         info!("[tcp listen] backlog: {}", backlog);
-        let sockmeta = self.sockmeta.lock();
-        info!("[tcp listen] stage 1");
-        let local_end = sockmeta.local_end.ok_or(Errno::EINVAL)?;
-        info!("[tcp listen] stage 2");
-        let res = self.with_socket(|socket| match socket.listen(local_end) {
-            Ok(_) => {
-                info!("[tcp listen] Listening on {:?}, state = {:?}", socket.listen_endpoint(), socket.state());
-                self.set_state(socket.state());
-                return Ok(());
-            }
-            Err(_) => {
-                let p = sockmeta.port.clone().ok_or(Errno::EINVAL)?;
-                info!("[tcp listen] Failed to listen on port: {}", p);
-                return Err(Errno::EINVAL);
-            }
-        });
-
-        res
+        let local_end = self.sockmeta.lock().local_end.ok_or(Errno::EINVAL)?;
+        // 与 Linux 一样，超出上限的 backlog 被截断；重复 listen 只会加长队列
+        let backlog = backlog.clamp(1, MAX_BACKLOG);
+        let mut pool = self.backlog.lock();
+        while pool.len() < backlog {
+            pool.push(Self::listen_handle(local_end)?);
+        }
+        info!("[tcp listen] Listening on {:?}, backlog = {}", local_end, pool.len());
+        self.set_state(TcpState::Listen);
+        Ok(())
     }
     async fn accept(&self, sockfd: usize, flags: OpenFlags) -> SysResult<(IpEndpoint, usize)> {
         info!("[TcpSocket::accept] flags: {:?}", flags);
-        // if self.state.lock().clone() != TcpState::Listen {
-        //     info!("[TcpSocket::accept] Socket is not in listening state");
-        //     return Err(Errno::EINVAL);
-        // }
+        if !self.is_listening() {
+            return Err(Errno::EINVAL);
+        }
 
         let cloexec_enable = flags.contains(OpenFlags::O_CLOEXEC);
-        let remote_end = TcpAcceptFuture::new(self).await?; // 这里的remote end是客户端
-        let ip_type = self.sockmeta.lock().iptype;
-        let local_end = self
-            .sockmeta
-            .lock()
-            .local_end
-            .expect("[tcp accept] no local end");
-        let newsock = TcpSocket::new(ip_type, flags);
-        {
-            newsock.sockmeta.lock().port = Some(local_end.port);
-        }
-        {
-            newsock.sockmeta.lock().local_end = Some(local_end);
-        }
-        // newsock.do_bind(local_end)?;
-        newsock.listen(0)?;
-        // newsock.set_state(TcpState::Established);
-        let newsock = Arc::new(newsock);
-        let newfd = sock_map_fd(newsock, cloexec_enable, flags).map_err(|_| Errno::EAFNOSUPPORT)?;
-        // 在accept成功后，oldsock的状态会转化为established，我们后面只能用oldsock来进行数据传输
-        // 然后服务器端任然需要一个socket来监听，所以使用newsock来监听
-        // 所以这里需要将oldsock对应fd的file和newsock对应的file进行交换
-        // 这样，我们返回newfd回去后，用户通过newfd可以访问到oldfile，通过oldfile获取到oldsock
-        exchange_sock_fdinfo(sockfd, newfd)?;
+        let timeout = self.sockmeta.lock().opts.recv_timeout;
+        let handle = with_timeout(TcpAcceptFuture::new(self), timeout).await?;
+        let (iptype, opts) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.iptype, sockmeta.opts)
+        };
+        let newsock = TcpSocket::from_established(handle, iptype, flags, opts);
+        // 这里的remote end是客户端
+        let remote_end = newsock.sockmeta.lock().remote_end.ok_or(Errno::ECONNABORTED)?;
+        let newfd = sock_map_fd(Arc::new(newsock), cloexec_enable, flags).map_err(|_| Errno::EAFNOSUPPORT)?;
 
         Ok((remote_end, newfd))
     }
     async fn connect(&self, sockfd: usize, addr: &SockAddr) -> SysResult<()> {
         info!("[Tcp::connect] start, remoteaddr = {:?}", addr);
-        let mut remote_endpoint = IpEndpoint::try_from(addr.clone())?;
+        let mut remote_endpoint = self.sockmeta.lock().remote_endpoint(addr)?;
         self.check_addr(sockfd, remote_endpoint)?;
         // yield_now().await;
 
-        let mut state = self.do_connect(remote_endpoint)?;
+        let mut state = self
+            .do_connect(remote_endpoint)
+            .inspect_err(|&e| self.sockmeta.lock().error = Some(e))?;
         loop {
             NET_DEV.lock().poll(); // poll 会修改socket的状态
             state = self.check_stat()?;
@@ -365,9 +472,10 @@ impl Socket for TcpSocket {
                     yield_now().await;
                 }
                 TcpState::Closed => {
-                    info!("[tcp connect] Connection closed, try again...");
-                    self.do_connect(remote_endpoint);
-                    yield_now().await;
+                    // 对端回复了 RST
+                    info!("[tcp connect] Connection refused");
+                    self.sockmeta.lock().error = Some(Errno::ECONNREFUSED);
+                    return Err(Errno::ECONNREFUSED);
                 }
                 _ => {
                     info!("[tcp connect] Waiting for connection...");
@@ -380,12 +488,14 @@ impl Socket for TcpSocket {
     }
     async fn send_msg(&self, buf: &[u8], dest_addr: Option<SockAddr>) -> SysResult<usize> {
         info!("[Tcp::send_msg] start, dest_addr = {:?}", dest_addr);
-        TcpSendFuture::new(buf, self).await
+        let timeout = self.sockmeta.lock().opts.send_timeout;
+        with_timeout(TcpSendFuture::new(buf, self), timeout).await
         // Ok(res)
     }
     async fn recv_msg(&self, buf: &mut [u8]) -> SysResult<(usize, SockAddr)> {
         self.set_remote_point();
-        TcpRecvFuture::new(buf, self).await
+        let timeout = self.sockmeta.lock().opts.recv_timeout;
+        with_timeout(TcpRecvFuture::new(buf, self), timeout).await
     }
 
     fn set_recv_buf_size(&self, size: u32) -> SysResult<()> {
@@ -426,67 +536,56 @@ impl Socket for TcpSocket {
         Ok(())
     }
     fn get_sockname(&self) -> SysResult<SockAddr> {
-        let local_end = self
-            .sockmeta
-            .lock()
-            .local_end
-            .expect("[tcp] get_sockname no local_end");
+        let (local_end, iptype) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.local_end.expect("[tcp] get_sockname no local_end"), sockmeta.iptype)
+        };
         let port = local_end.port;
         // let addr = local_end.addr.unwrap();
-        let addr = match local_end.addr {
-            None => IpAddress::v4(127, 0, 0, 1),
-            Some(addr) => addr,
+        let addr = match (local_end.addr, iptype) {
+            (None, IpType::Ipv4) => IpAddress::v4(127, 0, 0, 1),
+            (None, IpType::Ipv6) => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1),
+            (Some(addr), _) => addr,
         };
         info!(
             "[tcp]get_sockname local end port: {}, addr = {}",
             port, addr
         );
-        match addr {
-            IpAddress::Ipv4(addr) => {
-                let res = SockAddr::Inet4(SockIpv4::new(port, addr.octets()));
-                return Ok(res);
-            }
-            IpAddress::Ipv6(addr) => {
-                let res = SockAddr::Inet6(SockIpv6::new(port, addr.octets()));
-                return Ok(res);
-            }
-        }
-        Ok(SockAddr::Unspec)
+        Ok(iptype.sockaddr(IpEndpoint::new(addr, port)))
     }
     fn get_peername(&self) -> SysResult<SockAddr> {
         // 获取远程连接节点
         NET_DEV.lock().poll();
+        let iptype = self.sockmeta.lock().iptype;
         let mut binding = SOCKET_SET.lock();
         let socket = binding.get_mut::<tcp::Socket>(self.handle);
-        let remote_end: SockAddr = socket.remote_endpoint().ok_or(Errno::ENOTCONN)?.into();
-        Ok(remote_end)
-    }
-    fn set_keep_alive(&self, action: u32) -> SysResult<()> {
-        match action {
-            1 => self.with_socket(|socket| {
-                let interval = Duration::from_secs(1).into();
-                socket.set_keep_alive(Some(interval));
-            }),
-            _ => {}
-        }
-        Ok(())
+        let remote_end = socket.remote_endpoint().ok_or(Errno::ENOTCONN)?;
+        Ok(iptype.sockaddr(remote_end))
     }
-    fn enable_nagle(&self, action: u32) -> SysResult<()> {
-        match action {
-            1 => {
-                self.with_socket(|socket| {
-                    // nagle算法可以阻塞小packet的发送
-                    socket.set_nagle_enabled(true);
-                })
-            }
-            _ => {}
-        }
+    fn get_sockopts(&self) -> SysResult<SockOpts> {
+        Ok(self.sockmeta.lock().opts)
+    }
+    fn set_sockopts(&self, opts: SockOpts) -> SysResult<()> {
+        self.sockmeta.lock().opts = opts;
+        self.with_socket(|socket| Self::apply_opts(socket, &opts));
         Ok(())
     }
+    fn take_error(&self) -> SysResult<Option<Errno>> {
+        Ok(self.sockmeta.lock().error.take())
+    }
 
     fn get_socktype(&self) -> SysResult<Sock> {
         Ok(Sock::Tcp)
     }
+    fn get_family(&self) -> u16 {
+        self.sockmeta.lock().family()
+    }
+    fn set_v6only(&self, v6only: bool) -> SysResult<()> {
+        self.sockmeta.lock().set_v6only(v6only)
+    }
+    fn get_v6only(&self) -> SysResult<bool> {
+        self.sockmeta.lock().get_v6only()
+    }
 
     async fn pollin(&self) -> SysResult<bool> {
         info!("[TcpSocket::pollin] start");
@@ -496,6 +595,10 @@ impl Socket for TcpSocket {
             return Ok(true);
         }
         let waker = get_waker().await;
+        if self.is_listening() {
+            self.register_accept_waker(&waker);
+            return Ok(false);
+        }
         self.with_socket(|socket| {
             info!(
                 "[TcpSocket::pollin] nothing to read, state {:?}",
@@ -525,4 +628,29 @@ impl Socket for TcpSocket {
     fn get_flags(&self) -> SysResult<OpenFlags> {
         Ok(self.sockmeta.lock().flags)
     }
+
+    /// 发出 FIN 后等待已发送的数据和 FIN 都被对端确认，最多等待 SO_LINGER 设置的时长
+    async fn linger(&self) {
+        let Some(timeout) = self.sockmeta.lock().opts.linger.filter(|t| !t.is_zero()) else {
+            return;
+        };
+        if !self.with_socket(|socket| socket.is_open()) {
+            return;
+        }
+        self.with_socket(|socket| socket.close());
+        let wait_closed = async {
+            loop {
+                NET_DEV.lock().poll();
+                let closing = self.with_socket(|socket| {
+                    matches!(socket.state(), TcpState::FinWait1 | TcpState::Closing | TcpState::LastAck)
+                });
+                if !closing {
+                    return SysResult::Ok(());
+                }
+                yield_now().await;
+            }
+        };
+        // 超时后与 Linux 一样直接返回，剩下的在后台继续关闭
+        let _ = with_timeout(wait_closed, Some(timeout)).await;
+    }
 }
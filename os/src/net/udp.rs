@@ -1,6 +1,6 @@
 use super::{
     addr::{IpType, SockIpv4, SockIpv6, Sock, SockAddr},
-    SockMeta, Socket, AF_INET, BUFF_SIZE, META_SIZE, NET_DEV, PORT_MANAGER, SOCKET_SET,
+    with_timeout, SockMeta, SockOpts, Socket, AF_INET, AF_INET6, BUFF_SIZE, META_SIZE, NET_DEV, PORT_MANAGER, SOCKET_SET,
 };
 use crate::{fs::FileTrait, net::{net_async::UdpRecvFuture, PORT_FD_MANAMER, PORT_START}};
 use crate::{
@@ -108,7 +108,7 @@ impl UdpSocket {
                 }
                 IpType::Ipv6 => {
                     let addr = SockAddr::Inet6(SockIpv6 {
-                        family: AF_INET,
+                        family: AF_INET6,
                         port: 0,
                         flowinfo: 0,
                         addr: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
@@ -150,21 +150,22 @@ impl Socket for UdpSocket {
 
         NET_DEV.lock().poll();
 
-        let mut binding = SOCKET_SET.lock();
-        let socket = binding.get_mut::<udp::Socket>(self.handle);
         let mut endpoint = IpEndpoint::try_from(addr.clone())?;
+        // 指定的端口也要登记到 PortManager，SO_REUSEADDR / SO_REUSEPORT 才能生效
+        let reuse = self.sockmeta.lock().opts.reuse;
+        do_port_aloc(&mut endpoint, Sock::Udp, reuse)?;
 
+        let mut binding = SOCKET_SET.lock();
+        let socket = binding.get_mut::<udp::Socket>(self.handle);
         let mut localpoint;
         let res = if endpoint.addr.is_unspecified() {
-            if endpoint.port == 0 {
-                let p = do_port_aloc(&mut endpoint, Sock::Udp)?;
-            }
             localpoint = IpListenEndpoint::from(endpoint.port);
             socket.bind(localpoint)
         } else {
             localpoint = IpListenEndpoint::from(endpoint);
             socket.bind(localpoint)
         };
+        drop(binding);
 
         match res {
             Ok(_) => {
@@ -178,6 +179,7 @@ impl Socket for UdpSocket {
             }
             Err(_) => {
                 info!("[Udp::bind] bind failed, port may be in use");
+                PORT_MANAGER.lock().dealloc(Sock::Udp, endpoint.port);
                 Err(Errno::EINVAL)
             }
         }
@@ -191,7 +193,7 @@ impl Socket for UdpSocket {
     async fn connect(&self, sockfd: usize, addr: &SockAddr) -> SysResult<()> {
         info!("[Udp::connect] start, connect to remote_addr = {:?}", addr);
         /// 与TCP不同，UDP的connect函数不会引发三次握手，而是将目标IP和端口记录下来
-        let remote_endpoint = IpEndpoint::try_from(addr.clone())?;
+        let remote_endpoint = self.sockmeta.lock().remote_endpoint(addr)?;
         info!("[Udp::connect] now remote end = {:?}", remote_endpoint);
         self.check_addr(sockfd, remote_endpoint);
         self.sockmeta.lock().remote_end = Some(remote_endpoint);
@@ -203,7 +205,7 @@ impl Socket for UdpSocket {
         // 就算有远程地址，也要覆盖，使用提供的dest_addr
         info!("[Udp::send_msg] start, dest_addr = {:?}", dest_addr);
         let remote_endpoint = match dest_addr{
-            Some(addr) => IpEndpoint::try_from(addr)?,
+            Some(addr) => self.sockmeta.lock().remote_endpoint(&addr)?,
             None => {
                 let remote_end = self.sockmeta.lock().remote_end.ok_or(Errno::ENOTCONN)?;
                 remote_end
@@ -211,11 +213,13 @@ impl Socket for UdpSocket {
         };
         info!("[Udp::send_msg] remote_addr = {:?}", remote_endpoint);
 
-        UdpSendFuture::new(buf, self, remote_endpoint).await
+        let timeout = self.sockmeta.lock().opts.send_timeout;
+        with_timeout(UdpSendFuture::new(buf, self, remote_endpoint), timeout).await
     }
     async fn recv_msg(&self, buf: &mut [u8]) -> SysResult<(usize, SockAddr)> {
         info!("[Udp::recv_msg] start");
-        UdpRecvFuture::new(buf, self).await
+        let timeout = self.sockmeta.lock().opts.recv_timeout;
+        with_timeout(UdpRecvFuture::new(buf, self), timeout).await
     }
     fn set_recv_buf_size(&self, size: u32) -> SysResult<()> {
         self.sockmeta.lock().recv_buf_size = size as usize;
@@ -241,63 +245,57 @@ impl Socket for UdpSocket {
         Ok(())
     }
     fn get_sockname(&self) -> SysResult<SockAddr> {
-        let local_end = self
-            .sockmeta
-            .lock()
-            .local_end
-            .expect("[udp] get_sockname no local_end");
+        let (local_end, iptype) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.local_end.expect("[udp] get_sockname no local_end"), sockmeta.iptype)
+        };
         let port = local_end.port;
         // let addr = local_end.addr.unwrap();
-        let addr = match local_end.addr {
-            None => IpAddress::v4(127, 0, 0, 1),
-            Some(addr) => addr,
+        let addr = match (local_end.addr, iptype) {
+            (None, IpType::Ipv4) => IpAddress::v4(127, 0, 0, 1),
+            (None, IpType::Ipv6) => IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1),
+            (Some(addr), _) => addr,
         };
         info!(
             "[udp]get_sockname local end port: {}, addr = {}",
             port, addr
         );
-
-        match addr {
-            IpAddress::Ipv4(addr) => {
-                let res = SockAddr::Inet4(SockIpv4::new(port, addr.octets()));
-                return Ok(res);
-            }
-            IpAddress::Ipv6(addr) => {
-                let res = SockAddr::Inet6(SockIpv6::new(port, addr.octets()));
-                return Ok(res);
-            }
-        }
-        Ok(SockAddr::Unspec)
+        Ok(iptype.sockaddr(IpEndpoint::new(addr, port)))
     }
     fn get_peername(&self) -> SysResult<SockAddr> {
-        let remote_end = self.sockmeta.lock().remote_end.ok_or(Errno::ENOTCONN)?;
-        let port = remote_end.port;
-        let addr = remote_end.addr;
+        let (remote_end, iptype) = {
+            let sockmeta = self.sockmeta.lock();
+            (sockmeta.remote_end.ok_or(Errno::ENOTCONN)?, sockmeta.iptype)
+        };
         info!(
             "[udp]get_peername local end port: {}, addr = {}",
-            port, addr
+            remote_end.port, remote_end.addr
         );
-        match addr {
-            IpAddress::Ipv4(addr) => {
-                let res = SockAddr::Inet4(SockIpv4::new(port, addr.octets()));
-                return Ok(res);
-            }
-            IpAddress::Ipv6(addr) => {
-                let res = SockAddr::Inet6(SockIpv6::new(port, addr.octets()));
-                return Ok(res);
-            }
-        }
-        Ok(SockAddr::Unspec)
+        Ok(iptype.sockaddr(remote_end))
     }
-    fn set_keep_alive(&self, action: u32) -> SysResult<()> {
-        todo!()
+    fn get_sockopts(&self) -> SysResult<SockOpts> {
+        Ok(self.sockmeta.lock().opts)
     }
-    fn enable_nagle(&self, action: u32) -> SysResult<()> {
-        todo!()
+    fn set_sockopts(&self, opts: SockOpts) -> SysResult<()> {
+        self.sockmeta.lock().opts = opts;
+        self.with_socket(|socket| socket.set_hop_limit(Some(opts.ttl)));
+        Ok(())
+    }
+    fn take_error(&self) -> SysResult<Option<Errno>> {
+        Ok(self.sockmeta.lock().error.take())
     }
     fn get_socktype(&self) -> SysResult<Sock> {
         Ok(Sock::Udp)
     }
+    fn get_family(&self) -> u16 {
+        self.sockmeta.lock().family()
+    }
+    fn set_v6only(&self, v6only: bool) -> SysResult<()> {
+        self.sockmeta.lock().set_v6only(v6only)
+    }
+    fn get_v6only(&self) -> SysResult<bool> {
+        self.sockmeta.lock().get_v6only()
+    }
     async fn pollin(&self) -> SysResult<bool> {
         NET_DEV.lock().poll();
         let waker = get_waker().await;
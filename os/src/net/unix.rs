@@ -2,7 +2,7 @@ use core::{error, task::Waker};
 
 use super::{
     addr::{IpType, Sock, SockAddr},
-    SockMeta, Socket,
+    SockMeta, SockOpts, Socket, AF_UNIX,
 };
 use crate::fs::FileTrait;
 // use crate::mm::UserBuffer;
@@ -10,7 +10,7 @@ use crate::{
     fs::{FileMeta, InodeTrait, Kstat, OpenFlags, Page, Pipe, RenameFlags},
     sync::SpinNoIrqLock,
     syscall::ShutHow,
-    utils::SysResult,
+    utils::{Errno, SysResult},
 };
 use alloc::boxed::Box;
 use alloc::{string::String, sync::Arc};
@@ -72,15 +72,27 @@ impl Socket for UnixSocket {
     async fn recv_msg(&self, buf: &mut [u8]) -> SysResult<(usize, SockAddr)> {
         todo!()
     }
-    fn set_keep_alive(&self, action: u32) -> SysResult<()> {
-        todo!()
+    fn get_sockopts(&self) -> SysResult<SockOpts> {
+        Err(Errno::ENOPROTOOPT)
     }
-    fn enable_nagle(&self, action: u32) -> SysResult<()> {
-        todo!()
+    fn set_sockopts(&self, _opts: SockOpts) -> SysResult<()> {
+        Err(Errno::ENOPROTOOPT)
+    }
+    fn take_error(&self) -> SysResult<Option<Errno>> {
+        Ok(None)
     }
     fn get_socktype(&self) -> SysResult<Sock> {
         Ok(Sock::Unix)
     }
+    fn get_family(&self) -> u16 {
+        AF_UNIX
+    }
+    fn set_v6only(&self, _v6only: bool) -> SysResult<()> {
+        Err(Errno::ENOPROTOOPT)
+    }
+    fn get_v6only(&self) -> SysResult<bool> {
+        Err(Errno::ENOPROTOOPT)
+    }
     async fn pollin(&self) -> SysResult<bool> {
         warn!("UnixSocket::pollin not implemented");
         todo!()
@@ -1,8 +1,11 @@
 use crate::{
     hal::trap::__sigret_helper,
     // mm::translated_byte_buffer,
-    signal::{LinuxSigInfo, SigActionFlag, SigHandlerType, SigNom, UContext, SIG_DFL, SIG_IGN},
+    signal::{
+        LinuxSigInfo, SigActionFlag, SigDetails, SigHandlerType, SigNom, UContext, SIG_DFL, SIG_IGN,
+    },
     task::TaskControlBlock,
+    utils::Errno,
 };
 use alloc::sync::Arc;
 use core::alloc::Layout;
@@ -15,6 +18,9 @@ pub fn do_signal(task: &Arc<TaskControlBlock>) {
     let all_len = task.sig_pending.lock().len();
     let mut cur = 0;
     let old_sigmask = *task.get_blocked();
+    // 被信号打断的系统调用：默认处理或者忽略时重新执行，
+    // 进入用户的处理函数时只有 SA_RESTART 才重新执行，否则返回 EINTR
+    let mut restart = task.take_restart_syscall();
 
     loop {
         let siginfo = {
@@ -80,6 +86,11 @@ pub fn do_signal(task: &Arc<TaskControlBlock>) {
 
                 // 可能有其他信号也需要阻塞
                 *task.get_blocked_mut() |= sig_action.sa_mask;
+                if restart && !sig_action.sa_flags.contains(SigActionFlag::SA_RESTART) {
+                    trap_cx.user_gp.a0 = (-(Errno::EINTR as isize)) as usize;
+                    trap_cx.sepc += 4;
+                }
+                restart = false;
                 trap_cx.float_regs.save();
 
                 let old_sp = trap_cx.get_sp();
@@ -118,6 +129,9 @@ pub fn do_signal(task: &Arc<TaskControlBlock>) {
                     // a2(x12): ucontext 结构体指针
                     trap_cx.user_gp.a2 = new_sp; // a2
                     let mut siginfo_v = LinuxSigInfo::new(signo as i32, siginfo.sigcode as i32);
+                    if let SigDetails::Sys { call_addr, syscall, arch } = siginfo.sifields {
+                        siginfo_v.set_sigsys(call_addr, syscall, arch, siginfo.sigerr.bits());
+                    }
                     new_sp -= size_of::<LinuxSigInfo>();
                     // 将siginfo_v拷贝到用户栈中
                     unsafe { core::ptr::write(new_sp as *mut LinuxSigInfo, siginfo_v) };
@@ -167,7 +181,21 @@ fn default_func(task: &Arc<TaskControlBlock>, signo: SigNom) {
 fn do_group_exit(task: &Arc<TaskControlBlock>, signo: SigNom) {
     task.kill_all_thread();
     info!("[do_goroup_exit] signo = {}", signo as i32);
-    task.set_exit_code(signo as i32);
+    // 默认动作是 core dump 的信号，在 wstatus 中设置 WCOREDUMP 位
+    let core_dump = match signo {
+        SigNom::SIGQUIT
+        | SigNom::SIGILL
+        | SigNom::SIGTRAP
+        | SigNom::SIGABRT
+        | SigNom::SIGBUS
+        | SigNom::SIGFPE
+        | SigNom::SIGSEGV
+        | SigNom::SIGXCPU
+        | SigNom::SIGXFSZ
+        | SigNom::SIGSYS => 0x80,
+        _ => 0,
+    };
+    task.set_exit_code(signo as i32 | core_dump);
 }
 
 /// 在 Linux 中，当子进程状态变化时，内核会向父进程发送 SIGCHLD 信号，
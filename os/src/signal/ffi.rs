@@ -263,6 +263,9 @@ pub const SIGBLOCK: usize = 0;
 pub const SIGUNBLOCK: usize = 1;
 pub const SIGSETMASK: usize = 2;
 
+/// SIGSYS 的 si_code：由 seccomp 过滤产生
+pub const SYS_SECCOMP: i32 = 1;
+
 #[derive(Default, Copy, Clone)]
 #[repr(C)]
 pub struct LinuxSigInfo {
@@ -283,4 +286,15 @@ impl LinuxSigInfo {
             _align: [0; 0],
         }
     }
+
+    /// 填写 SIGSYS 的 si_call_addr、si_syscall 和 si_arch，
+    /// 它们位于 8 字节对齐的联合体中，_pad[0] 是对齐填充
+    pub fn set_sigsys(&mut self, call_addr: usize, syscall: i32, arch: u32, errno: i32) {
+        self.si_code = SYS_SECCOMP;
+        self.si_errno = errno;
+        self._pad[1] = call_addr as u32 as i32;
+        self._pad[2] = (call_addr >> 32) as i32;
+        self._pad[3] = syscall;
+        self._pad[4] = arch as i32;
+    }
 }
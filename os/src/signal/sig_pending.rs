@@ -31,7 +31,7 @@ pub struct SigInfo {
 pub enum SigDetails {
     Kill {
         pid: usize, // 发送信号的进程ID
-        uid: usize, // 发送信号的用户ID, 目前不用管
+        uid: usize, // 发送信号的进程的 real uid
     },
 
     Chld {
@@ -40,6 +40,13 @@ pub enum SigDetails {
         exit_code: i32,     // 退出码
     },
 
+    /// seccomp 的 SECCOMP_RET_TRAP 产生的 SIGSYS
+    Sys {
+        call_addr: usize, // 触发过滤的系统调用指令地址
+        syscall: i32,     // 系统调用号
+        arch: u32,        // AUDIT_ARCH_*
+    },
+
     None,
 }
 
@@ -62,13 +62,30 @@ impl Utsname {
 #[allow(unused)]
 #[allow(non_camel_case_types)]
 pub enum SysCode {
+    SYSCALL_SETXATTR = 5,
+    SYSCALL_LSETXATTR = 6,
+    SYSCALL_FSETXATTR = 7,
+    SYSCALL_GETXATTR = 8,
+    SYSCALL_LGETXATTR = 9,
+    SYSCALL_FGETXATTR = 10,
+    SYSCALL_LISTXATTR = 11,
+    SYSCALL_LLISTXATTR = 12,
+    SYSCALL_FLISTXATTR = 13,
+    SYSCALL_REMOVEXATTR = 14,
+    SYSCALL_LREMOVEXATTR = 15,
+    SYSCALL_FREMOVEXATTR = 16,
     SYSCALL_GETCWD = 17,
     SYSCALL_DUP = 23,
     SYSCALL_DUP3 = 24,
     SYSCALL_FCNTL = 25,
+    SYSCALL_INOTIFY_INIT1 = 26,
+    SYSCALL_INOTIFY_ADD_WATCH = 27,
+    SYSCALL_INOTIFY_RM_WATCH = 28,
     SYSCALL_IOCTL = 29,
+    SYSCALL_FLOCK = 32,
     SYSCALL_MKDIRAT = 34,
     SYSCALL_UNLINKAT = 35,
+    SYSCALL_SYMLINKAT = 36,
     SYSCALL_LINKAT = 37,
     SYSCALL_UMOUNT2 = 39,
     SYSCALL_MOUNT = 40,
@@ -78,8 +95,10 @@ pub enum SysCode {
     SYSCALL_FACCESSAT = 48,
     SYSCALL_CHDIR = 49,
     SYSCALL_FCHDIR = 50,
+    SYSCALL_FCHMOD = 52,
     SYSCALL_FCHMODAT = 53,
     SYSCALL_FCHOWNAT = 54,
+    SYSCALL_FCHOWN = 55,
     SYSCALL_OPENAT = 56,
     SYSCALL_CLOSE = 57,
     SYSCALL_PIPE2 = 59,
@@ -94,15 +113,20 @@ pub enum SysCode {
     SYSCALL_SENDFILE = 71,
     SYSCALL_PSELECT = 72,
     SYSCALL_PPOLL = 73,
+    SYSCALL_VMSPLICE = 75,
     SYSCALL_SPLICE = 76,
+    SYSCALL_TEE = 77,
     SYSCALL_READLINKAT = 78,
     SYSCALL_FSTATAT = 79,
     SYSCALL_FSTAT = 80,
     SYSCALL_SYNC = 81,
     SYSCALL_FSYNC = 82,
     SYSCALL_UTIMENSAT = 88,
+    SYSCALL_CAPGET = 90,
+    SYSCALL_CAPSET = 91,
     SYSCALL_EXIT = 93,
     SYSCALL_EXIT_GROUP = 94,
+    SYSCALL_WAITID = 95,
     SYSCALL_SET_TID_ADDRESS = 96,
     SYSCALL_FUTEX = 98,
     SYSCALL_SET_ROBUST_LIST = 99,
@@ -127,18 +151,29 @@ pub enum SysCode {
     SYSCALL_SIGPROCMASK = 135,
     SYSCALL_SIGTIMEDWAIT = 137,
     SYSCALL_SIGRETURN = 139,
+    SYSCALL_SETREGID = 143,
     SYSCALL_SETGID = 144,
+    SYSCALL_SETREUID = 145,
     SYSCALL_SETUID = 146,
     SYSCALL_SETRESUID = 147,
+    SYSCALL_GETRESUID = 148,
+    SYSCALL_SETRESGID = 149,
+    SYSCALL_GETRESGID = 150,
+    SYSCALL_SETFSUID = 151,
+    SYSCALL_SETFSGID = 152,
     SYSCALL_TIMES = 153,
     SYSCALL_SETPGID = 154,
     SYSCALL_GETPGID = 155,
+    SYSCALL_GETSID = 156,
     SYSCALL_SETSID = 157,
+    SYSCALL_GETGROUPS = 158,
+    SYSCALL_SETGROUPS = 159,
     SYSCALL_UNAME = 160,
     SYSCALL_SETHOSTNAME = 161,
     SYSCALL_SETDOMINNAME = 162,
     SYSCALL_GETRUSAGE = 165,
     SYSCALL_UMASK = 166,
+    SYSCALL_PRCTL = 167,
     SYSCALL_GETTIMEOFDAY = 169,
     SYSCALL_GETPID = 172,
     SYSCALL_GETPPID = 173,
@@ -178,7 +213,9 @@ pub enum SysCode {
     SYSCALL_ACCEPT4 = 242,
     SYSCALL_WAIT4 = 260,
     SYSCALL_FANOTIFY_INIT = 262,
+    SYSCALL_FANOTIFY_MARK = 263,
     SYSCALL_RENAMEAT2 = 276,
+    SYSCALL_SECCOMP = 277,
     SYSCALL_PRLIMIT64 = 261,
     GETRANDOM = 278,
     SYSCALL_MEMFD_CREATE = 279,
@@ -187,7 +224,13 @@ pub enum SysCode {
     SYSCALL_PREADV2 = 286,
     SYSCALL_PWRITEV2 = 287,
     SYS_STATX = 291,
+    SYSCALL_PIDFD_SEND_SIGNAL = 424,
+    SYSCALL_IO_URING_SETUP = 425,
+    SYSCALL_IO_URING_ENTER = 426,
+    SYSCALL_PIDFD_OPEN = 434,
     SYSCALL_CLONE3 = 435,
+    SYSCALL_PIDFD_GETFD = 438,
+    SYSCALL_FCHMODAT2 = 452,
     #[num_enum(default)]
     SYSCALL_UNKNOWN,
 }
@@ -205,11 +248,30 @@ impl SysCode {
             Self::SYSCALL_PWRITEV2 => "pwritev2",
             Self::SYSCALL_PREADV2 => "preadv2",
             Self::SYSCALL_FANOTIFY_INIT => "fanotify_init",
+            Self::SYSCALL_FANOTIFY_MARK => "fanotify_mark",
+            Self::SYSCALL_FLOCK => "flock",
+            Self::SYSCALL_SETXATTR => "setxattr",
+            Self::SYSCALL_LSETXATTR => "lsetxattr",
+            Self::SYSCALL_FSETXATTR => "fsetxattr",
+            Self::SYSCALL_GETXATTR => "getxattr",
+            Self::SYSCALL_LGETXATTR => "lgetxattr",
+            Self::SYSCALL_FGETXATTR => "fgetxattr",
+            Self::SYSCALL_LISTXATTR => "listxattr",
+            Self::SYSCALL_LLISTXATTR => "llistxattr",
+            Self::SYSCALL_FLISTXATTR => "flistxattr",
+            Self::SYSCALL_REMOVEXATTR => "removexattr",
+            Self::SYSCALL_LREMOVEXATTR => "lremovexattr",
+            Self::SYSCALL_FREMOVEXATTR => "fremovexattr",
+            Self::SYSCALL_INOTIFY_INIT1 => "inotify_init1",
+            Self::SYSCALL_INOTIFY_ADD_WATCH => "inotify_add_watch",
+            Self::SYSCALL_INOTIFY_RM_WATCH => "inotify_rm_watch",
             Self::SYSCALL_MEMFD_CREATE => "memfd_create",
             Self::SYSCALL_SETDOMINNAME => "setdominname",
             Self::SYSCALL_SETHOSTNAME => "sethostname",
             Self::SYSCALL_CLONE3 => "clone3",
             Self::SYSCALL_SPLICE => "splice",
+            Self::SYSCALL_VMSPLICE => "vmsplice",
+            Self::SYSCALL_TEE => "tee",
             Self::SYSCALL_SCHED_GETPARAM => "sched_getparam",
             Self::SYSCALL_SCHED_SETPARAM => "sched_setparam",
             Self::SYSCALL_SETRESUID => "setresuid",
@@ -276,6 +338,7 @@ impl SysCode {
             Self::SYSCALL_PREAD64 => "pread64",
             Self::SYSCALL_PWRITE64 => "pwrite64",
             Self::SYSCALL_FCHMODAT => "fchmodat",
+            Self::SYSCALL_FCHMODAT2 => "fchmodat2",
             Self::SYSCALL_FTRUNCATE64 => "ftruncate64",
             Self::SYSCALL_FCNTL => "fcntl",
             Self::SYSCALL_WRITEV => "writev",
@@ -297,6 +360,7 @@ impl SysCode {
             Self::SYSCALL_DUP3 => "dup3",
             Self::SYSCALL_MKDIRAT => "mkdirat",
             Self::SYSCALL_UNLINKAT => "unlinkat",
+            Self::SYSCALL_SYMLINKAT => "symlinkat",
             Self::SYSCALL_LINKAT => "linkat",
             Self::SYSCALL_UMOUNT2 => "umount2",
             Self::SYSCALL_MOUNT => "mount",
@@ -323,6 +387,28 @@ impl SysCode {
             Self::SYSCALL_MMAP => "mmap",
             Self::SYSCALL_MPROTECT => "mprotect",
             Self::SYSCALL_WAIT4 => "wait4",
+            Self::SYSCALL_PIDFD_SEND_SIGNAL => "pidfd_send_signal",
+            Self::SYSCALL_IO_URING_SETUP => "io_uring_setup",
+            Self::SYSCALL_IO_URING_ENTER => "io_uring_enter",
+            Self::SYSCALL_PIDFD_OPEN => "pidfd_open",
+            Self::SYSCALL_PIDFD_GETFD => "pidfd_getfd",
+            Self::SYSCALL_WAITID => "waitid",
+            Self::SYSCALL_GETSID => "getsid",
+            Self::SYSCALL_SETREGID => "setregid",
+            Self::SYSCALL_SETREUID => "setreuid",
+            Self::SYSCALL_GETRESUID => "getresuid",
+            Self::SYSCALL_SETRESGID => "setresgid",
+            Self::SYSCALL_GETRESGID => "getresgid",
+            Self::SYSCALL_SETFSUID => "setfsuid",
+            Self::SYSCALL_SETFSGID => "setfsgid",
+            Self::SYSCALL_GETGROUPS => "getgroups",
+            Self::SYSCALL_SETGROUPS => "setgroups",
+            Self::SYSCALL_FCHMOD => "fchmod",
+            Self::SYSCALL_FCHOWN => "fchown",
+            Self::SYSCALL_CAPGET => "capget",
+            Self::SYSCALL_CAPSET => "capset",
+            Self::SYSCALL_PRCTL => "prctl",
+            Self::SYSCALL_SECCOMP => "seccomp",
             Self::SYSCALL_UNKNOWN => "unknown",
             Self::GETRANDOM => "getrandom",
             Self::SYS_STATX => "statx",
@@ -401,12 +487,120 @@ bitflags! {
         const WUNTRACED = 1 << 1;
         /// 返回那些因收到SIGCONT信号而恢复执行并且已经停止的子进程信息
         const WCONTINUED = 1 << 3;
+        /// waitid 使用，和 WUNTRACED 相同
+        const WSTOPPED = 1 << 1;
+        /// waitid 使用，等待已经退出的子进程
+        const WEXITED = 1 << 2;
+        /// 只获取状态，不回收子进程，之后还可以再次 wait
+        const WNOWAIT = 0x0100_0000;
+        /// 不等待同一线程组中其他线程的子进程
+        const __WNOTHREAD = 0x2000_0000;
+        /// 等待所有子进程，不论是不是 clone 出来的
+        const __WALL = 0x4000_0000;
+        /// 只等待 clone 出来的子进程
+        const __WCLONE = 0x8000_0000u32 as i32;
+    }
+}
+
+/// waitid 的 idtype：等待任意子进程
+pub const P_ALL: u32 = 0;
+/// waitid 的 idtype：等待 pid 为 id 的子进程
+pub const P_PID: u32 = 1;
+/// waitid 的 idtype：等待进程组为 id 的子进程
+pub const P_PGID: u32 = 2;
+/// waitid 的 idtype：等待 pidfd 为 id 的子进程
+pub const P_PIDFD: u32 = 3;
+
+/// capget/capset 的接口版本，v1 只有 32 位能力集，v2 和 v3 用两个结构体表示 64 位
+pub const LINUX_CAPABILITY_VERSION_1: u32 = 0x1998_0330;
+pub const LINUX_CAPABILITY_VERSION_2: u32 = 0x2007_1026;
+pub const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// capget/capset 的第一个参数
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct CapUserHeader {
+    pub version: u32,
+    pub pid: i32,
+}
+
+/// capget/capset 的第二个参数，每个结构体保存 32 个能力
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct CapUserData {
+    pub effective: u32,
+    pub permitted: u32,
+    pub inheritable: u32,
+}
+
+/// seccomp 的 operation
+pub const SECCOMP_SET_MODE_STRICT: u32 = 0;
+pub const SECCOMP_SET_MODE_FILTER: u32 = 1;
+pub const SECCOMP_GET_ACTION_AVAIL: u32 = 2;
+
+bitflags! {
+    /// SECCOMP_SET_MODE_FILTER 的 flags
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SeccompFlags: u32 {
+        /// 同步到线程组中的所有线程
+        const TSYNC = 1 << 0;
+        /// 记录除 ALLOW 以外的动作，这里没有审计日志，忽略
+        const LOG = 1 << 1;
+        /// 不启用推测执行缓解，这里忽略
+        const SPEC_ALLOW = 1 << 2;
+    }
+}
+
+/// struct sock_fprog：BPF 程序的长度和指令数组地址
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SockFprog {
+    pub len: u16,
+    pub filter: usize,
+}
+
+/// prctl 的 option
+pub const PR_SET_PDEATHSIG: usize = 1;
+pub const PR_GET_PDEATHSIG: usize = 2;
+pub const PR_GET_DUMPABLE: usize = 3;
+pub const PR_SET_DUMPABLE: usize = 4;
+pub const PR_SET_NAME: usize = 15;
+pub const PR_GET_NAME: usize = 16;
+pub const PR_GET_SECCOMP: usize = 21;
+pub const PR_SET_SECCOMP: usize = 22;
+pub const PR_CAPBSET_READ: usize = 23;
+pub const PR_CAPBSET_DROP: usize = 24;
+pub const PR_SET_CHILD_SUBREAPER: usize = 36;
+pub const PR_GET_CHILD_SUBREAPER: usize = 37;
+pub const PR_SET_NO_NEW_PRIVS: usize = 38;
+pub const PR_GET_NO_NEW_PRIVS: usize = 39;
+pub const PR_CAP_AMBIENT: usize = 47;
+
+/// PR_CAP_AMBIENT 的子命令
+pub const PR_CAP_AMBIENT_IS_SET: usize = 1;
+pub const PR_CAP_AMBIENT_RAISE: usize = 2;
+pub const PR_CAP_AMBIENT_LOWER: usize = 3;
+pub const PR_CAP_AMBIENT_CLEAR_ALL: usize = 4;
+
+bitflags! {
+    /// getrandom 的 flags
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct GetRandomFlags: u32 {
+        /// CRNG 尚未就绪时返回 EAGAIN 而不是阻塞
+        const GRND_NONBLOCK = 1 << 0;
+        /// 从 /dev/random 取数，就绪后与 /dev/urandom 相同
+        const GRND_RANDOM = 1 << 1;
+        /// 不等待 CRNG 就绪
+        const GRND_INSECURE = 1 << 2;
     }
 }
 
 /// 允许删除目录（通常与unlinkat等系统调用一起使用）
 pub const AT_REMOVEDIR: u32 = 0x200;
 
+/// 不跟随符号链接（即操作符号链接本身而非其指向的目标）
+pub const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
 /// 跟随符号链接（即操作符号链接指向的目标文件）
 pub const AT_SYMLINK_FOLLOW: u32 = 0x400;
 
@@ -498,6 +692,24 @@ pub struct IoVec {
     pub iov_len: usize,
 }
 
+/// readv、writev、vmsplice 一次最多接受的 iovec 个数
+pub const IOV_MAX: usize = 1024;
+
+/// fcntl 记录锁使用的 struct flock
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Flock {
+    /// F_RDLCK、F_WRLCK 或 F_UNLCK
+    pub l_type: i16,
+    /// l_start 的起点：SEEK_SET、SEEK_CUR 或 SEEK_END
+    pub l_whence: i16,
+    pub l_start: i64,
+    /// 为 0 表示一直锁到文件末尾，为负数表示锁 l_start 之前的部分
+    pub l_len: i64,
+    /// F_GETLK 返回持有冲突锁的进程，OFD 锁为 -1
+    pub l_pid: i32,
+}
+
 bitflags! {
     #[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
     pub struct FcntlFlags: u32 {
@@ -513,6 +725,51 @@ bitflags! {
         const F_GETFL = 3;
         /// 设置文件状态
         const F_SETFL = 4;
+        /// 查询会阻止加锁的 POSIX 锁
+        const F_GETLK = 5;
+        /// 加 POSIX 锁或解锁，冲突时返回 EAGAIN
+        const F_SETLK = 6;
+        /// 加 POSIX 锁或解锁，冲突时等待
+        const F_SETLKW = 7;
+        /// F_GETLK/F_SETLK/F_SETLKW 的 OFD 版本，锁属于打开的文件而不是进程
+        const F_OFD_GETLK = 36;
+        const F_OFD_SETLK = 37;
+        const F_OFD_SETLKW = 38;
+        /// 给 memfd 添加 seal
+        const F_ADD_SEALS = 1033;
+        /// 获取 memfd 当前的 seal
+        const F_GET_SEALS = 1034;
+    }
+
+    /// flock 的 operation
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub struct FlockOp: u32 {
+        const LOCK_SH = 1;
+        const LOCK_EX = 2;
+        /// 不等待，冲突时返回 EWOULDBLOCK
+        const LOCK_NB = 4;
+        const LOCK_UN = 8;
+    }
+
+    /// memfd_create 的 flags
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub struct MemfdFlags: u32 {
+        const MFD_CLOEXEC = 0x0001;
+        const MFD_ALLOW_SEALING = 0x0002;
+        const MFD_HUGETLB = 0x0004;
+    }
+
+    /// splice、tee、vmsplice 的 flags
+    #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+    pub struct SpliceFlags: u32 {
+        /// 尽量移动页而不是复制（这里总是只传递页引用）
+        const SPLICE_F_MOVE = 0x01;
+        /// 管道上的操作不阻塞
+        const SPLICE_F_NONBLOCK = 0x02;
+        /// 之后还有更多数据
+        const SPLICE_F_MORE = 0x04;
+        /// vmsplice 把用户页交给内核
+        const SPLICE_F_GIFT = 0x08;
     }
 
     #[derive(PartialEq, Eq, Debug)]
@@ -546,19 +803,32 @@ bitflags! {
 
 pub const SOL_SOCKET: u8 = 1;
 pub const SOL_TCP: u8 = 6;
+pub const SOL_IPV6: u8 = 41;
 
 /// 如果协议是TCP，并且当前的套接字状态不是侦听(listen)或关闭(close)，
 /// 那么，当option_value不是零时，启用TCP保活定时 器，否则关闭保活定时器。
 pub const SO_KEEPALIVE: u32 = 9; // 设置是否保持连接
+pub const SO_REUSEADDR: u32 = 2; // 允许与设置了同一选项的套接字共用端口
+pub const SO_ERROR: u32 = 4; // 读取并清除套接字上的错误
 pub const SO_SNDBUF: u32 = 7; // 设置发送缓冲区大小
 pub const SO_RCVBUF: u32 = 8; // 设置接收缓冲区大小
 pub const SO_OOBINLINE: u32 = 10; // 用于处理TCP紧急数据的一个设置
+pub const SO_LINGER: u32 = 13; // close 时如何处理未发送的数据
+pub const SO_REUSEPORT: u32 = 15; // 允许与设置了同一选项的套接字共用端口
 pub const SO_RCVTIMEO: u32 = 20; // 设置接收超时时间
+pub const SO_SNDTIMEO: u32 = 21; // 设置发送超时时间
 pub const MAXSEGMENT: u32 = 2; // 限制TCP 最大段大小 MSS
+pub const TCP_KEEPIDLE: u32 = 4; // 连接空闲多久后开始发送保活报文
+pub const TCP_KEEPINTVL: u32 = 5; // 保活报文的间隔
 pub const CONGESTION: u32 = 13; // 拥塞控制算法
 pub const NODELAY: u32 = 1; // 关闭Nagle算法
+pub const IP_TTL: u32 = 2; // 发出报文的 TTL
+/// TCP_KEEPIDLE / TCP_KEEPINTVL 允许的最大秒数
+pub const MAX_TCP_KEEPIDLE: u32 = 32767;
 pub const IPPROTO_IP: u8 = 0;
 pub const IPPROTO_TCP: u8 = 6;
+/// 置位后 AF_INET6 套接字只收发 IPv6，不再通过 v4-mapped 地址与 IPv4 通信
+pub const IPV6_V6ONLY: u32 = 26;
 
 /// 主要用于ppoll系统调用
 #[repr(C)]
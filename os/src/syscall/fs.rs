@@ -1,26 +1,36 @@
-use crate::fs::ext4::{Ext4Inode, NormalFile};
-use crate::fs::fanotify::{FanEventFlags, FanFlags, FanMarkFlags};
+use crate::fs::ext4::NormalFile;
+use crate::fs::fanotify::{
+    FanEventFlags, FanFlags, FanMarkFlags, Fanotify, FAN_EVENT_ON_CHILD, FAN_FD_EVENTS, FAN_ONDIR,
+};
+use crate::fs::fsnotify::{fsnotify, fsnotify_delete, fsnotify_move, FsEventMask};
+use crate::fs::inotify::{Inotify, InotifyFlags, IN_DONT_FOLLOW, IN_ONLYDIR};
+use crate::fs::locks::{lock_acquire, lock_test, FileLock, LockOwner, LockRequest, LockType};
+use crate::fs::memfd::{MemfdFile, MemfdInode, SealFlags};
+use crate::fs::xattr::{xattr_name, XattrFlags, XATTR_LIST_MAX, XATTR_SIZE_MAX};
 use crate::fs::{
-    chdir, mkdir, open, resolve_path, AbsPath, Dentry, Dirent, FileClass, FileTrait, InodeType,
-    Kstat, ModeFlag, MountFlags, OpenFlags, Pipe, RenameFlags, StMode, Statx, StxMask,
-    UmountFlags, MNT_TABLE, SEEK_CUR,
+    chdir, may_create, may_open, may_unlink, mkdir, open, open_resolved, resolve_path, symlink, AbsPath,
+    Dentry, Dirent, FileClass, FileTrait, InodeTrait, InodeType, Kstat, MayAccess, MountFlags, OpenFlags,
+    Pipe, RenameFlags, Statx, StxMask, UmountFlags, MNT_TABLE, copy_pages, pipe_to_pipe,
+    splice_from_pipe, splice_to_pipe, SEEK_CUR, SEEK_END, SEEK_SET,
 };
-use crate::hal::config::{AT_FDCWD, PAGE_SIZE, PATH_MAX, RLIMIT_NOFILE, USER_SPACE_TOP};
-use crate::mm::user_ptr::{check_readable, user_cstr, user_ref_mut, user_slice, user_slice_mut};
+use crate::hal::config::{AT_FDCWD, PATH_MAX, RLIMIT_NOFILE, USER_SPACE_TOP};
+use crate::mm::user_ptr::{check_readable, user_cstr, user_ref, user_ref_mut, user_slice, user_slice_mut};
 // use crate::mm::{translated_byte_buffer, translated_refmut, translated_str};
 use crate::net::PORT_FD_MANAMER;
 use crate::sync::time::{UTIME_NOW, UTIME_OMIT};
 use crate::sync::{time_duration, TimeSpec, TimeStamp, CLOCK_MANAGER};
-use crate::syscall::ffi::{FaccessatMode, FcntlArgFlags, FcntlFlags, IoVec, StatFs, AT_REMOVEDIR};
+use crate::syscall::ffi::{
+    FaccessatFlags, FaccessatMode, FcntlArgFlags, FcntlFlags, Flock, FlockOp, IoVec, MemfdFlags,
+    SpliceFlags, StatFs, AT_REMOVEDIR, AT_SYMLINK_FOLLOW, AT_SYMLINK_NOFOLLOW, IOV_MAX,
+};
 // use crate::syscall::process::GLOBAL_UID;
-use crate::task::{current_task, current_user_token, FdInfo, FdTable};
+use crate::task::{capable, current_task, current_user_token, CapSet, FdInfo, FdTable};
 use crate::utils::downcast::Downcast;
 use crate::utils::{backtrace, Errno, SysResult};
 use alloc::boxed::Box;
 use alloc::ffi::CString;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
-use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::SyncUnsafeCell;
 use core::cmp::{max, min};
@@ -186,7 +196,6 @@ pub async fn sys_writev(fd: usize, iov: usize, iovcnt: usize) -> SysResult<usize
 /// ```
 pub fn sys_fstatat(dirfd: isize, pathname: usize, statbuf: usize, flags: u32) -> SysResult<usize> {
     let AT_EMPTY_PATH: u32 = 0x1000;
-    let AT_SYMLINK_NOFOLLOW: usize = 0x100;
 
     let task = current_task().unwrap();
     let ptr = statbuf as *mut Kstat;
@@ -197,6 +206,7 @@ pub fn sys_fstatat(dirfd: isize, pathname: usize, statbuf: usize, flags: u32) ->
             if (flags & AT_EMPTY_PATH) != 0 {
                 let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
                 file.fstat(&mut tempstat)?;
+                file.metadata().inode.metadata().fill_attr(&mut tempstat);
                 info!("[sys_fstatat] res: {:?}", &tempstat);
                 unsafe {
                     core::ptr::write(ptr, tempstat);
@@ -234,13 +244,19 @@ pub fn sys_fstatat(dirfd: isize, pathname: usize, statbuf: usize, flags: u32) ->
 
     info!("[sys_fstatat] pathname {:?},", target_path);
 
+    // AT_SYMLINK_NOFOLLOW 与 lstat 相同，以 O_PATH 打开链接本身
+    let open_flags = match flags & AT_SYMLINK_NOFOLLOW {
+        0 => OpenFlags::O_RDONLY,
+        _ => OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW,
+    };
     // 检查路径是否有效并打开文件
-    match open(target_path, OpenFlags::O_RDONLY) {
+    match open(target_path, open_flags) {
         Ok(file) => {
             if unlikely(!file.metadata().inode.is_valid()) {
                 return Err(Errno::ENOENT);
             }
             file.fstat(&mut tempstat)?;
+            file.metadata().inode.metadata().fill_attr(&mut tempstat);
             info!("[sys_fstatat] res: {:?}", &tempstat);
             unsafe {
                 core::ptr::write(ptr, tempstat);
@@ -255,6 +271,7 @@ pub fn sys_fstatat(dirfd: isize, pathname: usize, statbuf: usize, flags: u32) ->
         //     }
         //     return Ok(0);
         // }
+        Err(e @ (Errno::EACCES | Errno::ELOOP)) => return Err(e),
         _ => return Err(Errno::ENOENT),
     }
 }
@@ -283,6 +300,7 @@ pub fn sys_fstat(fd: usize, kst: usize) -> SysResult<usize> {
     let mut stat = Kstat::new();
     let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
     file.fstat(&mut stat)?;
+    file.metadata().inode.metadata().fill_attr(&mut stat);
     info!("fstat finished fd: {}, stat: {:?}", fd, stat);
     unsafe {
         core::ptr::write(ptr, stat);
@@ -358,10 +376,15 @@ pub fn sys_statx(
     };
 
     let mut stat = Kstat::new();
+    let open_flags = match flags & AT_SYMLINK_NOFOLLOW {
+        0 => OpenFlags::O_RDONLY,
+        _ => OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW,
+    };
     // 检查路径是否有效并打开文件
-    match open(target_path, OpenFlags::O_RDONLY) {
+    match open(target_path, open_flags) {
         Ok(file) => {
             file.fstat(&mut stat)?;
+            file.metadata().inode.metadata().fill_attr(&mut stat);
             let mut statx: Statx = stat.into();
             statx.set_mask(mask);
             unsafe {
@@ -418,10 +441,15 @@ pub fn sys_openat(fd: isize, path: usize, flags: u32, _mode: usize) -> SysResult
         // info!("[sys_openat] other cwd = {}", other_cwd);
         resolve_path(other_cwd, path.clone())
     };
+    let target_path = target_path.follow(!flags.contains(OpenFlags::O_NOFOLLOW))?;
 
+    may_open(&target_path, flags)?;
     // 检查路径是否有效并打开文件
-    match open(target_path, flags) {
+    match open_resolved(target_path, flags) {
         Ok(file) => {
+            if let Ok(file) = file.clone().downcast_arc::<NormalFile>() {
+                file.notify_open();
+            }
             let fd = task.alloc_fd(FdInfo::new(file, flags))?;
             info!("[sys_openat] finished path = {}, flags = {:?}", path, flags);
             info!(
@@ -442,7 +470,7 @@ pub fn sys_openat(fd: isize, path: usize, flags: u32, _mode: usize) -> SysResult
     }
 }
 
-pub fn sys_close(fd: usize) -> SysResult<usize> {
+pub async fn sys_close(fd: usize) -> SysResult<usize> {
     let task = current_task().unwrap();
     info!(
         "[sys_close] start, pid = {}, closed fd = {}",
@@ -454,7 +482,14 @@ pub fn sys_close(fd: usize) -> SysResult<usize> {
     }
 
     // 删除对应的fd
+    let file = task.get_file_by_fd(fd);
     task.remove_fd(fd)?;
+    // 关闭的是套接字的最后一个文件描述符时，SO_LINGER 可能要求等待连接关闭
+    if let Some(file) = file.filter(|file| Arc::strong_count(file) == 1) {
+        if let Ok(socket) = file.get_socket() {
+            socket.linger().await;
+        }
+    }
     Ok(0)
 }
 
@@ -678,6 +713,8 @@ pub fn sys_mkdirat(dirfd: isize, path: usize, mode: usize) -> SysResult<usize> {
         let other_cwd = file.abspath();
         resolve_path(other_cwd, path)
     };
+    // 已经存在的符号链接不跟随，mkdir 返回 EEXIST
+    let target_path = target_path.follow(false)?;
     // info!("sys_mkdirat target_path is {}", target_path);
 
     // TODO
@@ -685,6 +722,11 @@ pub fn sys_mkdirat(dirfd: isize, path: usize, mode: usize) -> SysResult<usize> {
     // ENOSPC:没有足够的空间;ENAMETOOLONG:路径过长;ENOTDIR:不是目录;
     // ELOOP:符号链接过多;ENOSPC:没有足够的空间;EFAULT:路径错误;等
 
+    let parent = Dentry::get_inode_from_path(&target_path.get_parent_abs())?;
+    if Dentry::get_inode_from_path(&target_path.get()).is_err() {
+        may_create(&parent)?;
+    }
+
     // 检查路径是否有效并创建目录
     match mkdir(target_path, mode) {
         Ok(_) => Ok(0), // 成功
@@ -698,6 +740,9 @@ pub fn sys_mkdirat(dirfd: isize, path: usize, mode: usize) -> SysResult<usize> {
 /// Success: 0; Fail: 返回-1
 pub fn sys_umount2(target: usize, flags: u32) -> SysResult<usize> {
     info!("[sys_umount2] start");
+    if !capable(CapSet::CAP_SYS_ADMIN) {
+        return Err(Errno::EPERM);
+    }
     let ufg = UmountFlags::from_bits(flags as u32).ok_or(Errno::EINVAL)?;
     if ufg.contains(UmountFlags::MNT_EXPIRE)
         && (ufg.contains(UmountFlags::MNT_DETACH) || ufg.contains(UmountFlags::MNT_FORCE))
@@ -724,6 +769,9 @@ pub fn sys_mount(
     data: usize,
 ) -> SysResult<usize> {
     info!("[sys_mount] start");
+    if !capable(CapSet::CAP_SYS_ADMIN) {
+        return Err(Errno::EPERM);
+    }
     if unlikely(source == 0 || target == 0 || fstype == 0) {
         return Err(Errno::EFAULT);
     }
@@ -790,7 +838,7 @@ pub fn sys_chdir(path: usize) -> SysResult<usize> {
     let current_path = task.get_current_path();
 
     // 计算新路径
-    let target_path = resolve_path(current_path, path);
+    let target_path = resolve_path(current_path, path).follow(true)?;
 
     // 检查路径是否有效
     chdir(target_path.clone())?;
@@ -808,9 +856,10 @@ pub fn sys_unlinkat(fd: isize, path: usize, flags: u32) -> SysResult<usize> {
         fd, base, path, flags
     );
 
-    let target_path = resolve_path(base, path);
+    // 删除的是链接本身，只跟随路径中间的符号链接
+    let target_path = resolve_path(base, path).follow(false)?;
 
-    match open(target_path.clone(), OpenFlags::O_RDWR) {
+    match open(target_path.clone(), OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW) {
         Ok(file) => {
             let is_dir = file.metadata().inode.metadata()._type.is_dir();
             if is_dir && flags != AT_REMOVEDIR {
@@ -819,8 +868,10 @@ pub fn sys_unlinkat(fd: isize, path: usize, flags: u32) -> SysResult<usize> {
             if flags == AT_REMOVEDIR && !is_dir {
                 return Err(Errno::ENOTDIR);
             }
+            may_unlink(&target_path)?;
             let target_dentry = Dentry::get_dentry_from_path(&target_path.get())?;
             file.metadata().inode.unlink(target_dentry)?;
+            fsnotify_delete(&target_path.get(), is_dir);
             // drop(target_dentry);
             // error!("[unlink] path: {}, inode ref count: {}", target_path.get() , Arc::strong_count(&file.get_inode()));
         }
@@ -843,9 +894,10 @@ pub fn ksys_unlinkat(fd: isize, path: String, flags: u32) -> SysResult<usize> {
         fd, base, path, flags
     );
 
-    let target_path = resolve_path(base, path);
+    // 删除的是链接本身，只跟随路径中间的符号链接
+    let target_path = resolve_path(base, path).follow(false)?;
 
-    match open(target_path.clone(), OpenFlags::O_RDWR) {
+    match open(target_path.clone(), OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW) {
         Ok(file) => {
             let is_dir = file.metadata().inode.metadata()._type.is_dir();
             if is_dir && flags != AT_REMOVEDIR {
@@ -909,17 +961,26 @@ pub fn sys_renameat2(
             }
         }
     };
+    // 改名的是链接本身，只跟随路径中间的符号链接
+    let old_path = old_path.follow(false)?;
+    let new_path = new_path.follow(false)?;
+    // 源目录和目标目录都需要写权限，目标已存在时还要能删除它
+    may_unlink(&old_path)?;
+    match Dentry::get_inode_from_path(&new_path.get()) {
+        Ok(_) => may_unlink(&new_path)?,
+        Err(_) => may_create(&Dentry::get_inode_from_path(&new_path.get_parent_abs())?)?,
+    }
     // 简单的实现, 当目标路径存在文件的时候就返回存在
     // FIX: 如果目标文件存在就删除
     // BUG: 注意到可能存在并发 bug，因为 git 程序使用 rename 系统调用
     //      来充当文件替换原语。
-    if let Ok(file) = open(new_path.clone(), OpenFlags::O_RDWR) {
+    if let Ok(file) = open(new_path.clone(), OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW) {
         // debug_point!("[sys_renameat2] return EEXIST");
         // return Err(Errno::EEXIST);
         ksys_unlinkat(0, new_path.clone().get(), 0);
     }
 
-    if let Ok(file) = open(old_path.clone(), OpenFlags::O_RDWR) {
+    if let Ok(file) = open(old_path.clone(), OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW) {
         // BUG: 注意到这里很丑陋，传入的 old_inode 应当与 old_dentry 相对应
         let old_inode = file.metadata().inode.clone();
         let old_dentry = Dentry::get_dentry_from_path(&old_path.get())?;
@@ -933,9 +994,11 @@ pub fn sys_renameat2(
                 return Err(Errno::EEXIST);
             };
         debug_point!("");
+        let is_dir = old_inode.metadata()._type.is_dir();
         if let Ok(_) = old_inode.rename(old_dentry, new_dentry) {
             // 如果重命名成功，返回0
             // debug_point!("[sys_renameat2] return Ok(0)");
+            fsnotify_move(&old_path.get(), &new_path.get(), is_dir);
             return Ok(0);
         } else {
             // 如果重命名失败，返回错误
@@ -1053,6 +1116,8 @@ pub fn sys_linkat(
         }
     };
 
+    // 默认对符号链接本身建立硬链接，AT_SYMLINK_FOLLOW 时链接到它指向的文件
+    let old_path = old_path.follow(flags & AT_SYMLINK_FOLLOW != 0)?;
     if let Ok(inode) = Dentry::get_inode_from_path(&old_path.get()) {
         if inode.metadata()._type.is_dir() {
             return Err(Errno::EISDIR);
@@ -1069,92 +1134,95 @@ pub fn sys_linkat(
             }
         }
     };
+    let new_path = new_path.follow(false)?;
 
     if olddirfd == AT_FDCWD {
-        if let Ok(file) = open(old_path, OpenFlags::O_RDWR) {
+        if let Ok(file) = open(old_path, OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW) {
+            may_create(&Dentry::get_inode_from_path(&new_path.get_parent_abs())?)?;
             let parent_dentry = Dentry::get_dentry_from_path(&new_path.get_parent_abs())?;
             let new_dentry = parent_dentry
                 .bare_child(&new_path.get_filename())
                 .ok_or(Errno::EEXIST)?;
 
             file.metadata().inode.link(new_dentry)?;
+            fsnotify(&file.abspath(), FsEventMask::ATTRIB);
+            fsnotify(&new_path.get(), FsEventMask::CREATE);
         }
     }
     Ok(0)
 }
 
 /// copies data between one file descriptor and another
+///
+/// 数据按页从 in_fd 取出后直接写入 out_fd，in_fd 有页缓存时不经过中间缓冲区。
+/// offset 不为空时从 *offset 处读并更新 *offset，不改变 in_fd 的偏移；否则使用并推进 in_fd 的偏移。
+/// in_fd 是管道时与 splice 相同，只取走写出去的部分
 pub async fn sys_sendfile(
     out_fd: usize,
     in_fd: usize,
     offset: usize,
     count: usize,
 ) -> SysResult<usize> {
-    // error!("[sys_sendfile] out_fd: {}, in_fd: {}, offset: {:#x}, count: {:#x}", out_fd, in_fd, offset, count);
+    info!(
+        "[sys_sendfile] out_fd: {}, in_fd: {}, offset: {:#x}, count: {:#x}",
+        out_fd, in_fd, offset, count
+    );
     let task = current_task().unwrap();
     let src = task.get_file_by_fd(in_fd).ok_or(Errno::EBADF)?;
     let dest = task.get_file_by_fd(out_fd).ok_or(Errno::EBADF)?;
     if !src.metadata().flags.read().readable() || !dest.metadata().flags.read().writable() {
-        return Err(Errno::EPERM);
+        return Err(Errno::EBADF);
+    }
+    if unlikely(dest.metadata().flags.read().contains(OpenFlags::O_APPEND)) {
+        return Err(Errno::EINVAL);
     }
-    // 可能可能有问题，我注释了，原来是打开的
-
-    // let file_size = {
-    //     if src.is_pipe() {
-    //         src.clone()
-    //             .downcast_arc::<Pipe>()
-    //             .map_err( |_| Errno::EINVAL )?
-    //             .with_mut_buffer( | buffer | {
-    //                 buffer.buf.len()
-    //             } )
-    //     }
-    //     else if src.is_deivce() {
-    //         todo!()
-    //     }
-    //     else { // file on disk
-    //         src.get_inode().get_size()
-    //     }
-    // };
-
-    // let count = count.min(file_size - offset + PAGE_SIZE);
-    let count = count.min(src.metadata().inode.get_size() - offset + PAGE_SIZE);
-
-    let mut len: usize = 0;
-    let mut buf = vec![0u8; count];
-    let mut new_offset = offset;
-    // if new_offset != 0 {
-    //     panic!("not implement")
-    // };
 
-    loop {
-        let read_size = src.read(&mut buf).await?;
-        if read_size == 0 {
-            break;
-        }
-        let write_size = dest.write(&buf[0..read_size]).await?;
-        if read_size != write_size {
-            return Err(Errno::EIO);
+    // 管道只能按顺序读，像 splice 一样先看数据，只取走真正写出去的部分
+    if let Ok(pipe) = src.clone().downcast_arc::<Pipe>() {
+        if offset != 0 {
+            return Err(Errno::ESPIPE);
         }
-        new_offset += read_size;
-        len += read_size;
+        let len = splice_from_pipe(&pipe, &dest, None, count, pipe.is_nonblock()).await?;
+        info!("[sys_sendfile] finished, len = {}", len);
+        return Ok(len);
     }
+    // 其他不能按偏移读的文件（套接字等）读出来的数据没法放回去，与 Linux 一样不支持
+    if unlikely(!src.metadata().inode.metadata()._type.is_file()) {
+        return Err(Errno::EINVAL);
+    }
+    let mut pos_in = match offset {
+        0 => src.metadata().offset(),
+        _ => read_user_offset(offset)?,
+    };
+    let len = copy_pages(&src, &mut pos_in, &dest, &mut None, count).await?;
 
-    // If offset is not NULL, then sendfile() does not modify the file offset of in_fd;
-    // otherwise the file offset is adjusted to reflect the number of bytes read from in_fd.
-    if offset != 0 {
-        // 重新设置offset：
-        let token = task.get_user_token();
-        src.lseek(len as isize, SEEK_CUR).unwrap();
-        let ptr = user_ref_mut(offset.into())?.unwrap();
-        *ptr = new_offset;
+    match offset {
+        0 => src.metadata().set_offset(pos_in),
+        _ => write_user_offset(offset, pos_in)?,
     }
-    info!("[sys_sendfile] finished");
+    info!("[sys_sendfile] finished, len = {}", len);
     Ok(len)
 }
 
+/// 读取 splice、sendfile 等用户传入的 loff_t 偏移
+fn read_user_offset(ptr: usize) -> SysResult<usize> {
+    let offset = *user_ref::<isize>(ptr.into())?.ok_or(Errno::EFAULT)?;
+    if offset < 0 {
+        return Err(Errno::EINVAL);
+    }
+    Ok(offset as usize)
+}
+
+fn write_user_offset(ptr: usize, offset: usize) -> SysResult {
+    *user_ref_mut::<usize>(ptr.into())?.ok_or(Errno::EFAULT)? = offset;
+    Ok(())
+}
+
 /// determine accessibility of a file relative to directory file descriptor
 /// If pathname is a symbolic link, it is dereferenced.
-pub fn sys_faccessat(dirfd: isize, pathname: usize, mode: u32, _flags: u32) -> SysResult<usize> {
+///
+/// 默认使用 real uid/gid 检查，带 AT_EACCESS 时使用 effective id
+pub fn sys_faccessat(dirfd: isize, pathname: usize, mode: u32, flags: u32) -> SysResult<usize> {
     if pathname as isize == -1 {
         return Err(Errno::EFAULT);
     }
@@ -1162,17 +1230,7 @@ pub fn sys_faccessat(dirfd: isize, pathname: usize, mode: u32, _flags: u32) -> S
     let mut path = user_cstr(pathname.into())?.unwrap();
     // error!("[sys_faccessat] start dirfd: {}, pathname: {}", dirfd, path);
     let mode = FaccessatMode::from_bits(mode).ok_or(Errno::EINVAL)?;
-    // pub struct FaccessatMode: u32 {
-    //     /// 检查文件是否存在
-    //     const F_OK = 0;
-    //     /// 检查文件是否可读
-    //     const R_OK = 4;
-    //     /// 检查文件是否可写
-    //     const W_OK = 2;
-    //     /// 检查文件是否可执行
-    //     const X_OK = 1;
-    // }
-    // error!("access mode: {:o}", mode.bits());
+    let flags = FaccessatFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
     let cwd = task.get_current_path();
 
     let abs = if dirfd == AT_FDCWD {
@@ -1181,10 +1239,6 @@ pub fn sys_faccessat(dirfd: isize, pathname: usize, mode: u32, _flags: u32) -> S
     } else {
         // 相对路径，以 fd 对应的目录为起点
         if unlikely(dirfd < 0 || dirfd as usize > RLIMIT_NOFILE) {
-            // error!(
-            //     "[sys_faccessat] return EBADF dirfd: {}, pathname: {}",
-            //     dirfd, path
-            // );
             return Err(Errno::EBADF);
         }
         let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
@@ -1195,125 +1249,24 @@ pub fn sys_faccessat(dirfd: isize, pathname: usize, mode: u32, _flags: u32) -> S
         let other_cwd = file.abspath();
         resolve_path(other_cwd, path.clone())
     };
-    let is_root = {
-        let uid_now = task.get_euid();
-        uid_now == 0
-    };
-    // TODO:  缺少去判断父文件夹的逻辑
-    let parent_abs = AbsPath::new(abs.get_parent_abs());
-    // error!("[sys_faccessat] parent_abs: {}", parent_abs.get());
-    if parent_abs.get().starts_with("/tmp/LTP")
-        && !parent_abs.split_last_with("/").1.starts_with("LTP")
-        && !is_root
-    {
-        // error!(
-        //     "[sys_faccessat] parent_dir: {}",
-        //     parent_abs.split_last_with("/").1
-        // );
-        if let Ok(file) = open(parent_abs, OpenFlags::O_RDONLY) {
-            // let file = file_class.file()?;
-            let inode = file.metadata().inode.clone();
-            // BUG: 临时去这样子做，如果不是 ext4 的就直接返回许可，只判断 ext4 的
-            let i_mode = match inode.downcast_arc::<Ext4Inode>() {
-                Some(x) => x.metadata.i_mode.lock().mode,
-                None => return Ok(0),
-            };
-            // error!("[sys_faccessat] parent inode mode: {:o}", i_mode);
-            // 注意到在鉴权的时候应当只关注 others 的位置就好了
-            // if mode.contains(FaccessatMode::F_OK) {
-            //     // error!(
-            //     //     "[sys_faccessat] return Ok dirfd: {}, pathname: {}",
-            //     //     dirfd, path
-            //     // );
-            //     return Ok(0);
-            // }
-            // if mode.contains(FaccessatMode::R_OK) && !i_mode.contains(ModeFlag::S_IWOTH) {
-            //     error!(
-            //         "[sys_faccessat] return no readable dirfd: {}, pathname: {}",
-            //         dirfd, path
-            //     );
-            //     return Err(Errno::EACCES);
-            // }
-            // if mode.contains(FaccessatMode::W_OK) && !i_mode.contains(ModeFlag::S_IWOTH) {
-            //     error!(
-            //         "[sys_faccessat] return no writeable dirfd: {}, pathname: {}",
-            //         dirfd, path
-            //     );
-            //     return Err(Errno::EACCES);
-            // }
-            if !i_mode.contains(ModeFlag::S_IXOTH) {
-                // error!(
-                //     "[sys_faccessat] return no executable dirfd: {}, pathname: {}",
-                //     dirfd, path
-                // );
-                return Err(Errno::EACCES);
-            }
-        } else {
-            // error!(
-            //     "[sys_faccessat] return ENOENT dirfd: {}, pathname: {}",
-            //     dirfd, path
-            // );
-            return Err(Errno::ENOENT);
-        }
-    }
 
-    // error!("parent passed");
-
-    // BUG: 这里应当根据的是根据 INODE 的i_mode 字段而不是 file 的打开标记位
-    if let Ok(file) = open(abs, OpenFlags::O_RDONLY) {
-        // let file = file_class.file()?;
-        let inode = file.metadata().inode.clone();
-        // BUG: 临时去这样子做，如果不是 ext4 的就直接返回许可，只判断 ext4 的
-        let i_mode = match inode.downcast_arc::<Ext4Inode>() {
-            Some(x) => x.metadata.i_mode.lock().mode,
-            None => return Ok(0),
-        };
-        info!("[sys_faccessat] got i_mode: {:0}", i_mode);
-
-        if is_root {
-            if mode.contains(FaccessatMode::X_OK) && !i_mode.intersects(ModeFlag::S_IXOTH | ModeFlag::S_IXGRP | ModeFlag::S_IXUSR) {
-                return Err(Errno::EACCES);
-            }
-            return Ok(0);
-        }
-
-        // 注意到在鉴权的时候应当只关注 others 的位置就好了
-        if mode.bits() == 0 {
-            // error!(
-            //     "[sys_faccessat] return Ok dirfd: {}, pathname: {}",
-            //     dirfd, path
-            // );
-            return Ok(0);
-        }
-        if mode.contains(FaccessatMode::R_OK) && !i_mode.contains(ModeFlag::S_IROTH) {
-            // error!(
-            //     "[sys_faccessat] return no readable dirfd: {}, pathname: {}",
-            //     dirfd, path
-            // );
-            return Err(Errno::EACCES);
-        }
-        if mode.contains(FaccessatMode::W_OK) && !i_mode.contains(ModeFlag::S_IWOTH) {
-            // error!(
-            //     "[sys_faccessat] return no writeable dirfd: {}, pathname: {}",
-            //     dirfd, path
-            // );
-            return Err(Errno::EACCES);
-        }
-        if mode.contains(FaccessatMode::X_OK) && !i_mode.contains(ModeFlag::S_IXOTH) {
-            // error!(
-            //     "[sys_faccessat] return no executable dirfd: {}, pathname: {}",
-            //     dirfd, path
-            // );
-            return Err(Errno::EACCES);
-        }
-    } else {
-        // error!(
-        //     "[sys_faccessat] return ENOENT dirfd: {}, pathname: {}",
-        //     dirfd, path
-        // );
-        return Err(Errno::ENOENT);
+    let abs = abs.follow(!flags.contains(FaccessatFlags::AT_SYMLINK_NOFOLLOW))?;
+    let inode = Dentry::get_inode_from_path(&abs.get())?;
+    if mode.is_empty() {
+        return Ok(0);
+    }
+    let mut cred = task.cred.lock().clone();
+    if !flags.contains(FaccessatFlags::AT_EACCESS) {
+        cred.fsuid = cred.ruid;
+        cred.fsgid = cred.rgid;
     }
-    // debug_point!("");
+    info!(
+        "[sys_faccessat] path: {}, mode: {:?}, attr: {:?}",
+        abs.get(),
+        mode,
+        inode.metadata().attr()
+    );
+    inode.permission_with(&cred, MayAccess::from_bits_truncate(mode.bits()))?;
     Ok(0)
 }
 
@@ -1336,7 +1289,7 @@ pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SysResult<usize> {
 /// TODO(YJJ): 有待完善
 /// 用于修改某个文件描述符的属性
 /// 第1个参数fd为待修改属性的文件描述符，第2个参数cmd为对应的操作命令，第3个参数为cmd的参数
-pub fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> SysResult<usize> {
+pub async fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> SysResult<usize> {
     let task = current_task().unwrap();
     let cmd = FcntlFlags::from_bits(cmd).ok_or(Errno::EINVAL)?;
     info!(
@@ -1438,8 +1391,149 @@ pub fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> SysResult<usize> {
             }
             return Err(Errno::EBADF);
         }
+        // F_ADD_SEALS/F_GET_SEALS：只对 memfd 有效，其他文件返回 EINVAL
+        FcntlFlags::F_ADD_SEALS => {
+            let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+            let memfd = file
+                .metadata()
+                .inode
+                .clone()
+                .downcast_arc::<MemfdInode>()
+                .ok_or(Errno::EINVAL)?;
+            if unlikely(!file.metadata().flags.read().writable()) {
+                return Err(Errno::EPERM);
+            }
+            let seals = SealFlags::from_bits(arg as u32).ok_or(Errno::EINVAL)?;
+            memfd.add_seals(seals)?;
+            return Ok(0);
+        }
+        FcntlFlags::F_GET_SEALS => {
+            let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+            let memfd = file
+                .metadata()
+                .inode
+                .clone()
+                .downcast_arc::<MemfdInode>()
+                .ok_or(Errno::EINVAL)?;
+            return Ok(memfd.get_seals().bits() as usize);
+        }
+        FcntlFlags::F_GETLK
+        | FcntlFlags::F_SETLK
+        | FcntlFlags::F_SETLKW
+        | FcntlFlags::F_OFD_GETLK
+        | FcntlFlags::F_OFD_SETLK
+        | FcntlFlags::F_OFD_SETLKW => fcntl_lock(fd, cmd, arg).await,
+        _ => return Err(Errno::EINVAL),
+    }
+}
+
+/// fcntl 的记录锁命令。POSIX 锁属于进程，OFD 锁属于打开的文件，两者互相冲突
+async fn fcntl_lock(fd: usize, cmd: FcntlFlags, arg: usize) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    let user_flock = user_ref_mut::<Flock>(arg.into())?.ok_or(Errno::EFAULT)?;
+    let ofd = matches!(
+        cmd,
+        FcntlFlags::F_OFD_GETLK | FcntlFlags::F_OFD_SETLK | FcntlFlags::F_OFD_SETLKW
+    );
+    if unlikely(ofd && user_flock.l_pid != 0) {
+        return Err(Errno::EINVAL);
+    }
+    let ty = LockType::try_from(user_flock.l_type)?;
+    let (start, end) = flock_range(&file, user_flock)?;
+    let owner = match ofd {
+        true => LockOwner::File(file.metadata().file_id()),
+        false => LockOwner::Process(task.get_tgid()),
+    };
+    let lock = FileLock {
+        owner,
+        ty,
+        start,
+        end,
+        pid: task.get_tgid(),
+    };
+    let ino = file.metadata().inode.metadata().ino;
+    info!("[fcntl_lock] fd = {}, cmd = {:?}, lock = {:?}", fd, cmd, lock);
+
+    if matches!(cmd, FcntlFlags::F_GETLK | FcntlFlags::F_OFD_GETLK) {
+        if ty == LockType::Unlock {
+            return Err(Errno::EINVAL);
+        }
+        match lock_test(ino, &lock) {
+            Some(other) => {
+                user_flock.l_type = other.ty as i16;
+                user_flock.l_whence = SEEK_SET as i16;
+                user_flock.l_start = other.start as i64;
+                user_flock.l_len = match other.end {
+                    u64::MAX => 0,
+                    end => (end - other.start + 1) as i64,
+                };
+                user_flock.l_pid = match other.owner {
+                    LockOwner::Process(_) => other.pid as i32,
+                    LockOwner::File(_) => -1,
+                };
+            }
+            None => user_flock.l_type = LockType::Unlock as i16,
+        }
+        return Ok(0);
+    }
+
+    let flags = *file.metadata().flags.read();
+    match ty {
+        LockType::Read if !flags.readable() => return Err(Errno::EBADF),
+        LockType::Write if !flags.writable() => return Err(Errno::EBADF),
+        _ => {}
+    }
+    let wait = matches!(cmd, FcntlFlags::F_SETLKW | FcntlFlags::F_OFD_SETLKW);
+    lock_acquire(ino, LockRequest::Range(lock), wait).await?;
+    Ok(0)
+}
+
+/// 把 struct flock 描述的范围换算成闭区间，锁到文件末尾时 end 为 u64::MAX
+fn flock_range(file: &Arc<dyn FileTrait>, flock: &Flock) -> SysResult<(u64, u64)> {
+    let base = match flock.l_whence as usize {
+        SEEK_SET => 0,
+        SEEK_CUR => file.metadata().offset() as i64,
+        SEEK_END => {
+            let mut stat = Kstat::new();
+            file.fstat(&mut stat)?;
+            stat.st_size
+        }
         _ => return Err(Errno::EINVAL),
+    };
+    let start = base.checked_add(flock.l_start).ok_or(Errno::EOVERFLOW)?;
+    let (start, end) = match flock.l_len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start.checked_add(len - 1).ok_or(Errno::EOVERFLOW)?)),
+        len => (start.checked_add(len).ok_or(Errno::EOVERFLOW)?, Some(start - 1)),
+    };
+    if start < 0 {
+        return Err(Errno::EINVAL);
     }
+    Ok((start as u64, end.map_or(u64::MAX, |end| end as u64)))
+}
+
+/// 对整个文件加建议性锁：https://man7.org/linux/man-pages/man2/flock.2.html
+///
+/// 锁属于打开的文件，dup 或 fork 得到的 fd 共享同一把锁
+pub async fn sys_flock(fd: usize, operation: u32) -> SysResult<usize> {
+    let op = FlockOp::from_bits(operation).ok_or(Errno::EINVAL)?;
+    info!("[sys_flock] fd = {}, operation = {:?}", fd, op);
+    let ty = match op - FlockOp::LOCK_NB {
+        FlockOp::LOCK_SH => LockType::Read,
+        FlockOp::LOCK_EX => LockType::Write,
+        FlockOp::LOCK_UN => LockType::Unlock,
+        _ => return Err(Errno::EINVAL),
+    };
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    let req = LockRequest::Flock {
+        file: file.metadata().file_id(),
+        ty,
+    };
+    let ino = file.metadata().inode.metadata().ino;
+    lock_acquire(ino, req, !op.contains(FlockOp::LOCK_NB)).await?;
+    Ok(0)
 }
 
 /// 改变文件大小
@@ -1451,12 +1545,21 @@ pub fn sys_ftruncate64(fd: usize, length: usize) -> SysResult<usize> {
     //     return Err(Errno::EBADF);
     // }
     let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
-    file.metadata().inode.truncate(length);
+    let inode = file.metadata().inode.clone();
+    if let Some(memfd) = inode.clone().downcast_arc::<MemfdInode>() {
+        memfd.check_resize(length)?;
+    }
+    inode.truncate(length);
+    fsnotify(&file.abspath(), FsEventMask::MODIFY);
     Ok(0)
 }
 
 /// 可更改现有文件的访问权限
-pub fn sys_fchmodat(dirfd: isize, path: usize, mode: usize, _flags: u32) -> SysResult<usize> {
+/// 修改文件的权限位，flags 只能是 AT_SYMLINK_NOFOLLOW（fchmodat2）
+pub fn sys_fchmodat(dirfd: isize, path: usize, mode: usize, flags: u32) -> SysResult<usize> {
+    if unlikely(flags & !AT_SYMLINK_NOFOLLOW != 0) {
+        return Err(Errno::EINVAL);
+    }
     let task = current_task().unwrap();
     let path = user_cstr(path.into())?.unwrap();
 
@@ -1478,20 +1581,86 @@ pub fn sys_fchmodat(dirfd: isize, path: usize, mode: usize, _flags: u32) -> SysR
         resolve_path(other_cwd, path.clone())
     };
     // error!("[sys_f1chmodat], path: {}, mode: {:o}", abs_path.get(), mode);
+    let abs_path = abs_path.follow(flags & AT_SYMLINK_NOFOLLOW == 0)?;
 
-    if let Ok(file) = open(abs_path, OpenFlags::O_RDONLY) {
-        // let file = file_class.file()?;
-        let inode = file.metadata().inode.clone();
-        // BUG: 临时去这样子做，如果不是 ext4 的就直接返回许可，只判断 ext4 的
-        let inode = match inode.downcast_arc::<Ext4Inode>() {
-            Some(x) => x,
-            None => return Ok(0),
-        };
-        let mode = StMode::from(mode as u32);
-        *inode.metadata.i_mode.lock() = mode;
-        return Ok(0);
+    let inode = Dentry::get_inode_from_path(&abs_path.get())?;
+    // 与 Linux 相同，符号链接的权限位不能修改
+    if inode.metadata()._type.is_symlink() {
+        return Err(Errno::EOPNOTSUPP);
     }
-    return Ok(0);
+    inode.chmod(mode as u32)?;
+    let isdir = inode.metadata()._type.is_dir();
+    fsnotify(&abs_path.get(), FsEventMask::ATTRIB | FsEventMask::dir(isdir));
+    Ok(0)
+}
+
+pub fn sys_fchmod(fd: usize, mode: usize) -> SysResult<usize> {
+    info!("[sys_fchmod] fd: {}, mode: {:o}", fd, mode);
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    file.metadata().inode.chmod(mode as u32)?;
+    let isdir = file.metadata().inode.metadata()._type.is_dir();
+    fsnotify(&file.abspath(), FsEventMask::ATTRIB | FsEventMask::dir(isdir));
+    Ok(0)
+}
+
+/// chown 系列中 owner/group 为 -1 表示不修改
+fn chown_id(id: usize) -> Option<u32> {
+    match id as u32 {
+        u32::MAX => None,
+        id => Some(id),
+    }
+}
+
+/// 修改文件的所有者和组
+pub fn sys_fchownat(
+    dirfd: isize,
+    path: usize,
+    owner: usize,
+    group: usize,
+    flags: u32,
+) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let flags = FaccessatFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let path = user_cstr(path.into())?.ok_or(Errno::EFAULT)?;
+    info!(
+        "[sys_fchownat] dirfd: {}, path: {}, owner: {}, group: {}",
+        dirfd, path, owner as i32, group as i32
+    );
+
+    let (inode, abs_path) = if path.is_empty() {
+        if !flags.contains(FaccessatFlags::AT_EMPTY_PATH) {
+            return Err(Errno::ENOENT);
+        }
+        let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
+        (file.metadata().inode.clone(), file.abspath())
+    } else {
+        let abs_path = if dirfd == AT_FDCWD || path.starts_with('/') {
+            resolve_path(task.get_current_path(), path)
+        } else {
+            let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
+            if unlikely(!file.metadata().inode.metadata()._type.is_dir()) {
+                return Err(Errno::ENOTDIR);
+            }
+            resolve_path(file.abspath(), path)
+        };
+        let abs_path = abs_path.follow(!flags.contains(FaccessatFlags::AT_SYMLINK_NOFOLLOW))?;
+        (Dentry::get_inode_from_path(&abs_path.get())?, abs_path.get())
+    };
+    inode.chown(chown_id(owner), chown_id(group))?;
+    let isdir = inode.metadata()._type.is_dir();
+    fsnotify(&abs_path, FsEventMask::ATTRIB | FsEventMask::dir(isdir));
+    Ok(0)
+}
+
+pub fn sys_fchown(fd: usize, owner: usize, group: usize) -> SysResult<usize> {
+    info!("[sys_fchown] fd: {}, owner: {}, group: {}", fd, owner as i32, group as i32);
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    file.metadata().inode.chown(chown_id(owner), chown_id(group))?;
+    let isdir = file.metadata().inode.metadata()._type.is_dir();
+    fsnotify(&file.abspath(), FsEventMask::ATTRIB | FsEventMask::dir(isdir));
+    Ok(0)
 }
 
 /// 从描述符为fd的文件中，从offset位置开始，读取count个字节存入buf中。
@@ -1564,12 +1733,15 @@ pub fn sys_utimensat(
         let path = user_cstr(pathname.into())?.unwrap();
         let target_path = resolve_path(cwd, path);
 
-        let flags = OpenFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
-
-        open(target_path, OpenFlags::O_RDWR | OpenFlags::O_CREAT)?
-            .metadata()
-            .inode
-            .clone()
+        if flags as u32 & AT_SYMLINK_NOFOLLOW != 0 {
+            // 修改符号链接本身的时间戳
+            Dentry::get_inode_from_path(&target_path.follow(false)?.get())?
+        } else {
+            open(target_path, OpenFlags::O_RDWR | OpenFlags::O_CREAT)?
+                .metadata()
+                .inode
+                .clone()
+        }
     } else {
         let res = match dirfd {
             AT_FDCWD => {
@@ -1637,71 +1809,74 @@ pub fn sys_utimensat(
     }
 
     inode.set_timestamps(new_time);
+    let isdir = inode.metadata()._type.is_dir();
+    fsnotify(&inode.metadata().abspath, FsEventMask::ATTRIB | FsEventMask::dir(isdir));
 
     Ok(0)
 }
 
+/// 在 newdirfd 和 linkpath 指定的位置创建指向 target 的符号链接
+///
+/// target 原样保存，创建时不检查它是否存在
+pub fn sys_symlinkat(target: usize, newdirfd: isize, linkpath: usize) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let target = user_cstr(target.into())?.ok_or(Errno::EFAULT)?;
+    let path = user_cstr(linkpath.into())?.ok_or(Errno::EFAULT)?;
+    info!(
+        "[sys_symlinkat] target: {}, newdirfd: {}, linkpath: {}",
+        target, newdirfd, path
+    );
+    if unlikely(target.is_empty() || path.is_empty()) {
+        return Err(Errno::ENOENT);
+    }
+    let link_path = if newdirfd == AT_FDCWD || path.starts_with('/') {
+        resolve_path(task.get_current_path(), path)
+    } else {
+        let file = task.get_file_by_fd(newdirfd as usize).ok_or(Errno::EBADF)?;
+        if unlikely(!file.metadata().inode.metadata()._type.is_dir()) {
+            return Err(Errno::ENOTDIR);
+        }
+        resolve_path(file.abspath(), path)
+    };
+    symlink(&target, link_path.follow(false)?)?;
+    Ok(0)
+}
+
 /// read value of a symbolic link
-/// 一个符号链接当中获得真实的路径地址
-/// 注意到当前没有真正地实现,返回值全为0,代表不支持该功能
+///
+/// 把链接的目标写入 buf，不以 0 结尾，超过 bufsiz 的部分被截断。
+/// pathname 的最后一个分量不是符号链接时返回 EINVAL
 pub fn sys_readlinkat(
     dirfd: isize,
     pathname: usize,
     buf: usize,
     bufsiz: usize,
 ) -> SysResult<usize> {
-    return Err(Errno::ENOSYS);
     let task = current_task().unwrap();
-    let cwd = task.get_current_path();
-    let pathname = user_cstr(pathname.into())?.unwrap();
+    let pathname = user_cstr(pathname.into())?.ok_or(Errno::EFAULT)?;
     info!(
         "[sys_readlinkat] start, dirfd: {}, pathname: {}.",
         dirfd, pathname
     );
-    if unlikely(bufsiz == 0) {
+    if unlikely(bufsiz as isize <= 0) {
         return Err(Errno::EINVAL);
     }
 
-    let target_path = resolve_path(cwd, pathname);
-    if !target_path.is_absolute() {
-        if dirfd == AT_FDCWD {
-            let cwd = task.get_current_path();
-            todo!();
-            // log::error!("case which is no abs path hasn't implement");
-            return Ok(0);
-        }
+    let target_path = if dirfd == AT_FDCWD || pathname.starts_with('/') {
+        resolve_path(task.get_current_path(), pathname)
     } else {
-        // 由于暂时没有实现软链接,所以先这么做吧,把这个文件重定向到/musl/busybox
-        if target_path.get() == "/proc/self/exe" {
-            let ub = if let Ok(Some(buf)) = user_slice_mut::<u8>(buf.into(), bufsiz) {
-                buf
-            } else {
-                return Err(Errno::EFAULT);
-            };
-            let path_bytes = "/musl/busybox\0".as_bytes();
-            if path_bytes.len() > bufsiz {
-                ub[0..bufsiz].copy_from_slice(&path_bytes[0..bufsiz]);
-                return Ok(bufsiz);
-            } else {
-                ub[0..path_bytes.len()].copy_from_slice(&path_bytes[0..path_bytes.len()]);
-                return Ok(path_bytes.len());
-            }
-        }
-
-        if let Ok(file) = open(target_path, OpenFlags::O_RDONLY) {
-            let ub = if let Ok(Some(buf)) = user_slice_mut::<u8>(buf.into(), bufsiz) {
-                buf
-            } else {
-                return Err(Errno::EFAULT);
-            };
-            let c_path = alloc::format!("{}\0", file.metadata().inode.metadata().abspath);
-            let path_bytes = c_path.as_bytes();
-            let len = min(path_bytes.len(), bufsiz);
-            ub[0..len].copy_from_slice(&path_bytes[0..len]);
-            return Ok(len);
+        let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
+        if unlikely(!file.metadata().inode.metadata()._type.is_dir()) {
+            return Err(Errno::ENOTDIR);
         }
-    }
-    Ok(0)
+        resolve_path(file.abspath(), pathname)
+    };
+    let link_path = target_path.follow(false)?;
+    let target = Dentry::get_inode_from_path(&link_path.get())?.readlink()?;
+    let len = min(target.len(), bufsiz);
+    let ub = user_slice_mut::<u8>(buf.into(), len)?.ok_or(Errno::EFAULT)?;
+    ub.copy_from_slice(&target.as_bytes()[..len]);
+    Ok(len)
 }
 
 pub fn sys_statfs(path: usize, buf: usize) -> SysResult<usize> {
@@ -1759,24 +1934,22 @@ pub fn sys_fchdir(fd: usize) -> SysResult<usize> {
 /// up to size bytes of data from the file descriptor fd_in to the
 /// file descriptor fd_out, where one of the file descriptors must
 /// refer to a pipe.
+///
+/// 管道中的数据是页引用：从有页缓存的文件读入时直接引用缓存页，管道之间只移动页引用
 pub async fn sys_splice(
     fd_in: usize,
     off_in: usize,
     fd_out: usize,
     off_out: usize,
     size: usize,
-    _flags: u32,
+    flags: u32,
 ) -> SysResult<usize> {
     // INFO: 决赛系统调用
     info!(
         "[sys_splice] start, fd_in = {}, off_in = {}, fd_out = {}, off_out = {}, size = {}",
         fd_in, off_in, fd_out, off_out, size
     );
-
-    if unlikely(fd_in == fd_out) {
-        info!("[sys_splice] fd_in and fd_out are the same, return EINVAL");
-        return Err(Errno::EINVAL);
-    }
+    let flags = SpliceFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
 
     let task = current_task().unwrap();
     let file_in = task.get_file_by_fd(fd_in).ok_or(Errno::EBADF)?;
@@ -1789,171 +1962,233 @@ pub async fn sys_splice(
         info!("[sys_splice] file_out is not writable, return EBADF");
         return Err(Errno::EBADF);
     }
-
-    if unlikely(
-        file_out
-            .metadata()
-            .flags
-            .read()
-            .contains(OpenFlags::O_APPEND),
-    ) {
+    if unlikely(file_out.metadata().flags.read().contains(OpenFlags::O_APPEND)) {
         info!("[sys_splice] file_out is O_APPEND, return EINVAL");
         return Err(Errno::EINVAL);
     }
-    if unlikely(file_in.metadata().inode.metadata()._type.is_fifo() && off_in != 0) {
-        return Err(Errno::ESPIPE);
-    }
-    if unlikely(file_out.metadata().inode.metadata()._type.is_fifo() && off_out != 0) {
+
+    let pipe_in = file_in.clone().downcast_arc::<Pipe>().ok();
+    let pipe_out = file_out.clone().downcast_arc::<Pipe>().ok();
+    if unlikely((pipe_in.is_some() && off_in != 0) || (pipe_out.is_some() && off_out != 0)) {
         return Err(Errno::ESPIPE);
     }
-    if unlikely(
-        !file_in.metadata().inode.metadata()._type.is_fifo()
-            && !file_out.metadata().inode.metadata()._type.is_fifo(),
-    ) {
-        return Err(Errno::EINVAL);
+    if size == 0 {
+        return Ok(0);
     }
+    let nonblock = |pipe: &Pipe| flags.contains(SpliceFlags::SPLICE_F_NONBLOCK) || pipe.is_nonblock();
 
-    let in_offset = match off_in {
-        0 => 0,
-        _ => unsafe { *(off_in as *const isize) },
-    };
-    let out_offet = match off_out {
-        0 => 0,
-        _ => unsafe { *(off_out as *const isize) },
+    let res = match (pipe_in, pipe_out) {
+        (Some(pipe_in), Some(pipe_out)) => {
+            let nonblock = nonblock(&pipe_in) || nonblock(&pipe_out);
+            pipe_to_pipe(&pipe_in, &pipe_out, size, nonblock, false).await?
+        }
+        (Some(pipe_in), None) => {
+            let offset = match off_out {
+                0 => None,
+                _ => Some(read_user_offset(off_out)?),
+            };
+            let len = splice_from_pipe(&pipe_in, &file_out, offset, size, nonblock(&pipe_in)).await?;
+            if let Some(offset) = offset {
+                write_user_offset(off_out, offset + len)?;
+            }
+            len
+        }
+        (None, Some(pipe_out)) => {
+            let offset = match off_in {
+                0 => None,
+                _ => Some(read_user_offset(off_in)?),
+            };
+            let len = splice_to_pipe(&file_in, offset, &pipe_out, size, nonblock(&pipe_out)).await?;
+            if let Some(offset) = offset {
+                write_user_offset(off_in, offset + len)?;
+            }
+            len
+        }
+        (None, None) => return Err(Errno::EINVAL),
     };
 
-    if in_offset < 0 || out_offet < 0 {
-        info!(
-            "[sys_splice] in_offset = {}, out_offset = {}",
-            in_offset, out_offet
-        );
-        return Err(Errno::EINVAL);
-    }
+    info!("[splice] return size: {}", res);
+    Ok(res)
+}
 
-    // 开辟一个内核缓冲区承载数据
-    let mut buffer = vec![0u8; size];
-    let read_size = match file_in.metadata().inode.metadata()._type.is_fifo() {
-        true => file_in.read(&mut buffer).await?,
-        false => file_in.read_at(in_offset as usize, &mut buffer).await?,
-    };
-    if unlikely(read_size == 0) {
-        info!("[sys_splice] read_size is 0, return 0");
+/// duplicating pipe content
+/// tee() duplicates up to len bytes of data from the pipe referred to
+/// by the file descriptor fd_in to the pipe referred to by the file
+/// descriptor fd_out.  It does not consume the data that is
+/// duplicated from fd_in.
+///
+/// 两个管道共享同一批页，不复制数据
+pub async fn sys_tee(fd_in: usize, fd_out: usize, len: usize, flags: u32) -> SysResult<usize> {
+    info!("[sys_tee] start, fd_in = {}, fd_out = {}, len = {}", fd_in, fd_out, len);
+    let flags = SpliceFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let task = current_task().unwrap();
+    let file_in = task.get_file_by_fd(fd_in).ok_or(Errno::EBADF)?;
+    let file_out = task.get_file_by_fd(fd_out).ok_or(Errno::EBADF)?;
+    if unlikely(!file_in.metadata().flags.read().readable() || !file_out.metadata().flags.read().writable()) {
+        return Err(Errno::EBADF);
+    }
+    let pipe_in = file_in.downcast_arc::<Pipe>().map_err(|_| Errno::EINVAL)?;
+    let pipe_out = file_out.downcast_arc::<Pipe>().map_err(|_| Errno::EINVAL)?;
+    if len == 0 {
         return Ok(0);
     }
-    // BUG: 这里应当判断 off_in 为 0 的逻辑
-    // 当 off_in 为 0 的时候应当采用文件的偏移，而不是像这样子采用输入的偏移
-    // 这里也应当是一样的
+    let nonblock = flags.contains(SpliceFlags::SPLICE_F_NONBLOCK) || pipe_in.is_nonblock() || pipe_out.is_nonblock();
+    pipe_to_pipe(&pipe_in, &pipe_out, len, nonblock, true).await
+}
 
-    let len = min(size, read_size);
-    let write_size = match file_out.metadata().inode.metadata()._type.is_fifo() {
-        true => file_out.write(&buffer[..len]).await?,
-        false => {
-            debug_point!("");
-            file_out
-                .write_at(out_offet as usize, &buffer[..len])
-                .await?
-        }
-    };
-    if unlikely(write_size == 0) {
-        info!("[sys_splice] write_size is 0, return 0");
-        return Ok(0);
+/// splice user pages to/from a pipe
+/// If fd is opened for writing, the iov segments are copied into the pipe;
+/// if fd is opened for reading, data is copied from the pipe into the segments.
+///
+/// 写入时用户数据复制到管道自己的页中，不把用户页直接交给管道，所以 SPLICE_F_GIFT 没有作用
+pub async fn sys_vmsplice(fd: usize, iov: usize, nr_segs: usize, flags: u32) -> SysResult<usize> {
+    info!("[sys_vmsplice] start, fd = {}, nr_segs = {}", fd, nr_segs);
+    let flags = SpliceFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    if unlikely(nr_segs > IOV_MAX) {
+        return Err(Errno::EINVAL);
     }
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    let pipe = file.downcast_arc::<Pipe>().map_err(|_| Errno::EBADF)?;
+    let nonblock = flags.contains(SpliceFlags::SPLICE_F_NONBLOCK) || pipe.is_nonblock();
 
-    match off_in {
-        0 => {}
-        _ => {
-            unsafe { core::ptr::write(off_in as *mut usize, in_offset as usize + read_size) };
-            return Ok(read_size);
+    let mut segs = Vec::with_capacity(nr_segs);
+    for i in 0..nr_segs {
+        let seg = unsafe { *((iov + core::mem::size_of::<IoVec>() * i) as *const IoVec) };
+        if unlikely((seg.iov_len as isize) < 0) {
+            return Err(Errno::EINVAL);
+        }
+        if seg.iov_len != 0 {
+            segs.push(seg);
         }
     }
+    if segs.is_empty() {
+        return Ok(0);
+    }
 
-    match off_out {
-        0 => {}
-        _ => {
-            unsafe { core::ptr::write(off_out as *mut usize, out_offet as usize + write_size) };
-            return Ok(write_size);
+    // 与 Linux 相同，只在开始时等待一次，之后能传多少传多少
+    let mut total = 0;
+    if pipe.is_reader {
+        if !pipe.wait_readable(nonblock).await? {
+            return Ok(0);
+        }
+        for seg in segs {
+            let buf = user_slice_mut::<u8>(seg.iov_base.into(), seg.iov_len)?.ok_or(Errno::EFAULT)?;
+            let len = pipe.with_mut_buffer(|inner| inner.read_to(buf));
+            total += len;
+            if len < seg.iov_len {
+                break;
+            }
+        }
+        pipe.with_mut_buffer(|inner| pipe.wake_writers(inner));
+    } else {
+        pipe.wait_writable(nonblock).await?;
+        for seg in segs {
+            let buf = user_slice::<u8>(seg.iov_base.into(), seg.iov_len)?.ok_or(Errno::EFAULT)?;
+            let len = pipe.with_mut_buffer(|inner| inner.write_from(buf));
+            total += len;
+            if len < seg.iov_len {
+                break;
+            }
         }
+        pipe.with_mut_buffer(|inner| pipe.wake_readers(inner));
     }
-
-    info!("[splice] return write_size: {}", write_size);
-    Ok(write_size)
+    Ok(total)
 }
 
+/// copy a range of data from one file to another
+///
+/// 数据按页从页缓存直接写入目标文件；偏移指针为空时使用并推进文件自己的偏移
 pub async fn sys_copy_file_range(
     fd_in: u32,
     off_in: usize,
     fd_out: u32,
     off_out: usize,
     len: usize,
-    _flags: usize,
+    flags: usize,
 ) -> SysResult<usize> {
     // INFO: 决赛测试用例
     // TODO: 需要检查范围，如果两个 fd 是同一个文件的话应当检查范围不应当重叠，这个逻辑没有被实现
+    if unlikely(flags != 0) {
+        return Err(Errno::EINVAL);
+    }
     let task = current_task().unwrap();
     let file_in = task.get_file_by_fd(fd_in as usize).ok_or(Errno::EBADF)?;
     let file_out = task.get_file_by_fd(fd_out as usize).ok_or(Errno::EBADF)?;
+    if unlikely(!file_in.metadata().flags.read().readable() || !file_out.metadata().flags.read().writable()) {
+        return Err(Errno::EBADF);
+    }
+    if unlikely(file_out.metadata().flags.read().contains(OpenFlags::O_APPEND)) {
+        return Err(Errno::EBADF);
+    }
+    let type_in = file_in.metadata().inode.metadata()._type;
+    let type_out = file_out.metadata().inode.metadata()._type;
+    if unlikely(type_in.is_dir() || type_out.is_dir()) {
+        return Err(Errno::EISDIR);
+    }
+    if unlikely(!type_in.is_file() || !type_out.is_file()) {
+        return Err(Errno::EINVAL);
+    }
 
-    let offset_in = (off_in != 0)
-        .then(|| unsafe { *(off_in as *const usize) })
-        .unwrap_or(0);
-
-    let offset_out = (off_out != 0)
-        .then(|| unsafe { *(off_out as *const usize) })
-        .unwrap_or(0);
-
+    let mut pos_in = match off_in {
+        0 => file_in.metadata().offset(),
+        _ => read_user_offset(off_in)?,
+    };
+    let mut pos_out = Some(match off_out {
+        0 => file_out.metadata().offset(),
+        _ => read_user_offset(off_out)?,
+    });
     info!(
-        "[sys_copy_file_range] fd_in: {}, off_in: {}, fd_out: {}, off_out: {}, len: {}",
-        fd_in, offset_in, fd_out, offset_out, len,
+        "[sys_copy_file_range] fd_in: {}, off_in: {:?}, fd_out: {}, off_out: {:?}, len: {}",
+        fd_in, pos_in, fd_out, pos_out, len,
     );
-    let mut buffer = vec![0u8; len];
+    let copied = copy_pages(&file_in, &mut pos_in, &file_out, &mut pos_out, len).await?;
 
-    let read_size = match file_in.metadata().inode.metadata()._type.is_fifo() | (off_in == 0) {
-        true => {
-            debug_point!("read by file's off");
-            file_in.read(&mut buffer).await?
-        }
-        false => {
-            debug_point!("read by offset sended");
-            file_in.read_at(offset_in, &mut buffer).await?
-        }
-    };
-    if unlikely(read_size == 0) {
-        return Ok(0);
+    let pos_out = pos_out.unwrap_or(0);
+    match off_in {
+        0 => file_in.metadata().set_offset(pos_in),
+        _ => write_user_offset(off_in, pos_in)?,
     }
-
-    // INFO: 关键步骤，读多少就应该写多少
-    buffer.truncate(read_size);
-
-    let write_size = match file_out.metadata().inode.metadata()._type.is_fifo() | (off_out == 0) {
-        true => {
-            debug_point!("write by file's off");
-            file_out.write(&buffer).await?
-        }
-        false => {
-            debug_point!("write by offset sended");
-            file_out.write_at(offset_out, &buffer).await?
-        }
-    };
-    if unlikely(write_size == 0) {
-        return Ok(0);
+    match off_out {
+        0 => file_out.metadata().set_offset(pos_out),
+        _ => write_user_offset(off_out, pos_out)?,
     }
-    if off_in != 0 {
-        unsafe {
-            core::ptr::write(off_in as *mut usize, offset_in + read_size);
-        }
+    Ok(copied)
+}
+
+/// 创建一个只存在于内存中的匿名文件
+///
+/// name：文件名，只用于调试，显示为 /memfd:name
+///
+/// flags：MFD_CLOEXEC、MFD_ALLOW_SEALING
+pub fn sys_memfd_create(name: usize, flags: u32) -> SysResult<usize> {
+    let flags = MemfdFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let name = user_cstr(name.into())?.ok_or(Errno::EFAULT)?;
+    info!("[sys_memfd_create] name = {}, flags = {:?}", name, flags);
+    // 名字最长 249 字节（NAME_MAX - strlen("memfd:")）
+    if unlikely(name.len() > 249) {
+        return Err(Errno::EINVAL);
     }
-    if off_out != 0 {
-        unsafe {
-            core::ptr::write(off_out as *mut usize, offset_out + write_size);
-        }
+    if unlikely(flags.contains(MemfdFlags::MFD_HUGETLB)) {
+        return Err(Errno::EINVAL);
     }
-    Ok(write_size)
+    let task = current_task().unwrap();
+    let mut open_flags = OpenFlags::O_RDWR;
+    if flags.contains(MemfdFlags::MFD_CLOEXEC) {
+        open_flags |= OpenFlags::O_CLOEXEC;
+    }
+    let file = MemfdFile::new(
+        &name,
+        open_flags,
+        flags.contains(MemfdFlags::MFD_ALLOW_SEALING),
+    );
+    task.alloc_fd(FdInfo::new(file, open_flags))
 }
 
-use crate::fs::fanotify::*;
-
 /// fanotify_init() initializes a new fanotify group and returns a
 /// file descriptor for the event queue associated with the group.
+///
+/// 不支持 FID 和 pidfd 形式的报告
 pub fn sys_fanotify_init(flags: usize, event_f_flags: usize) -> SysResult<usize> {
     let fanflags = FanFlags::from_bits(flags as u32).ok_or(Errno::EINVAL)?;
     let event_flags = FanEventFlags::from_bits(event_f_flags as u32).ok_or(Errno::EINVAL)?;
@@ -1961,65 +2196,61 @@ pub fn sys_fanotify_init(flags: usize, event_f_flags: usize) -> SysResult<usize>
         "[sys_fanotify_init] start, flags: {:?}, event_f_flags: {:?}",
         fanflags, event_flags
     );
-
-    if unlikely(
-        (fanflags.contains(FanFlags::FAN_CLASS_PRE_CONTENT)
-            && fanflags.contains(FanFlags::FAN_CLASS_CONTENT))
-            || (fanflags.contains(FanFlags::FAN_CLASS_PRE_CONTENT)
-                && fanflags.contains(FanFlags::FAN_CLASS_NOTIF))
-            || (fanflags.contains(FanFlags::FAN_CLASS_CONTENT)
-                && fanflags.contains(FanFlags::FAN_CLASS_NOTIF)),
-    ) {
-        return Err(Errno::EINVAL);
+    if !capable(CapSet::CAP_SYS_ADMIN) {
+        return Err(Errno::EPERM);
     }
-    if unlikely(
-        fanflags.contains(FanFlags::FAN_REPORT_TID)
-            && fanflags.contains(FanFlags::FAN_REPORT_PIDFD),
-    ) {
+    if unlikely(fanflags.contains(FanFlags::FAN_CLASS_CONTENT | FanFlags::FAN_CLASS_PRE_CONTENT)) {
         return Err(Errno::EINVAL);
     }
-    if unlikely(
-        fanflags.contains(FanFlags::FAN_CLASS_CONTENT)
-            && fanflags.contains(FanFlags::FAN_REPORT_FID)
-            || fanflags.contains(FanFlags::FAN_CLASS_PRE_CONTENT)
-                && fanflags.contains(FanFlags::FAN_REPORT_FID),
-    ) {
+    if unlikely(fanflags.intersects(
+        FanFlags::FAN_REPORT_FID
+            | FanFlags::FAN_REPORT_DIR_FID
+            | FanFlags::FAN_REPORT_NAME
+            | FanFlags::FAN_REPORT_TARGET_FID
+            | FanFlags::FAN_REPORT_PIDFD,
+    )) {
         return Err(Errno::EINVAL);
     }
-    if unlikely(
-        fanflags.contains(FanFlags::FAN_REPORT_NAME)
-            && !fanflags.contains(FanFlags::FAN_REPORT_DIR_FID),
-    ) {
-        return Err(Errno::EINVAL);
-    }
-    // if unlikely(
-    //     fanflags.contains(FanFlags::FAN_REPORT_TARGET_FID)
-    //         && !(fanflags.contains(FanFlags::FAN_REPORT_FID)
-    //             || fanflags.contains(FanFlags::FAN_REPORT_DIR_FID)
-    //             || fanflags.contains(FanFlags::FAN_REPORT_NAME)),
-    // ) {
-    //     return Err(Errno::EINVAL);
-    // }
 
+    let mut open_flags = OpenFlags::O_RDONLY;
+    if fanflags.contains(FanFlags::FAN_CLOEXEC) {
+        open_flags |= OpenFlags::O_CLOEXEC;
+    }
+    if fanflags.contains(FanFlags::FAN_NONBLOCK) {
+        open_flags |= OpenFlags::O_NONBLOCK;
+    }
     let task = current_task().unwrap();
-    let group = Arc::new(FanotifyGroup::new(fanflags, event_flags));
-
-    // Workaround: Create a pipe for event notification and associate the group with the fd.
-    // The user reads events from this pipe. A full implementation of permission-based events
-    // would require a custom FileTrait object to handle write() responses.
-    let (read_pipe, _write_pipe) = Pipe::new();
-    let open_flags = OpenFlags::from_bits_truncate(event_flags.bits() as i32);
-    let fd_info = FdInfo::new(read_pipe, open_flags);
-    let fd = task.alloc_fd(fd_info)?;
-
-    FANOTIFY_GROUPS.lock().insert(fd, group);
+    let fd = task.alloc_fd(FdInfo::new(
+        Fanotify::new(fanflags, event_flags, open_flags),
+        open_flags,
+    ))?;
     info!("[sys_fanotify_init] created fanotify group, fd: {}", fd);
-
     Ok(fd)
 }
 
+/// 解析 dirfd 和 pathname 指定的监视对象，pathname 为空指针时就是 dirfd 本身。
+/// follow 为 false 时不跟随最后一级符号链接
+fn notify_target(dirfd: isize, pathname: usize, follow: bool) -> SysResult<AbsPath> {
+    let task = current_task().unwrap();
+    if pathname == 0 {
+        let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
+        return Ok(AbsPath::new(file.abspath()));
+    }
+    let path = user_cstr(pathname.into())?.ok_or(Errno::EFAULT)?;
+    if dirfd == AT_FDCWD || path.starts_with('/') {
+        return resolve_path(task.get_current_path(), path).follow(follow);
+    }
+    let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
+    if unlikely(!file.metadata().inode.metadata()._type.is_dir()) {
+        return Err(Errno::ENOTDIR);
+    }
+    resolve_path(file.abspath(), path).follow(follow)
+}
+
 /// fanotify_mark() adds, removes, or modifies an fanotify mark on a
 /// filesystem object.
+///
+/// 挂载点和文件系统标记都作用于路径所在挂载点下的整棵子树
 pub fn sys_fanotify_mark(
     fanotify_fd: usize,
     flags: u32,
@@ -2032,47 +2263,279 @@ pub fn sys_fanotify_mark(
         "[sys_fanotify_mark] fanotify_fd: {}, flags: {:?}, mask: {:#x}, dirfd: {}, pathname: {:#x}",
         fanotify_fd, mark_flags, mask, dirfd, pathname
     );
+    let task = current_task().unwrap();
+    let group = task
+        .get_file_by_fd(fanotify_fd)
+        .ok_or(Errno::EBADF)?
+        .downcast_arc::<Fanotify>()
+        .map_err(|_| Errno::EINVAL)?;
+
+    let op = mark_flags
+        & (FanMarkFlags::FAN_MARK_ADD | FanMarkFlags::FAN_MARK_REMOVE | FanMarkFlags::FAN_MARK_FLUSH);
+    if op.bits().count_ones() != 1 {
+        return Err(Errno::EINVAL);
+    }
+    let subtree =
+        mark_flags.intersects(FanMarkFlags::FAN_MARK_MOUNT | FanMarkFlags::FAN_MARK_FILESYSTEM);
+    if op == FanMarkFlags::FAN_MARK_FLUSH {
+        group.flush_marks(subtree);
+        return Ok(0);
+    }
+    // 权限事件需要阻塞等待监听者的回复，其余事件需要 FID 形式的报告，都不支持
+    let valid = FAN_FD_EVENTS | FAN_ONDIR | FAN_EVENT_ON_CHILD;
+    if unlikely(mask == 0 || mask & !valid != 0) {
+        return Err(Errno::EINVAL);
+    }
 
-    let group = FANOTIFY_GROUPS
-        .lock()
-        .get(&fanotify_fd)
-        .cloned()
-        .ok_or(Errno::EINVAL)?;
+    let follow = !mark_flags.contains(FanMarkFlags::FAN_MARK_DONT_FOLLOW);
+    let target = notify_target(dirfd, pathname, follow)?;
+    let inode = Dentry::get_inode_from_path(&target.get())?;
+    if mark_flags.contains(FanMarkFlags::FAN_MARK_ONLYDIR) && !inode.metadata()._type.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    let path = match subtree {
+        true => MNT_TABLE.lock().mount_point_of(&target.get()),
+        false => target.get(),
+    };
+    let ignore = mark_flags.contains(FanMarkFlags::FAN_MARK_IGNORED_MASK);
+    match op {
+        FanMarkFlags::FAN_MARK_ADD => group.add_mark(&path, mask, ignore, subtree),
+        _ => group.remove_mark(&path, mask, ignore, subtree)?,
+    }
+    Ok(0)
+}
 
+/// 创建一个 inotify 实例
+pub fn sys_inotify_init1(flags: u32) -> SysResult<usize> {
+    let flags = InotifyFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    info!("[sys_inotify_init1] flags: {:?}", flags);
+    let mut open_flags = OpenFlags::O_RDONLY;
+    if flags.contains(InotifyFlags::IN_CLOEXEC) {
+        open_flags |= OpenFlags::O_CLOEXEC;
+    }
+    if flags.contains(InotifyFlags::IN_NONBLOCK) {
+        open_flags |= OpenFlags::O_NONBLOCK;
+    }
     let task = current_task().unwrap();
-    let path = user_cstr(pathname.into())?.ok_or(Errno::EINVAL)?;
-    let cwd = task.get_current_path();
+    task.alloc_fd(FdInfo::new(Inotify::new(open_flags), open_flags))
+}
 
-    let target_path = if dirfd == AT_FDCWD {
-        resolve_path(cwd, path)
-    } else {
-        let file = task.get_file_by_fd(dirfd as usize).ok_or(Errno::EBADF)?;
-        if !file.metadata().inode.metadata()._type.is_dir() {
-            return Err(Errno::ENOTDIR);
-        }
-        resolve_path(file.abspath(), path)
+/// 监视 pathname，返回 watch descriptor。同一个对象再次添加时返回原来的 wd
+pub fn sys_inotify_add_watch(fd: usize, pathname: usize, mask: u32) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let inotify = task
+        .get_file_by_fd(fd)
+        .ok_or(Errno::EBADF)?
+        .downcast_arc::<Inotify>()
+        .map_err(|_| Errno::EINVAL)?;
+    let path = user_cstr(pathname.into())?.ok_or(Errno::EFAULT)?;
+    let target = resolve_path(task.get_current_path(), path).follow(mask & IN_DONT_FOLLOW == 0)?;
+    info!("[sys_inotify_add_watch] fd: {}, path: {}, mask: {:#x}", fd, target.get(), mask);
+
+    let inode = Dentry::get_inode_from_path(&target.get())?;
+    inode.permission(MayAccess::MAY_READ)?;
+    if mask & IN_ONLYDIR != 0 && !inode.metadata()._type.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    inotify.add_watch(&target.get(), mask).map(|wd| wd as usize)
+}
+
+/// 移除 watch
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> SysResult<usize> {
+    info!("[sys_inotify_rm_watch] fd: {}, wd: {}", fd, wd);
+    let task = current_task().unwrap();
+    let inotify = task
+        .get_file_by_fd(fd)
+        .ok_or(Errno::EBADF)?
+        .downcast_arc::<Inotify>()
+        .map_err(|_| Errno::EINVAL)?;
+    inotify.rm_watch(wd)?;
+    Ok(0)
+}
+
+/// xattr 系列系统调用按路径找到的对象，l 开头的调用不跟随最后一级符号链接
+fn xattr_path_inode(pathname: usize, follow: bool) -> SysResult<(Arc<dyn InodeTrait>, String)> {
+    let task = current_task().unwrap();
+    let path = user_cstr(pathname.into())?.ok_or(Errno::EFAULT)?;
+    let abs_path = resolve_path(task.get_current_path(), path).follow(follow)?.get();
+    Ok((Dentry::get_inode_from_path(&abs_path)?, abs_path))
+}
+
+/// f 开头的 xattr 系统调用按 fd 找到的对象
+fn xattr_fd_inode(fd: usize) -> SysResult<(Arc<dyn InodeTrait>, String)> {
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    Ok((file.metadata().inode.clone(), file.abspath()))
+}
+
+/// 从用户空间读出属性名并检查命名空间
+fn xattr_user_name(name: usize) -> SysResult<String> {
+    let name = user_cstr(name.into())?.ok_or(Errno::EFAULT)?;
+    xattr_name(&name)?;
+    Ok(name)
+}
+
+fn do_setxattr(
+    inode: &Arc<dyn InodeTrait>,
+    path: &str,
+    name: usize,
+    value: usize,
+    size: usize,
+    flags: u32,
+) -> SysResult<usize> {
+    let flags = XattrFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let name = xattr_user_name(name)?;
+    if unlikely(size > XATTR_SIZE_MAX) {
+        return Err(Errno::E2BIG);
+    }
+    let value = match size {
+        0 => &[][..],
+        _ => user_slice::<u8>(value.into(), size)?.ok_or(Errno::EFAULT)?,
     };
+    inode.xattr_permission(&name, MayAccess::MAY_WRITE)?;
+    inode.setxattr(&name, value, flags)?;
+    let isdir = inode.metadata()._type.is_dir();
+    fsnotify(path, FsEventMask::ATTRIB | FsEventMask::dir(isdir));
+    Ok(0)
+}
 
-    let mut marks = group.marks.lock();
+/// size 为 0 时只返回属性值的长度
+fn do_getxattr(inode: &Arc<dyn InodeTrait>, name: usize, value: usize, size: usize) -> SysResult<usize> {
+    let name = xattr_user_name(name)?;
+    inode.xattr_permission(&name, MayAccess::MAY_READ)?;
+    let attr = inode.getxattr(&name)?;
+    if size == 0 || attr.is_empty() {
+        return Ok(attr.len());
+    }
+    if size < attr.len() {
+        return Err(Errno::ERANGE);
+    }
+    let buf = user_slice_mut::<u8>(value.into(), attr.len())?.ok_or(Errno::EFAULT)?;
+    buf.copy_from_slice(&attr);
+    Ok(attr.len())
+}
 
-    if mark_flags.contains(FanMarkFlags::FAN_MARK_ADD) {
-        let mark = FanotifyMark {
-            mask,
-            flags: mark_flags,
-        };
-        marks.insert(target_path.get(), mark);
-        info!("[fanotify] Added mark for path: {}", target_path.get());
-    } else if mark_flags.contains(FanMarkFlags::FAN_MARK_REMOVE) {
-        if marks.remove(&target_path.get()).is_some() {
-            info!("[fanotify] Removed mark for path: {}", target_path.get());
-        }
-        // Removing a non-existent mark is not an error according to man page.
-    } else if mark_flags.contains(FanMarkFlags::FAN_MARK_FLUSH) {
-        marks.clear();
-        info!("[fanotify] Flushed all marks for fd: {}", fanotify_fd);
-    } else {
-        return Err(Errno::EINVAL);
+/// 名字以 0 结尾依次排列，调用者看不到的 trusted.* 属性不列出
+fn do_listxattr(inode: &Arc<dyn InodeTrait>, list: usize, size: usize) -> SysResult<usize> {
+    let names = match inode.listxattr() {
+        Ok(names) => names,
+        // 不支持扩展属性的文件系统上没有任何属性
+        Err(Errno::EOPNOTSUPP) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let mut buf = Vec::new();
+    for name in names.iter().filter(|name| inode.xattr_listable(name)) {
+        buf.extend_from_slice(name.as_bytes());
+        buf.push(0);
     }
+    if unlikely(buf.len() > XATTR_LIST_MAX) {
+        return Err(Errno::E2BIG);
+    }
+    if size == 0 || buf.is_empty() {
+        return Ok(buf.len());
+    }
+    if size < buf.len() {
+        return Err(Errno::ERANGE);
+    }
+    let out = user_slice_mut::<u8>(list.into(), buf.len())?.ok_or(Errno::EFAULT)?;
+    out.copy_from_slice(&buf);
+    Ok(buf.len())
+}
 
+fn do_removexattr(inode: &Arc<dyn InodeTrait>, path: &str, name: usize) -> SysResult<usize> {
+    let name = xattr_user_name(name)?;
+    inode.xattr_permission(&name, MayAccess::MAY_WRITE)?;
+    inode.removexattr(&name)?;
+    let isdir = inode.metadata()._type.is_dir();
+    fsnotify(path, FsEventMask::ATTRIB | FsEventMask::dir(isdir));
     Ok(0)
-}
\ No newline at end of file
+}
+
+/// 设置 pathname 的扩展属性
+pub fn sys_setxattr(
+    pathname: usize,
+    name: usize,
+    value: usize,
+    size: usize,
+    flags: u32,
+) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, true)?;
+    info!("[sys_setxattr] path: {}, size: {}, flags: {:#x}", path, size, flags);
+    do_setxattr(&inode, &path, name, value, size, flags)
+}
+
+/// 与 setxattr 相同，pathname 是符号链接时作用于链接本身
+pub fn sys_lsetxattr(
+    pathname: usize,
+    name: usize,
+    value: usize,
+    size: usize,
+    flags: u32,
+) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, false)?;
+    info!("[sys_lsetxattr] path: {}, size: {}, flags: {:#x}", path, size, flags);
+    do_setxattr(&inode, &path, name, value, size, flags)
+}
+
+pub fn sys_fsetxattr(fd: usize, name: usize, value: usize, size: usize, flags: u32) -> SysResult<usize> {
+    let (inode, path) = xattr_fd_inode(fd)?;
+    info!("[sys_fsetxattr] fd: {}, size: {}, flags: {:#x}", fd, size, flags);
+    do_setxattr(&inode, &path, name, value, size, flags)
+}
+
+/// 读取 pathname 的扩展属性，返回属性值的长度
+pub fn sys_getxattr(pathname: usize, name: usize, value: usize, size: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, true)?;
+    info!("[sys_getxattr] path: {}, size: {}", path, size);
+    do_getxattr(&inode, name, value, size)
+}
+
+pub fn sys_lgetxattr(pathname: usize, name: usize, value: usize, size: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, false)?;
+    info!("[sys_lgetxattr] path: {}, size: {}", path, size);
+    do_getxattr(&inode, name, value, size)
+}
+
+pub fn sys_fgetxattr(fd: usize, name: usize, value: usize, size: usize) -> SysResult<usize> {
+    let (inode, _) = xattr_fd_inode(fd)?;
+    info!("[sys_fgetxattr] fd: {}, size: {}", fd, size);
+    do_getxattr(&inode, name, value, size)
+}
+
+/// 列出 pathname 的扩展属性名，返回名字列表的长度
+pub fn sys_listxattr(pathname: usize, list: usize, size: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, true)?;
+    info!("[sys_listxattr] path: {}, size: {}", path, size);
+    do_listxattr(&inode, list, size)
+}
+
+pub fn sys_llistxattr(pathname: usize, list: usize, size: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, false)?;
+    info!("[sys_llistxattr] path: {}, size: {}", path, size);
+    do_listxattr(&inode, list, size)
+}
+
+pub fn sys_flistxattr(fd: usize, list: usize, size: usize) -> SysResult<usize> {
+    let (inode, _) = xattr_fd_inode(fd)?;
+    info!("[sys_flistxattr] fd: {}, size: {}", fd, size);
+    do_listxattr(&inode, list, size)
+}
+
+/// 删除 pathname 的扩展属性
+pub fn sys_removexattr(pathname: usize, name: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, true)?;
+    info!("[sys_removexattr] path: {}", path);
+    do_removexattr(&inode, &path, name)
+}
+
+pub fn sys_lremovexattr(pathname: usize, name: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_path_inode(pathname, false)?;
+    info!("[sys_lremovexattr] path: {}", path);
+    do_removexattr(&inode, &path, name)
+}
+
+pub fn sys_fremovexattr(fd: usize, name: usize) -> SysResult<usize> {
+    let (inode, path) = xattr_fd_inode(fd)?;
+    info!("[sys_fremovexattr] fd: {}", fd);
+    do_removexattr(&inode, &path, name)
+}
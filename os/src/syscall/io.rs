@@ -127,6 +127,12 @@ pub fn sys_ioctl(fd: usize, op: usize, arg: usize) -> SysResult<usize> {
     }
     // Ok(0)
     if let Some(file) = task.get_file_by_fd(fd) {
+        if file.metadata().inode.metadata()._type.is_socket() {
+            let family = file.get_socket()?.get_family();
+            if let Some(res) = crate::net::sock_ioctl(family, op, arg) {
+                return res;
+            }
+        }
         if file.metadata().inode.metadata()._type.is_fifo() {
             info!("is pipe");
             return Ok(0);
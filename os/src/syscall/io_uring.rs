@@ -0,0 +1,349 @@
+//! io_uring_setup 和 io_uring_enter
+//!
+//! 每个 SQE（或者用 IOSQE_IO_LINK 串起来的一串 SQE）变成一个 future 交给 executor，
+//! 它以提交者的身份运行（见 `spawn_user_io`），所以可以直接复用 sys_read 等系统调用访问用户内存。
+//! 提交者在 io_uring_enter 中等待完成事件，也可以完全在用户态轮询 CQ
+
+use super::{
+    ffi::{IoVec, PollEvents, IOV_MAX},
+    fs::{sys_pread64, sys_pwrite64, sys_read, sys_readv, sys_write, sys_writev},
+    io::SigMaskGuard,
+    net::{sys_accept4, sys_connect},
+};
+use crate::{
+    fs::{
+        io_uring::{
+            CqWait, IoRingCtx, IoRingOpFuture, IoUring, IoUringEnterFlags, IoUringOp, IoUringParams,
+            IoUringSetupFlags, IoUringSqe, SqeFlags, IORING_MAX_CQ_ENTRIES, IORING_MAX_ENTRIES,
+            IORING_TIMEOUT_ABS,
+        },
+        OpenFlags,
+    },
+    mm::user_ptr::{user_ref, user_ref_mut, user_slice, user_slice_mut},
+    signal::SigMask,
+    sync::{suspend_now, time_duration, TimeSpec, TimeoutFuture},
+    task::{current_task, spawn_user_io, FdInfo, TaskControlBlock},
+    utils::{Errno, SysResult},
+};
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    intrinsics::unlikely,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use log::info;
+
+/// setup an io_uring instance
+/// 分配 SQ 和 CQ，把用户 mmap 需要的偏移写回 params，返回 io_uring 的 fd。
+/// 不支持 SQPOLL、IOPOLL 等需要内核线程或者轮询驱动的模式
+pub fn sys_io_uring_setup(entries: u32, params: usize) -> SysResult<usize> {
+    info!("[sys_io_uring_setup] start, entries = {}, params = {:#x}", entries, params);
+    let params = user_ref_mut::<IoUringParams>(params.into())?.ok_or(Errno::EFAULT)?;
+    let flags = IoUringSetupFlags::from_bits(params.flags).ok_or(Errno::EINVAL)?;
+    let supported = IoUringSetupFlags::IORING_SETUP_CQSIZE | IoUringSetupFlags::IORING_SETUP_CLAMP;
+    if unlikely(!supported.contains(flags) || params.resv.iter().any(|&resv| resv != 0)) {
+        return Err(Errno::EINVAL);
+    }
+    let clamp = flags.contains(IoUringSetupFlags::IORING_SETUP_CLAMP);
+    if unlikely(entries == 0 || (entries > IORING_MAX_ENTRIES && !clamp)) {
+        return Err(Errno::EINVAL);
+    }
+    let sq_entries = entries.min(IORING_MAX_ENTRIES).next_power_of_two();
+    let cq_entries = match flags.contains(IoUringSetupFlags::IORING_SETUP_CQSIZE) {
+        true => {
+            let cq_entries = params.cq_entries;
+            if unlikely(cq_entries == 0 || (cq_entries > IORING_MAX_CQ_ENTRIES && !clamp)) {
+                return Err(Errno::EINVAL);
+            }
+            let cq_entries = cq_entries.min(IORING_MAX_CQ_ENTRIES).next_power_of_two();
+            if unlikely(cq_entries < sq_entries) {
+                return Err(Errno::EINVAL);
+            }
+            cq_entries
+        }
+        false => 2 * sq_entries,
+    };
+
+    let ctx = IoRingCtx::new(sq_entries, cq_entries);
+    ctx.fill_params(params);
+    let task = current_task().unwrap();
+    let file = IoUring::new(ctx, OpenFlags::O_RDWR);
+    let fd = task.alloc_fd(FdInfo::new(file, OpenFlags::O_RDWR | OpenFlags::O_CLOEXEC))?;
+    info!("[sys_io_uring_setup] fd = {}, sq = {}, cq = {}", fd, sq_entries, cq_entries);
+    Ok(fd)
+}
+
+/// initiate and/or complete asynchronous I/O
+/// 提交最多 to_submit 个 SQE，返回提交的个数。带 IORING_ENTER_GETEVENTS 时
+/// 再等到 CQ 中至少有 min_complete 个事件，sig 不为空时等待期间使用它作为信号掩码
+pub async fn sys_io_uring_enter(
+    fd: usize,
+    to_submit: u32,
+    min_complete: u32,
+    flags: u32,
+    sig: usize,
+    sigsz: usize,
+) -> SysResult<usize> {
+    info!(
+        "[sys_io_uring_enter] start, fd = {}, to_submit = {}, min_complete = {}, flags = {:#x}",
+        fd, to_submit, min_complete, flags
+    );
+    let flags = IoUringEnterFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    // 没有声明 IORING_FEAT_EXT_ARG；SQ_WAKEUP 和 SQ_WAIT 只对 SQPOLL 有意义，这里忽略
+    if unlikely(flags.contains(IoUringEnterFlags::IORING_ENTER_EXT_ARG)) {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    let ring = file.downcast_arc::<IoUring>().map_err(|_| Errno::EOPNOTSUPP)?;
+    let ctx = ring.ctx.clone();
+
+    let submitted = submit_sqes(&task, &ctx, to_submit);
+
+    if flags.contains(IoUringEnterFlags::IORING_ENTER_GETEVENTS) && min_complete > 0 {
+        let new_mask = match sig {
+            0 => None,
+            _ => {
+                if unlikely(sigsz != size_of::<SigMask>()) {
+                    return Err(Errno::EINVAL);
+                }
+                Some(*user_ref::<SigMask>(sig.into())?.ok_or(Errno::EFAULT)?)
+            }
+        };
+        let _guard = SigMaskGuard::new(task.clone(), new_mask);
+        let until = CqWait::Ready(min_complete.min(ctx.cq_entries));
+        let wait = CqWaitFuture::new(ctx, until, Some(task));
+        if let Err(e) = wait.await {
+            // 已经提交了请求时返回提交的个数，否则才报告被信号打断
+            if submitted == 0 {
+                return Err(e);
+            }
+        }
+    }
+    Ok(submitted)
+}
+
+/// 从 SQ 取出 SQE，按链拆分后交给 executor
+fn submit_sqes(task: &Arc<TaskControlBlock>, ctx: &Arc<IoRingCtx>, to_submit: u32) -> usize {
+    let sqes = ctx.take_sqes(to_submit);
+    let submitted = sqes.len();
+    let mut chain = Vec::new();
+    for sqe in sqes {
+        let flags = SqeFlags::from_bits_truncate(sqe.flags);
+        chain.push((ctx.register_op(sqe.user_data), sqe));
+        if !flags.intersects(SqeFlags::IOSQE_IO_LINK | SqeFlags::IOSQE_IO_HARDLINK) {
+            spawn_chain(task, ctx, core::mem::take(&mut chain));
+        }
+    }
+    // 最后一个 SQE 带着 IOSQE_IO_LINK 时链就在这里结束
+    if !chain.is_empty() {
+        spawn_chain(task, ctx, chain);
+    }
+    submitted
+}
+
+fn spawn_chain(task: &Arc<TaskControlBlock>, ctx: &Arc<IoRingCtx>, chain: Vec<(u64, IoUringSqe)>) {
+    let ctx = ctx.clone();
+    spawn_user_io(task.clone(), async move { run_chain(ctx, chain).await });
+}
+
+/// 依次执行一条链上的请求。某个请求失败后，除非它带 IOSQE_IO_HARDLINK，
+/// 链上后面的请求都以 ECANCELED 完成
+async fn run_chain(ctx: Arc<IoRingCtx>, chain: Vec<(u64, IoUringSqe)>) {
+    let mut broken = false;
+    for (id, sqe) in chain {
+        let res = match broken {
+            true => Err(Errno::ECANCELED),
+            false => IoRingOpFuture::new(ctx.clone(), id, run_sqe(&ctx, id, &sqe)).await,
+        };
+        ctx.finish_op(id);
+        let res = match res {
+            Ok(ret) => ret as i32,
+            // 异步执行的操作没有系统调用可以重新执行
+            Err(Errno::ERESTARTSYS) => -(Errno::EINTR as isize) as i32,
+            Err(e) => -(e as isize) as i32,
+        };
+        info!("[io_uring] op {} done, opcode = {}, res = {}", id, sqe.opcode, res);
+        ctx.post_cqe(sqe.user_data, res);
+        let hardlink = SqeFlags::from_bits_truncate(sqe.flags).contains(SqeFlags::IOSQE_IO_HARDLINK);
+        broken |= res < 0 && !hardlink;
+    }
+}
+
+async fn run_sqe(ctx: &Arc<IoRingCtx>, id: u64, sqe: &IoUringSqe) -> SysResult<usize> {
+    let flags = SqeFlags::from_bits(sqe.flags).ok_or(Errno::EINVAL)?;
+    // 没有注册文件和缓冲区的接口；IOSQE_ASYNC 没有意义，因为请求总是异步执行的
+    let unsupported = SqeFlags::IOSQE_FIXED_FILE | SqeFlags::IOSQE_IO_DRAIN | SqeFlags::IOSQE_BUFFER_SELECT;
+    if unlikely(flags.intersects(unsupported)) {
+        return Err(Errno::EINVAL);
+    }
+    let fd = sqe.fd as usize;
+    let (addr, len) = (sqe.addr as usize, sqe.len as usize);
+    match IoUringOp::from(sqe.opcode) {
+        IoUringOp::Nop => Ok(0),
+        op @ (IoUringOp::Read | IoUringOp::Write | IoUringOp::Readv | IoUringOp::Writev) => {
+            io_rw(op, sqe).await
+        }
+        // 与 sys_fsync 相同，写入总是直接到达文件系统
+        IoUringOp::Fsync => {
+            let task = current_task().unwrap();
+            task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+            Ok(0)
+        }
+        IoUringOp::PollAdd => io_poll(fd, PollEvents::from_bits_truncate(sqe.op_flags as i16)).await,
+        IoUringOp::PollRemove | IoUringOp::TimeoutRemove | IoUringOp::AsyncCancel => {
+            ctx.cancel(sqe.addr, id).map(|_| 0)
+        }
+        IoUringOp::Timeout => io_timeout(ctx, sqe).await,
+        IoUringOp::Accept => sys_accept4(fd, addr, sqe.off as usize, sqe.op_flags).await,
+        IoUringOp::Connect => sys_connect(fd, addr, sqe.off as usize).await,
+        IoUringOp::Send => {
+            let task = current_task().unwrap();
+            let socket = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?.get_socket()?;
+            let buf = user_slice::<u8>(addr.into(), len)?.ok_or(Errno::EFAULT)?;
+            socket.send_msg(buf, None).await
+        }
+        IoUringOp::Recv => {
+            let task = current_task().unwrap();
+            let socket = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?.get_socket()?;
+            let buf = user_slice_mut::<u8>(addr.into(), len)?.ok_or(Errno::EFAULT)?;
+            socket.recv_msg(buf).await.map(|(size, _)| size)
+        }
+        IoUringOp::Unsupported => Err(Errno::EINVAL),
+    }
+}
+
+/// 读写请求。偏移为 -1 或者文件不可寻址（管道、套接字等）时使用并推进文件自己的偏移
+async fn io_rw(op: IoUringOp, sqe: &IoUringSqe) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let fd = sqe.fd as usize;
+    let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+    let seekable = file.metadata().inode.metadata()._type.is_file();
+    let offset = match sqe.off as i64 {
+        -1 => None,
+        off if off < 0 => return Err(Errno::EINVAL),
+        off => seekable.then_some(off as usize),
+    };
+    let (addr, len) = (sqe.addr as usize, sqe.len as usize);
+    match (op, offset) {
+        (IoUringOp::Read, None) => sys_read(fd, addr, len).await,
+        (IoUringOp::Read, Some(off)) => sys_pread64(fd, addr, len, off).await,
+        (IoUringOp::Write, None) => sys_write(fd, addr, len).await,
+        (IoUringOp::Write, Some(off)) => sys_pwrite64(fd, addr, len, off).await,
+        (IoUringOp::Readv, None) => sys_readv(fd, addr, len).await,
+        (IoUringOp::Writev, None) => sys_writev(fd, addr, len).await,
+        (IoUringOp::Readv, Some(off)) => rw_vectored_at(fd, addr, len, off, false).await,
+        (IoUringOp::Writev, Some(off)) => rw_vectored_at(fd, addr, len, off, true).await,
+        _ => unreachable!(),
+    }
+}
+
+/// 带偏移的 readv/writev，某一段没有读写满时停止
+async fn rw_vectored_at(fd: usize, iov: usize, iovcnt: usize, mut offset: usize, write: bool) -> SysResult<usize> {
+    if unlikely(iovcnt > IOV_MAX) {
+        return Err(Errno::EINVAL);
+    }
+    let mut total = 0;
+    for i in 0..iovcnt {
+        let seg = unsafe { *((iov + size_of::<IoVec>() * i) as *const IoVec) };
+        if seg.iov_len == 0 {
+            continue;
+        }
+        let len = match write {
+            true => sys_pwrite64(fd, seg.iov_base, seg.iov_len, offset).await?,
+            false => sys_pread64(fd, seg.iov_base, seg.iov_len, offset).await?,
+        };
+        total += len;
+        offset += len;
+        if len < seg.iov_len {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// 等待文件就绪，返回发生的事件
+///
+/// 没有就绪时 pollin / pollout 已经把本请求的 waker 挂到文件上，挂起直到文件状态变化再检查
+async fn io_poll(fd: usize, events: PollEvents) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    loop {
+        let file = task.get_file_by_fd(fd).ok_or(Errno::EBADF)?;
+        let mut revents = PollEvents::empty();
+        let mut check = |ready: SysResult<bool>, event: PollEvents| match ready {
+            Ok(true) => revents |= event,
+            Ok(false) => {}
+            Err(_) => revents |= PollEvents::POLLERR,
+        };
+        if events.contains(PollEvents::POLLIN) {
+            check(file.pollin().await, PollEvents::POLLIN);
+        }
+        if events.contains(PollEvents::POLLOUT) {
+            check(file.pollout().await, PollEvents::POLLOUT);
+        }
+        if !revents.is_empty() {
+            return Ok(revents.bits() as u16 as usize);
+        }
+        suspend_now().await;
+    }
+}
+
+/// IORING_OP_TIMEOUT：off 为 0 时是单纯的定时器，到期以 ETIME 完成；
+/// 否则在此之后又有 off 个请求完成时以 0 完成，先到期则仍以 ETIME 完成
+async fn io_timeout(ctx: &Arc<IoRingCtx>, sqe: &IoUringSqe) -> SysResult<usize> {
+    if unlikely(sqe.len != 1 || sqe.op_flags & !IORING_TIMEOUT_ABS != 0) {
+        return Err(Errno::EINVAL);
+    }
+    let ts = *user_ref::<TimeSpec>((sqe.addr as usize).into())?.ok_or(Errno::EFAULT)?;
+    if unlikely(!ts.check_valid()) {
+        return Err(Errno::EINVAL);
+    }
+    let span = match sqe.op_flags & IORING_TIMEOUT_ABS {
+        0 => Duration::from(ts),
+        _ => Duration::from(ts).saturating_sub(time_duration()),
+    };
+    if sqe.off == 0 {
+        let _ = TimeoutFuture::new(core::future::pending::<()>(), span).await;
+        return Err(Errno::ETIME);
+    }
+    let until = CqWait::Completed(ctx.completed() + sqe.off);
+    let wait = CqWaitFuture::new(ctx.clone(), until, None);
+    match TimeoutFuture::new(wait, span).await {
+        Ok(res) => res.map(|_| 0),
+        Err(_) => Err(Errno::ETIME),
+    }
+}
+
+/// 等待完成队列满足条件。给出 task 时，在等待期间收到未屏蔽的信号返回 EINTR
+struct CqWaitFuture {
+    ctx: Arc<IoRingCtx>,
+    until: CqWait,
+    task: Option<Arc<TaskControlBlock>>,
+}
+
+impl CqWaitFuture {
+    fn new(ctx: Arc<IoRingCtx>, until: CqWait, task: Option<Arc<TaskControlBlock>>) -> Self {
+        if let Some(task) = &task {
+            task.set_wake_up_signal(!*task.get_blocked());
+        }
+        Self { ctx, until, task }
+    }
+}
+
+impl Future for CqWaitFuture {
+    type Output = SysResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.ctx.poll_wait(self.until, cx.waker()) {
+            return Poll::Ready(Ok(()));
+        }
+        if let Some(task) = &self.task {
+            if task.sig_pending.lock().has_expected(!*task.get_blocked()).0 {
+                return Poll::Ready(Err(Errno::EINTR));
+            }
+        }
+        Poll::Pending
+    }
+}
@@ -5,6 +5,7 @@ use lwext4_rust::bindings::EINVAL;
 
 use crate::task::current_task;
 use crate::{
+    fs::{io_uring::IoUring, memfd::MemfdInode},
     hal::config::{align_up_by_page, is_aligned_to_page, PAGE_MASK, PAGE_SIZE},
     ipc::{
         shm::{self, ShmAtFlags, ShmGetFlags, ShmObject, ShmidDs, SHARED_MEMORY_MANAGER},
@@ -18,7 +19,7 @@ use crate::{
         user_ptr::user_ref_mut,
         VirtAddr,
     },
-    utils::{Errno, SysResult},
+    utils::{downcast::Downcast, Errno, SysResult},
 };
 
 use super::ffi::ShmOp;
@@ -105,11 +106,25 @@ pub fn sys_mmap(
             return Err(e);
         }
         let file = fd.file.unwrap();
+        // 被 F_SEAL_WRITE 封印的 memfd 不允许可写的共享映射
+        if let Some(memfd) = file.metadata().inode.clone().downcast_arc::<MemfdInode>() {
+            if flags.contains(MmapFlags::MAP_SHARED)
+                && prot.contains(MmapProt::PROT_WRITE)
+                && memfd.get_seals().deny_write()
+            {
+                return Err(Errno::EPERM);
+            }
+        }
+        // io_uring 的环只有创建时分配的那些页，不能映射到环外
+        if let Ok(ring) = file.clone().downcast_arc::<IoUring>() {
+            if !ring.ctx.mapping_fits(offset, length) {
+                return Err(Errno::EINVAL);
+            }
+        }
         let start_va = task
             .with_mut_memory_space(|m| {
                 m.alloc_mmap_area_lazily(addr.into(), length, perm, flags, file, offset)
-            })
-            .unwrap();
+            })?;
         info!("[sys_mmap] ret {:#x}", start_va.0);
         return Ok(start_va.0);
     }
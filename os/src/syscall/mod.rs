@@ -2,12 +2,14 @@ mod ffi;
 pub mod fs;
 mod io;
 mod io_async;
+mod io_uring;
 mod mm;
 mod net;
 mod process;
 mod sync;
 
 use crate::sync::TimeSpec;
+use crate::task::{secure_computing, SeccompAction};
 use crate::utils::{backtrace, Errno, SysResult};
 pub use ffi::CloneFlags;
 pub use ffi::CpuSet;
@@ -18,6 +20,7 @@ pub use ffi::StatFs;
 pub use ffi::SysCode;
 use fs::*;
 use io::*;
+use io_uring::{sys_io_uring_enter, sys_io_uring_setup};
 use log::info;
 use mm::{sys_brk, sys_mmap, sys_munmap};
 use mm::{sys_membarrier, sys_mprotect, sys_mremap, sys_shmat, sys_shmctl, sys_shmdt, sys_shmget};
@@ -26,7 +29,17 @@ use process::*;
 use sync::*;
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
+///
+/// 返回 None 表示任务已经被 seccomp 杀死，调用者不能再写回返回值
+pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> Option<SysResult<usize>> {
+    match secure_computing(syscall_id, &args) {
+        SeccompAction::Allow => Some(dispatch_syscall(syscall_id, args).await),
+        SeccompAction::Return(ret) => Some(ret),
+        SeccompAction::Kill => None,
+    }
+}
+
+async fn dispatch_syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
     let syscode = SysCode::from(syscall_id);
     // info!("syscode = {}", syscode);
     match syscode {
@@ -44,9 +57,44 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
             )
             .await
         }
+        SysCode::SYSCALL_TEE => {
+            sys_tee(args[0] as usize, args[1] as usize, args[2] as usize, args[3] as u32).await
+        }
+        SysCode::SYSCALL_VMSPLICE => {
+            sys_vmsplice(args[0] as usize, args[1] as usize, args[2] as usize, args[3] as u32).await
+        }
         SysCode::SYSCALL_SCHED_GETPARAM => sys_sched_getparam(args[0] as usize, args[1] as usize),
         SysCode::SYSCALL_SCHED_SETPARAM => sys_sched_setparam(args[0] as usize, args[1] as usize),
-        SysCode::SYSCALL_SETRESUID => sys_setresuid(),
+        SysCode::SYSCALL_SETRESUID => {
+            sys_setresuid(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_GETRESUID => {
+            sys_getresuid(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_SETRESGID => {
+            sys_setresgid(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_GETRESGID => {
+            sys_getresgid(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_SETREUID => sys_setreuid(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_SETREGID => sys_setregid(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_SETFSUID => sys_setfsuid(args[0] as usize),
+        SysCode::SYSCALL_SETFSGID => sys_setfsgid(args[0] as usize),
+        SysCode::SYSCALL_GETGROUPS => sys_getgroups(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_SETGROUPS => sys_setgroups(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_CAPGET => sys_capget(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_CAPSET => sys_capset(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_SECCOMP => {
+            sys_seccomp(args[0] as u32, args[1] as u32, args[2] as usize)
+        }
+        SysCode::SYSCALL_PRCTL => sys_prctl(
+            args[0] as usize,
+            args[1] as usize,
+            args[2] as usize,
+            args[3] as usize,
+            args[4] as usize,
+        ),
         SysCode::SYSCALL_SETUID => sys_setuid(args[0] as usize),
         SysCode::SYSCALL_FCHDIR => sys_fchdir(args[0] as usize),
         SysCode::SYSCALL_SETGID => sys_setgid(args[0] as usize),
@@ -71,7 +119,15 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
         }
         SysCode::SYSCALL_FALLOCAT => sys_fallocate(),
         SysCode::SYSCALL_MSYNC => sys_msync(),
-        SysCode::SYSCALL_FCHOWNAT => sys_fchownat(),
+        SysCode::SYSCALL_FCHOWNAT => sys_fchownat(
+            args[0] as isize,
+            args[1] as usize,
+            args[2] as usize,
+            args[3] as usize,
+            args[4] as u32,
+        ),
+        SysCode::SYSCALL_FCHOWN => sys_fchown(args[0] as usize, args[1] as usize, args[2] as usize),
+        SysCode::SYSCALL_FCHMOD => sys_fchmod(args[0] as usize, args[1] as usize),
         SysCode::SYSCALL_GETGID => sys_getgid(),
         SysCode::SYSCALL_SCHED_GETAFFINITY => {
             sys_sched_getaffinity(args[0] as usize, args[1] as usize, args[2] as usize)
@@ -171,7 +227,10 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
         SysCode::SYSCALL_GETEGID => sys_getegid(),
         SysCode::SYSCALL_GETEUID => sys_geteuid(),
         SysCode::SYSCALL_GETTID => sys_gettid(),
-        SysCode::SYSCALL_FCNTL => sys_fcntl(args[0] as usize, args[1] as u32, args[2] as usize),
+        SysCode::SYSCALL_FCNTL => {
+            sys_fcntl(args[0] as usize, args[1] as u32, args[2] as usize).await
+        }
+        SysCode::SYSCALL_FLOCK => sys_flock(args[0] as usize, args[1] as u32).await,
         SysCode::SYSCALL_SIGACTION => {
             sys_sigaction(args[0] as usize, args[1] as usize, args[2] as usize)
         }
@@ -191,6 +250,9 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
         SysCode::SYSCALL_UNLINKAT => {
             sys_unlinkat(args[0] as isize, args[1] as usize, args[2] as u32)
         }
+        SysCode::SYSCALL_SYMLINKAT => {
+            sys_symlinkat(args[0] as usize, args[1] as isize, args[2] as usize)
+        }
         SysCode::SYSCALL_LINKAT => sys_linkat(
             args[0] as isize,
             args[1] as usize,
@@ -213,7 +275,7 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
             args[2] as u32,
             args[3] as usize,
         ),
-        SysCode::SYSCALL_CLOSE => sys_close(args[0]),
+        SysCode::SYSCALL_CLOSE => sys_close(args[0]).await,
         SysCode::SYSCALL_PIPE2 => sys_pipe2(args[0] as *mut u32, args[1] as i32),
         SysCode::SYSCALL_GETDENTS64 => {
             sys_getdents64(args[0] as usize, args[1] as usize, args[2] as usize)
@@ -252,6 +314,16 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
             args[4],
             args[5],
         ),
+        SysCode::SYSCALL_WAITID => {
+            sys_waitid(
+                args[0] as u32,
+                args[1] as usize,
+                args[2] as usize,
+                args[3] as i32,
+                args[4] as usize,
+            )
+            .await
+        }
         SysCode::SYSCALL_WAIT4 => {
             sys_wait4(
                 args[0] as isize,
@@ -285,6 +357,7 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
         ),
         SysCode::SYSCALL_LSEEK => sys_lseek(args[0] as usize, args[1] as isize, args[2] as usize),
         SysCode::SYSCALL_SETSID => sys_setsid(),
+        SysCode::SYSCALL_GETSID => sys_getsid(args[0] as usize),
         SysCode::SYSCALL_SETPGID => sys_setpgid(args[0] as usize, args[1] as usize),
         SysCode::SYSCALL_GETPGID => sys_getpgid(args[0] as usize),
         SysCode::SYSCALL_SIGRETURN => sys_sigreturn(),
@@ -293,7 +366,9 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
             sys_readv(args[0] as usize, args[1] as usize, args[2] as usize).await
         }
         SysCode::SYSCALL_FTRUNCATE64 => sys_ftruncate64(args[0] as usize, args[1] as usize),
-        SysCode::SYSCALL_FCHMODAT => sys_fchmodat(args[0] as isize, args[1] as usize, args[2] as usize, args[3] as u32),
+        // fchmodat 没有 flags 参数，只有 fchmodat2 才能指定 AT_SYMLINK_NOFOLLOW
+        SysCode::SYSCALL_FCHMODAT => sys_fchmodat(args[0] as isize, args[1] as usize, args[2] as usize, 0),
+        SysCode::SYSCALL_FCHMODAT2 => sys_fchmodat(args[0] as isize, args[1] as usize, args[2] as usize, args[3] as u32),
         SysCode::SYSCALL_PREAD64 => {
             sys_pread64(
                 args[0] as usize,
@@ -419,6 +494,83 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> SysResult<usize> {
             .await
         }
         SysCode::SYSCALL_FANOTIFY_INIT => sys_fanotify_init(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_FANOTIFY_MARK => sys_fanotify_mark(
+            args[0] as usize,
+            args[1] as u32,
+            args[2] as u64,
+            args[3] as isize,
+            args[4] as usize,
+        ),
+        SysCode::SYSCALL_INOTIFY_INIT1 => sys_inotify_init1(args[0] as u32),
+        SysCode::SYSCALL_INOTIFY_ADD_WATCH => {
+            sys_inotify_add_watch(args[0] as usize, args[1] as usize, args[2] as u32)
+        }
+        SysCode::SYSCALL_INOTIFY_RM_WATCH => sys_inotify_rm_watch(args[0] as usize, args[1] as i32),
+        SysCode::SYSCALL_SETXATTR => sys_setxattr(
+            args[0] as usize,
+            args[1] as usize,
+            args[2] as usize,
+            args[3] as usize,
+            args[4] as u32,
+        ),
+        SysCode::SYSCALL_LSETXATTR => sys_lsetxattr(
+            args[0] as usize,
+            args[1] as usize,
+            args[2] as usize,
+            args[3] as usize,
+            args[4] as u32,
+        ),
+        SysCode::SYSCALL_FSETXATTR => sys_fsetxattr(
+            args[0] as usize,
+            args[1] as usize,
+            args[2] as usize,
+            args[3] as usize,
+            args[4] as u32,
+        ),
+        SysCode::SYSCALL_GETXATTR => {
+            sys_getxattr(args[0] as usize, args[1] as usize, args[2] as usize, args[3] as usize)
+        }
+        SysCode::SYSCALL_LGETXATTR => {
+            sys_lgetxattr(args[0] as usize, args[1] as usize, args[2] as usize, args[3] as usize)
+        }
+        SysCode::SYSCALL_FGETXATTR => {
+            sys_fgetxattr(args[0] as usize, args[1] as usize, args[2] as usize, args[3] as usize)
+        }
+        SysCode::SYSCALL_LISTXATTR => {
+            sys_listxattr(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_LLISTXATTR => {
+            sys_llistxattr(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_FLISTXATTR => {
+            sys_flistxattr(args[0] as usize, args[1] as usize, args[2] as usize)
+        }
+        SysCode::SYSCALL_REMOVEXATTR => sys_removexattr(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_LREMOVEXATTR => sys_lremovexattr(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_FREMOVEXATTR => sys_fremovexattr(args[0] as usize, args[1] as usize),
+        SysCode::SYSCALL_MEMFD_CREATE => sys_memfd_create(args[0] as usize, args[1] as u32),
+        SysCode::SYSCALL_PIDFD_OPEN => sys_pidfd_open(args[0] as isize, args[1] as u32),
+        SysCode::SYSCALL_IO_URING_SETUP => sys_io_uring_setup(args[0] as u32, args[1] as usize),
+        SysCode::SYSCALL_IO_URING_ENTER => {
+            sys_io_uring_enter(
+                args[0] as usize,
+                args[1] as u32,
+                args[2] as u32,
+                args[3] as u32,
+                args[4] as usize,
+                args[5] as usize,
+            )
+            .await
+        }
+        SysCode::SYSCALL_PIDFD_SEND_SIGNAL => sys_pidfd_send_signal(
+            args[0] as usize,
+            args[1] as i32,
+            args[2] as usize,
+            args[3] as u32,
+        ),
+        SysCode::SYSCALL_PIDFD_GETFD => {
+            sys_pidfd_getfd(args[0] as usize, args[1] as usize, args[2] as u32)
+        }
 
         _ => {
             // log::error!("Unsupported syscall_id: {}", syscall_id);
@@ -1,13 +1,14 @@
-use core::{intrinsics::unlikely, sync::atomic::Ordering};
+use core::{intrinsics::unlikely, time::Duration};
 
 use super::ffi::{
-    ShutHow, CONGESTION, MAXSEGMENT, NODELAY, SOL_SOCKET, SOL_TCP, SO_KEEPALIVE, SO_RCVBUF,
-    SO_SNDBUF,
+    ShutHow, CONGESTION, IPV6_V6ONLY, IP_TTL, MAXSEGMENT, MAX_TCP_KEEPIDLE, NODELAY, SOL_IPV6, SOL_SOCKET,
+    SOL_TCP, SO_ERROR, SO_KEEPALIVE, SO_LINGER, SO_RCVBUF, SO_REUSEADDR, SO_REUSEPORT, SO_SNDBUF, SO_SNDTIMEO,
+    TCP_KEEPIDLE, TCP_KEEPINTVL,
 };
 use crate::{
     fs::{FileTrait, OpenFlags, Pipe}, hal::config::USER_SPACE_TOP, mm::user_ptr::check_readable, net::{
-        addr::{IpType, Sock, SockAddr, SockIpv4, SockIpv6}, Congestion, Protocol, Socket, SocketType, TcpSocket, AF_INET, AF_INET6, AF_UNIX, PORT_FD_MANAMER, HOST_NAME, MAX_HOST_NAME, MAX_NIS_LEN, NIS_DOMAIN_NAME, TCP_MSS
-    }, syscall::ffi::{IPPROTO_IP, IPPROTO_TCP, SO_OOBINLINE, SO_RCVTIMEO}, task::{current_task, sock_map_fd, FdInfo}, utils::{Errno, SysResult}
+        addr::{IpType, Sock, SockAddr, SockIpv4, SockIpv6}, Congestion, Linger, Protocol, Socket, SocketType, TcpSocket, AF_INET, AF_INET6, AF_UNIX, DEFAULT_TTL, PORT_FD_MANAMER, HOST_NAME, MAX_HOST_NAME, MAX_NIS_LEN, NIS_DOMAIN_NAME
+    }, sync::TimeVal, syscall::ffi::{IPPROTO_IP, IPPROTO_TCP, SO_OOBINLINE, SO_RCVTIMEO}, task::{capable, current_task, sock_map_fd, CapSet, FdInfo}, utils::{Errno, SysResult}
 };
 use log::{info, trace, warn};
 use smoltcp::wire::IpAddress;
@@ -20,14 +21,15 @@ pub fn sys_socket(domain: usize, type_: usize, protocol: usize) -> SysResult<usi
         domain, type_, protocol
     );
     let type_ = SocketType::from_bits(type_ as u32).ok_or(Errno::EINVAL)?;
-    let protocol = protocol as u8;
+    // 协议号只有 8 位，不能直接截断
+    let protocol = u8::try_from(protocol).map_err(|_| Errno::EPROTONOSUPPORT)?;
     let cloexec_enable = type_.contains(SocketType::SOCK_CLOEXEC);
     if unlikely(domain == AF_UNIX.into()) {
         return Ok(4);
     } // 这里是特殊处理，通过musl libctest的网络测例，后序要修改
 
     // 根据协议族、套口类型、传输层协议创建套口
-    let socket = <dyn Socket>::new(domain as u16, type_).map_err(|_| Errno::EAFNOSUPPORT)?;
+    let socket = <dyn Socket>::new(domain as u16, type_, protocol)?;
 
     // 将socket和一个fd绑定
     let fd = sock_map_fd(socket.get(), cloexec_enable, OpenFlags::O_RDWR).map_err(|_| Errno::EMFILE)?;
@@ -162,16 +164,17 @@ pub async fn sys_accept(sockfd: usize, addr: usize, addrlen_ptr: usize) -> SysRe
     let socket = file.get_socket()?;
 
     let (remote_end, newfd) = socket.accept(sockfd, flags).await?;
-    // 将remote_end保存在addr中
+    // 将remote_end保存在addr中，addr 为空表示不关心对端地址
     let ptr = addr as *mut u8;
-    if unlikely(addr == 0) {
-        return Err(Errno::EFAULT);
+    if addr == 0 {
+        return Ok(newfd);
     }
 
     let len = unsafe{ *(addrlen_ptr as *const u32) };
     let buf = unsafe { core::slice::from_raw_parts_mut(ptr, len as usize) };
     info!("[sys_accept] server get user, remote end {:?}", remote_end);
-    let user_sockaddr: SockAddr = remote_end.into();
+    // 经新连接转换可以得到 v4-mapped 形式的地址
+    let user_sockaddr = accepted_peername(newfd).unwrap_or_else(|_| remote_end.into());
     info!("[sys_accept] server get user, after remote end {:?}", user_sockaddr);
 
     user_sockaddr.write2user(buf, len as usize)?;
@@ -195,21 +198,21 @@ pub async fn sys_accept4(
     if flags == 0 {
         return sys_accept(sockfd, addr, addrlen_ptr).await;
     }
-    let flags = OpenFlags::from_bits(flags as i32).expect("[sys_accept4] flag parse fail");
+    let flags = OpenFlags::from_bits(flags as i32).ok_or(Errno::EINVAL)?;
     let task = current_task().unwrap();
     let file = task.get_file_by_fd(sockfd).ok_or(Errno::EBADF)?;
     let socket = file.get_socket()?;
 
     let (remote_end, newfd) = socket.accept(sockfd, flags).await?;
     let ptr = addr as *mut u8;
-    if unlikely(addr == 0) {
-        return Err(Errno::EFAULT);
+    if addr == 0 {
+        return Ok(newfd);
     }
 
     // maybe bug: 需要检查懒分配
     let len = unsafe{ *(addrlen_ptr as *const u32) };
     let buf = unsafe { core::slice::from_raw_parts_mut(ptr, len as usize) };
-    let user_sockaddr: SockAddr = remote_end.into();
+    let user_sockaddr = accepted_peername(newfd).unwrap_or_else(|_| remote_end.into());
 
     user_sockaddr.write2user(buf, len as usize)?;
     info!("[sys_accept4] new sockfd: {}", newfd);
@@ -217,6 +220,13 @@ pub async fn sys_accept4(
     Ok(newfd)
 }
 
+/// accept 返回的新连接的对端地址
+fn accepted_peername(newfd: usize) -> SysResult<SockAddr> {
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(newfd).ok_or(Errno::EBADF)?;
+    file.get_socket()?.get_peername()
+}
+
 /// getsockname() returns the current address to which the socket sockfd is bound, in the buffer pointed to by addr.
 /// The addrlen argument should be initialized to
 /// indicate the amount of space (in bytes) pointed to by addr.
@@ -342,6 +352,7 @@ pub async fn sys_sendto(
             socket.connect(sockfd, &dest_sockaddr.unwrap()).await;
             socket.send_msg(buf, dest_sockaddr).await?
         }
+        Sock::Icmp | Sock::Raw => socket.send_msg(buf, dest_sockaddr).await?,
         _ => todo!(),
     };
 
@@ -460,45 +471,45 @@ pub fn sys_getsockopt(
     if unlikely(optval_ptr == 0 || optlen == 0) {
         return Err(Errno::EFAULT);
     }
-    match (level as u8, optname as u32) {
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(sockfd).ok_or(Errno::EBADF)?;
+    let socket = file.get_socket()?;
+    let value: u32 = match (level as u8, optname as u32) {
         (SOL_SOCKET, SO_OOBINLINE) => {
-            let task = current_task().unwrap();
-            let file = task.get_file_by_fd(sockfd).ok_or(Errno::EBADF)?;
-            let socket = file.get_socket()?;
             let len = unsafe { *(optlen as *const i32) };
             if unlikely(len <= 0) {
                 return Err(Errno::EINVAL);
             }
+            return Ok(0);
         }
-        (SOL_SOCKET, SO_SNDBUF) => {
-            let task = current_task().unwrap();
-            let file = task.get_file_by_fd(sockfd).ok_or(Errno::EBADF)?;
-            let socket = file.get_socket()?;
-            // 获取发送缓冲区大小，并写到用户空间
-            let send_buf_size = socket.get_send_buf_size()?;
-            unsafe {
-                *(optval_ptr as *mut u32) = send_buf_size as u32;
-                *(optlen as *mut u32) = core::mem::size_of::<u32>() as u32;
-            }
+        // 获取发送缓冲区大小，并写到用户空间
+        (SOL_SOCKET, SO_SNDBUF) => socket.get_send_buf_size()? as u32,
+        // 获取接收缓冲区大小，并写到用户空间
+        (SOL_SOCKET, SO_RCVBUF) => socket.get_recv_buf_size()? as u32,
+        (SOL_SOCKET, SO_REUSEADDR) => socket.get_sockopts()?.reuse.addr as u32,
+        (SOL_SOCKET, SO_REUSEPORT) => socket.get_sockopts()?.reuse.port as u32,
+        (SOL_SOCKET, SO_KEEPALIVE) => socket.get_sockopts()?.keep_alive as u32,
+        (SOL_SOCKET, SO_ERROR) => socket.take_error()?.map_or(0, |e| e as u32),
+        (SOL_SOCKET, SO_LINGER) => {
+            let linger = match socket.get_sockopts()?.linger {
+                Some(time) => Linger { l_onoff: 1, l_linger: time.as_secs() as i32 },
+                None => Linger { l_onoff: 0, l_linger: 0 },
+            };
+            return write_optval(optval_ptr, optlen, &linger);
         }
-        (SOL_SOCKET, SO_RCVBUF) => {
-            let task = current_task().unwrap();
-            let file = task.get_file_by_fd(sockfd).ok_or(Errno::EBADF)?;
-            let socket = file.get_socket()?;
-            // 获取接收缓冲区大小，并写到用户空间
-            let recv_buf_size = socket.get_recv_buf_size()?;
-            unsafe {
-                *(optval_ptr as *mut u32) = recv_buf_size as u32;
-                *(optlen as *mut u32) = core::mem::size_of::<u32>() as u32;
-            }
+        (SOL_SOCKET, SO_RCVTIMEO) => {
+            let timeout = socket.get_sockopts()?.recv_timeout.unwrap_or(Duration::ZERO);
+            return write_optval(optval_ptr, optlen, &TimeVal::from(timeout));
         }
-        (SOL_TCP, MAXSEGMENT) => {
-            // 返回TCP最大段大小 MSS
-            unsafe {
-                *(optval_ptr as *mut u32) = TCP_MSS.load(Ordering::Relaxed);
-                *(optlen as *mut u32) = core::mem::size_of::<u32>() as u32;
-            }
+        (SOL_SOCKET, SO_SNDTIMEO) => {
+            let timeout = socket.get_sockopts()?.send_timeout.unwrap_or(Duration::ZERO);
+            return write_optval(optval_ptr, optlen, &TimeVal::from(timeout));
         }
+        // 返回TCP最大段大小 MSS
+        (SOL_TCP, MAXSEGMENT) => socket.get_sockopts()?.max_seg,
+        (SOL_TCP, NODELAY) => socket.get_sockopts()?.nodelay as u32,
+        (SOL_TCP, TCP_KEEPIDLE) => socket.get_sockopts()?.keep_idle.as_secs() as u32,
+        (SOL_TCP, TCP_KEEPINTVL) => socket.get_sockopts()?.keep_intvl.as_secs() as u32,
         (SOL_TCP, CONGESTION) => {
             // 获取 TCP 拥塞控制算法名称
             let name_len = Congestion.len();
@@ -506,11 +517,14 @@ pub fn sys_getsockopt(
             let bytes = Congestion.as_bytes();
             buf.copy_from_slice(bytes);
             unsafe { *(optlen as *mut u32) = name_len as u32 };
+            return Ok(0);
         }
-        _ => {}
-    }
+        (IPPROTO_IP, IP_TTL) => socket.get_sockopts()?.ttl as u32,
+        (SOL_IPV6, IPV6_V6ONLY) => socket.get_v6only()? as u32,
+        _ => return Ok(0),
+    };
 
-    Ok(0)
+    write_optval(optval_ptr, optlen, &value)
 }
 
 pub fn sys_setsockopt(
@@ -533,37 +547,93 @@ pub fn sys_setsockopt(
     let task = current_task().unwrap();
     let file = task.get_file_by_fd(sockfd).ok_or(Errno::EBADF)?;
     let socket = file.get_socket()?;
-    match (level as u8, optname as u32) {
+    let mut opts = match (level as u8, optname as u32) {
         (SOL_SOCKET, SO_SNDBUF) => {
             // 修改发送缓冲区大小
-            let new_size = unsafe { *(optval_ptr as *const u32) };
+            let new_size = read_optval::<u32>(optval_ptr, optlen)?;
             socket.set_send_buf_size(new_size)?;
+            return Ok(0);
         }
         (SOL_SOCKET, SO_RCVBUF) => {
             // 修改接受缓冲区大小
-            let new_size = unsafe { *(optval_ptr as *const u32) };
+            let new_size = read_optval::<u32>(optval_ptr, optlen)?;
             socket.set_recv_buf_size(new_size)?;
+            return Ok(0);
         }
-        (SOL_SOCKET, SO_KEEPALIVE) => {
-            let action = unsafe { *(optval_ptr as *const u32) };
-            socket.set_keep_alive(action)?;
-        }
-        (SOL_SOCKET, MAXSEGMENT) => {
-            // 设置TCP最大段大小 MSS
-            let new_mss = unsafe { *(optval_ptr as *const u32) };
-            TCP_MSS.store(new_mss, Ordering::Relaxed);
+        (SOL_IPV6, IPV6_V6ONLY) => {
+            let v6only = read_optval::<u32>(optval_ptr, optlen)?;
+            socket.set_v6only(v6only != 0)?;
+            return Ok(0);
         }
-        (SOL_TCP, NODELAY) => {
-            let action = unsafe { *(optval_ptr as *const u32) };
-            socket.enable_nagle(action);
+        (SOL_SOCKET, SO_RCVTIMEO | SO_SNDTIMEO | SO_REUSEADDR | SO_REUSEPORT | SO_KEEPALIVE | SO_LINGER)
+        | (SOL_TCP, MAXSEGMENT | NODELAY | TCP_KEEPIDLE | TCP_KEEPINTVL)
+        | (IPPROTO_IP, IP_TTL) => socket.get_sockopts()?,
+        _ => return Ok(0),
+    };
+    match (level as u8, optname as u32) {
+        (SOL_SOCKET, SO_REUSEADDR) => opts.reuse.addr = read_optval::<u32>(optval_ptr, optlen)? != 0,
+        (SOL_SOCKET, SO_REUSEPORT) => opts.reuse.port = read_optval::<u32>(optval_ptr, optlen)? != 0,
+        (SOL_SOCKET, SO_KEEPALIVE) => opts.keep_alive = read_optval::<u32>(optval_ptr, optlen)? != 0,
+        (SOL_SOCKET, SO_LINGER) => {
+            let linger = read_optval::<Linger>(optval_ptr, optlen)?;
+            opts.linger = match linger.l_onoff {
+                0 => None,
+                _ => Some(Duration::from_secs(linger.l_linger.max(0) as u64)),
+            };
         }
+        // 与 Linux 一样，超时为 0 表示一直阻塞
         (SOL_SOCKET, SO_RCVTIMEO) => {
-            // 设置超时时间暂时未实现
-            return Ok(0);
+            let timeout = Duration::from(read_optval::<TimeVal>(optval_ptr, optlen)?);
+            opts.recv_timeout = (!timeout.is_zero()).then_some(timeout);
         }
-        _ => return Ok(0),
+        (SOL_SOCKET, SO_SNDTIMEO) => {
+            let timeout = Duration::from(read_optval::<TimeVal>(optval_ptr, optlen)?);
+            opts.send_timeout = (!timeout.is_zero()).then_some(timeout);
+        }
+        // 设置TCP最大段大小 MSS
+        (SOL_TCP, MAXSEGMENT) => opts.max_seg = read_optval::<u32>(optval_ptr, optlen)?,
+        (SOL_TCP, NODELAY) => opts.nodelay = read_optval::<u32>(optval_ptr, optlen)? != 0,
+        (SOL_TCP, TCP_KEEPIDLE | TCP_KEEPINTVL) => {
+            let secs = read_optval::<u32>(optval_ptr, optlen)?;
+            if unlikely(secs == 0 || secs > MAX_TCP_KEEPIDLE) {
+                return Err(Errno::EINVAL);
+            }
+            match optname as u32 {
+                TCP_KEEPIDLE => opts.keep_idle = Duration::from_secs(secs as u64),
+                _ => opts.keep_intvl = Duration::from_secs(secs as u64),
+            }
+        }
+        (IPPROTO_IP, IP_TTL) => {
+            // -1 表示恢复默认值
+            opts.ttl = match read_optval::<i32>(optval_ptr, optlen)? {
+                -1 => DEFAULT_TTL,
+                ttl @ 1..=255 => ttl as u8,
+                _ => return Err(Errno::EINVAL),
+            };
+        }
+        _ => unreachable!(),
+    }
+    socket.set_sockopts(opts)?;
+
+    Ok(0)
+}
+
+/// 从用户空间读取选项值，optlen 小于选项的大小时返回 EINVAL
+fn read_optval<T: Copy>(optval_ptr: usize, optlen: usize) -> SysResult<T> {
+    if unlikely(optlen < core::mem::size_of::<T>()) {
+        return Err(Errno::EINVAL);
     }
+    Ok(unsafe { (optval_ptr as *const T).read_unaligned() })
+}
 
+/// 把选项值写回用户空间，用户缓冲区较小时截断，optlen 返回实际写入的长度
+fn write_optval<T: Copy>(optval_ptr: usize, optlen: usize, value: &T) -> SysResult<usize> {
+    let len = unsafe { *(optlen as *const u32) } as usize;
+    let len = len.min(core::mem::size_of::<T>());
+    let bytes = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, len) };
+    let buf = unsafe { core::slice::from_raw_parts_mut(optval_ptr as *mut u8, len) };
+    buf.copy_from_slice(bytes);
+    unsafe { *(optlen as *mut u32) = len as u32 };
     Ok(0)
 }
 
@@ -572,6 +642,9 @@ pub fn sys_setdominname(name: usize, size: usize) -> SysResult<usize> {
         "[sys_setdominname] start, name = {:#x}, size = {:#x}",
         name, size
     );
+    if !capable(CapSet::CAP_SYS_ADMIN) {
+        return Err(Errno::EPERM);
+    }
     if unlikely((size as isize) < 0) || unlikely(size > MAX_NIS_LEN) {
         return Err(Errno::EINVAL);
     }
@@ -593,6 +666,9 @@ pub fn sys_sethostname(name: usize, size: usize) -> SysResult<usize> {
         "[sys_sethostname] start, name = {:#x}, size = {:#x}",
         name, size
     );
+    if !capable(CapSet::CAP_SYS_ADMIN) {
+        return Err(Errno::EPERM);
+    }
     if unlikely((size as isize) < 0) || unlikely(size > MAX_HOST_NAME) {
         return Err(Errno::EINVAL);
     }
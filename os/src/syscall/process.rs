@@ -1,11 +1,12 @@
-use crate::fs::{open, resolve_path, AbsPath, FileClass, OpenFlags};
+use crate::fs::pidfd::PidFd;
+use crate::fs::{open_resolved, resolve_path, AbsPath, Dentry, FileClass, MayAccess, OpenFlags};
 use crate::hal::config::{INITPROC_PID, KERNEL_HEAP_SIZE, USER_SPACE_TOP, USER_STACK_SIZE};
-use crate::mm::user_ptr::{user_cstr, user_cstr_array, user_ref, user_ref_mut, user_slice_mut};
+use crate::mm::user_ptr::{user_cstr, user_cstr_array, user_ref, user_ref_mut, user_slice, user_slice_mut};
 // use crate::mm::{
 //     translated_byte_buffer, translated_ref, translated_refmut, translated_str, UserBuffer,
 // };
 use crate::signal::{
-    KSigAction, SigAction, SigActionFlag, SigCode, SigDetails, SigErr, SigHandlerType, SigInfo,
+    KSigAction, LinuxSigInfo, SigAction, SigActionFlag, SigCode, SigDetails, SigErr, SigHandlerType, SigInfo,
     SigMask, SigNom, UContext, WhichQueue, MAX_SIGNUM, SIGBLOCK, SIGSETMASK, SIGUNBLOCK, SIG_DFL,
     SIG_IGN,
 };
@@ -19,19 +20,30 @@ use crate::sync::{
     NullFuture, TimeSpec, TimeVal, TimeoutFuture, Tms, CLOCK_MANAGER,
 };
 use crate::syscall::ffi::{
-    CloneArgs, CloneFlags, RlimResource, Rusage, Sysinfo, SyslogCmd, Utsname, WaitOptions,
-    CPUSET_LEN, LOGINFO, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD,
+    CapUserData, CapUserHeader, CloneArgs, CloneFlags, RlimResource, Rusage, Sysinfo, SyslogCmd,
+    GetRandomFlags, Utsname, WaitOptions, CPUSET_LEN, LINUX_CAPABILITY_VERSION_1, LINUX_CAPABILITY_VERSION_2,
+    LINUX_CAPABILITY_VERSION_3, LOGINFO, PR_CAPBSET_DROP, PR_CAPBSET_READ, PR_CAP_AMBIENT,
+    PR_CAP_AMBIENT_CLEAR_ALL, PR_CAP_AMBIENT_IS_SET, PR_CAP_AMBIENT_LOWER, PR_CAP_AMBIENT_RAISE,
+    PR_GET_CHILD_SUBREAPER, PR_GET_DUMPABLE, PR_GET_NAME, PR_GET_NO_NEW_PRIVS, PR_GET_PDEATHSIG,
+    PR_GET_SECCOMP, PR_SET_CHILD_SUBREAPER, PR_SET_DUMPABLE, PR_SET_NAME, PR_SET_NO_NEW_PRIVS,
+    PR_SET_PDEATHSIG, PR_SET_SECCOMP, P_ALL, SECCOMP_GET_ACTION_AVAIL, SECCOMP_SET_MODE_FILTER,
+    SECCOMP_SET_MODE_STRICT, SeccompFlags, SockFprog, P_PGID, P_PID, P_PIDFD, RUSAGE_CHILDREN, RUSAGE_SELF, RUSAGE_THREAD,
 };
 use crate::syscall::io::SigMaskGuard;
 use crate::syscall::{CpuSet, RLimit64, SchedParam};
 use crate::task::{
     add_proc_group_member, add_task, current_task, current_user_token, extract_proc_to_new_group,
     get_proc_num, get_target_proc_group, get_task_by_pid, new_process_group,
-    remove_proc_group_member, spawn_kernel_task, spawn_user_task, TaskStatus, MANAGER,
+    remove_proc_group_member, spawn_kernel_task, spawn_user_task, capable, CapSet, FdInfo,
+    Seccomp, SeccompMode, SockFilter, TaskControlBlock, TaskStatus, WaitEvent, BPF_MAXINSNS, MANAGER,
+    NGROUPS_MAX, SECCOMP_RET_ALLOW, SECCOMP_RET_ERRNO, SECCOMP_RET_KILL_PROCESS,
+    SECCOMP_RET_KILL_THREAD, SECCOMP_RET_LOG, SECCOMP_RET_TRACE, SECCOMP_RET_TRAP,
+    SUID_DUMP_DISABLE, SUID_DUMP_USER, TASK_COMM_LEN,
 };
-use crate::utils::{Errno, SysResult, RNG};
+use crate::utils::{get_random_bytes, rng_is_ready, wait_for_random_bytes, Errno, SysResult};
 use alloc::ffi::CString;
 use alloc::string::{String, ToString};
+use alloc::sync::Arc;
 use alloc::task;
 use alloc::vec::Vec;
 use core::intrinsics::unlikely;
@@ -177,7 +189,14 @@ pub fn sys_clone3(cl_args: usize, size: usize) -> SysResult<usize> {
     let tls = cl_args_ptr.tls;
     let ctid = cl_args_ptr.child_tid;
 
-    sys_clone(flags, child_stack + child_stack_size, ptid, tls, ctid)
+    do_clone(
+        flags,
+        child_stack + child_stack_size,
+        ptid,
+        tls,
+        ctid,
+        cl_args_ptr.pidfd as usize,
+    )
 }
 
 pub fn sys_clone(
@@ -188,6 +207,27 @@ pub fn sys_clone(
     ctid: usize,
 ) -> SysResult<usize> {
     info!("[sys_clone] start");
+    // 老的 clone 接口中 pidfd 和 parent_tid 共用同一个参数
+    let flag = CloneFlags::from_bits(flags as u32).ok_or(Errno::EINVAL)?;
+    if unlikely(
+        flag.contains(CloneFlags::CLONE_PIDFD) && flag.contains(CloneFlags::CLONE_PARENT_SETTID),
+    ) {
+        return Err(Errno::EINVAL);
+    }
+    do_clone(flags, child_stack, ptid, tls, ctid, ptid)
+}
+
+/// clone 和 clone3 的公共部分
+///
+/// pidfd: CLONE_PIDFD 时写回 pidfd 的用户地址
+fn do_clone(
+    flags: usize,
+    child_stack: usize,
+    ptid: usize,
+    tls: usize,
+    ctid: usize,
+    pidfd: usize,
+) -> SysResult<usize> {
     let flag = CloneFlags::from_bits(flags as u32).ok_or(Errno::EINVAL)?;
     if unlikely(flag.contains(CloneFlags::CLONE_SIGHAND) && !flag.contains(CloneFlags::CLONE_VM)) {
         return Err(Errno::EINVAL);
@@ -197,6 +237,9 @@ pub fn sys_clone(
     ) {
         return Err(Errno::EINVAL);
     }
+    if unlikely(flag.contains(CloneFlags::CLONE_PIDFD) && flag.contains(CloneFlags::CLONE_THREAD)) {
+        return Err(Errno::EINVAL);
+    }
     info!(
         "[sys_clone] start child_stack {}, flag: {:?}",
         child_stack, flag
@@ -207,6 +250,16 @@ pub fn sys_clone(
         true => current_task.do_thread_fork(flag),
         false => current_task.do_process_fork(flag),
     };
+    // 在父进程中为子进程分配 pidfd，子进程的 fd 表已经在 fork 时复制过了，不会包含它
+    if flag.contains(CloneFlags::CLONE_PIDFD) {
+        let file = PidFd::new(&new_task, OpenFlags::O_RDWR);
+        let fd = current_task.alloc_fd(FdInfo::new(
+            file,
+            OpenFlags::O_RDWR | OpenFlags::O_CLOEXEC,
+        ))?;
+        let pidfd_ptr = user_ref_mut::<i32>(pidfd.into())?.ok_or(Errno::EFAULT)?;
+        *pidfd_ptr = fd as i32;
+    }
     drop(current_task);
     info!(
         "[sys_clone] start, flags: {:?}, ptid: {}, tls: {}, ctid: {:#x}",
@@ -319,8 +372,14 @@ pub async fn sys_execve(path: usize, argv: usize, env: usize) -> SysResult<usize
     // 应当去实现复杂的错误处理
     // 对于路径上文件的问题,返回值应当和open的返回值一样?
     // 当返回的文件不是可执行文件的时候应当返回 Errno::ENOEXEC?
-    let target_path = resolve_path(cwd, path);
-    if let Ok(file) = open(target_path, OpenFlags::O_RDONLY) {
+    let target_path = resolve_path(cwd, path).follow(true)?;
+    if let Ok(inode) = Dentry::get_inode_from_path(&target_path.get()) {
+        if inode.metadata()._type.is_dir() {
+            return Err(Errno::EACCES);
+        }
+        inode.permission(MayAccess::MAY_EXEC)?;
+    }
+    if let Ok(file) = open_resolved(target_path, OpenFlags::O_RDONLY) {
         let task: alloc::sync::Arc<crate::task::TaskControlBlock> = current_task().unwrap();
         task.execve(file, argv, env).await;
         Ok(0)
@@ -329,6 +388,162 @@ pub async fn sys_execve(path: usize, argv: usize, env: usize) -> SysResult<usize
     }
 }
 
+/// wait 系列系统调用等待的子进程范围
+enum WaitTarget {
+    /// 任意子进程
+    Any,
+    /// pid 为指定值的子进程
+    Pid(usize),
+    /// 进程组为指定值的子进程
+    Pgid(usize),
+}
+
+impl WaitTarget {
+    fn matches(&self, child: &Arc<TaskControlBlock>) -> bool {
+        match self {
+            WaitTarget::Any => true,
+            WaitTarget::Pid(pid) => child.get_pid() == *pid,
+            WaitTarget::Pgid(pgid) => child.get_pgid() == *pgid,
+        }
+    }
+}
+
+/// 子进程可以被 wait 报告的状态变化
+#[derive(Clone, Copy)]
+enum ChildEvent {
+    /// 已经退出，保存 wstatus 格式的退出码
+    Exited(i32),
+    /// 被信号 signo 停止
+    Stopped(usize),
+    /// 收到 SIGCONT 后继续运行
+    Continued,
+}
+
+impl ChildEvent {
+    /// 编码成 wait4 的 wstatus
+    fn wstatus(&self) -> i32 {
+        match *self {
+            ChildEvent::Exited(exit_code) => exit_code,
+            ChildEvent::Stopped(signo) => ((signo as i32) << 8) | 0x7f,
+            ChildEvent::Continued => 0xffff,
+        }
+    }
+
+    /// 转换成 waitid 的 (si_code, si_status)
+    fn sigcode_status(&self) -> (SigCode, i32) {
+        match *self {
+            ChildEvent::Exited(exit_code) if exit_code & 0x7f == 0 => {
+                (SigCode::CLD_EXITED, (exit_code >> 8) & 0xff)
+            }
+            ChildEvent::Exited(exit_code) if exit_code & 0x80 != 0 => {
+                (SigCode::CLD_DUMPED, exit_code & 0x7f)
+            }
+            ChildEvent::Exited(exit_code) => (SigCode::CLD_KILLED, exit_code & 0x7f),
+            ChildEvent::Stopped(signo) => (SigCode::CLD_STOPPED, signo as i32),
+            ChildEvent::Continued => (SigCode::CLD_CONTINUED, SigNom::SIGCONT as i32),
+        }
+    }
+}
+
+/// 在子进程中找一个状态发生变化、符合 op 要求的子进程
+///
+/// 没有任何符合 target 的子进程时返回 ECHILD
+fn find_wait_child(
+    task: &Arc<TaskControlBlock>,
+    target: &WaitTarget,
+    op: WaitOptions,
+) -> SysResult<Option<(Arc<TaskControlBlock>, ChildEvent)>> {
+    let children = task.children.lock();
+    let mut has_target = false;
+    for child in children.values().filter(|child| target.matches(child)) {
+        has_target = true;
+        let thread_group = child.thread_group.lock();
+        // 所有线程都退出后才能回收
+        if op.contains(WaitOptions::WEXITED) && child.is_zombie() && thread_group.thread_num() == 1
+        {
+            return Ok(Some((child.clone(), ChildEvent::Exited(child.get_exit_code()))));
+        }
+        match thread_group.wait_event {
+            Some(WaitEvent::Stopped(signo)) if op.contains(WaitOptions::WUNTRACED) => {
+                return Ok(Some((child.clone(), ChildEvent::Stopped(signo))));
+            }
+            Some(WaitEvent::Continued) if op.contains(WaitOptions::WCONTINUED) => {
+                return Ok(Some((child.clone(), ChildEvent::Continued)));
+            }
+            _ => {}
+        }
+    }
+    if !has_target {
+        info!("task {} has no child to wait.", task.get_pid());
+        return Err(Errno::ECHILD);
+    }
+    Ok(None)
+}
+
+/// wait4 和 waitid 的公共部分：等待一个子进程的状态变化
+///
+/// WNOHANG 并且没有子进程状态变化时返回 None
+async fn do_wait(
+    task: &Arc<TaskControlBlock>,
+    target: WaitTarget,
+    op: WaitOptions,
+) -> SysResult<Option<(Arc<TaskControlBlock>, ChildEvent)>> {
+    loop {
+        if let Some(found) = find_wait_child(task, &target, op)? {
+            return Ok(Some(found));
+        }
+        if op.contains(WaitOptions::WNOHANG) {
+            return Ok(None);
+        }
+        // 子进程状态变化时会发送 SIGCHLD，已经在 pending 中就不用睡眠了
+        if task
+            .sig_pending
+            .lock()
+            .take_expected_one(SigMask::SIGCHLD)
+            .is_some()
+        {
+            continue;
+        }
+        task.set_wake_up_signal(SigMask::SIGCHLD);
+        suspend_now().await;
+        // 在pending队列中取出希望的信号，也就是子进程状态改变后发送给父进程的信号
+        if task
+            .sig_pending
+            .lock()
+            .take_expected_one(SigMask::SIGCHLD)
+            .is_none()
+        {
+            info!(
+                "[do_wait] task {} is interrupted while waiting for child.",
+                task.get_pid()
+            );
+            return Err(Errno::EINTR);
+        }
+    }
+}
+
+/// 报告完子进程状态后的收尾：回收退出的子进程，清除停止/继续状态
+fn finish_wait(
+    task: &Arc<TaskControlBlock>,
+    child: &Arc<TaskControlBlock>,
+    event: ChildEvent,
+    op: WaitOptions,
+    rusage: usize,
+) -> SysResult<()> {
+    if let Some(ru) = user_ref_mut::<Rusage>(rusage.into())? {
+        let (utime, stime) = child.process_ustime();
+        *ru = Rusage::new(utime.into(), stime.into());
+    }
+    if op.contains(WaitOptions::WNOWAIT) {
+        return Ok(());
+    }
+    match event {
+        ChildEvent::Exited(exit_code) => task.do_wait4(child.get_pid(), 0, exit_code),
+        _ => child.thread_group.lock().wait_event = None,
+    }
+    Ok(())
+}
+
 /// If there is not a child process whose pid is same as given, return -1.
 /// Else if there is a child process but it is still running, return -2.
 /// pid = -1: 等待任意子进程
@@ -339,142 +554,139 @@ pub async fn sys_wait4(
     pid: isize,
     wstatus: usize,
     options: usize,
-    _rusage: usize,
+    rusage: usize,
 ) -> SysResult<usize> {
     info!("[sys_wait4] start");
     debug!("sys_wait4 start, pid = {}, options = {}", pid, options);
-    // info!("[sys_wait4] start, pid = {}, options = {}", pid,options);
     let task = current_task().unwrap();
-    let self_pid = task.get_pid();
-    if task.children.lock().is_empty() {
-        info!("task {}, has no child, want pid = {}.", task.get_pid(), pid);
-        return Err(Errno::ECHILD);
+    let mut op = WaitOptions::from_bits(options as i32).ok_or(Errno::EINVAL)?;
+    if unlikely(op.intersects(WaitOptions::WEXITED | WaitOptions::WNOWAIT)) {
+        return Err(Errno::EINVAL);
     }
+    // wait4 总是等待退出的子进程
+    op |= WaitOptions::WEXITED;
 
-    let op = WaitOptions::from_bits(options as i32).ok_or(Errno::EINVAL)?;
-
-    // 缩小 locked_child 的作用域
-    let target_task = {
-        let locked_child = task.children.lock();
-        match pid {
-            // pid = -1: 等待任意子进程
-            -1 => {
-                info!("wait any child, child = {}", locked_child.len());
-                locked_child
-                    .values()
-                    .find(|task| task.is_zombie() && task.thread_group.lock().thread_num() == 1)
-                    .cloned()
-            }
-            // pid > 0：等待进程id为pid的子进程
-            p if p > 0 => {
-                info!("wait target pid = {}", p);
-                // println!("wait target pid = {}", p);
-                locked_child
-                    .values()
-                    .find(|task| {
-                        task.is_zombie()
-                            && p as usize == task.get_pid()
-                            && task.thread_group.lock().thread_num() == 1
-                    })
-                    .cloned()
-            }
-            // pid < -1: 等待进程组标识符与pid绝对值相等的所有子进程
-            p if p < -1 => locked_child
-                .values()
-                .find(|task| task.get_pid() == p.abs() as usize)
-                .cloned(),
-            // 等待和当前进程组同组的任意一个进程
-            _ => {
-                drop(locked_child);
-                let pgid = task.get_pgid();
-                let mut zombie_task = None;
-                let mut target_group = get_target_proc_group(pgid).unwrap();
-
-                // 移除无效 PID 和 找到 Zombie 进程
-                for &pid in target_group.iter() {
-                    match get_task_by_pid(pid) {
-                        None => {
-                            info!(
-                                "[sys_wait4] task pid = {} has been dead, removing from group.",
-                                pid
-                            );
-                            remove_proc_group_member(pgid, pid);
-                        }
-                        Some(peer)
-                            if peer.is_zombie() && peer.thread_group.lock().thread_num() == 1 =>
-                        {
-                            zombie_task = Some(peer);
-                            break; // 找到一个就停止
-                        }
-                        _ => {}
-                    }
-                }
-                zombie_task
-            }
-        }
+    let target = match pid {
+        // pid = -1: 等待任意子进程
+        -1 => WaitTarget::Any,
+        // pid > 0：等待进程id为pid的子进程
+        p if p > 0 => WaitTarget::Pid(p as usize),
+        // pid = 0: 等待和当前进程同组的任意子进程
+        0 => WaitTarget::Pgid(task.get_pgid()),
+        // pid < -1: 等待进程组标识符与pid绝对值相等的所有子进程
+        p => WaitTarget::Pgid(p.unsigned_abs()),
     };
 
-    match target_task {
-        Some(zombie_child) => {
+    match do_wait(&task, target, op).await? {
+        Some((child, event)) => {
+            let child_pid = child.get_pid();
             info!(
-                "[sys_wait4] find a target zombie child task pid = {}.",
-                zombie_child.get_pid()
-            );
-            let zombie_pid = zombie_child.get_pid();
-            let exit_code = zombie_child.get_exit_code();
-            task.do_wait4(zombie_pid, wstatus, exit_code);
-            return Ok(zombie_pid);
-        }
-        None => {
-            info!("[sys_wait4] current task pid = {}", task.get_pid());
-            // println!("[sys_wait4] current task pid = {}", task.get_pid());
-            if op.contains(WaitOptions::WNOHANG) {
-                return Ok(0);
-            }
-            // 如果等待的进程还不是zombie，那么本进程进行await，
-            // 直到等待的进程do_exit然后发送SIGCHLD信号唤醒自己
-            let (child_pid, _status, exit_code) = loop {
-                // task.set_wake_up_signal(!*task.get_blocked() | SigMask::SIGCHLD);
-                task.set_wake_up_signal(SigMask::SIGCHLD);
-                suspend_now().await;
-                // 在pending队列中取出希望的信号，也就是子进程结束后发送给父进程的信号
-                match task.sig_pending.lock().take_expected_one(SigMask::SIGCHLD) {
-                    Some(sig_info) => {
-                        if let SigDetails::Chld {
-                            pid: find_pid,
-                            status,
-                            exit_code,
-                        } = sig_info.sifields
-                        {
-                            break (find_pid, status, exit_code);
-                        }
-                    }
-                    None => {
-                        info!(
-                            "[sys_wait4] task {} is waiting for child process to exit.",
-                            task.get_pid()
-                        );
-                        return Err(Errno::EINTR);
-                    }
-                }
-            };
-            info!(
-                "[sys_wait4]: task {} find a child: pid = {}, exit_code = {} , exitcode << 8 = {}.",
+                "[sys_wait4]: task {} find a child: pid = {}, wstatus = {:#x}.",
                 task.get_pid(),
                 child_pid,
-                exit_code,
-                (exit_code & 0xFF) << 8
+                event.wstatus()
             );
-            task.do_wait4(child_pid, wstatus, exit_code);
-            return Ok(child_pid);
+            if let Some(ws) = user_ref_mut::<i32>(wstatus.into())? {
+                *ws = event.wstatus();
+            }
+            finish_wait(&task, &child, event, op, rusage)?;
+            Ok(child_pid)
+        }
+        None => Ok(0),
+    }
+}
+
+/// 等待子进程状态改变，结果写入 siginfo_t
+///
+/// idtype: P_ALL、P_PID、P_PGID、P_PIDFD
+///
+/// options: 必须包含 WEXITED、WSTOPPED、WCONTINUED 中的至少一个
+pub async fn sys_waitid(
+    idtype: u32,
+    id: usize,
+    infop: usize,
+    options: i32,
+    rusage: usize,
+) -> SysResult<usize> {
+    info!(
+        "[sys_waitid] start, idtype = {}, id = {}, options = {:#x}",
+        idtype, id, options
+    );
+    let task = current_task().unwrap();
+    let op = WaitOptions::from_bits(options).ok_or(Errno::EINVAL)?;
+    if unlikely(
+        !op.intersects(WaitOptions::WEXITED | WaitOptions::WSTOPPED | WaitOptions::WCONTINUED),
+    ) {
+        return Err(Errno::EINVAL);
+    }
+
+    let target = match idtype {
+        P_ALL => WaitTarget::Any,
+        P_PID if id > 0 => WaitTarget::Pid(id),
+        P_PGID if id == 0 => WaitTarget::Pgid(task.get_pgid()),
+        P_PGID => WaitTarget::Pgid(id),
+        P_PIDFD => {
+            let file = task.get_file_by_fd(id).ok_or(Errno::EBADF)?;
+            let pidfd = file.downcast_arc::<PidFd>().map_err(|_| Errno::EBADF)?;
+            WaitTarget::Pid(pidfd.pid())
         }
+        _ => return Err(Errno::EINVAL),
+    };
+
+    let found = do_wait(&task, target, op).await?;
+    let siginfo = match &found {
+        Some((child, event)) => {
+            let (code, status) = event.sigcode_status();
+            let (utime, stime) = child.process_ustime();
+            let mut siginfo = LinuxSigInfo::new(SigNom::SIGCHLD as i32, code as i32);
+            // 联合体从偏移 16 开始: si_pid, si_uid, si_status, 然后是 si_utime, si_stime
+            siginfo._pad[1] = child.get_pid() as i32;
+            siginfo._pad[2] = child.cred.lock().ruid as i32;
+            siginfo._pad[3] = status;
+            let utime = utime.as_millis() as i64 / 10;
+            let stime = stime.as_millis() as i64 / 10;
+            siginfo._pad[5] = utime as i32;
+            siginfo._pad[6] = (utime >> 32) as i32;
+            siginfo._pad[7] = stime as i32;
+            siginfo._pad[8] = (stime >> 32) as i32;
+            siginfo
+        }
+        // WNOHANG 时没有子进程状态变化，si_pid 和 si_signo 都为 0
+        None => LinuxSigInfo::default(),
+    };
+    if let Some(info) = user_ref_mut::<LinuxSigInfo>(infop.into())? {
+        *info = siginfo;
     }
+    if let Some((child, event)) = found {
+        finish_wait(&task, &child, event, op, rusage)?;
+    }
+    Ok(0)
 }
 
-pub fn sys_getrandom(buf: *const u8, buflen: usize, _flags: usize) -> SysResult<usize> {
-    info!("[sys_get_random] start, buflen = {}", buflen);
-    let buffer = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, buflen) };
-    Ok(RNG.lock().fill_buf(buffer))
+/// 从内核 CRNG 取随机字节。
+///
+/// CRNG 未就绪时：带 GRND_INSECURE 直接输出，带 GRND_NONBLOCK 返回 EAGAIN，
+/// 否则采集计时器抖动直到就绪。GRND_RANDOM 在 CRNG 就绪后与默认行为相同。
+pub fn sys_getrandom(buf: *const u8, buflen: usize, flags: usize) -> SysResult<usize> {
+    info!("[sys_get_random] start, buflen = {}, flags = {:#x}", buflen, flags);
+    let flags = u32::try_from(flags)
+        .ok()
+        .and_then(GetRandomFlags::from_bits)
+        .ok_or(Errno::EINVAL)?;
+    if flags.contains(GetRandomFlags::GRND_INSECURE | GetRandomFlags::GRND_RANDOM) {
+        return Err(Errno::EINVAL);
+    }
+    if !flags.contains(GetRandomFlags::GRND_INSECURE) && !rng_is_ready() {
+        if flags.contains(GetRandomFlags::GRND_NONBLOCK) {
+            return Err(Errno::EAGAIN);
+        }
+        wait_for_random_bytes();
+    }
+    if buflen == 0 {
+        return Ok(0);
+    }
+    let buffer = user_slice_mut::<u8>((buf as usize).into(), buflen)?.ok_or(Errno::EFAULT)?;
+    Ok(get_random_bytes(buffer))
 }
 
 /// set pointer to thread ID
@@ -519,6 +731,9 @@ pub fn sys_clock_settime(clock_id: usize, timespec: usize) -> SysResult<usize> {
 
     match clock_id {
         CLOCK_REALTIME => {
+            if !capable(CapSet::CAP_SYS_TIME) {
+                return Err(Errno::EPERM);
+            }
             CLOCK_MANAGER.lock()[CLOCK_REALTIME] = Duration::from(ts) - time_duration();
         }
         _ => return Err(Errno::EINVAL),
@@ -566,13 +781,27 @@ pub fn sys_setsid() -> SysResult<usize> {
     let task = current_task().unwrap();
     let pid = task.get_pid(); // task的pid
     let old_pgid = task.get_pgid(); // task现在所属的进程组
-    if !task.is_leader() {
-        // set the calling task to new process group
-        let new_pgid = pid;
-        task.set_pgid(new_pgid); // 设置进程组ID为pid
-        extract_proc_to_new_group(old_pgid, new_pgid, pid); // 从原进程组中提取，放入一个新的进程组
-    }
-    Ok(pid) // 返回新进程组的ID
+    // 进程组组长不能创建新会话
+    if old_pgid == pid {
+        return Err(Errno::EPERM);
+    }
+    // 新会话没有控制终端，调用者成为会话首进程和新进程组的组长
+    task.set_sid(pid);
+    task.set_pgid(pid);
+    extract_proc_to_new_group(old_pgid, pid, pid); // 从原进程组中提取，放入一个新的进程组
+    Ok(pid) // 返回新会话的ID
+}
+
+/// returns the session ID of the process with process ID pid.
+///
+/// If pid is 0, getsid() returns the session ID of the calling process.
+pub fn sys_getsid(pid: usize) -> SysResult<usize> {
+    info!("[sys_getsid] start, pid = {}", pid);
+    let task = match pid {
+        0 => current_task().unwrap(),
+        _ => get_task_by_pid(pid).ok_or(Errno::ESRCH)?,
+    };
+    Ok(task.get_sid())
 }
 
 /// sets the PGID of the process specified by pid to pgid.
@@ -595,6 +824,23 @@ pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult<usize> {
     let old_pgid = target_task.get_pgid();
     let pid = target_task.get_pid();
     info!("[sys_setpgid] pid is {pid} old_pgid is {old_pgid}");
+    // 会话首进程不能改变进程组，也不能把进程移到别的会话
+    let sid = current_task().unwrap().get_sid();
+    if target_task.is_session_leader() {
+        return Err(Errno::EPERM);
+    }
+    if target_task.get_sid() != sid {
+        return Err(Errno::EPERM);
+    }
+    // 只能加入同一会话中已经存在的进程组
+    if pgid != 0 && pgid != pid {
+        let in_same_session = get_target_proc_group(pgid)
+            .and_then(|group| group.first().and_then(|p| get_task_by_pid(*p)))
+            .map_or(false, |member| member.get_sid() == sid);
+        if !in_same_session {
+            return Err(Errno::EPERM);
+        }
+    }
     if pgid == 0 {
         let new_pgid = pid;
         target_task.set_pgid(new_pgid);
@@ -651,7 +897,7 @@ pub fn sys_sysinfo(sysinfo: usize) -> SysResult<usize> {
 
 pub fn sys_getuid() -> SysResult<usize> {
     // info!("[sys_getuid]: 0");
-    Ok(current_task().unwrap().get_euid())
+    Ok(current_task().unwrap().cred.lock().ruid as usize)
 }
 
 /// examine and change blocked signals
@@ -781,11 +1027,11 @@ pub fn sys_gettid() -> SysResult<usize> {
 
 pub fn sys_geteuid() -> SysResult<usize> {
     // Ok(0)
-    Ok(current_task().unwrap().get_euid())
+    Ok(current_task().unwrap().cred.lock().euid as usize)
 }
 
 pub fn sys_getegid() -> SysResult<usize> {
-    Ok(0)
+    Ok(current_task().unwrap().cred.lock().egid as usize)
 }
 
 pub fn sys_sync() -> SysResult<usize> {
@@ -812,6 +1058,35 @@ pub fn sys_log(cmd: i32, buf: usize, len: usize) -> SysResult<usize> {
     res
 }
 
+/// 检查当前任务能否向 target 发送信号
+fn check_kill(target: &TaskControlBlock) -> SysResult {
+    // 同一线程组的任务共享凭证，先复制一份避免重复加锁
+    let cred = current_task().unwrap().cred.lock().clone();
+    match cred.may_signal(&target.cred.lock()) {
+        true => Ok(()),
+        false => Err(Errno::EPERM),
+    }
+}
+
+/// 检查当前任务能否像 ptrace attach 一样访问 target 的资源，
+/// 不可转储的进程只有 CAP_SYS_PTRACE 才能访问
+fn check_ptrace_attach(target: &TaskControlBlock) -> SysResult {
+    let cred = current_task().unwrap().cred.lock().clone();
+    if !cred.may_ptrace(&target.cred.lock()) {
+        return Err(Errno::EPERM);
+    }
+    let leader = get_task_by_pid(target.get_tgid()).ok_or(Errno::ESRCH)?;
+    if leader.get_dumpable() != SUID_DUMP_USER && !cred.capable(CapSet::CAP_SYS_PTRACE) {
+        return Err(Errno::EPERM);
+    }
+    Ok(())
+}
+
+/// 发送者的 real uid，填入 siginfo 的 si_uid
+fn sender_uid() -> usize {
+    current_task().unwrap().cred.lock().ruid as usize
+}
+
 /// send signal to a process
 pub fn sys_kill(pid: isize, signum: usize) -> SysResult<usize> {
     info!(
@@ -856,6 +1131,7 @@ pub fn sys_kill(pid: isize, signum: usize) -> SysResult<usize> {
             // let cur_task = current_task().unwrap();
             let recv_task = get_task_by_pid(p).ok_or(Errno::ESRCH)?;
             if recv_task.is_leader() && signum != SigNom::NOSIG {
+                check_kill(&recv_task)?;
                 let recv_pid = recv_task.get_tgid();
                 let siginfo = SigInfo::new(
                     signum,
@@ -863,7 +1139,7 @@ pub fn sys_kill(pid: isize, signum: usize) -> SysResult<usize> {
                     SigErr::empty(),
                     SigDetails::Kill {
                         pid: recv_pid,
-                        uid: 0,
+                        uid: sender_uid(),
                     },
                 );
                 recv_task.proc_recv_siginfo(siginfo);
@@ -885,12 +1161,15 @@ pub fn sys_kill(pid: isize, signum: usize) -> SysResult<usize> {
                 SigErr::empty(),
                 SigDetails::Kill {
                     pid: sender_pid,
-                    uid: 0,
+                    uid: sender_uid(),
                 },
             );
+            // 没有权限的进程直接跳过
             for target_pid in target_group.into_iter().filter(|pid| *pid != sender_pid) {
                 let recv_task = get_task_by_pid(target_pid).ok_or(Errno::ESRCH)?;
-                recv_task.proc_recv_siginfo(siginfo);
+                if check_kill(&recv_task).is_ok() {
+                    recv_task.proc_recv_siginfo(siginfo);
+                }
             }
             // yield_now();
             info!("[sys_kill] return Ok(0)");
@@ -904,11 +1183,12 @@ pub fn sys_kill(pid: isize, signum: usize) -> SysResult<usize> {
                 SigErr::empty(),
                 SigDetails::Kill { pid: 0, uid: 0 },
             );
+            let uid = sender_uid();
             let manager = MANAGER.task_manager.lock();
             for (pid, weak_task) in manager.0.iter().filter(|&(pid, _)| *pid != INITPROC_PID) {
                 let task = weak_task.upgrade().unwrap();
-                if task.is_leader() {
-                    siginfo.sifields = SigDetails::Kill { pid: *pid, uid: 0 };
+                if task.is_leader() && check_kill(&task).is_ok() {
+                    siginfo.sifields = SigDetails::Kill { pid: *pid, uid };
                     task.proc_recv_siginfo(siginfo);
                 }
             }
@@ -921,11 +1201,13 @@ pub fn sys_kill(pid: isize, signum: usize) -> SysResult<usize> {
                 signum,
                 SigCode::User,
                 SigErr::empty(),
-                SigDetails::Kill { pid: p, uid: 0 },
+                SigDetails::Kill { pid: p, uid: sender_uid() },
             );
             for target_pid in target_group {
                 let recv_task = get_task_by_pid(target_pid).ok_or(Errno::ESRCH)?;
-                recv_task.proc_recv_siginfo(siginfo);
+                if check_kill(&recv_task).is_ok() {
+                    recv_task.proc_recv_siginfo(siginfo);
+                }
             }
             return Ok(0);
         }
@@ -1019,13 +1301,14 @@ pub fn sys_tgkill(tgid: usize, tid: usize, sig: i32) -> SysResult<usize> {
         .ok_or(Errno::ESRCH)?
         .upgrade()
         .unwrap();
+    check_kill(&target)?;
     let siginfo = SigInfo::new(
         signom,
         SigCode::TKILL,
         SigErr::empty(),
         SigDetails::Kill {
             pid: task.get_pid(),
-            uid: 0,
+            uid: sender_uid(),
         },
     );
     target.thread_recv_siginfo(siginfo);
@@ -1134,6 +1417,7 @@ pub fn sys_tkill(tid: usize, sig: i32) -> SysResult<usize> {
     }
     let signom = SigNom::from(sig as usize);
     let target = get_task_by_pid(tid).ok_or(Errno::ESRCH)?;
+    check_kill(&target)?;
     let task = current_task().unwrap();
     let sender_pid = task.get_tgid();
     let siginfo = SigInfo::new(
@@ -1142,13 +1426,110 @@ pub fn sys_tkill(tid: usize, sig: i32) -> SysResult<usize> {
         SigErr::empty(),
         SigDetails::Kill {
             pid: sender_pid,
-            uid: 0,
+            uid: sender_uid(),
         },
     );
     target.thread_recv_siginfo(siginfo);
     Ok(0)
 }
 
+/// 获取一个指向 pid 进程的文件描述符
+///
+/// flags 只支持 PIDFD_NONBLOCK（和 O_NONBLOCK 相同）
+pub fn sys_pidfd_open(pid: isize, flags: u32) -> SysResult<usize> {
+    info!("[sys_pidfd_open] start, pid = {}, flags = {:#x}", pid, flags);
+    let flags = OpenFlags::from_bits(flags as i32).ok_or(Errno::EINVAL)?;
+    if unlikely(pid <= 0 || !(flags & !OpenFlags::O_NONBLOCK).is_empty()) {
+        return Err(Errno::EINVAL);
+    }
+    let target = get_task_by_pid(pid as usize).ok_or(Errno::ESRCH)?;
+    // 只能指向进程（线程组 leader）
+    if unlikely(!target.is_leader()) {
+        return Err(Errno::EINVAL);
+    }
+    if unlikely(target.is_zombie()) {
+        return Err(Errno::ESRCH);
+    }
+    let task = current_task().unwrap();
+    let file = PidFd::new(&target, OpenFlags::O_RDWR | flags);
+    // pidfd 总是 close-on-exec 的
+    task.alloc_fd(FdInfo::new(file, OpenFlags::O_RDWR | OpenFlags::O_CLOEXEC | flags))
+}
+
+/// 通过 pidfd 发送信号，不会因为 pid 复用而发错进程
+///
+/// info 为空时和 kill 的行为相同
+pub fn sys_pidfd_send_signal(pidfd: usize, sig: i32, info: usize, flags: u32) -> SysResult<usize> {
+    info!(
+        "[sys_pidfd_send_signal] start, pidfd = {}, sig = {}, flags = {:#x}",
+        pidfd, sig, flags
+    );
+    if unlikely(flags != 0) {
+        return Err(Errno::EINVAL);
+    }
+    if unlikely(sig < 0 || sig as usize > MAX_SIGNUM) {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(pidfd).ok_or(Errno::EBADF)?;
+    let pidfd = file.downcast_arc::<PidFd>().map_err(|_| Errno::EBADF)?;
+    let target = pidfd.get_task()?;
+    check_kill(&target)?;
+    if sig == 0 {
+        return Ok(0);
+    }
+
+    let code = match user_ref::<[i32; 3]>(info.into())? {
+        Some(user_info) => {
+            // [si_signo, si_errno, si_code]
+            let (signo, code) = (user_info[0], user_info[2]);
+            if unlikely(signo != sig) {
+                return Err(Errno::EINVAL);
+            }
+            // 不能伪装成内核或 kill 发出的信号
+            if unlikely(
+                (code >= 0 || code == SigCode::TKILL as i32)
+                    && target.get_tgid() != task.get_tgid(),
+            ) {
+                return Err(Errno::EPERM);
+            }
+            SigCode::from(code)
+        }
+        None => SigCode::User,
+    };
+    let siginfo = SigInfo::new(
+        SigNom::from(sig as usize),
+        code,
+        SigErr::empty(),
+        SigDetails::Kill {
+            pid: task.get_tgid(),
+            uid: sender_uid(),
+        },
+    );
+    target.proc_recv_siginfo(siginfo);
+    Ok(0)
+}
+
+/// 从 pidfd 指向的进程中复制一个文件描述符到当前进程，需要有 ptrace attach 目标进程的权限
+pub fn sys_pidfd_getfd(pidfd: usize, targetfd: usize, flags: u32) -> SysResult<usize> {
+    info!(
+        "[sys_pidfd_getfd] start, pidfd = {}, targetfd = {}",
+        pidfd, targetfd
+    );
+    if unlikely(flags != 0) {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let file = task.get_file_by_fd(pidfd).ok_or(Errno::EBADF)?;
+    let pidfd = file.downcast_arc::<PidFd>().map_err(|_| Errno::EBADF)?;
+    let target = pidfd.get_task()?;
+    check_ptrace_attach(&target)?;
+    let target_file = target.get_file_by_fd(targetfd).ok_or(Errno::EBADF)?;
+    let flags = target_file.metadata().flags.read().clone();
+    // 新的 fd 总是 close-on-exec 的
+    task.alloc_fd(FdInfo::new(target_file, flags | OpenFlags::O_CLOEXEC))
+}
+
 pub fn sys_madvise() -> SysResult<usize> {
     info!("[sys_madvise] start");
     Ok(0)
@@ -1342,20 +1723,347 @@ pub fn sys_sched_getaffinity(pid: usize, cpusetsize: usize, mask: usize) -> SysR
 
 pub fn sys_getgid() -> SysResult<usize> {
     info!("[sys_getgid] start");
-    Ok(0)
+    Ok(current_task().unwrap().cred.lock().rgid as usize)
 }
 
+/// 特权进程同时设置 real、effective、saved gid；
+/// 非特权进程只能把 egid 设为自己的 rgid 或 sgid
 pub fn sys_setgid(gid: usize) -> SysResult<usize> {
     info!("[sys_setgid] start, gid = {}", gid);
     // println!("[sys_setgid] start, gid = {}", gid);
+    current_task().unwrap().cred.lock().setgid(gid as u32)?;
+    Ok(0)
+}
+
+pub fn sys_setregid(rgid: usize, egid: usize) -> SysResult<usize> {
+    info!("[sys_setregid] start, rgid = {}, egid = {}", rgid as i32, egid as i32);
+    current_task().unwrap().cred.lock().setregid(rgid as u32, egid as u32)?;
+    Ok(0)
+}
+
+pub fn sys_setresgid(rgid: usize, egid: usize, sgid: usize) -> SysResult<usize> {
+    info!(
+        "[sys_setresgid] start, rgid = {}, egid = {}, sgid = {}",
+        rgid as i32, egid as i32, sgid as i32
+    );
+    current_task()
+        .unwrap()
+        .cred
+        .lock()
+        .setresgid(rgid as u32, egid as u32, sgid as u32)?;
+    Ok(0)
+}
+
+pub fn sys_getresgid(rgid: usize, egid: usize, sgid: usize) -> SysResult<usize> {
+    let (r, e, s) = {
+        let cred = current_task().unwrap().cred.lock();
+        (cred.rgid, cred.egid, cred.sgid)
+    };
+    *user_ref_mut::<u32>(rgid.into())?.ok_or(Errno::EFAULT)? = r;
+    *user_ref_mut::<u32>(egid.into())?.ok_or(Errno::EFAULT)? = e;
+    *user_ref_mut::<u32>(sgid.into())?.ok_or(Errno::EFAULT)? = s;
+    Ok(0)
+}
+
+/// 返回原来的 fsgid，失败时也不报错
+pub fn sys_setfsgid(fsgid: usize) -> SysResult<usize> {
+    info!("[sys_setfsgid] start, fsgid = {}", fsgid as i32);
+    Ok(current_task().unwrap().cred.lock().setfsgid(fsgid as u32) as usize)
+}
+
+/// size 为 0 时只返回补充组的数量
+pub fn sys_getgroups(size: usize, list: usize) -> SysResult<usize> {
+    info!("[sys_getgroups] start, size = {}", size);
+    let groups = current_task().unwrap().cred.lock().groups.clone();
+    if size == 0 {
+        return Ok(groups.len());
+    }
+    if (size as i32) < 0 || size < groups.len() {
+        return Err(Errno::EINVAL);
+    }
+    let buf = user_slice_mut::<u8>(list.into(), groups.len() * size_of::<u32>())?
+        .ok_or(Errno::EFAULT)?;
+    buf.copy_from_slice(groups.as_slice().as_bytes());
+    Ok(groups.len())
+}
+
+pub fn sys_setgroups(size: usize, list: usize) -> SysResult<usize> {
+    info!("[sys_setgroups] start, size = {}", size);
+    if size > NGROUPS_MAX {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    if !task.cred.lock().gid_privileged() {
+        return Err(Errno::EPERM);
+    }
+    let groups = match size {
+        0 => Vec::new(),
+        _ => user_slice::<u8>(list.into(), size * size_of::<u32>())?
+            .ok_or(Errno::EFAULT)?
+            .chunks_exact(size_of::<u32>())
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    };
+    task.cred.lock().setgroups(groups)?;
+    Ok(0)
+}
+
+/// capget/capset 的 pid 对应的任务，0 表示调用者自己
+fn cap_target(pid: i32) -> SysResult<Arc<TaskControlBlock>> {
+    match pid {
+        0 => Ok(current_task().unwrap()),
+        pid if pid < 0 => Err(Errno::EINVAL),
+        pid => get_task_by_pid(pid as usize).ok_or(Errno::ESRCH),
+    }
+}
+
+/// 检查 capget/capset 的版本号，返回用户数据中结构体的个数。
+/// 版本不支持时把内核使用的版本写回 header
+fn cap_version(header: &mut CapUserHeader) -> SysResult<usize> {
+    match header.version {
+        LINUX_CAPABILITY_VERSION_1 => Ok(1),
+        LINUX_CAPABILITY_VERSION_2 | LINUX_CAPABILITY_VERSION_3 => Ok(2),
+        _ => {
+            header.version = LINUX_CAPABILITY_VERSION_3;
+            Err(Errno::EINVAL)
+        }
+    }
+}
+
+/// 获取任务的 effective、permitted、inheritable 能力集
+pub fn sys_capget(hdrp: usize, datap: usize) -> SysResult<usize> {
+    info!("[sys_capget] start");
+    let header = user_ref_mut::<CapUserHeader>(hdrp.into())?.ok_or(Errno::EFAULT)?;
+    let count = cap_version(header)?;
+    let task = cap_target(header.pid)?;
+    // datap 为空时只用来探测内核支持的版本
+    if datap == 0 {
+        return Ok(0);
+    }
+    let (effective, permitted, inheritable) = {
+        let cred = task.cred.lock();
+        (cred.cap_effective.bits(), cred.cap_permitted.bits(), cred.cap_inheritable.bits())
+    };
+    for i in 0..count {
+        let data = user_ref_mut::<CapUserData>((datap + i * size_of::<CapUserData>()).into())?
+            .ok_or(Errno::EFAULT)?;
+        let shift = i * 32;
+        *data = CapUserData {
+            effective: (effective >> shift) as u32,
+            permitted: (permitted >> shift) as u32,
+            inheritable: (inheritable >> shift) as u32,
+        };
+    }
     Ok(0)
 }
 
-pub fn sys_fchownat() -> SysResult<usize> {
-    info!("[sys_fchownat] start");
+/// 设置调用者自己的能力集
+pub fn sys_capset(hdrp: usize, datap: usize) -> SysResult<usize> {
+    info!("[sys_capset] start");
+    let header = user_ref_mut::<CapUserHeader>(hdrp.into())?.ok_or(Errno::EFAULT)?;
+    let count = cap_version(header)?;
+    let task = current_task().unwrap();
+    if header.pid != 0 && header.pid as usize != task.get_pid() {
+        return Err(Errno::EPERM);
+    }
+    let (mut effective, mut permitted, mut inheritable) = (0u64, 0u64, 0u64);
+    for i in 0..count {
+        let data = user_ref::<CapUserData>((datap + i * size_of::<CapUserData>()).into())?
+            .ok_or(Errno::EFAULT)?;
+        let shift = i * 32;
+        effective |= (data.effective as u64) << shift;
+        permitted |= (data.permitted as u64) << shift;
+        inheritable |= (data.inheritable as u64) << shift;
+    }
+    task.cred.lock().capset(
+        CapSet::from_bits_truncate(effective),
+        CapSet::from_bits_truncate(permitted),
+        CapSet::from_bits_truncate(inheritable),
+    )?;
     Ok(0)
 }
 
+/// 进程相关的杂项操作
+pub fn sys_prctl(option: usize, arg2: usize, arg3: usize, arg4: usize, arg5: usize) -> SysResult<usize> {
+    info!("[sys_prctl] start, option = {}, arg2 = {:#x}", option, arg2);
+    let task = current_task().unwrap();
+    // 进程级的属性保存在线程组 leader 上
+    let leader = get_task_by_pid(task.get_tgid()).ok_or(Errno::ESRCH)?;
+    match option {
+        PR_SET_PDEATHSIG => {
+            if arg2 > MAX_SIGNUM {
+                return Err(Errno::EINVAL);
+            }
+            task.set_pdeath_signal(arg2);
+            Ok(0)
+        }
+        PR_GET_PDEATHSIG => {
+            let ptr = user_ref_mut::<i32>(arg2.into())?.ok_or(Errno::EFAULT)?;
+            *ptr = task.get_pdeath_signal() as i32;
+            Ok(0)
+        }
+        PR_GET_DUMPABLE => Ok(leader.get_dumpable()),
+        PR_SET_DUMPABLE => {
+            // prctl 只能设置 0 和 1
+            if arg2 != SUID_DUMP_DISABLE && arg2 != SUID_DUMP_USER {
+                return Err(Errno::EINVAL);
+            }
+            leader.set_dumpable(arg2);
+            Ok(0)
+        }
+        PR_SET_NAME => {
+            let name = user_slice::<u8>(arg2.into(), TASK_COMM_LEN)?.ok_or(Errno::EFAULT)?;
+            let len = name.iter().position(|&c| c == 0).unwrap_or(TASK_COMM_LEN - 1);
+            task.set_comm(&String::from_utf8_lossy(&name[..len]));
+            Ok(0)
+        }
+        PR_GET_NAME => {
+            let buf = user_slice_mut::<u8>(arg2.into(), TASK_COMM_LEN)?.ok_or(Errno::EFAULT)?;
+            let comm = task.get_comm();
+            buf.fill(0);
+            buf[..comm.len()].copy_from_slice(comm.as_bytes());
+            Ok(0)
+        }
+        PR_SET_CHILD_SUBREAPER => {
+            leader.set_child_subreaper(arg2 != 0);
+            Ok(0)
+        }
+        PR_GET_CHILD_SUBREAPER => {
+            let ptr = user_ref_mut::<i32>(arg2.into())?.ok_or(Errno::EFAULT)?;
+            *ptr = leader.is_child_subreaper() as i32;
+            Ok(0)
+        }
+        PR_SET_NO_NEW_PRIVS => {
+            // 只能置位，不能清除
+            if arg2 != 1 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(Errno::EINVAL);
+            }
+            task.set_no_new_privs();
+            Ok(0)
+        }
+        PR_GET_NO_NEW_PRIVS => {
+            if arg2 != 0 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(Errno::EINVAL);
+            }
+            Ok(task.get_no_new_privs() as usize)
+        }
+        PR_GET_SECCOMP => Ok(task.seccomp.lock().mode as usize),
+        PR_SET_SECCOMP => match arg2 {
+            1 => sys_seccomp(SECCOMP_SET_MODE_STRICT, 0, 0),
+            2 => sys_seccomp(SECCOMP_SET_MODE_FILTER, 0, arg3),
+            _ => Err(Errno::EINVAL),
+        },
+        PR_CAPBSET_READ => {
+            let cap = CapSet::from_index(arg2).ok_or(Errno::EINVAL)?;
+            Ok(task.cred.lock().cap_bset.contains(cap) as usize)
+        }
+        PR_CAPBSET_DROP => {
+            let cap = CapSet::from_index(arg2).ok_or(Errno::EINVAL)?;
+            task.cred.lock().capbset_drop(cap)?;
+            Ok(0)
+        }
+        PR_CAP_AMBIENT => {
+            if arg4 != 0 || arg5 != 0 {
+                return Err(Errno::EINVAL);
+            }
+            let mut cred = task.cred.lock();
+            if arg2 == PR_CAP_AMBIENT_CLEAR_ALL {
+                if arg3 != 0 {
+                    return Err(Errno::EINVAL);
+                }
+                cred.cap_ambient = CapSet::empty();
+                return Ok(0);
+            }
+            let cap = CapSet::from_index(arg3).ok_or(Errno::EINVAL)?;
+            match arg2 {
+                PR_CAP_AMBIENT_IS_SET => Ok(cred.cap_ambient.contains(cap) as usize),
+                PR_CAP_AMBIENT_RAISE => cred.ambient_raise(cap).map(|_| 0),
+                PR_CAP_AMBIENT_LOWER => {
+                    cred.cap_ambient.remove(cap);
+                    Ok(0)
+                }
+                _ => Err(Errno::EINVAL),
+            }
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// 进入 seccomp strict 模式，或者安装一个 BPF 过滤程序
+pub fn sys_seccomp(op: u32, flags: u32, uargs: usize) -> SysResult<usize> {
+    info!("[sys_seccomp] start, op = {}, flags = {:#x}", op, flags);
+    let task = current_task().unwrap();
+    match op {
+        SECCOMP_SET_MODE_STRICT => {
+            if flags != 0 || uargs != 0 {
+                return Err(Errno::EINVAL);
+            }
+            Arc::make_mut(&mut *task.seccomp.lock()).set_strict()?;
+            Ok(0)
+        }
+        SECCOMP_SET_MODE_FILTER => {
+            let flags = SeccompFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+            // 没有 no_new_privs 时，过滤程序可能被用来干扰 set-user-id 程序
+            if !task.get_no_new_privs() && !capable(CapSet::CAP_SYS_ADMIN) {
+                return Err(Errno::EACCES);
+            }
+            let fprog = user_ref::<SockFprog>(uargs.into())?.ok_or(Errno::EFAULT)?;
+            let len = fprog.len as usize;
+            if len == 0 || len > BPF_MAXINSNS {
+                return Err(Errno::EINVAL);
+            }
+            let prog: Vec<SockFilter> = user_slice::<u8>(fprog.filter.into(), len * size_of::<SockFilter>())?
+                .ok_or(Errno::EFAULT)?
+                .chunks_exact(size_of::<SockFilter>())
+                .map(|b| SockFilter {
+                    code: u16::from_ne_bytes([b[0], b[1]]),
+                    jt: b[2],
+                    jf: b[3],
+                    k: u32::from_ne_bytes([b[4], b[5], b[6], b[7]]),
+                })
+                .collect();
+            if !flags.contains(SeccompFlags::TSYNC) {
+                Arc::make_mut(&mut *task.seccomp.lock()).attach_filter(prog)?;
+                return Ok(0);
+            }
+            // TSYNC：安装到当前线程后复制给线程组中的所有线程，
+            // 有线程处于 strict 模式时不做修改，返回它的 tid
+            let mut seccomp = Seccomp::clone(&task.seccomp.lock());
+            seccomp.attach_filter(prog)?;
+            let seccomp = Arc::new(seccomp);
+            let threads: Vec<Arc<TaskControlBlock>> = task
+                .thread_group
+                .lock()
+                .tasks
+                .values()
+                .filter_map(|thread| thread.upgrade())
+                .collect();
+            if let Some(thread) = threads
+                .iter()
+                .find(|thread| thread.seccomp.lock().mode == SeccompMode::Strict)
+            {
+                return Ok(thread.get_pid());
+            }
+            for thread in threads {
+                *thread.seccomp.lock() = seccomp.clone();
+            }
+            Ok(0)
+        }
+        SECCOMP_GET_ACTION_AVAIL => {
+            if flags != 0 {
+                return Err(Errno::EINVAL);
+            }
+            let action = *user_ref::<u32>(uargs.into())?.ok_or(Errno::EFAULT)?;
+            match action {
+                SECCOMP_RET_KILL_PROCESS | SECCOMP_RET_KILL_THREAD | SECCOMP_RET_TRAP
+                | SECCOMP_RET_ERRNO | SECCOMP_RET_TRACE | SECCOMP_RET_LOG | SECCOMP_RET_ALLOW => Ok(0),
+                _ => Err(Errno::EOPNOTSUPP),
+            }
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
 /// synchronize a file with a memory map
 /// TODO: 有待实现
 pub fn sys_msync() -> SysResult<usize> {
@@ -1401,18 +2109,52 @@ pub async fn sys_sigsuspend(mask: usize) -> SysResult<usize> {
 //     pub static ref GLOBAL_UID: AtomicU32 = AtomicU32::new(0);
 // }
 
+/// 特权进程同时设置 real、effective、saved uid；
+/// 非特权进程只能把 euid 设为自己的 ruid 或 suid
 pub fn sys_setuid(uid: usize) -> SysResult<usize> {
     info!("[sys_setuid] set uid: {}", uid);
     // GLOBAL_UID.store(uid as u32, core::sync::atomic::Ordering::Relaxed);
-    current_task().unwrap().set_euid(uid);
+    current_task().unwrap().cred.lock().setuid(uid as u32)?;
     Ok(0)
 }
 
-pub fn sys_setresuid() -> SysResult<usize> {
-    info!("[sys_setresuid] start, 0");
+pub fn sys_setreuid(ruid: usize, euid: usize) -> SysResult<usize> {
+    info!("[sys_setreuid] start, ruid = {}, euid = {}", ruid as i32, euid as i32);
+    current_task().unwrap().cred.lock().setreuid(ruid as u32, euid as u32)?;
     Ok(0)
 }
 
+/// 参数为 -1 表示对应的 id 保持不变
+pub fn sys_setresuid(ruid: usize, euid: usize, suid: usize) -> SysResult<usize> {
+    info!(
+        "[sys_setresuid] start, ruid = {}, euid = {}, suid = {}",
+        ruid as i32, euid as i32, suid as i32
+    );
+    current_task()
+        .unwrap()
+        .cred
+        .lock()
+        .setresuid(ruid as u32, euid as u32, suid as u32)?;
+    Ok(0)
+}
+
+pub fn sys_getresuid(ruid: usize, euid: usize, suid: usize) -> SysResult<usize> {
+    let (r, e, s) = {
+        let cred = current_task().unwrap().cred.lock();
+        (cred.ruid, cred.euid, cred.suid)
+    };
+    *user_ref_mut::<u32>(ruid.into())?.ok_or(Errno::EFAULT)? = r;
+    *user_ref_mut::<u32>(euid.into())?.ok_or(Errno::EFAULT)? = e;
+    *user_ref_mut::<u32>(suid.into())?.ok_or(Errno::EFAULT)? = s;
+    Ok(0)
+}
+
+/// 返回原来的 fsuid，失败时也不报错
+pub fn sys_setfsuid(fsuid: usize) -> SysResult<usize> {
+    info!("[sys_setfsuid] start, fsuid = {}", fsuid as i32);
+    Ok(current_task().unwrap().cred.lock().setfsuid(fsuid as u32) as usize)
+}
+
 /// 设置进程的优先级
 pub fn sys_sched_setparam(pid: usize, param: usize) -> SysResult<usize> {
     info!("[sys_sched_setparam] start");
@@ -1424,6 +2166,10 @@ pub fn sys_sched_setparam(pid: usize, param: usize) -> SysResult<usize> {
         0 => current_task().unwrap(),
         _ => get_task_by_pid(pid).ok_or(Errno::ESRCH)?,
     };
+    let cred = current_task().unwrap().cred.lock().clone();
+    if !cred.may_renice(&task.cred.lock()) {
+        return Err(Errno::EPERM);
+    }
 
     let ptr = unsafe { *(param as *const SchedParam) };
     let dst = task.get_prio_mut();
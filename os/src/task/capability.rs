@@ -0,0 +1,86 @@
+//! Linux capabilities
+//!
+//! 把 root 的特权拆分成若干独立的能力，每个任务的凭证中保存
+//! permitted、effective、inheritable、bounding、ambient 五个能力集。
+
+use bitflags::bitflags;
+
+use super::current_task;
+
+bitflags! {
+    /// 能力集，第 n 位对应编号为 n 的能力
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct CapSet: u64 {
+        const CAP_CHOWN = 1 << 0;
+        const CAP_DAC_OVERRIDE = 1 << 1;
+        const CAP_DAC_READ_SEARCH = 1 << 2;
+        const CAP_FOWNER = 1 << 3;
+        const CAP_FSETID = 1 << 4;
+        const CAP_KILL = 1 << 5;
+        const CAP_SETGID = 1 << 6;
+        const CAP_SETUID = 1 << 7;
+        const CAP_SETPCAP = 1 << 8;
+        const CAP_LINUX_IMMUTABLE = 1 << 9;
+        const CAP_NET_BIND_SERVICE = 1 << 10;
+        const CAP_NET_BROADCAST = 1 << 11;
+        const CAP_NET_ADMIN = 1 << 12;
+        const CAP_NET_RAW = 1 << 13;
+        const CAP_IPC_LOCK = 1 << 14;
+        const CAP_IPC_OWNER = 1 << 15;
+        const CAP_SYS_MODULE = 1 << 16;
+        const CAP_SYS_RAWIO = 1 << 17;
+        const CAP_SYS_CHROOT = 1 << 18;
+        const CAP_SYS_PTRACE = 1 << 19;
+        const CAP_SYS_PACCT = 1 << 20;
+        const CAP_SYS_ADMIN = 1 << 21;
+        const CAP_SYS_BOOT = 1 << 22;
+        const CAP_SYS_NICE = 1 << 23;
+        const CAP_SYS_RESOURCE = 1 << 24;
+        const CAP_SYS_TIME = 1 << 25;
+        const CAP_SYS_TTY_CONFIG = 1 << 26;
+        const CAP_MKNOD = 1 << 27;
+        const CAP_LEASE = 1 << 28;
+        const CAP_AUDIT_WRITE = 1 << 29;
+        const CAP_AUDIT_CONTROL = 1 << 30;
+        const CAP_SETFCAP = 1 << 31;
+        const CAP_MAC_OVERRIDE = 1 << 32;
+        const CAP_MAC_ADMIN = 1 << 33;
+        const CAP_SYSLOG = 1 << 34;
+        const CAP_WAKE_ALARM = 1 << 35;
+        const CAP_BLOCK_SUSPEND = 1 << 36;
+        const CAP_AUDIT_READ = 1 << 37;
+        const CAP_PERFMON = 1 << 38;
+        const CAP_BPF = 1 << 39;
+        const CAP_CHECKPOINT_RESTORE = 1 << 40;
+    }
+}
+
+/// 编号最大的能力
+pub const CAP_LAST_CAP: usize = 40;
+
+impl CapSet {
+    /// 和文件系统访问相关的能力，fsuid 在 0 和非 0 之间切换时随之清除或恢复
+    pub const FS_MASK: Self = Self::from_bits_retain(
+        Self::CAP_CHOWN.bits()
+            | Self::CAP_DAC_OVERRIDE.bits()
+            | Self::CAP_DAC_READ_SEARCH.bits()
+            | Self::CAP_FOWNER.bits()
+            | Self::CAP_FSETID.bits()
+            | Self::CAP_LINUX_IMMUTABLE.bits()
+            | Self::CAP_MKNOD.bits()
+            | Self::CAP_MAC_OVERRIDE.bits(),
+    );
+
+    /// 编号为 cap 的能力，超出范围时返回 None
+    pub fn from_index(cap: usize) -> Option<Self> {
+        match cap <= CAP_LAST_CAP {
+            true => Some(Self::from_bits_retain(1 << cap)),
+            false => None,
+        }
+    }
+}
+
+/// 当前任务的 effective 能力集中是否包含 cap，内核初始化时没有当前任务，视为拥有全部能力
+pub fn capable(cap: CapSet) -> bool {
+    current_task().map_or(true, |task| task.cred.lock().capable(cap))
+}
@@ -0,0 +1,382 @@
+//! 进程的身份凭证
+//!
+//! 和 Linux 一样区分 real/effective/saved/fs 四组 id：
+//! - real: 进程真正的所有者，kill 等权限检查使用
+//! - effective: 大多数权限检查使用
+//! - saved: execve 时保存的 effective id，允许非特权进程切换回来
+//! - fs: 文件系统访问时使用，一般跟随 effective id
+//!
+//! 特权检查不看 uid 是否为 0，而是看 effective 能力集，
+//! uid 变化和 execve 时按照 capabilities(7) 的规则调整能力集。
+
+use alloc::vec::Vec;
+
+use super::capability::CapSet;
+use crate::utils::{Errno, SysResult};
+
+/// 补充组的最大数量
+pub const NGROUPS_MAX: usize = 65536;
+
+/// set*id 系统调用中表示“不修改”的取值 (uid_t)-1
+const ID_UNCHANGED: u32 = u32::MAX;
+
+/// 文件模式中的 set-user-id 和 set-group-id 位
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_IXGRP: u32 = 0o0010;
+
+#[derive(Clone, Debug)]
+pub struct Cred {
+    pub ruid: u32,
+    pub euid: u32,
+    pub suid: u32,
+    pub fsuid: u32,
+    pub rgid: u32,
+    pub egid: u32,
+    pub sgid: u32,
+    pub fsgid: u32,
+    /// 补充组列表
+    pub groups: Vec<u32>,
+    pub cap_inheritable: CapSet,
+    pub cap_permitted: CapSet,
+    pub cap_effective: CapSet,
+    /// bounding set，execve 时能获得的能力上限
+    pub cap_bset: CapSet,
+    pub cap_ambient: CapSet,
+}
+
+impl Cred {
+    /// initproc 使用的 root 凭证
+    pub fn new_root() -> Self {
+        Self {
+            ruid: 0,
+            euid: 0,
+            suid: 0,
+            fsuid: 0,
+            rgid: 0,
+            egid: 0,
+            sgid: 0,
+            fsgid: 0,
+            groups: Vec::new(),
+            cap_inheritable: CapSet::empty(),
+            cap_permitted: CapSet::all(),
+            cap_effective: CapSet::all(),
+            cap_bset: CapSet::all(),
+            cap_ambient: CapSet::empty(),
+        }
+    }
+
+    /// effective 能力集中是否包含 cap
+    pub fn capable(&self, cap: CapSet) -> bool {
+        self.cap_effective.contains(cap)
+    }
+
+    /// 修改 uid 时是否不受限制
+    pub fn uid_privileged(&self) -> bool {
+        self.capable(CapSet::CAP_SETUID)
+    }
+
+    /// 修改 gid 和补充组时是否不受限制
+    pub fn gid_privileged(&self) -> bool {
+        self.capable(CapSet::CAP_SETGID)
+    }
+
+    /// real、effective、saved uid 中有 0 的进程在三者都变成非 0 后失去全部能力；
+    /// euid 从 0 变成非 0 时清空 effective，从非 0 变回 0 时恢复成 permitted
+    fn fix_setxuid_caps(&mut self, old_ruid: u32, old_euid: u32, old_suid: u32) {
+        let was_root = old_ruid == 0 || old_euid == 0 || old_suid == 0;
+        if was_root && self.ruid != 0 && self.euid != 0 && self.suid != 0 {
+            self.cap_permitted = CapSet::empty();
+            self.cap_effective = CapSet::empty();
+            self.cap_ambient = CapSet::empty();
+        }
+        if old_euid == 0 && self.euid != 0 {
+            self.cap_effective = CapSet::empty();
+        }
+        if old_euid != 0 && self.euid == 0 {
+            self.cap_effective = self.cap_permitted;
+        }
+    }
+
+    /// fsuid 从 0 变成非 0 时去掉文件系统相关的能力，变回 0 时从 permitted 中恢复
+    fn fix_setfsuid_caps(&mut self, old_fsuid: u32) {
+        if old_fsuid == 0 && self.fsuid != 0 {
+            self.cap_effective.remove(CapSet::FS_MASK);
+        }
+        if old_fsuid != 0 && self.fsuid == 0 {
+            self.cap_effective.insert(self.cap_permitted & CapSet::FS_MASK);
+        }
+    }
+
+    /// gid 是否是进程的 fsgid 或者补充组之一
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.fsgid == gid || self.groups.contains(&gid)
+    }
+
+    pub fn setuid(&mut self, uid: u32) -> SysResult {
+        if uid == ID_UNCHANGED {
+            return Err(Errno::EINVAL);
+        }
+        let (old_ruid, old_euid, old_suid) = (self.ruid, self.euid, self.suid);
+        if self.uid_privileged() {
+            self.ruid = uid;
+            self.suid = uid;
+        } else if uid != self.ruid && uid != self.suid {
+            return Err(Errno::EPERM);
+        }
+        self.euid = uid;
+        self.fsuid = uid;
+        self.fix_setxuid_caps(old_ruid, old_euid, old_suid);
+        Ok(())
+    }
+
+    pub fn setgid(&mut self, gid: u32) -> SysResult {
+        if gid == ID_UNCHANGED {
+            return Err(Errno::EINVAL);
+        }
+        if self.gid_privileged() {
+            self.rgid = gid;
+            self.sgid = gid;
+        } else if gid != self.rgid && gid != self.sgid {
+            return Err(Errno::EPERM);
+        }
+        self.egid = gid;
+        self.fsgid = gid;
+        Ok(())
+    }
+
+    /// 非特权进程：ruid 只能设为 ruid 或 euid，euid 只能设为 ruid、euid 或 suid。
+    /// 设置了 ruid，或者 euid 被设成和原 ruid 不同的值时，suid 跟随新的 euid。
+    pub fn setreuid(&mut self, ruid: u32, euid: u32) -> SysResult {
+        let privileged = self.uid_privileged();
+        if ruid != ID_UNCHANGED && !privileged && ruid != self.ruid && ruid != self.euid {
+            return Err(Errno::EPERM);
+        }
+        if euid != ID_UNCHANGED
+            && !privileged
+            && euid != self.ruid
+            && euid != self.euid
+            && euid != self.suid
+        {
+            return Err(Errno::EPERM);
+        }
+        let (old_ruid, old_euid, old_suid) = (self.ruid, self.euid, self.suid);
+        if ruid != ID_UNCHANGED {
+            self.ruid = ruid;
+        }
+        if euid != ID_UNCHANGED {
+            self.euid = euid;
+        }
+        if ruid != ID_UNCHANGED || (euid != ID_UNCHANGED && euid != old_ruid) {
+            self.suid = self.euid;
+        }
+        self.fsuid = self.euid;
+        self.fix_setxuid_caps(old_ruid, old_euid, old_suid);
+        Ok(())
+    }
+
+    pub fn setregid(&mut self, rgid: u32, egid: u32) -> SysResult {
+        let privileged = self.gid_privileged();
+        if rgid != ID_UNCHANGED && !privileged && rgid != self.rgid && rgid != self.egid {
+            return Err(Errno::EPERM);
+        }
+        if egid != ID_UNCHANGED
+            && !privileged
+            && egid != self.rgid
+            && egid != self.egid
+            && egid != self.sgid
+        {
+            return Err(Errno::EPERM);
+        }
+        let old_rgid = self.rgid;
+        if rgid != ID_UNCHANGED {
+            self.rgid = rgid;
+        }
+        if egid != ID_UNCHANGED {
+            self.egid = egid;
+        }
+        if rgid != ID_UNCHANGED || (egid != ID_UNCHANGED && egid != old_rgid) {
+            self.sgid = self.egid;
+        }
+        self.fsgid = self.egid;
+        Ok(())
+    }
+
+    /// 非特权进程的三个新值都必须是当前 ruid、euid、suid 之一
+    pub fn setresuid(&mut self, ruid: u32, euid: u32, suid: u32) -> SysResult {
+        let old = [self.ruid, self.euid, self.suid];
+        let allowed = |id: u32| id == ID_UNCHANGED || old.contains(&id);
+        if !self.uid_privileged() && !(allowed(ruid) && allowed(euid) && allowed(suid)) {
+            return Err(Errno::EPERM);
+        }
+        let [old_ruid, old_euid, old_suid] = old;
+        if ruid != ID_UNCHANGED {
+            self.ruid = ruid;
+        }
+        if euid != ID_UNCHANGED {
+            self.euid = euid;
+        }
+        if suid != ID_UNCHANGED {
+            self.suid = suid;
+        }
+        self.fsuid = self.euid;
+        self.fix_setxuid_caps(old_ruid, old_euid, old_suid);
+        Ok(())
+    }
+
+    pub fn setresgid(&mut self, rgid: u32, egid: u32, sgid: u32) -> SysResult {
+        let old = [self.rgid, self.egid, self.sgid];
+        let allowed = |id: u32| id == ID_UNCHANGED || old.contains(&id);
+        if !self.gid_privileged() && !(allowed(rgid) && allowed(egid) && allowed(sgid)) {
+            return Err(Errno::EPERM);
+        }
+        if rgid != ID_UNCHANGED {
+            self.rgid = rgid;
+        }
+        if egid != ID_UNCHANGED {
+            self.egid = egid;
+        }
+        if sgid != ID_UNCHANGED {
+            self.sgid = sgid;
+        }
+        self.fsgid = self.egid;
+        Ok(())
+    }
+
+    /// 无论成功与否都返回原来的 fsuid
+    pub fn setfsuid(&mut self, fsuid: u32) -> u32 {
+        let old = self.fsuid;
+        if fsuid == ID_UNCHANGED {
+            return old;
+        }
+        if self.uid_privileged() || [self.ruid, self.euid, self.suid, self.fsuid].contains(&fsuid) {
+            self.fsuid = fsuid;
+            self.fix_setfsuid_caps(old);
+        }
+        old
+    }
+
+    pub fn setfsgid(&mut self, fsgid: u32) -> u32 {
+        let old = self.fsgid;
+        if fsgid == ID_UNCHANGED {
+            return old;
+        }
+        if self.gid_privileged() || [self.rgid, self.egid, self.sgid, self.fsgid].contains(&fsgid) {
+            self.fsgid = fsgid;
+        }
+        old
+    }
+
+    pub fn setgroups(&mut self, groups: Vec<u32>) -> SysResult {
+        if !self.gid_privileged() {
+            return Err(Errno::EPERM);
+        }
+        if groups.len() > NGROUPS_MAX {
+            return Err(Errno::EINVAL);
+        }
+        self.groups = groups;
+        Ok(())
+    }
+
+    /// execve 时根据可执行文件的 set-user-id/set-group-id 位更新凭证，
+    /// 之后 saved id 总是等于新的 effective id
+    pub fn apply_exec(&mut self, mode: u32, file_uid: u32, file_gid: u32) {
+        let (old_euid, old_egid) = (self.euid, self.egid);
+        if mode & S_ISUID != 0 {
+            self.euid = file_uid;
+        }
+        // 没有组执行权限时 S_ISGID 表示强制锁，不是 set-group-id
+        if mode & S_ISGID != 0 && mode & S_IXGRP != 0 {
+            self.egid = file_gid;
+        }
+        self.suid = self.euid;
+        self.fsuid = self.euid;
+        self.sgid = self.egid;
+        self.fsgid = self.egid;
+        self.exec_caps(self.euid != old_euid || self.egid != old_egid);
+    }
+
+    /// execve 的能力变换。没有文件能力，root 执行的程序视为拥有全部文件能力：
+    /// P'(permitted) = root ? (P(inheritable) | P(bounding)) | P'(ambient) : P'(ambient)，
+    /// euid 为 0 时 effective 等于新的 permitted，否则等于 ambient
+    fn exec_caps(&mut self, is_setid: bool) {
+        if is_setid {
+            self.cap_ambient = CapSet::empty();
+        }
+        let mut permitted = self.cap_ambient;
+        if self.euid == 0 || self.ruid == 0 {
+            permitted |= self.cap_inheritable | self.cap_bset;
+        }
+        self.cap_permitted = permitted;
+        self.cap_effective = match self.euid {
+            0 => permitted,
+            _ => self.cap_ambient,
+        };
+    }
+
+    /// capset：inheritable 只能增加 permitted 或原 inheritable 中已有、且在 bounding set 中的能力
+    /// （有 CAP_SETPCAP 时不受前一个限制），permitted 只能缩小，effective 必须是 permitted 的子集
+    pub fn capset(&mut self, effective: CapSet, permitted: CapSet, inheritable: CapSet) -> SysResult {
+        let inheritable_limit = match self.capable(CapSet::CAP_SETPCAP) {
+            true => CapSet::all(),
+            false => self.cap_inheritable | self.cap_permitted,
+        };
+        if !inheritable_limit.contains(inheritable)
+            || !(self.cap_inheritable | self.cap_bset).contains(inheritable)
+            || !self.cap_permitted.contains(permitted)
+            || !permitted.contains(effective)
+        {
+            return Err(Errno::EPERM);
+        }
+        self.cap_effective = effective;
+        self.cap_permitted = permitted;
+        self.cap_inheritable = inheritable;
+        // ambient 必须同时在 permitted 和 inheritable 中
+        self.cap_ambient &= permitted & inheritable;
+        Ok(())
+    }
+
+    /// 从 bounding set 中去掉一项能力，需要 CAP_SETPCAP
+    pub fn capbset_drop(&mut self, cap: CapSet) -> SysResult {
+        if !self.capable(CapSet::CAP_SETPCAP) {
+            return Err(Errno::EPERM);
+        }
+        self.cap_bset.remove(cap);
+        Ok(())
+    }
+
+    /// 能否向凭证为 target 的任务发送信号：发送者的 real 或 effective uid
+    /// 要等于目标的 real 或 saved uid，否则需要 CAP_KILL
+    pub fn may_signal(&self, target: &Cred) -> bool {
+        self.capable(CapSet::CAP_KILL)
+            || [self.ruid, self.euid]
+                .iter()
+                .any(|uid| *uid == target.ruid || *uid == target.suid)
+    }
+
+    /// 能否以 PTRACE_MODE_ATTACH_REALCREDS 的方式访问 target：调用者的 real uid 和 gid
+    /// 要分别等于目标的 real、effective、saved uid 和 gid，否则需要 CAP_SYS_PTRACE
+    pub fn may_ptrace(&self, target: &Cred) -> bool {
+        self.capable(CapSet::CAP_SYS_PTRACE)
+            || ([target.ruid, target.euid, target.suid]
+                .iter()
+                .all(|uid| *uid == self.ruid)
+                && [target.rgid, target.egid, target.sgid]
+                    .iter()
+                    .all(|gid| *gid == self.rgid))
+    }
+
+    /// 修改其他任务的调度参数要求 euid 等于目标的 real 或 effective uid，否则需要 CAP_SYS_NICE
+    pub fn may_renice(&self, target: &Cred) -> bool {
+        self.capable(CapSet::CAP_SYS_NICE) || self.euid == target.ruid || self.euid == target.euid
+    }
+
+    /// 添加 ambient 能力，要求它同时在 permitted 和 inheritable 中
+    pub fn ambient_raise(&mut self, cap: CapSet) -> SysResult {
+        if !self.cap_permitted.contains(cap) || !self.cap_inheritable.contains(cap) {
+            return Err(Errno::EPERM);
+        }
+        self.cap_ambient.insert(cap);
+        Ok(())
+    }
+}
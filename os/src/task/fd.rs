@@ -3,7 +3,7 @@ use core::{cmp::min, fmt::Display, intrinsics::unlikely};
 // #![allow(unused)]
 use super::current_task;
 use crate::{
-    fs::{open, socketfs::{socketfile::SocketFile, socketinode::SocketInode}, FileTrait, InodeTrait, Kstat, OpenFlags, Page, RenameFlags}, hal::config::RLIMIT_NOFILE, mm::memory_space::{MmapFlags, MmapProt}, net::{Socket, PORT_FD_MANAMER}, sync::time_duration, syscall::RLimit64, utils::{Errno, SysResult}
+    fs::{locks, open, socketfs::{socketfile::SocketFile, socketinode::SocketInode}, FileTrait, InodeTrait, Kstat, OpenFlags, Page, RenameFlags}, hal::config::RLIMIT_NOFILE, mm::memory_space::{MmapFlags, MmapProt}, net::{Socket, PORT_FD_MANAMER}, sync::time_duration, syscall::RLimit64, utils::{Errno, SysResult}
 };
 use alloc::{collections::binary_heap::BinaryHeap, format, string::String, sync::Arc, vec::Vec};
 use log::info;
@@ -209,6 +209,9 @@ impl FdTable {
         }
         // 清理已关闭的文件描述符
         for fd in to_free {
+            if let Some(file) = &self.table[fd].file {
+                release_posix_locks(file);
+            }
             self.free_fd_slot(fd);
             self.table[fd].clear();
         }
@@ -302,6 +305,9 @@ impl FdTable {
         if idx >= self.table_len() {
             self.table.resize(idx + 1, FdInfo::new_bare());
         }
+        if let Some(old) = &self.table[idx].file {
+            release_posix_locks(old);
+        }
         self.table[idx] = info;
         Ok(())
     }
@@ -314,6 +320,7 @@ impl FdTable {
             return Err(Errno::EBADF);
         }
         let file = self.table[fd].file.take().unwrap();
+        release_posix_locks(&file);
         if file.metadata().inode.metadata()._type.is_socket() {
             let pid = current_task().unwrap().get_pid();
             PORT_FD_MANAMER.lock().remove_all_fds_by_pid_and_fd(pid, fd);
@@ -356,6 +363,13 @@ impl FdTable {
     }
 }
 
+/// 关闭 fd 时释放当前进程在这个文件上的全部 POSIX 锁
+fn release_posix_locks(file: &Arc<dyn FileTrait>) {
+    if let Some(task) = current_task() {
+        locks::release_posix_locks(file.metadata().inode.metadata().ino, task.get_tgid());
+    }
+}
+
 /// 将一个socket加入到fd表中
 pub fn sock_map_fd(socket: Arc<dyn Socket>, cloexec_enable: bool, flags: OpenFlags) -> SysResult<usize> {
     // let mut flag = OpenFlags::O_RDWR; // 这里的flag基本没用
@@ -370,24 +384,6 @@ pub fn sock_map_fd(socket: Arc<dyn Socket>, cloexec_enable: bool, flags: OpenFla
     Ok(fd)
 }
 
-pub fn exchange_sock_fdinfo(oldfd: usize, newfd: usize) -> SysResult<()> {
-    let task = current_task().unwrap();
-    if unlikely(oldfd >= task.fd_table_len() || newfd >= task.fd_table_len()) {
-        // info!("[exchange_sock_fdinfo] out of range: oldfd = {}, newfd = {}, fdtable len = {}",
-        //     oldfd,
-        //     newfd,
-        //     task.fd_table_len()
-        // );
-        return Err(Errno::EBADF);
-    }
-    let mut fdtable = task.fd_table.lock();
-    fdtable.table.swap(oldfd, newfd);
-    let temp = fdtable.table[newfd].flags;
-    fdtable.table[newfd].flags = fdtable.table[oldfd].flags;
-    fdtable.table[oldfd].flags = temp;
-    Ok(())
-}
-
 pub fn test_fd_performance() {
     use alloc::sync::Arc;
     use core::time::Duration;
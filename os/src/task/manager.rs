@@ -145,3 +145,21 @@ pub fn extract_proc_to_new_group(old_pgid: PGid, new_pgid: PGid, pid: Pid) {
 pub fn get_target_proc_group(pgid: PGid) -> Option<Vec<usize>> {
     MANAGER.process_group.lock().0.get(&pgid).cloned()
 }
+
+/// 是否是孤儿进程组：组内没有一个进程的父进程在同一会话中的另一个进程组里，
+/// 这样的进程组被停止后没有进程能让它继续运行
+pub fn is_orphaned_proc_group(pgid: PGid) -> bool {
+    let Some(group) = get_target_proc_group(pgid) else {
+        return true;
+    };
+    !group
+        .iter()
+        .filter_map(|pid| get_task_by_pid(*pid))
+        .filter(|task| !task.is_zombie())
+        .any(|task| {
+            let Some(parent) = task.parent.lock().clone().and_then(|p| p.upgrade()) else {
+                return false;
+            };
+            parent.get_pgid() != pgid && parent.get_sid() == task.get_sid()
+        })
+}
@@ -1,4 +1,6 @@
 pub mod aux;
+mod capability;
+mod cred;
 pub mod executor;
 mod fd;
 pub mod futex;
@@ -7,18 +9,21 @@ mod manager;
 mod pid;
 mod processor;
 mod sched;
+mod seccomp;
 #[allow(clippy::module_inception)]
 mod task;
 mod thread_group;
 
+pub use capability::{capable, CapSet, CAP_LAST_CAP};
+pub use cred::{Cred, NGROUPS_MAX};
 pub use fd::test_fd_performance;
-pub use fd::{sock_map_fd, exchange_sock_fdinfo, FdInfo, FdTable};
+pub use fd::{sock_map_fd, FdInfo, FdTable};
 pub use futex::*;
 pub use ipc::ShmidTable;
 pub use manager::{
     add_proc_group_member, add_task, extract_proc_to_new_group, get_init_proc, get_proc_num,
-    get_target_proc_group, get_task_by_pid, new_process_group, remove_proc_group_member,
-    remove_task_by_pid, MANAGER,
+    get_target_proc_group, get_task_by_pid, is_orphaned_proc_group, new_process_group,
+    remove_proc_group_member, remove_task_by_pid, MANAGER,
 };
 pub use pid::pid_alloc;
 pub use pid::{Pid, PidAllocator};
@@ -28,8 +33,9 @@ pub use processor::{
     get_current_hart_id, init_processors, set_ktrap_ret, take_current_task, take_ktrap_ret,
 };
 pub use sched::TaskFuture;
-pub use sched::{spawn_kernel_task, spawn_user_task, spawn_idle_task};
-pub use task::{TaskControlBlock, TaskStatus};
+pub use sched::{spawn_kernel_task, spawn_user_task, spawn_idle_task, spawn_user_io};
+pub use seccomp::*;
+pub use task::{TaskControlBlock, TaskStatus, SUID_DUMP_DISABLE, SUID_DUMP_USER, TASK_COMM_LEN};
 
 use crate::fs::{autorun, gbshell, initproc, mbshell};
 use crate::fs::{test_initproc, OpenFlags};
@@ -38,6 +44,7 @@ use async_task::Task;
 use cfg_if::cfg_if;
 use log::info;
 use thread_group::ThreadGroup;
+pub use thread_group::WaitEvent;
 
 ///Add init process to the manager
 pub async fn add_initproc() {
@@ -114,6 +114,21 @@ impl CPU {
         task.get_time_data_mut().set_sched_out_time();
         enable_supervisor_interrupt();
     }
+
+    /// 以 task 的身份运行内核请求：只切换页表和当前任务，
+    /// 不记录调度时间，也不碰 trap 上下文中的浮点寄存器
+    pub fn user_io_checkin(&mut self, task: &Arc<TaskControlBlock>) {
+        disable_supervisor_interrupt();
+        self.set_cpu_task(task.clone());
+        task.switch_pgtable();
+        enable_supervisor_interrupt();
+    }
+
+    pub fn user_io_checkout(&mut self) {
+        disable_supervisor_interrupt();
+        self.clear_cpu_task();
+        enable_supervisor_interrupt();
+    }
 }
 
 pub struct SyncProcessors(UnsafeCell<[CPU; HART_NUM]>);
@@ -11,6 +11,11 @@ pub enum TaskFuture<F: Future<Output = ()> + Send + 'static> {
     KernelTaskFuture {
         future: F,
     },
+    /// 借用用户任务的地址空间和 fd 表运行的内核请求，不碰该任务的 trap 上下文
+    UserIoFuture {
+        task: Arc<TaskControlBlock>,
+        future: F,
+    },
 }
 
 impl<F: Future<Output = ()> + Send + 'static> TaskFuture<F> {
@@ -23,6 +28,11 @@ impl<F: Future<Output = ()> + Send + 'static> TaskFuture<F> {
     pub fn kernel_task(future: F) -> Self {
         TaskFuture::KernelTaskFuture { future }
     }
+
+    /// 创建一个以用户任务身份运行的请求的 Future
+    pub fn user_io(task: Arc<TaskControlBlock>, future: F) -> Self {
+        TaskFuture::UserIoFuture { task, future }
+    }
 }
 
 impl<F: Future<Output = ()> + Send + 'static> Future for TaskFuture<F> {
@@ -46,6 +56,13 @@ impl<F: Future<Output = ()> + Send + 'static> Future for TaskFuture<F> {
                 // TODO: 实现kernel中断时完善 checkin checkout
                 unsafe { Pin::new_unchecked(future).poll(cx) }
             }
+            TaskFuture::UserIoFuture { task, future } => {
+                let processor = get_current_cpu();
+                processor.user_io_checkin(task);
+                let ret = unsafe { Pin::new_unchecked(future).poll(cx) };
+                processor.user_io_checkout();
+                ret
+            }
         }
     }
 }
@@ -62,6 +79,14 @@ pub fn spawn_kernel_task<T: Future<Output = ()> + Send + 'static>(kernel_task: T
     executor::spawn(future);
 }
 
+/// 用于 io_uring 的请求，以提交者的身份运行，使用它的地址空间和 fd 表
+///
+/// 提交者可能同时在另一个核上运行，请求不能保存或恢复它的浮点寄存器和调度时间
+pub fn spawn_user_io<T: Future<Output = ()> + Send + 'static>(task: Arc<TaskControlBlock>, io: T) {
+    let future = TaskFuture::user_io(task, io);
+    executor::spawn(future);
+}
+
 pub fn spawn_idle_task<T: Future<Output = ()> + Send + 'static>(idle_task: T) {
     let future = TaskFuture::kernel_task(idle_task);
     executor::spawn_idle(future);
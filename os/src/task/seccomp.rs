@@ -0,0 +1,437 @@
+//! seccomp 系统调用过滤
+//!
+//! strict 模式只允许 read、write、exit 和 sigreturn；filter 模式下每个任务持有一串
+//! 经典 BPF 程序，新安装的程序指向之前的程序。系统调用分发前依次运行所有程序，
+//! 取优先级最高的返回值决定这次系统调用的结果。fork 时复制，execve 时保留。
+
+use alloc::{sync::Arc, vec::Vec};
+use core::mem::size_of;
+
+use num_enum::TryFromPrimitive;
+
+use super::{current_task, current_trap_cx, get_task_by_pid, TaskControlBlock};
+use crate::{
+    signal::{KSigAction, SigCode, SigDetails, SigErr, SigHandlerType, SigInfo, SigNom},
+    syscall::SysCode,
+    utils::{Errno, SysResult},
+};
+
+/// 单个过滤程序的最大指令数
+pub const BPF_MAXINSNS: usize = 4096;
+/// 一个任务上所有过滤程序的指令总数上限，每个程序额外计 4 条
+const MAX_INSNS_PER_PATH: usize = (1 << 18) / 8;
+/// BPF 程序可用的临时存储 M[] 的大小
+const BPF_MEMWORDS: usize = 16;
+
+/// 过滤程序返回值的高 16 位是动作，低 16 位是附加数据；按有符号数比较，越小优先级越高
+pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+pub const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+pub const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+pub const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+pub const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+pub const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+pub const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+pub const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// 用户能设置的最大 errno
+const MAX_ERRNO: u32 = 4095;
+
+/// seccomp_data.arch 的取值
+#[cfg(target_arch = "riscv64")]
+pub const AUDIT_ARCH_CURRENT: u32 = 0xc000_00f3;
+#[cfg(target_arch = "loongarch64")]
+pub const AUDIT_ARCH_CURRENT: u32 = 0xc000_0102;
+
+// 经典 BPF 指令编码
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ST: u16 = 0x02;
+const BPF_STX: u16 = 0x03;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+const BPF_MISC: u16 = 0x07;
+
+const BPF_W: u16 = 0x00;
+const BPF_IMM: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_MEM: u16 = 0x60;
+const BPF_LEN: u16 = 0x80;
+
+const BPF_ADD: u16 = 0x00;
+const BPF_SUB: u16 = 0x10;
+const BPF_MUL: u16 = 0x20;
+const BPF_DIV: u16 = 0x30;
+const BPF_OR: u16 = 0x40;
+const BPF_AND: u16 = 0x50;
+const BPF_LSH: u16 = 0x60;
+const BPF_RSH: u16 = 0x70;
+const BPF_NEG: u16 = 0x80;
+const BPF_MOD: u16 = 0x90;
+const BPF_XOR: u16 = 0xa0;
+
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+
+const BPF_K: u16 = 0x00;
+const BPF_X: u16 = 0x08;
+const BPF_A: u16 = 0x10;
+
+const BPF_TAX: u16 = 0x00;
+const BPF_TXA: u16 = 0x80;
+
+const LD_W_ABS: u16 = BPF_LD | BPF_W | BPF_ABS;
+const LD_W_LEN: u16 = BPF_LD | BPF_W | BPF_LEN;
+const LDX_W_LEN: u16 = BPF_LDX | BPF_W | BPF_LEN;
+const LD_IMM: u16 = BPF_LD | BPF_IMM;
+const LDX_IMM: u16 = BPF_LDX | BPF_IMM;
+const LD_MEM: u16 = BPF_LD | BPF_MEM;
+const LDX_MEM: u16 = BPF_LDX | BPF_MEM;
+const RET_K: u16 = BPF_RET | BPF_K;
+const RET_A: u16 = BPF_RET | BPF_A;
+const MISC_TAX: u16 = BPF_MISC | BPF_TAX;
+const MISC_TXA: u16 = BPF_MISC | BPF_TXA;
+
+/// 经典 BPF 指令，和 struct sock_filter 布局相同
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// 过滤程序的输入
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SeccompData {
+    pub nr: i32,
+    pub arch: u32,
+    pub instruction_pointer: u64,
+    pub args: [u64; 6],
+}
+
+impl SeccompData {
+    fn load_word(&self, offset: usize) -> u32 {
+        // 偏移在安装时已经检查过，一定按 4 字节对齐且在结构体内
+        let bytes = unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>())
+        };
+        u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeccompMode {
+    Disabled = 0,
+    Strict = 1,
+    Filter = 2,
+}
+
+/// 一个过滤程序，prev 指向在它之前安装的程序
+pub struct SeccompFilter {
+    prog: Vec<SockFilter>,
+    prev: Option<Arc<SeccompFilter>>,
+}
+
+/// 任务的 seccomp 状态
+#[derive(Clone)]
+pub struct Seccomp {
+    pub mode: SeccompMode,
+    pub filter: Option<Arc<SeccompFilter>>,
+}
+
+impl Seccomp {
+    pub fn new() -> Self {
+        Self {
+            mode: SeccompMode::Disabled,
+            filter: None,
+        }
+    }
+
+    /// 进入 strict 模式，已经处于 filter 模式时不能切换
+    pub fn set_strict(&mut self) -> SysResult {
+        if self.mode == SeccompMode::Filter {
+            return Err(Errno::EINVAL);
+        }
+        self.mode = SeccompMode::Strict;
+        Ok(())
+    }
+
+    /// 检查并安装一个过滤程序，放在已有程序之前
+    pub fn attach_filter(&mut self, mut prog: Vec<SockFilter>) -> SysResult {
+        if self.mode == SeccompMode::Strict {
+            return Err(Errno::EINVAL);
+        }
+        check_filter(&mut prog)?;
+        let mut total = prog.len() + 4;
+        let mut cur = self.filter.as_ref();
+        while let Some(filter) = cur {
+            total += filter.prog.len() + 4;
+            cur = filter.prev.as_ref();
+        }
+        if total > MAX_INSNS_PER_PATH {
+            return Err(Errno::ENOMEM);
+        }
+        self.filter = Some(Arc::new(SeccompFilter {
+            prog,
+            prev: self.filter.take(),
+        }));
+        self.mode = SeccompMode::Filter;
+        Ok(())
+    }
+
+    /// 运行所有过滤程序，返回优先级最高的结果
+    fn run_filters(&self, data: &SeccompData) -> u32 {
+        let mut ret = SECCOMP_RET_ALLOW;
+        let mut cur = self.filter.as_ref();
+        while let Some(filter) = cur {
+            let cur_ret = filter.run(data);
+            if ((cur_ret & SECCOMP_RET_ACTION_FULL) as i32) < ((ret & SECCOMP_RET_ACTION_FULL) as i32) {
+                ret = cur_ret;
+            }
+            cur = filter.prev.as_ref();
+        }
+        ret
+    }
+}
+
+/// 检查程序是否合法：只允许 seccomp 用得到的指令，跳转不能越界，最后一条必须是 RET。
+/// 取 seccomp_data 长度的指令在这里替换成立即数
+fn check_filter(prog: &mut [SockFilter]) -> SysResult {
+    let len = prog.len();
+    if len == 0 || len > BPF_MAXINSNS {
+        return Err(Errno::EINVAL);
+    }
+    for pc in 0..len {
+        let ins = &mut prog[pc];
+        let k = ins.k as usize;
+        let valid = match ins.code {
+            LD_W_ABS => k < size_of::<SeccompData>() && k & 3 == 0,
+            LD_W_LEN => {
+                ins.code = LD_IMM;
+                ins.k = size_of::<SeccompData>() as u32;
+                true
+            }
+            LDX_W_LEN => {
+                ins.code = LDX_IMM;
+                ins.k = size_of::<SeccompData>() as u32;
+                true
+            }
+            LD_IMM | LDX_IMM | RET_K | RET_A | MISC_TAX | MISC_TXA => true,
+            LD_MEM | LDX_MEM | BPF_ST | BPF_STX => k < BPF_MEMWORDS,
+            code if code & 0x07 == BPF_ALU => match code & 0xf0 {
+                BPF_DIV | BPF_MOD if code & BPF_X == BPF_K => k != 0,
+                BPF_NEG => code & BPF_X == BPF_K,
+                BPF_ADD | BPF_SUB | BPF_MUL | BPF_OR | BPF_AND | BPF_LSH | BPF_RSH | BPF_XOR
+                | BPF_DIV | BPF_MOD => true,
+                _ => false,
+            },
+            code if code & 0x07 == BPF_JMP => match code & 0xf0 {
+                BPF_JA => code & BPF_X == BPF_K && pc + 1 + k < len,
+                BPF_JEQ | BPF_JGT | BPF_JGE | BPF_JSET => {
+                    pc + 1 + (ins.jt as usize) < len && pc + 1 + (ins.jf as usize) < len
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        if !valid {
+            return Err(Errno::EINVAL);
+        }
+    }
+    match prog[len - 1].code & 0x07 {
+        BPF_RET => Ok(()),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+impl SeccompFilter {
+    /// 解释执行程序，程序在安装时已经检查过
+    fn run(&self, data: &SeccompData) -> u32 {
+        let (mut a, mut x) = (0u32, 0u32);
+        let mut mem = [0u32; BPF_MEMWORDS];
+        let mut pc = 0;
+        loop {
+            let ins = self.prog[pc];
+            let k = ins.k;
+            pc += 1;
+            match ins.code {
+                LD_W_ABS => a = data.load_word(k as usize),
+                LD_IMM => a = k,
+                LDX_IMM => x = k,
+                LD_MEM => a = mem[k as usize],
+                LDX_MEM => x = mem[k as usize],
+                BPF_ST => mem[k as usize] = a,
+                BPF_STX => mem[k as usize] = x,
+                RET_K => return k,
+                RET_A => return a,
+                MISC_TAX => x = a,
+                MISC_TXA => a = x,
+                code if code & 0x07 == BPF_ALU => {
+                    let src = match code & BPF_X {
+                        BPF_K => k,
+                        _ => x,
+                    };
+                    a = match code & 0xf0 {
+                        BPF_ADD => a.wrapping_add(src),
+                        BPF_SUB => a.wrapping_sub(src),
+                        BPF_MUL => a.wrapping_mul(src),
+                        // 运行时除以 0 时程序返回 0，即 KILL_THREAD
+                        BPF_DIV => match a.checked_div(src) {
+                            Some(v) => v,
+                            None => return 0,
+                        },
+                        BPF_MOD => match a.checked_rem(src) {
+                            Some(v) => v,
+                            None => return 0,
+                        },
+                        BPF_OR => a | src,
+                        BPF_AND => a & src,
+                        BPF_LSH => a.checked_shl(src).unwrap_or(0),
+                        BPF_RSH => a.checked_shr(src).unwrap_or(0),
+                        BPF_NEG => a.wrapping_neg(),
+                        _ => a ^ src,
+                    };
+                }
+                code => {
+                    // 只剩跳转指令
+                    let src = match code & BPF_X {
+                        BPF_K => k,
+                        _ => x,
+                    };
+                    let taken = match code & 0xf0 {
+                        BPF_JA => {
+                            pc += k as usize;
+                            continue;
+                        }
+                        BPF_JEQ => a == src,
+                        BPF_JGT => a > src,
+                        BPF_JGE => a >= src,
+                        _ => a & src != 0,
+                    };
+                    pc += match taken {
+                        true => ins.jt as usize,
+                        false => ins.jf as usize,
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// seccomp 检查的结论
+pub enum SeccompAction {
+    /// 正常执行系统调用
+    Allow,
+    /// 不执行系统调用，把其中的结果返回给用户
+    Return(SysResult<usize>),
+    /// 任务已经被杀死，不能再回到用户态
+    Kill,
+}
+
+/// 系统调用分发前的检查
+pub fn secure_computing(syscall_id: usize, args: &[usize; 6]) -> SeccompAction {
+    let Some(task) = current_task() else {
+        return SeccompAction::Allow;
+    };
+    let seccomp = {
+        let seccomp = task.seccomp.lock();
+        if seccomp.mode == SeccompMode::Disabled {
+            return SeccompAction::Allow;
+        }
+        seccomp.clone()
+    };
+    match seccomp.mode {
+        SeccompMode::Disabled => SeccompAction::Allow,
+        SeccompMode::Strict => match SysCode::from(syscall_id) {
+            SysCode::SYSCALL_READ
+            | SysCode::SYSCALL_WRITE
+            | SysCode::SYSCALL_EXIT
+            | SysCode::SYSCALL_SIGRETURN => SeccompAction::Allow,
+            _ => {
+                seccomp_kill(&task, false, SigNom::SIGKILL);
+                SeccompAction::Kill
+            }
+        },
+        SeccompMode::Filter => {
+            let data = SeccompData {
+                nr: syscall_id as i32,
+                arch: AUDIT_ARCH_CURRENT,
+                instruction_pointer: current_trap_cx().get_sepc() as u64,
+                args: args.map(|arg| arg as u64),
+            };
+            let ret = seccomp.run_filters(&data);
+            let ret_data = ret & SECCOMP_RET_DATA;
+            match ret & SECCOMP_RET_ACTION_FULL {
+                SECCOMP_RET_ALLOW | SECCOMP_RET_LOG => SeccompAction::Allow,
+                SECCOMP_RET_ERRNO => {
+                    let errno = ret_data.min(MAX_ERRNO) as isize;
+                    SeccompAction::Return(Err(Errno::try_from_primitive(errno).unwrap_or(Errno::EPERM)))
+                }
+                SECCOMP_RET_TRAP => {
+                    force_sigsys(&task, &data, ret_data);
+                    // 和 Linux 一样回滚系统调用，a0 保持原来的参数
+                    SeccompAction::Return(Ok(args[0]))
+                }
+                // 没有 ptrace 和用户态通知，等同于系统调用不存在
+                SECCOMP_RET_TRACE | SECCOMP_RET_USER_NOTIF => SeccompAction::Return(Err(Errno::ENOSYS)),
+                SECCOMP_RET_KILL_THREAD => {
+                    seccomp_kill(&task, false, SigNom::SIGSYS);
+                    SeccompAction::Kill
+                }
+                // 未知的动作按照 KILL_PROCESS 处理
+                _ => {
+                    seccomp_kill(&task, true, SigNom::SIGSYS);
+                    SeccompAction::Kill
+                }
+            }
+        }
+    }
+}
+
+/// 发送带有系统调用信息的 SIGSYS，信号被屏蔽或者忽略时恢复默认处理，保证任务一定能收到
+fn force_sigsys(task: &Arc<TaskControlBlock>, data: &SeccompData, errno: u32) {
+    let signo = SigNom::SIGSYS as usize;
+    task.get_blocked_mut().unset_sig(signo);
+    {
+        let mut handler = task.handler.lock();
+        if handler.fetch_signal_handler(signo).sa_type == SigHandlerType::IGNORE {
+            handler.set_action(signo, KSigAction::new(SigNom::SIGSYS));
+        }
+    }
+    let siginfo = SigInfo::new(
+        SigNom::SIGSYS,
+        SigCode::Kernel,
+        SigErr::from_bits_retain(errno as i32),
+        SigDetails::Sys {
+            call_addr: data.instruction_pointer as usize,
+            syscall: data.nr,
+            arch: data.arch,
+        },
+    );
+    task.thread_recv_siginfo(siginfo);
+}
+
+/// 杀死当前线程；整个进程只剩这一个线程或者 whole_process 时结束整个进程，退出状态为 signo
+fn seccomp_kill(task: &Arc<TaskControlBlock>, whole_process: bool, signo: SigNom) {
+    let single = task.thread_group.lock().tasks.len() == 1;
+    if !whole_process && !single {
+        task.set_zombie();
+        return;
+    }
+    task.kill_all_thread();
+    let core_dump = match signo {
+        SigNom::SIGSYS => 0x80,
+        _ => 0,
+    };
+    if let Some(leader) = get_task_by_pid(task.get_tgid()) {
+        leader.set_exit_code(signo as i32 | core_dump);
+    }
+}
@@ -1,10 +1,13 @@
 use super::{
     add_proc_group_member, remove_proc_group_member, FdInfo, FdTable, FutexBucket, RobustList,
-    ShmidTable, ThreadGroup,
+    ShmidTable, ThreadGroup, WaitEvent,
 };
-use super::{pid_alloc, Pid};
+use super::{pid_alloc, Cred, Pid, Seccomp};
 use crate::fs::ext4::NormalFile;
-use crate::fs::{init, FileClass, FileTrait};
+use crate::drivers::tty::tty_core::tty_session_leader_exit;
+use crate::fs::pidfd::wake_pidfd_waiters;
+use crate::fs::locks::release_process_locks;
+use crate::fs::{init, FileClass, FileTrait, Kstat, ModeFlag};
 use crate::hal::arch::{sfence, shutdown};
 use crate::hal::config::INITPROC_PID;
 use crate::hal::trap::TrapContext;
@@ -43,6 +46,12 @@ use core::time::Duration;
 use log::{debug, info};
 use xmas_elf::dynamic;
 
+/// 线程名的最大长度，包括结尾的 '\0'
+pub const TASK_COMM_LEN: usize = 16;
+/// PR_SET_DUMPABLE 的取值
+pub const SUID_DUMP_DISABLE: usize = 0;
+pub const SUID_DUMP_USER: usize = 1;
+
 pub struct TaskControlBlock {
     // 不可变
     pub pid: Pid,
@@ -50,8 +59,10 @@ pub struct TaskControlBlock {
     // 可变
     pub tgid: AtomicUsize, // 所属线程组的leader的 pid，如果自己是leader，那tgid = pid
     pub pgid: AtomicUsize, // 所属进程组id号
-    pub euid: AtomicUsize,
+    pub sid: AtomicUsize, // 所属会话id号，等于会话首进程的pid
+    pub cred: Shared<Cred>, // 身份凭证，同一进程的线程共享
     pub task_status: SpinNoIrqLock<TaskStatus>,
+    pub comm: SpinNoIrqLock<String>, // 线程名，execve 时设为可执行文件名，prctl(PR_SET_NAME) 可以修改
 
     pub thread_group: Shared<ThreadGroup>,
     pub memory_space: SyncUnsafeCell<Shared<MemorySpace>>,
@@ -59,6 +70,7 @@ pub struct TaskControlBlock {
     pub children: Shared<BTreeMap<usize, Arc<TaskControlBlock>>>,
     pub fd_table: Shared<FdTable>,
     pub current_path: Shared<String>,
+    pub exe_path: Shared<String>, // 可执行文件的绝对路径，即 /proc/self/exe 指向的路径
     pub robust_list: Shared<RobustList>,
     pub futex_list: Shared<FutexBucket>,
     pub itimers: Shared<[ITimerVal; 3]>, // 三个定时器，分别对应SIGALRM, SIGVTALRM, SIGPROF
@@ -74,6 +86,14 @@ pub struct TaskControlBlock {
     pub blocked: SyncUnsafeCell<SigMask>, // 信号屏蔽字,表明进程不处理的信号
     pub handler: Shared<SigStruct>, // 表示信号相应的处理方法,一共64个信号
     pub sig_stack: SyncUnsafeCell<Option<SignalStack>>, // 信号栈，保存信号栈信息
+    pub pdeath_signal: AtomicUsize, // 父进程退出时发给自己的信号，0 表示不发送
+    pub restart_syscall: AtomicBool, // 上一个系统调用返回了 ERESTARTSYS，处理信号时决定是否重新执行
+
+    // prctl
+    pub no_new_privs: AtomicBool, // 置位后 execve 不再获得新的特权，不能清除
+    pub child_subreaper: AtomicBool, // 子孙进程成为孤儿时由最近的 subreaper 收养，只在 leader 上有效
+    pub dumpable: AtomicUsize, // PR_SET_DUMPABLE 的值，只在 leader 上有效
+    pub seccomp: SpinNoIrqLock<Arc<Seccomp>>, // 系统调用过滤，fork 时共享，修改时复制一份，execve 时保留
 
     pub waker: SyncUnsafeCell<Option<Waker>>,
     pub trap_cx: SyncUnsafeCell<TrapContext>,
@@ -89,6 +109,7 @@ pub struct TaskControlBlock {
 impl TaskControlBlock {
     /// 创建新task,只有initproc会调用
     pub async fn new(elf_file: Arc<dyn FileTrait>) -> Arc<Self> {
+        let exe_path = elf_file.abspath();
         let (mut memory_space, entry_point, sp_init, auxv) =
             MemorySpace::new_user_from_elf(elf_file)
                 .await
@@ -111,15 +132,18 @@ impl TaskControlBlock {
 
             // Shared
             pgid: AtomicUsize::new(1),
+            sid: AtomicUsize::new(1),
             tgid: AtomicUsize::new(tgid),
-            euid: AtomicUsize::new(0),
+            cred: new_shared(Cred::new_root()),
             task_status: SpinNoIrqLock::new(TaskStatus::Ready),
+            comm: SpinNoIrqLock::new(String::from("initproc")),
             thread_group: new_shared(ThreadGroup::new()),
             memory_space: SyncUnsafeCell::new(new_shared(memory_space)),
             parent: new_shared(None),
             children: new_shared(BTreeMap::new()),
             fd_table: new_shared(FdTable::new()),
             current_path: new_shared(String::from("/")), // root directory
+            exe_path: new_shared(exe_path),
             robust_list: new_shared(RobustList::new()),
             futex_list: new_shared(FutexBucket::new()),
             itimers: new_shared([ITimerVal::default(); 3]),
@@ -133,6 +157,13 @@ impl TaskControlBlock {
             blocked: SyncUnsafeCell::new(SigMask::empty()),
             handler: new_shared(SigStruct::new()),
             sig_stack: SyncUnsafeCell::new(None),
+            pdeath_signal: AtomicUsize::new(0),
+            restart_syscall: AtomicBool::new(false),
+
+            no_new_privs: AtomicBool::new(false),
+            child_subreaper: AtomicBool::new(false),
+            dumpable: AtomicUsize::new(SUID_DUMP_USER),
+            seccomp: SpinNoIrqLock::new(Arc::new(Seccomp::new())),
 
             // SyncUnsafeCell
             waker: SyncUnsafeCell::new(None),
@@ -160,6 +191,10 @@ impl TaskControlBlock {
     pub async fn execve(&self, elf_file: Arc<dyn FileTrait>, argv: Vec<String>, env: Vec<String>) {
         info!("execve start");
         // info!("[execve] argv:{:?}, env:{:?}", argv, env);
+        let mut stat = Kstat::new();
+        let exec_stat = elf_file.fstat(&mut stat);
+        let elf_path = elf_file.abspath();
+        elf_file.metadata().inode.metadata().fill_attr(&mut stat);
         let (mut memory_space, entry_point, sp_init, auxv) =
             MemorySpace::new_user_from_elf_lazily(elf_file)
                 .await
@@ -194,6 +229,32 @@ impl TaskControlBlock {
         self.fd_table.lock().close_on_exec();
         // 重置自定义的信号处理
         self.handler.lock().flash_signal_handlers();
+        // 处理可执行文件的 set-user-id/set-group-id 位，no_new_privs 时忽略
+        if exec_stat.is_ok() {
+            let mut cred = self.cred.lock();
+            let old = cred.clone();
+            match self.get_no_new_privs() {
+                true => {
+                    let setid = (ModeFlag::S_ISUID | ModeFlag::S_ISGID).bits();
+                    cred.apply_exec(stat.st_mode & !setid, stat.st_uid, stat.st_gid)
+                }
+                false => cred.apply_exec(stat.st_mode, stat.st_uid, stat.st_gid),
+            }
+            if self.get_no_new_privs() {
+                // 能力集不能超过 execve 之前的 permitted
+                cred.cap_permitted &= old.cap_permitted;
+                cred.cap_effective &= old.cap_permitted;
+            }
+            // 身份发生变化时不再接收父进程的退出信号，也不允许 core dump
+            if cred.euid != old.euid || cred.egid != old.egid {
+                self.set_pdeath_signal(0);
+                self.set_dumpable(SUID_DUMP_DISABLE);
+            } else {
+                self.set_dumpable(SUID_DUMP_USER);
+            }
+        }
+        self.set_comm(elf_path.rsplit('/').next().unwrap_or_default());
+        *self.exe_path.lock() = elf_path;
         unsafe { *self.sig_stack.get() = None };
         debug_point!("");
 
@@ -201,19 +262,75 @@ impl TaskControlBlock {
     }
 
     pub fn get_euid(&self) -> usize {
-        self.euid.load(core::sync::atomic::Ordering::Relaxed)
+        self.cred.lock().euid as usize
+    }
+
+    pub fn get_comm(&self) -> String {
+        self.comm.lock().clone()
+    }
+    /// 设置线程名，超过 TASK_COMM_LEN - 1 字节的部分被截断
+    pub fn set_comm(&self, name: &str) {
+        let mut len = name.len().min(TASK_COMM_LEN - 1);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        *self.comm.lock() = String::from(&name[..len]);
+    }
+
+    pub fn get_pdeath_signal(&self) -> usize {
+        self.pdeath_signal.load(core::sync::atomic::Ordering::SeqCst)
+    }
+    pub fn set_pdeath_signal(&self, signo: usize) {
+        self.pdeath_signal.store(signo, core::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn get_no_new_privs(&self) -> bool {
+        self.no_new_privs.load(core::sync::atomic::Ordering::SeqCst)
+    }
+    pub fn set_no_new_privs(&self) {
+        self.no_new_privs.store(true, core::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_child_subreaper(&self) -> bool {
+        self.child_subreaper.load(core::sync::atomic::Ordering::SeqCst)
+    }
+    pub fn set_child_subreaper(&self, value: bool) {
+        self.child_subreaper.store(value, core::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn get_dumpable(&self) -> usize {
+        self.dumpable.load(core::sync::atomic::Ordering::SeqCst)
+    }
+    pub fn set_dumpable(&self, value: usize) {
+        self.dumpable.store(value, core::sync::atomic::Ordering::SeqCst);
     }
 
-    pub fn set_euid(&self, euid: usize) {
-        self.euid.store(euid, core::sync::atomic::Ordering::Relaxed);
+    /// 进程退出时收养其子进程的进程：最近的仍然存活的 subreaper 祖先，没有时为 initproc
+    fn find_reaper(&self) -> Arc<TaskControlBlock> {
+        let mut ancestor = self.get_parent();
+        while let Some(task) = ancestor {
+            if task.is_child_subreaper() && !task.is_zombie() {
+                return task;
+            }
+            ancestor = task.get_parent();
+        }
+        get_init_proc()
     }
 
     pub fn do_process_fork(self: &Arc<Self>, flag: CloneFlags) -> Arc<Self> {
         info!("[process_fork] start, flags = {:?}", flag);
         let pid = pid_alloc();
         let pgid = AtomicUsize::new(self.get_pgid());
+        let sid = AtomicUsize::new(self.get_sid());
         let tgid = AtomicUsize::new(pid.0);
-        let euid = AtomicUsize::new(self.get_euid());
+        let cred = new_shared(self.cred.lock().clone());
+        let comm = SpinNoIrqLock::new(self.get_comm());
+        // pdeath_signal 和 child_subreaper 不会被子进程继承
+        let pdeath_signal = AtomicUsize::new(0);
+        let no_new_privs = AtomicBool::new(self.get_no_new_privs());
+        let child_subreaper = AtomicBool::new(false);
+        let dumpable = AtomicUsize::new(self.get_dumpable());
+        let seccomp = SpinNoIrqLock::new(self.seccomp.lock().clone());
         let pending = AtomicBool::new(false);
         let ucontext = AtomicUsize::new(0);
         let sig_pending = SpinNoIrqLock::new(SigPending::new());
@@ -229,6 +346,7 @@ impl TaskControlBlock {
         let waker = SyncUnsafeCell::new(None);
         let parent = new_shared(Some(Arc::downgrade(self)));
         let current_path = new_shared(self.current_path.lock().clone());
+        let exe_path = new_shared(self.get_exe_path());
         let robust_list = new_shared(RobustList::new());
         let clear_child_tid = SyncUnsafeCell::new(None);
         let set_child_tid = SyncUnsafeCell::new(None);
@@ -281,8 +399,10 @@ impl TaskControlBlock {
 
             // Shared
             pgid,
+            sid,
             tgid,
-            euid,
+            cred,
+            comm,
             thread_group,
             task_status,
             memory_space,
@@ -290,6 +410,7 @@ impl TaskControlBlock {
             children,
             fd_table,
             current_path,
+            exe_path,
             robust_list,
             futex_list,
             itimers,
@@ -303,6 +424,13 @@ impl TaskControlBlock {
             blocked,
             handler: sig,
             sig_stack,
+            pdeath_signal,
+            restart_syscall: AtomicBool::new(false),
+
+            no_new_privs,
+            child_subreaper,
+            dumpable,
+            seccomp,
 
             // SyncUnsafeCell
             waker,
@@ -331,8 +459,15 @@ impl TaskControlBlock {
         info!("[thread_fork] start, flags = {:?}", flag);
         let pid = pid_alloc();
         let pgid = AtomicUsize::new(self.get_pgid());
+        let sid = AtomicUsize::new(self.get_sid());
         let tgid = AtomicUsize::new(self.get_tgid());
-        let euid = AtomicUsize::new(self.get_euid());
+        let cred = self.cred.clone();
+        let comm = SpinNoIrqLock::new(self.get_comm());
+        let pdeath_signal = AtomicUsize::new(0);
+        let no_new_privs = AtomicBool::new(self.get_no_new_privs());
+        let child_subreaper = AtomicBool::new(false);
+        let dumpable = AtomicUsize::new(self.get_dumpable());
+        let seccomp = SpinNoIrqLock::new(self.seccomp.lock().clone());
         let pending = AtomicBool::new(false);
         let ucontext = AtomicUsize::new(0);
         let fsz_limit = self.fsz_limit.clone();
@@ -345,6 +480,7 @@ impl TaskControlBlock {
         let parent = self.parent.clone();
         let children = self.children.clone();
         let current_path = self.current_path.clone();
+        let exe_path = self.exe_path.clone();
         let robust_list = self.robust_list.clone();
         let waker = SyncUnsafeCell::new(None);
         let trap_cx = SyncUnsafeCell::new(*self.get_trap_cx());
@@ -394,14 +530,22 @@ impl TaskControlBlock {
             pid,
 
             pgid,
+            sid,
             tgid,
-            euid,
+            cred,
+            comm,
             pending,
             ucontext,
             sig_pending,
             blocked,
             handler: sig,
             sig_stack,
+            pdeath_signal,
+            restart_syscall: AtomicBool::new(false),
+            no_new_privs,
+            child_subreaper,
+            dumpable,
+            seccomp,
             robust_list,
             futex_list,
             itimers,
@@ -416,6 +560,7 @@ impl TaskControlBlock {
             children,
             fd_table,
             current_path,
+            exe_path,
             waker,
             trap_cx,
             time_data,
@@ -468,20 +613,32 @@ impl TaskControlBlock {
             return; // 致命错误，这里是处理线程的分支
         }
 
-        // 将当前进程的子进程移动到initproc下
+        // 将当前进程的子进程移动到最近的 subreaper 或者 initproc 下
         // info!("[do_exit] task is leader");
+        let reaper = self.find_reaper();
         let mut lock_child = self.children.lock();
         if !lock_child.is_empty() {
-            info!("[do_exit] task has child");
-            let init_proc = get_init_proc();
+            info!("[do_exit] task has child, reaper pid = {}", reaper.get_pid());
             for (child_pid, child) in lock_child.iter() {
-                child.set_parent(Some(Arc::downgrade(&init_proc)));
+                child.set_parent(Some(Arc::downgrade(&reaper)));
                 if child.is_zombie() {
                     info!("[do_exit] child pdi = {} is zmobie", child_pid);
-                    child.exit_notify(&init_proc);
+                    child.exit_notify(&reaper);
+                    continue;
+                }
+                // 子进程设置了 PR_SET_PDEATHSIG
+                let signo = child.get_pdeath_signal();
+                if signo != 0 {
+                    let sig_info = SigInfo::new(
+                        SigNom::from(signo),
+                        SigCode::User,
+                        SigErr::empty(),
+                        SigDetails::Kill { pid, uid: 0 },
+                    );
+                    child.proc_recv_siginfo(sig_info);
                 }
             }
-            init_proc.children.lock().extend(lock_child.clone());
+            reaper.children.lock().extend(lock_child.clone());
             lock_child.clear();
         }
         drop(lock_child);
@@ -517,9 +674,16 @@ impl TaskControlBlock {
                 // error!("proc {} has no parent!", self.get_pid());
             }
         }
+        // 唤醒在 pidfd 上等待本进程退出的任务
+        wake_pidfd_waiters(pid);
+        // 会话首进程退出，挂断它的控制终端
+        if self.is_session_leader() {
+            tty_session_leader_exit(pid);
+        }
 
         // self.remove_thread_group_member(pid);
         self.clear_fd_table();
+        release_process_locks(self.get_tgid());
         self.detach_all_shm();
         self.recycle_data_pages();
         PORT_FD_MANAMER.lock().remove_pid(self.get_pid());
@@ -674,6 +838,17 @@ impl TaskControlBlock {
             .store(value, core::sync::atomic::Ordering::SeqCst);
     }
 
+    /// 记录刚返回的系统调用是否需要在处理完信号后重新执行
+    pub fn set_restart_syscall(&self, value: bool) {
+        self.restart_syscall
+            .store(value, core::sync::atomic::Ordering::SeqCst);
+    }
+    /// 取出并清除重新执行系统调用的标记
+    pub fn take_restart_syscall(&self) -> bool {
+        self.restart_syscall
+            .swap(false, core::sync::atomic::Ordering::SeqCst)
+    }
+
     /// 取出task中的sig stack 留下None
     pub fn get_sig_stack_mut(&self) -> &mut Option<SignalStack> {
         unsafe { &mut *self.sig_stack.get() }
@@ -766,6 +941,7 @@ impl TaskControlBlock {
             thread.set_stopped();
             thread.set_wake_up_signal(SigMask::SIGCONT | SigMask::SIGKILL | SigMask::SIGSTOP);
         }
+        self.thread_group.lock().wait_event = Some(WaitEvent::Stopped(signo as usize));
         // self.do_notify_parent(signo, SigCode::CLD_STOPPED);
         self.do_notify_parent(SigNom::SIGCHLD, SigCode::CLD_STOPPED);
     }
@@ -777,6 +953,7 @@ impl TaskControlBlock {
             thread.set_running();
             self.wake_up();
         }
+        self.thread_group.lock().wait_event = Some(WaitEvent::Continued);
         // self.do_notify_parent(signo, SigCode::CLD_CONTINUED);
         self.do_notify_parent(SigNom::SIGCHLD, SigCode::CLD_CONTINUED);
     }
@@ -799,6 +976,19 @@ impl TaskControlBlock {
         self.pgid.store(pgid, core::sync::atomic::Ordering::SeqCst);
     }
 
+    /// 获取当前进程所属会话的sid
+    pub fn get_sid(&self) -> usize {
+        self.sid.load(core::sync::atomic::Ordering::SeqCst)
+    }
+    /// 设置当前进程所属的会话
+    pub fn set_sid(&self, sid: usize) {
+        self.sid.store(sid, core::sync::atomic::Ordering::SeqCst);
+    }
+    /// 是否是会话首进程
+    pub fn is_session_leader(&self) -> bool {
+        self.get_pid() == self.get_sid()
+    }
+
     pub fn get_tgid(&self) -> usize {
         self.tgid.load(core::sync::atomic::Ordering::SeqCst)
     }
@@ -952,6 +1142,10 @@ impl TaskControlBlock {
     pub fn set_current_path(&self, path: String) {
         *self.current_path.lock() = path;
     }
+    /// 获取当前进程的可执行文件路径
+    pub fn get_exe_path(&self) -> String {
+        self.exe_path.lock().clone()
+    }
 
     /// waker
     /// 获取当前进程的waker
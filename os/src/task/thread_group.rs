@@ -4,14 +4,26 @@ use alloc::{
     sync::{Arc, Weak},
 };
 
+/// 进程被信号停止或继续后，还没有被父进程 wait 取走的状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitEvent {
+    /// 被编号为 signo 的信号停止
+    Stopped(usize),
+    /// 收到 SIGCONT 后继续运行
+    Continued,
+}
+
 pub struct ThreadGroup {
     pub tasks: BTreeMap<usize, Weak<TaskControlBlock>>,
+    /// 给 wait4/waitid 的 WUNTRACED、WCONTINUED 使用
+    pub wait_event: Option<WaitEvent>,
 }
 
 impl ThreadGroup {
     pub fn new() -> Self {
         Self {
             tasks: BTreeMap::new(),
+            wait_event: None,
         }
     }
 
@@ -1,6 +1,9 @@
+use num_enum::TryFromPrimitive;
+
 pub type SysResult<T = ()> = Result<T, Errno>;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, TryFromPrimitive)]
+#[repr(isize)]
 pub enum Errno {
     /// 处理返回值为-1的情况
     EBADCALL = -1,
@@ -137,6 +140,8 @@ pub enum Errno {
     ENOTRECOVERABLE = 131,
     ERFKILL = 132,
     EHWPOISON = 133,
+    /// 内核内部使用，不会返回给用户：系统调用被信号打断，处理完信号后重新执行
+    ERESTARTSYS = 512,
     ENOIMPL = 999,
 }
 
@@ -186,6 +191,7 @@ impl Errno {
             ENOTEMPTY => "Directory not empty",
             ENOTCONN => "Transport endpoint is not connected",
             ECONNREFUSED => "Connection refused",
+            ERESTARTSYS => "Restart system call",
             ENOIMPL => "Function not implemented, use default implementation",
             _ => "Unknown error",
         }
@@ -21,7 +21,7 @@ use log::warn;
 
 pub use errtype::{Errno, SysResult};
 pub use logger::logger_init;
-pub use random::{LcgRng, RNG};
+pub use random::*;
 // pub use boot::{boot_all_harts, jump_helper, clear_bss, logo};
 
 pub fn backtrace() {
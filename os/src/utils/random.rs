@@ -1,44 +1,350 @@
 #![allow(unused)]
-use crate::{sync::SpinNoIrqLock};
+//! 内核随机数发生器
+//!
+//! 结构参考 Linux 的 `drivers/char/random.c`：
+//! - 熵池：各熵源（计时器抖动、中断时间、通过 `register_hwrng` 注册的硬件源）的样本被异或进一个
+//!   16 字的池中，每积累若干样本就用 ChaCha 置换搅拌一次，同时按来源估计可信熵位数；
+//! - CRNG：以 ChaCha20 为核心的密码学安全伪随机数发生器，密钥由熵池提取，
+//!   每次输出后都用一个额外的密钥流块替换密钥（fast key erasure），
+//!   因此即使事后泄露内部状态也无法回推之前的输出；
+//! - 熵池积累到 `POOL_READY_BITS` 之后 CRNG 才被视为初始化完成，
+//!   此后每隔 `RESEED_INTERVAL_MS` 从熵池重新播种一次。
+use alloc::sync::Arc;
+use crate::{hal::arch::get_time, sync::{timer::get_time_ms, SpinNoIrqLock}};
+use log::warn;
 
-const LCG_MULTIPLIER: u64 = 6364136223846793005;
-const LCG_INCREMENT: u64 = 1;
-const LCG_MODULUS: u64 = 1 << 63;
+/// "expand 32-byte k"
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+/// ChaCha20 的一个输出块的字节数
+pub const CHACHA_BLOCK_SIZE: usize = 64;
 
-pub struct LcgRng {
-    state: u64,
+/// 熵池中积累到这么多可信熵位后 CRNG 才算初始化完成
+const POOL_READY_BITS: usize = 256;
+/// 初始化完成后重新播种的最小间隔
+const RESEED_INTERVAL_MS: usize = 60_000;
+/// 每向熵池混入这么多个样本就搅拌一次
+const POOL_MIX_INTERVAL: usize = 8;
+/// 每 64 次中断记 1 位熵（与 Linux 相同的保守估计）
+const IRQ_SAMPLES_PER_BIT: usize = 64;
+/// 计时器抖动采样的上限，防止在时钟完全确定的环境里永远等不到熵
+const JITTER_MAX_SAMPLES: usize = 1 << 20;
+/// 一次持锁生成的最大字节数，较大的请求分多次加锁完成，避免长时间关中断
+const FILL_CHUNK: usize = 256;
+/// 每次从硬件随机数发生器读取的字节数，恰好满足一次播种所需的熵
+const HWRNG_PULL_BYTES: usize = POOL_READY_BITS / 8;
+
+#[inline(always)]
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// 20 轮 ChaCha 置换，带前馈相加
+fn chacha20_permute(state: &mut [u32; 16]) {
+    let input = *state;
+    for _ in 0..10 {
+        quarter_round(state, 0, 4, 8, 12);
+        quarter_round(state, 1, 5, 9, 13);
+        quarter_round(state, 2, 6, 10, 14);
+        quarter_round(state, 3, 7, 11, 15);
+        quarter_round(state, 0, 5, 10, 15);
+        quarter_round(state, 1, 6, 11, 12);
+        quarter_round(state, 2, 7, 8, 13);
+        quarter_round(state, 3, 4, 9, 14);
+    }
+    for (word, init) in state.iter_mut().zip(input.iter()) {
+        *word = word.wrapping_add(*init);
+    }
+}
+
+/// 生成 ChaCha20 的一个密钥流块（原始 DJB 布局：64 位计数器 + 64 位 nonce，这里 nonce 固定为 0）
+fn chacha20_block(key: &[u32; 8], counter: u64) -> [u32; 16] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12] = counter as u32;
+    state[13] = (counter >> 32) as u32;
+    chacha20_permute(&mut state);
+    state
+}
+
+/// 熵池
+struct EntropyPool {
+    words: [u32; 16],
+    /// 下一个样本写入的位置
+    pos: usize,
+    /// 自上次搅拌以来混入的样本数
+    pending: usize,
+    /// 自上次提取以来积累的可信熵位数
+    bits: usize,
+    /// 中断样本计数，用于按 `IRQ_SAMPLES_PER_BIT` 记熵
+    irq_samples: usize,
+}
+
+impl EntropyPool {
+    const fn new() -> Self {
+        Self { words: [0; 16], pos: 0, pending: 0, bits: 0, irq_samples: 0 }
+    }
+
+    /// 把一个 64 位样本混入池中，并记入 `credit` 位熵
+    fn mix(&mut self, sample: u64, credit: usize) {
+        let rot = (self.pos as u32 * 7) & 31;
+        self.words[self.pos] ^= (sample as u32).rotate_left(rot);
+        self.words[self.pos + 1] ^= ((sample >> 32) as u32).rotate_left(rot);
+        self.pos = (self.pos + 2) % self.words.len();
+        self.pending += 1;
+        if self.pending >= POOL_MIX_INTERVAL {
+            self.stir();
+        }
+        self.bits = (self.bits + credit).min(POOL_READY_BITS * 2);
+    }
+
+    fn mix_bytes(&mut self, bytes: &[u8], credit: usize) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.mix(u64::from_le_bytes(word), 0);
+        }
+        self.bits = (self.bits + credit).min(POOL_READY_BITS * 2);
+    }
+
+    fn stir(&mut self) {
+        chacha20_permute(&mut self.words);
+        self.pending = 0;
+    }
+
+    /// 从池中提取 256 位种子，提取后清空熵计数；
+    /// 提取前后各搅拌一次，使输出的种子与池的剩余状态无关
+    fn extract(&mut self) -> [u32; 8] {
+        self.stir();
+        let mut seed = [0u32; 8];
+        seed.copy_from_slice(&self.words[..8]);
+        self.words[0] ^= 1;
+        self.stir();
+        self.bits = 0;
+        seed
+    }
+}
+
+/// 基于 ChaCha20 的 CSPRNG
+pub struct ChaChaRng {
+    key: [u32; 8],
+    pool: EntropyPool,
+    /// 熵池是否已积累到足够的熵完成首次播种
+    ready: bool,
+    /// 上次播种的时间（毫秒）
+    last_reseed: usize,
+    /// 播种次数
+    generation: usize,
 }
 
-impl LcgRng {
-    // 使用当前时间作为种子初始化 RNG
+impl ChaChaRng {
     const fn new() -> Self {
-        let seed = 42;
+        Self {
+            key: [0; 8],
+            pool: EntropyPool::new(),
+            ready: false,
+            last_reseed: 0,
+            generation: 0,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// 混入不计熵的设备数据（如用户写入 /dev/urandom 的内容）
+    pub fn add_device_randomness(&mut self, bytes: &[u8]) {
+        self.pool.mix(get_time() as u64, 0);
+        self.pool.mix_bytes(bytes, 0);
+    }
+
+    /// 混入硬件随机数发生器（如 virtio-rng）提供的数据，按全部位数记熵；
+    /// 熵足够时立即重新播种，不受 `RESEED_INTERVAL_MS` 限制
+    pub fn add_hwrng_randomness(&mut self, bytes: &[u8]) {
+        self.pool.mix_bytes(bytes, bytes.len() * 8);
+        if self.pool.bits >= POOL_READY_BITS {
+            self.reseed(get_time_ms());
+        }
+    }
+
+    /// 距上次播种已超过 `RESEED_INTERVAL_MS`
+    fn reseed_due(&self) -> bool {
+        self.ready && get_time_ms().wrapping_sub(self.last_reseed) >= RESEED_INTERVAL_MS
+    }
+
+    /// 混入一次中断的到达时间
+    pub fn add_interrupt_randomness(&mut self, irq: usize) {
+        self.pool.irq_samples += 1;
+        let credit = if self.pool.irq_samples % IRQ_SAMPLES_PER_BIT == 0 { 1 } else { 0 };
+        self.pool.mix((get_time() as u64) ^ ((irq as u64) << 48), credit);
+        self.try_reseed();
+    }
 
-        LcgRng { state: seed }
+    /// 混入一次计时器抖动样本
+    fn add_jitter_sample(&mut self, sample: u64, credit: usize) {
+        self.pool.mix(sample, credit);
+        self.try_reseed();
     }
 
-    // 生成下一个随机数
+    /// 熵池的熵足够时播种：首次播种使 CRNG 进入就绪状态，之后按 `RESEED_INTERVAL_MS` 周期重播种
+    fn try_reseed(&mut self) {
+        if self.pool.bits < POOL_READY_BITS {
+            return;
+        }
+        let now = get_time_ms();
+        if self.ready && now.wrapping_sub(self.last_reseed) < RESEED_INTERVAL_MS {
+            return;
+        }
+        self.reseed(now);
+    }
+
+    fn reseed(&mut self, now: usize) {
+        let seed = self.pool.extract();
+        for (k, s) in self.key.iter_mut().zip(seed.iter()) {
+            *k ^= *s;
+        }
+        self.rekey();
+        self.ready = true;
+        self.last_reseed = now;
+        self.generation += 1;
+    }
+
+    /// 用一个不对外输出的密钥流块替换密钥
+    fn rekey(&mut self) {
+        let block = chacha20_block(&self.key, u64::MAX);
+        self.key.copy_from_slice(&block[..8]);
+    }
+
+    /// 生成下一个随机数
     pub fn next(&mut self) -> u32 {
-        self.state = self
-            .state
-            .wrapping_mul(LCG_MULTIPLIER)
-            .wrapping_add(LCG_INCREMENT);
-        (self.state >> 32) as u32
+        let mut bytes = [0u8; 4];
+        self.fill_buf(&mut bytes);
+        u32::from_le_bytes(bytes)
     }
 
-    /// 用随机字节填充缓冲区
+    /// 用随机字节填充缓冲区，返回填充的字节数
     pub fn fill_buf(&mut self, buf: &mut [u8]) -> usize {
-        let mut offset = 0;
-
-        while offset < buf.len() {
-            let rand = self.next();
-            let rand_bytes = rand.to_le_bytes();
-            let chunk_size = (buf.len() - offset).min(4);
-            buf[offset..chunk_size + offset].copy_from_slice(&rand_bytes[..chunk_size]);
-            offset += chunk_size;
+        for (counter, chunk) in buf.chunks_mut(CHACHA_BLOCK_SIZE).enumerate() {
+            let block = chacha20_block(&self.key, counter as u64);
+            let mut bytes = [0u8; CHACHA_BLOCK_SIZE];
+            for (dst, word) in bytes.chunks_exact_mut(4).zip(block.iter()) {
+                dst.copy_from_slice(&word.to_le_bytes());
+            }
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
         }
+        // fast key erasure
+        self.rekey();
         buf.len()
     }
 }
 
-pub static RNG: SpinNoIrqLock<LcgRng> = SpinNoIrqLock::new(LcgRng::new());
+pub static RNG: SpinNoIrqLock<ChaChaRng> = SpinNoIrqLock::new(ChaChaRng::new());
+
+/// 硬件随机数发生器，由驱动在探测到设备后注册
+pub trait HwRng: Send + Sync {
+    /// 读取随机字节，返回实际读到的字节数
+    fn read(&self, buf: &mut [u8]) -> usize;
+}
+
+static HWRNG: SpinNoIrqLock<Option<Arc<dyn HwRng>>> = SpinNoIrqLock::new(None);
+
+/// 注册硬件随机数发生器，并立即从中取一次熵
+pub fn register_hwrng(rng: Arc<dyn HwRng>) {
+    *HWRNG.lock() = Some(rng);
+    pull_hwrng();
+}
+
+/// 从已注册的硬件随机数发生器取熵混入熵池。
+/// 设备读取可能需要轮询等待，因此只在进程上下文中调用，不在中断路径上调用
+fn pull_hwrng() {
+    let Some(rng) = HWRNG.lock().clone() else {
+        return;
+    };
+    let mut bytes = [0u8; HWRNG_PULL_BYTES];
+    let len = rng.read(&mut bytes);
+    if len > 0 {
+        add_hwrng_randomness(&bytes[..len]);
+    }
+}
+
+/// 用随机字节填充缓冲区；大缓冲区分块加锁，不等待 CRNG 就绪
+pub fn get_random_bytes(buf: &mut [u8]) -> usize {
+    if RNG.lock().reseed_due() {
+        pull_hwrng();
+    }
+    for chunk in buf.chunks_mut(FILL_CHUNK) {
+        RNG.lock().fill_buf(chunk);
+    }
+    buf.len()
+}
+
+pub fn get_random_u32() -> u32 {
+    RNG.lock().next()
+}
+
+pub fn rng_is_ready() -> bool {
+    RNG.lock().is_ready()
+}
+
+pub fn add_interrupt_randomness(irq: usize) {
+    RNG.lock().add_interrupt_randomness(irq);
+}
+
+pub fn add_hwrng_randomness(bytes: &[u8]) {
+    RNG.lock().add_hwrng_randomness(bytes);
+}
+
+pub fn add_device_randomness(bytes: &[u8]) {
+    RNG.lock().add_device_randomness(bytes);
+}
+
+/// 采集计时器抖动直到 CRNG 就绪。
+///
+/// 每个样本是两次读时钟之间做一次置换所花的时间；只有当一阶、二阶差分都与上一个样本不同时
+/// 才记 1 位熵，以排除时钟停滞或步长恒定的情况。
+/// 若采集 `JITTER_MAX_SAMPLES` 次后仍未就绪（例如时钟完全确定的模拟器），强制完成播种并给出警告。
+pub fn wait_for_random_bytes() {
+    if rng_is_ready() {
+        return;
+    }
+    pull_hwrng();
+    if rng_is_ready() {
+        return;
+    }
+    let mut scratch = [0u32; 16];
+    let mut last = get_time();
+    let mut last_delta = 0usize;
+    let mut last_delta2 = 0usize;
+    for i in 0..JITTER_MAX_SAMPLES {
+        scratch[i % 16] ^= last as u32;
+        chacha20_permute(&mut scratch);
+        let now = get_time();
+        let delta = now.wrapping_sub(last);
+        let delta2 = delta.wrapping_sub(last_delta);
+        let credit = (delta != last_delta && delta2 != last_delta2) as usize;
+        let mut rng = RNG.lock();
+        rng.add_jitter_sample((now as u64) ^ ((scratch[0] as u64) << 32), credit);
+        if rng.is_ready() {
+            return;
+        }
+        drop(rng);
+        last = now;
+        last_delta = delta;
+        last_delta2 = delta2;
+    }
+    warn!("[random] not enough timer jitter, forcing crng initialization");
+    let mut rng = RNG.lock();
+    if !rng.is_ready() {
+        rng.reseed(get_time_ms());
+    }
+}
+
+/// 启动时调用：混入启动时间等设备数据并用计时器抖动完成首次播种
+pub fn rand_init(hart_id: usize, dt_root: usize) {
+    add_device_randomness(&hart_id.to_le_bytes());
+    add_device_randomness(&dt_root.to_le_bytes());
+    wait_for_random_bytes();
+}